@@ -0,0 +1,42 @@
+//! Benchmarks of the per-call overhead of PiControl
+//!
+//! The processimage is kept in memory, so only the overhead of the library is
+//! measured and not the one of the driver. Since name lookups are free here,
+//! caching them doesn't pay off like it does with the driver, where every
+//! lookup is an ioctl.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+
+fn pi(cache: bool) -> PiControl {
+    PiControl::builder()
+        .backend(MockBackend::new().variable("Word", 42, 0, 16))
+        .cache(cache)
+        .build()
+        .unwrap()
+}
+
+fn get_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_value");
+    for cache in [false, true] {
+        let pi = pi(cache);
+        let id = if cache { "cached" } else { "uncached" };
+        group.bench_function(id, |b| b.iter(|| pi.get_value(black_box("Word")).unwrap()));
+    }
+    group.finish();
+}
+
+fn set_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_value");
+    for cache in [false, true] {
+        let pi = pi(cache);
+        let id = if cache { "cached" } else { "uncached" };
+        group.bench_function(id, |b| {
+            b.iter(|| pi.set_value(black_box("Word"), Value::Word(1)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, get_value, set_value);
+criterion_main!(benches);
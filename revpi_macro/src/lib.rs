@@ -7,32 +7,71 @@
 //! [`revpi!`] and [`revpi_from_json!`] basically both do the same. The only
 //! difference is that [`revpi!`] reads the config from the standard locations
 //! (`"/etc/revpi/config.rsc"` or `"/opt/KUNBUS/config.rsc"`) while
-//! [`revpi_from_json!`] reads it from a given path.
+//! [`revpi_from_json!`] reads it from a given path. [`revpi_from_json_str!`]
+//! takes the json itself, which is mostly useful for tests.
+//!
+//! Configs that can't be read or contain variables the macros can't handle
+//! result in a compile error.
 //!
 //! # Usage
 //! [`revpi!`] just needs the name of the struct it should produce, while
 //! [`revpi_from_json!`] also needs a path to an rsc file.
 //!
+//! The generated code refers to the `revpi` crate as `::revpi`. If it is
+//! renamed or only available through a re-export, the path to it can be given
+//! as last argument:
+//! ```ignore
+//! revpi!(RevPi, crate = ::my_revpi);
+//! revpi_from_json!(RevPi "config.rsc", crate = crate::deps::revpi);
+//! ```
+//!
+//! With the `grouped` option, the functions are grouped by device, see
+//! [below](#grouped-output).
+//!
+//! PiCtory names like `RevPiLED` or `RS485ErrorLimit1` make for method names
+//! that trigger Rust's naming lints. The names of the methods can be changed
+//! with these options:
+//! - `rename = "snake_case"` converts the names, e.g. `get_rev_pi_led`
+//!   instead of `get_RevPiLED`. `"none"`, the default, keeps them as they
+//!   are.
+//! - `prefix = "<prefix>"` and `suffix = "<suffix>"` are put around the
+//!   converted name, e.g. `prefix = "io_"` yields `get_io_rev_pi_led`.
+//! - `skip_unexported` only generates methods for variables that are
+//!   exported in PiCtory.
+//!
+//! ```ignore
+//! revpi!(RevPi, rename = "snake_case", skip_unexported);
+//! ```
+//! If two variables end up with the same method name, this is a compile
+//! error. The constants in `names` aren't affected by these options.
+//!
 //! # Output
 //! Both output a struct with the given name. That struct contains functions
 //! of the form\
 //! `get_<name>` and `set_<name>`, where `<name>` is the name given
 //! to the field in PiCtory. Inputs only have getters, while outputs and memory
 //! fields also have setters.
+//!
+//! `new()` opens the piControl device, `with_backend` takes any
+//! `Arc<dyn Backend>` instead, e.g. a `MockBackend` in tests:
+//! ```ignore
+//! pub fn new() -> Result<Self, PiControlError> {...}
+//! pub fn with_backend(backend: Arc<dyn Backend>) -> Self {...}
+//! ```
 //! ## Getters
-//! Getters need no arguments and their return value depends on the type of
+//! Getters need no arguments besides `&self` and their return value depends on the type of
 //! the field they read out. Getters return `Result<<type>, PiControlError>`
 //! where `<type>` is the type of the field they read out. So a getter could look
 //! like this:
 //! ```ignore
-//! pub fn get_RevPiStatus() -> Result<u8, PiControlError> {...}
+//! pub fn get_RevPiStatus(&self) -> Result<u8, PiControlError> {...}
 //! ```
 //! ## Setters
 //! Setters take an argument, the type of which depends on the type of field they
 //! set. They return `Result<(), PiControlError>`. So a setter could look like
 //! this:
 //! ```ignore
-//! pub fn set_RevPiLED(byte: u8) -> Result<(), PiControlError> {...}
+//! pub fn set_RevPiLED(&self, byte: u8) -> Result<(), PiControlError> {...}
 //! ```
 //!
 //! # Examples
@@ -109,38 +148,261 @@
 //! struct RevPi {...}
 //!
 //! impl RevPi {
-//!     pub fn get_RevPiStatus() -> Result<u8, PiControlError> {...}
-//!     pub fn get_RevPiLED() -> Result<u8, PiControlError> {...}
-//!     pub fn set_RevPiLED(byte: u8) -> Result<(), PiControlError> {...}
-//!     pub fn get_RS485ErrorLimit1() -> Result<u16, PiControlError> {...}
-//!     pub fn set_RS485ErrorLimit1(word: u16) -> Result<(), PiControlError> {...}
+//!     pub fn get_RevPiStatus(&self) -> Result<u8, PiControlError> {...}
+//!     pub fn get_RevPiLED(&self) -> Result<u8, PiControlError> {...}
+//!     pub fn set_RevPiLED(&self, byte: u8) -> Result<(), PiControlError> {...}
+//!     pub fn get_RS485ErrorLimit1(&self) -> Result<u16, PiControlError> {...}
+//!     pub fn set_RS485ErrorLimit1(&self, word: u16) -> Result<(), PiControlError> {...}
+//! }
+//!
+//! pub mod names {
+//!     pub const REV_PI_STATUS: &str = "RevPiStatus";
+//!     pub const REV_PI_LED: &str = "RevPiLED";
+//!     pub const RS485_ERROR_LIMIT1: &str = "RS485ErrorLimit1";
 //! }
 //! ```
+//! The constants in `names` can be used with the string based API, e.g.
+//! `pi.get_value(names::REV_PI_LED)`, so the names are checked at compile time
+//! even if the generated struct isn't used.
 //!
+//! # Batched access
+//! Besides the getters and setters, the struct gets
+//! ```ignore
+//! pub fn read_inputs(&self) -> Result<RevPiInputs, PiControlError> {...}
+//! pub fn write_outputs(&self, values: &RevPiOutputs) -> Result<(), PiControlError> {...}
+//! ```
+//! `RevPiInputs` and `RevPiOutputs` are generated plain structs, named after
+//! the main struct, with a public field for every input or output that has
+//! methods, see `skip_unexported`. The fields are named like the methods
+//! without `get_`, names that aren't valid identifiers get a leading `_`:
+//! ```ignore
+//! struct RevPiInputs {
+//!     pub RevPiStatus: u8,
+//! }
+//!
+//! struct RevPiOutputs {
+//!     pub RevPiLED: u8,
+//!     pub RS485ErrorLimit1: u16,
+//! }
+//! ```
+//! Adjacent variables are read or written with a single call, so a scan cycle
+//! usually needs one read and one write per device instead of one call per
+//! variable. Bytes containing single bit outputs are read before writing
+//! them, so bits that aren't part of `RevPiOutputs` keep their value, but
+//! might miss changes made by others in between.
+//!
+//! # Address constants
+//! `revpi_offsets!(RevPi, "/etc/revpi/config.rsc")` only generates the
+//! addresses, so the raw layer or code without the `revpi` crate can use them
+//! at compile time:
+//! ```ignore
+//! struct RevPi;
+//!
+//! impl RevPi {
+//!     pub const REV_PI_LED_ADDR: u16 = 6;
+//!     pub const REV_PI_LED_BIT: u8 = 0;
+//!     pub const REV_PI_LED_LEN: u16 = 8;
+//!     ...
+//! }
+//! ```
+//! The constants are named like the ones in `names`. `_BIT` is the bit inside
+//! the byte at `_ADDR` and only differs from `0` for single bits, `_LEN` is
+//! the length in bits. Of the options, only `skip_unexported` has an effect.
+//!
+//! # Deriving ProcessImage
+//! `#[derive(ProcessImage)]` binds the fields of your own struct to variables,
+//! without reading an rsc file at compile time. The names are looked up at
+//! runtime by the `PiControl` given to `read_from` and `write_to`:
+//! ```ignore
+//! use revpi::picontrol::{PiControl, ProcessImage};
+//!
+//! #[derive(ProcessImage)]
+//! struct Machine {
+//!     #[pi(name = "RevPiLED")]
+//!     led: u8,
+//!     #[pi(name = "I_1", read_only)]
+//!     door_open: bool,
+//!     #[pi(skip)]
+//!     cycles: u64,
+//! }
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut machine = Machine::read_from(&pi).unwrap();
+//! machine.led = machine.door_open as u8;
+//! machine.write_to(&pi).unwrap();
+//! ```
+//! Fields are bound to the variable named like the field unless `name` is
+//! given. Their types have to implement `FromValue` and `Into<Value>`.
+//! `read_only` fields are only read, e.g. inputs, and `skip` fields are
+//! neither read nor written, but set to their default by `read_from`.
+//! `#[pi(crate = "::my_revpi")]` on the struct sets the path to the `revpi`
+//! crate.
+//!
+//! # Grouped output
+//! Large configs with many modules yield hundreds of functions on a single
+//! struct. `revpi!(RevPi, grouped)` instead generates one struct per device,
+//! named after the main struct and the position of the device, and a field
+//! for each of them, named after the device in snake case. Names starting
+//! with a digit get `device_` prepended. If the name isn't unique or no valid
+//! identifier, the position is appended. With a DIO named
+//! `"DIO 1"` at position 32, this yields:
+//! ```ignore
+//! struct RevPi {
+//!     pub rev_pi_core_3_3_s: RevPiDevice0,
+//!     pub dio_1: RevPiDevice32,
+//! }
+//!
+//! impl RevPi {
+//!     pub fn new() -> Result<Self, PiControlError> {...}
+//!     pub fn with_backend(backend: Arc<dyn Backend>) -> Self {...}
+//! }
+//!
+//! struct RevPiDevice32 {...}
+//!
+//! impl RevPiDevice32 {
+//!     pub fn get_I_1(&self) -> Result<bool, PiControlError> {...}
+//!     ...
+//! }
+//! ```
+//! so inputs are read with `revpi.dio_1.get_I_1()`. All devices share a single
+//! backend. Every device struct has its own `read_inputs` and `write_outputs`,
+//! e.g. returning `RevPiDevice32Inputs`. `names` is generated the same way as
+//! before.
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote};
-use revpi_rsc::{InOutMem, RSC};
-use serde_json;
-use std::fs::File;
-use syn::{parse::Parse, parse_macro_input, Ident, LitStr};
+use revpi_rsc::{Device, InOutMem, RSC};
+use std::{collections::BTreeMap, fs::File, io::Read};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Fields, Ident, Lit, LitStr, Meta,
+    NestedMeta, Path, Token,
+};
+
+#[cfg(test)]
+mod tests;
+
+// how the names of variables are converted for the names of methods
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Rename {
+    None,
+    SnakeCase,
+}
+
+// options that can follow the required arguments, each preceded by a comma
+struct Options {
+    krate: Path,
+    grouped: bool,
+    rename: Rename,
+    prefix: String,
+    suffix: String,
+    skip_unexported: bool,
+}
+
+impl Options {
+    // the part of the method names after `get_` and `set_` for `name`
+    fn method_name(&self, name: &str) -> String {
+        let name = match self.rename {
+            Rename::None => name.to_string(),
+            Rename::SnakeCase => snake_name(name),
+        };
+        format!("{}{}{}", self.prefix, name, self.suffix)
+    }
+
+    // whether methods are generated for `item`
+    fn has_methods(&self, item: &InOutMem) -> bool {
+        item.exported || !self.skip_unexported
+    }
+}
 
+// parses `, crate = <path>`, `, grouped`, `, rename = "<case>"`,
+// `, prefix = "<prefix>"`, `, suffix = "<suffix>"` and `, skip_unexported`
+// at the end of the input
+fn parse_options(input: ParseStream) -> syn::Result<Options> {
+    let mut options = Options {
+        krate: parse_quote!(::revpi),
+        grouped: false,
+        rename: Rename::None,
+        prefix: String::new(),
+        suffix: String::new(),
+        skip_unexported: false,
+    };
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.peek(Token![crate]) {
+            input.parse::<Token![crate]>()?;
+            input.parse::<Token![=]>()?;
+            options.krate = input.parse()?;
+            continue;
+        }
+        let option: Ident = input.parse()?;
+        match option.to_string().as_str() {
+            "grouped" => options.grouped = true,
+            "skip_unexported" => options.skip_unexported = true,
+            "rename" | "prefix" | "suffix" => {
+                input.parse::<Token![=]>()?;
+                let value: LitStr = input.parse()?;
+                match option.to_string().as_str() {
+                    "prefix" => options.prefix = value.value(),
+                    "suffix" => options.suffix = value.value(),
+                    _ => {
+                        options.rename = match value.value().as_str() {
+                            "none" => Rename::None,
+                            "snake_case" => Rename::SnakeCase,
+                            _ => {
+                                return Err(syn::Error::new(
+                                    value.span(),
+                                    "expected \"none\" or \"snake_case\"",
+                                ))
+                            }
+                        }
+                    }
+                }
+            }
+            _ => return Err(syn::Error::new(option.span(), "unknown option")),
+        }
+    }
+    Ok(options)
+}
+
+struct Input {
+    name: Ident,
+    options: Options,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Input {
+            name: input.parse()?,
+            options: parse_options(input)?,
+        })
+    }
+}
+
+// the literal is either the path to the rsc file or the json itself
 struct JsonInput {
     name: Ident,
-    path: LitStr,
+    lit: LitStr,
+    options: Options,
 }
 
 impl Parse for JsonInput {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        // revpi_offsets! separates the name and the path with a comma
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
         Ok(JsonInput {
-            name: input.parse()?,
-            path: input.parse()?,
+            name,
+            lit: input.parse()?,
+            options: parse_options(input)?,
         })
     }
 }
 
-fn u8_to_bit(b: u8) -> String {
+fn u8_to_bit(b: u8, krate: &Path) -> TokenStream2 {
     let bit = match b {
         0 => "Zero",
         1 => "One",
@@ -152,118 +414,754 @@ fn u8_to_bit(b: u8) -> String {
         7 => "Seven",
         _ => panic!("integer out of range of enum"),
     };
-    format!("revpi::picontrol::raw::Bit::{}", bit)
+    let bit = format_ident!("{}", bit);
+    quote!(#krate::picontrol::raw::Bit::#bit)
+}
+
+// absolute address and bit of the given InOutMem, bit positions can be
+// larger than 7
+fn address(mod_offset: u64, item: &InOutMem) -> (u16, u8) {
+    let bit = item.bit_position.unwrap_or(0);
+    ((mod_offset + item.offset + bit as u64 / 8) as u16, bit % 8)
+}
+
+// converts a name given in PiCtory to SCREAMING_SNAKE_CASE, e.g.
+// "RevPiLED" to "REV_PI_LED"
+fn const_name(name: &str) -> String {
+    let chars: Vec<char> = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let mut out = String::with_capacity(chars.len() + 4);
+    if chars.first().is_none_or(|c| c.is_ascii_digit()) {
+        out.push('_');
+    }
+    for (i, c) in chars.iter().enumerate() {
+        if i > 0 && c.is_ascii_uppercase() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            if prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out
+}
+
+// converts a name given in PiCtory to snake_case, e.g. "RevPiLED" to
+// "rev_pi_led" and "DIO 1" to "dio_1"
+fn snake_name(name: &str) -> String {
+    let name = const_name(name).to_ascii_lowercase();
+    let parts: Vec<_> = name.split('_').filter(|p| !p.is_empty()).collect();
+    parts.join("_")
+}
+
+// produces a constant holding the name of the given InOutMem
+fn name_const(item: &InOutMem) -> TokenStream2 {
+    let ident = format_ident!("{}", const_name(&item.name));
+    let name = &item.name;
+    quote!(pub const #ident: &str = #name;)
 }
 
 // produces a getter of the given InOutMem
 // since InOutMem only contains the offset inside the module, we also need
 // the module offset
-fn get_fn(mod_offset: u64, item: &InOutMem) -> TokenStream2 {
-    let name = format_ident!("get_{}", item.name);
-    let address = (mod_offset + item.offset) as u16;
-    let (ret, function, fnargs) = match item.bit_length {
-        1 => (
-            "bool",
-            "get_bit",
-            format!("{}, {}", address, u8_to_bit(item.bit_position.unwrap())),
-        ),
-        8 => ("u8", "get_byte", format!("{}", address)),
-        16 => ("u16", "get_word", format!("{}", address)),
-        32 => ("u32", "get_dword", format!("{}", address)),
-        _ => panic!("invalid bitlength"),
+fn get_fn(mod_offset: u64, item: &InOutMem, options: &Options) -> TokenStream2 {
+    let krate = &options.krate;
+    let name = format_ident!("get_{}", options.method_name(&item.name));
+    let (address, bit) = address(mod_offset, item);
+    let (ret, call) = match item.bit_length {
+        1 => {
+            let bit = u8_to_bit(bit, krate);
+            (quote!(bool), quote!(get_bit(&self.inner, #address, #bit)))
+        }
+        8 => (quote!(u8), quote!(get_byte(&self.inner, #address))),
+        16 => (quote!(u16), quote!(get_word(&self.inner, #address))),
+        32 => (quote!(u32), quote!(get_dword(&self.inner, #address))),
+        _ => unreachable!("bitlengths are checked before"),
     };
-
-    format!(
-        "pub fn {}() -> Result<{}, revpi::picontrol::raw::PiControRawError> {{
-    unsafe {{ self.inner.{}({}) }}
-}}",
-        name, ret, function, fnargs
+    quote!(
+        pub fn #name(&self) -> ::std::result::Result<#ret, #krate::picontrol::PiControlError> {
+            unsafe { #krate::picontrol::Backend::#call }
+        }
     )
-    .parse()
-    .unwrap()
 }
 
 // produces a setter of the given InOutMem
 // since InOutMem only contains the offset inside the module, we also need
 // the module offset
-fn set_fn(mod_offset: u64, item: &InOutMem) -> TokenStream2 {
-    let name = format_ident!("set_{}", item.name);
-    let address = (mod_offset + item.offset) as u16;
-    let (args, function, fnargs) = match item.bit_length {
-        1 => (
-            "bit: bool",
-            "set_bit",
-            format!(
-                "{}, {}, bit",
-                address,
-                u8_to_bit(item.bit_position.unwrap())
-            ),
+fn set_fn(mod_offset: u64, item: &InOutMem, options: &Options) -> TokenStream2 {
+    let krate = &options.krate;
+    let name = format_ident!("set_{}", options.method_name(&item.name));
+    let (address, bit) = address(mod_offset, item);
+    let (arg, call) = match item.bit_length {
+        1 => {
+            let bit = u8_to_bit(bit, krate);
+            (
+                quote!(bit: bool),
+                quote!(set_bit(&self.inner, #address, #bit, bit)),
+            )
+        }
+        8 => (
+            quote!(byte: u8),
+            quote!(set_byte(&self.inner, #address, byte)),
         ),
-        8 => ("byte: u8", "set_byte", format!("{}, byte", address)),
-        16 => ("word: u16", "set_word", format!("{}, word", address)),
-        32 => ("dword: u32", "set_dword", format!("{}, dword", address)),
-        _ => panic!("invalid bitlength"),
+        16 => (
+            quote!(word: u16),
+            quote!(set_word(&self.inner, #address, word)),
+        ),
+        32 => (
+            quote!(dword: u32),
+            quote!(set_dword(&self.inner, #address, dword)),
+        ),
+        _ => unreachable!("bitlengths are checked before"),
     };
-
-    format!(
-        "pub fn {}({}) -> Result<(), revpi::picontrol::raw::PiControlRawError> {{
-    unsafe {{ self.inner.{}({}) }}
-}}",
-        name, args, function, fnargs
+    quote!(
+        pub fn #name(&self, #arg) -> ::std::result::Result<(), #krate::picontrol::PiControlError> {
+            unsafe { #krate::picontrol::Backend::#call }
+        }
     )
-    .parse()
-    .unwrap()
 }
 
-// produce the struct and impl withe the given name from the given rsc
-fn from_json(rsc: &RSC, name: Ident) -> TokenStream2 {
+// produces the getters and setters of all variables of the given device
+fn device_fns(d: &Device, options: &Options) -> TokenStream2 {
     let mut functions = TokenStream2::default();
-    for d in rsc.devices.iter() {
-        for i in d.inp.values() {
-            functions.extend(get_fn(d.offset, i));
+    for i in d.inp.values().filter(|i| options.has_methods(i)) {
+        functions.extend(get_fn(d.offset, i, options));
+    }
+    for o in d.out.values().chain(d.mem.values()) {
+        if options.has_methods(o) {
+            functions.extend(get_fn(d.offset, o, options));
+            functions.extend(set_fn(d.offset, o, options));
         }
-        for o in d.inp.values() {
-            functions.extend(get_fn(d.offset, o));
-            functions.extend(set_fn(d.offset, o));
+    }
+    functions
+}
+
+// name of the field of `item` in the structs of `batch_fns`, prefixed with
+// `_` if the method name without `get_` isn't a valid identifier, e.g. a
+// keyword or starting with a digit
+fn field_name(item: &InOutMem, options: &Options) -> Ident {
+    let name = options.method_name(&item.name);
+    syn::parse_str(&name).unwrap_or_else(|_| format_ident!("_{}", name))
+}
+
+// bytes of the processimage transferred with a single read or write
+struct Region<'a> {
+    start: u16,
+    end: u16,
+    // whether a single bit lies in the region
+    bits: bool,
+    // address, bit and the item itself
+    items: Vec<(u16, u8, &'a InOutMem)>,
+}
+
+// produces a struct named `ty` with a field for every item and a function
+// `read_<kind>` or `write_<kind>` transferring all of them. Adjacent items are
+// read or written at once, bytes containing single bits are read before they
+// are written, so the other bits in them are kept.
+fn batch_fns(
+    items: &[(u64, &InOutMem)],
+    ty: &Ident,
+    kind: &str,
+    write: bool,
+    options: &Options,
+) -> (TokenStream2, TokenStream2) {
+    let krate = &options.krate;
+    let mut regions: Vec<Region> = Vec::new();
+    let mut sorted: Vec<_> = items
+        .iter()
+        .filter(|(_, item)| options.has_methods(item))
+        .map(|(offset, item)| {
+            let (address, bit) = address(*offset, item);
+            (address, bit, *item)
+        })
+        .collect();
+    sorted.sort_by_key(|(address, bit, _)| (*address, *bit));
+    for (address, bit, item) in sorted {
+        let end = address + (item.bit_length as u16).div_ceil(8);
+        let is_bit = item.bit_length == 1;
+        match regions.last_mut() {
+            Some(last) if address <= last.end => {
+                last.end = last.end.max(end);
+                last.bits |= is_bit;
+                last.items.push((address, bit, item));
+            }
+            _ => regions.push(Region {
+                start: address,
+                end,
+                bits: is_bit,
+                items: vec![(address, bit, item)],
+            }),
         }
-        for m in d.mem.values() {
-            functions.extend(get_fn(d.offset, m));
-            functions.extend(set_fn(d.offset, m));
+    }
+    let mut fields = TokenStream2::default();
+    let mut blocks = TokenStream2::default();
+    for Region {
+        start,
+        end,
+        bits,
+        items,
+    } in regions
+    {
+        let len = (end - start) as usize;
+        let mut body = TokenStream2::default();
+        for (address, bit, item) in items {
+            let field = field_name(item, options);
+            let i = (address - start) as usize;
+            let (ty, get, set) = match item.bit_length {
+                1 => (
+                    quote!(bool),
+                    quote!((buf[#i] >> #bit) & 1 == 1),
+                    quote!(buf[#i] = (buf[#i] & !(1 << #bit)) | ((values.#field as u8) << #bit);),
+                ),
+                8 => (
+                    quote!(u8),
+                    quote!(buf[#i]),
+                    quote!(buf[#i] = values.#field;),
+                ),
+                16 => (
+                    quote!(u16),
+                    quote!(u16::from_le_bytes([buf[#i], buf[#i + 1]])),
+                    quote!(buf[#i..#i + 2].copy_from_slice(&values.#field.to_le_bytes());),
+                ),
+                32 => (
+                    quote!(u32),
+                    quote!(u32::from_le_bytes([buf[#i], buf[#i + 1], buf[#i + 2], buf[#i + 3]])),
+                    quote!(buf[#i..#i + 4].copy_from_slice(&values.#field.to_le_bytes());),
+                ),
+                _ => unreachable!("bitlengths are checked before"),
+            };
+            let doc = &item.name;
+            fields.extend(quote!(#[doc = #doc] pub #field: #ty,));
+            body.extend(match write {
+                true => set,
+                false => quote!(values.#field = #get;),
+            });
         }
+        let get_bytes = quote!(
+            unsafe { #krate::picontrol::Backend::get_bytes(&self.inner, #start, &mut buf) }?;
+        );
+        blocks.extend(match (write, bits) {
+            (false, _) => quote!({
+                let mut buf = [0u8; #len];
+                #get_bytes
+                #body
+            }),
+            (true, true) => quote!({
+                let mut buf = [0u8; #len];
+                #get_bytes
+                #body
+                unsafe { #krate::picontrol::Backend::set_bytes(&self.inner, #start, &buf) }?;
+            }),
+            (true, false) => quote!({
+                let mut buf = [0u8; #len];
+                #body
+                unsafe { #krate::picontrol::Backend::set_bytes(&self.inner, #start, &buf) }?;
+            }),
+        });
+    }
+    let struct_doc = format!(
+        "{}{} of [`{}`], see `{}_{}`",
+        kind[..1].to_uppercase(),
+        &kind[1..],
+        ty,
+        if write { "write" } else { "read" },
+        kind
+    );
+    let structure = quote!(
+        #[doc = #struct_doc]
+        #[allow(non_snake_case)]
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        struct #ty {
+            #fields
+        }
+    );
+    let function = match write {
+        true => {
+            let name = format_ident!("write_{}", kind);
+            quote!(
+                pub fn #name(&self, values: &#ty) -> ::std::result::Result<(), #krate::picontrol::PiControlError> {
+                    #blocks
+                    ::std::result::Result::Ok(())
+                }
+            )
+        }
+        false => {
+            let name = format_ident!("read_{}", kind);
+            quote!(
+                pub fn #name(&self) -> ::std::result::Result<#ty, #krate::picontrol::PiControlError> {
+                    let mut values = #ty::default();
+                    #blocks
+                    ::std::result::Result::Ok(values)
+                }
+            )
+        }
+    };
+    (structure, function)
+}
+
+// produces the structs for the inputs and outputs of `devices` and the
+// functions reading and writing them, see `batch_fns`
+fn io_fns(devices: &[&Device], name: &Ident, options: &Options) -> (TokenStream2, TokenStream2) {
+    let inputs: Vec<_> = devices
+        .iter()
+        .flat_map(|d| d.inp.values().map(move |item| (d.offset, item)))
+        .collect();
+    let outputs: Vec<_> = devices
+        .iter()
+        .flat_map(|d| d.out.values().map(move |item| (d.offset, item)))
+        .collect();
+    let (mut structs, mut functions) = batch_fns(
+        &inputs,
+        &format_ident!("{}Inputs", name),
+        "inputs",
+        false,
+        options,
+    );
+    let (structure, function) = batch_fns(
+        &outputs,
+        &format_ident!("{}Outputs", name),
+        "outputs",
+        true,
+        options,
+    );
+    structs.extend(structure);
+    functions.extend(function);
+    (structs, functions)
+}
+
+// converts the name of a device to a field name, e.g. "DIO 1" to "dio_1".
+// Names starting with a digit get `device_` prepended, names that aren't
+// unique or no valid identifier get the position appended.
+fn field_names(devices: &[Device]) -> Vec<Ident> {
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| snake_name(&d.name))
+        .map(
+            |name| match name.starts_with(|c: char| c.is_ascii_digit()) {
+                true => format!("device_{}", name),
+                false => name,
+            },
+        )
+        .collect();
+    devices
+        .iter()
+        .zip(names.iter())
+        .map(|(d, name)| {
+            let unique = names.iter().filter(|n| *n == name).count() == 1;
+            match syn::parse_str::<Ident>(name) {
+                Ok(ident) if unique => ident,
+                _ if name.is_empty() => format_ident!("device_{}", d.position),
+                _ => format_ident!("{}_{}", name, d.position),
+            }
+        })
+        .collect()
+}
+
+// produces the fields holding one struct per device and these structs
+fn grouped(
+    rsc: &RSC,
+    name: &Ident,
+    options: &Options,
+) -> (TokenStream2, TokenStream2, TokenStream2) {
+    let krate = &options.krate;
+    let mut fields = TokenStream2::default();
+    let mut init = TokenStream2::default();
+    let mut structs = TokenStream2::default();
+    for (d, field) in rsc.devices.iter().zip(field_names(&rsc.devices)) {
+        let ty = format_ident!("{}Device{}", name, d.position);
+        let functions = device_fns(d, options);
+        let (io_structs, io_functions) = io_fns(&[d], &ty, options);
+        let doc = format!(
+            "Variables of the device {:?} at position {}",
+            d.name, d.position
+        );
+        fields.extend(quote!(#[doc = #doc] pub #field: #ty,));
+        init.extend(quote!(#field: #ty { inner: ::std::sync::Arc::clone(&backend) },));
+        structs.extend(quote!(
+            #[doc = #doc]
+            struct #ty {
+                inner: ::std::sync::Arc<dyn #krate::picontrol::Backend>,
+            }
+            impl #ty {
+                #functions
+                #io_functions
+            }
+            #io_structs
+        ));
     }
+    (fields, init, structs)
+}
+
+// produce the struct and impl withe the given name from the given rsc
+fn from_json(rsc: &RSC, name: Ident, options: &Options) -> TokenStream2 {
+    let krate = &options.krate;
+    let mut names = TokenStream2::default();
+    for var in rsc.devices.iter().flat_map(Device::variables) {
+        names.extend(name_const(var));
+    }
+    let names = quote!(
+        /// Names of all variables in the rsc file
+        pub mod names {
+            #names
+        }
+    );
+    if options.grouped {
+        let (fields, init, structs) = grouped(rsc, &name, options);
+        return quote!(struct #name {
+            #fields
+        }
+        impl #name {
+            pub fn new() -> ::std::result::Result<Self, #krate::picontrol::PiControlError> {
+                let raw = #krate::picontrol::raw::PiControlRaw::new()?;
+                ::std::result::Result::Ok(Self::with_backend(::std::sync::Arc::new(raw)))
+            }
+
+            pub fn with_backend(backend: ::std::sync::Arc<dyn #krate::picontrol::Backend>) -> Self {
+                Self {
+                    #init
+                }
+            }
+        }
+        #structs
+        #names);
+    }
+    let functions: TokenStream2 = rsc.devices.iter().map(|d| device_fns(d, options)).collect();
+    let devices: Vec<_> = rsc.devices.iter().collect();
+    let (io_structs, io_functions) = io_fns(&devices, &name, options);
     quote!(struct #name {
-        inner: revpi::picontrol::raw::PiControlRaw,
+        inner: ::std::sync::Arc<dyn #krate::picontrol::Backend>,
     }
     impl #name {
-        pub fn new() -> Result<Self, revpi::picontrol::raw::PiControlRawError> {
-            Ok(Self {
-                inner: revpi::picontrol::raw::PiControlRaw::new()?,
-            })
+        pub fn new() -> ::std::result::Result<Self, #krate::picontrol::PiControlError> {
+            let raw = #krate::picontrol::raw::PiControlRaw::new()?;
+            ::std::result::Result::Ok(Self::with_backend(::std::sync::Arc::new(raw)))
+        }
+
+        pub fn with_backend(backend: ::std::sync::Arc<dyn #krate::picontrol::Backend>) -> Self {
+            Self { inner: backend }
         }
 
         #functions
-    })
-    .into()
+        #io_functions
+    }
+    #io_structs
+    #names)
+}
+
+// checks everything the generated code relies on, so errors point to the
+// macro invocation instead of panicking
+fn check(rsc: &RSC, options: &Options, span: Span) -> syn::Result<()> {
+    // method names of the struct, the devices' ones when grouped
+    let mut methods = BTreeMap::new();
+    // constants in `names`
+    let mut consts = BTreeMap::new();
+    for (position, var) in rsc
+        .devices
+        .iter()
+        .flat_map(|d| d.variables().map(move |var| (d.position, var)))
+    {
+        if !matches!(var.bit_length, 1 | 8 | 16 | 32) {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "variable {:?} has the unsupported bitlength {}",
+                    var.name, var.bit_length
+                ),
+            ));
+        }
+        let base = const_name(&var.name);
+        if let Some(other) = consts.insert(base.clone(), &var.name) {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "variables {:?} and {:?} both get the constant names::{}",
+                    other, var.name, base
+                ),
+            ));
+        }
+        if !options.has_methods(var) {
+            continue;
+        }
+        let method = format!("get_{}", options.method_name(&var.name));
+        if syn::parse_str::<Ident>(&method).is_err() {
+            return Err(syn::Error::new(
+                span,
+                format!("variable {:?} has no valid identifier as name", var.name),
+            ));
+        }
+        let scope = options.grouped.then_some(position);
+        if let Some(other) = methods.insert((scope, method.clone()), &var.name) {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "variables {:?} and {:?} both get the method {}",
+                    other, var.name, method
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// produces the struct with the getters and setters
+fn generate(rsc: &RSC, name: Ident, options: &Options, span: Span) -> syn::Result<TokenStream2> {
+    check(rsc, options, span)?;
+    Ok(from_json(rsc, name, options))
+}
+
+// produces a unit struct with the address, bit and length of every variable
+// as associated constants
+fn offsets(rsc: &RSC, name: Ident, options: &Options, span: Span) -> syn::Result<TokenStream2> {
+    let mut consts = TokenStream2::default();
+    let mut seen = BTreeMap::new();
+    for d in rsc.devices.iter() {
+        for var in d.variables().filter(|var| options.has_methods(var)) {
+            let base = const_name(&var.name);
+            if let Some(other) = seen.insert(base.clone(), &var.name) {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "variables {:?} and {:?} both get the constants {}_*",
+                        other, var.name, base
+                    ),
+                ));
+            }
+            let (address, bit) = address(d.offset, var);
+            let length = var.bit_length as u16;
+            let (addr_ident, bit_ident, len_ident) = (
+                format_ident!("{}_ADDR", base),
+                format_ident!("{}_BIT", base),
+                format_ident!("{}_LEN", base),
+            );
+            let addr_doc = format!("Address of the first byte of `{}`", var.name);
+            let bit_doc = format!(
+                "Bit of `{}` inside the byte at [`Self::{}`], `0` unless it is a single bit",
+                var.name, addr_ident
+            );
+            let len_doc = format!("Length of `{}` in bits", var.name);
+            consts.extend(quote!(
+                #[doc = #addr_doc]
+                pub const #addr_ident: u16 = #address;
+                #[doc = #bit_doc]
+                pub const #bit_ident: u8 = #bit;
+                #[doc = #len_doc]
+                pub const #len_ident: u16 = #length;
+            ));
+        }
+    }
+    Ok(quote!(
+        /// Addresses of all variables in the rsc file
+        struct #name;
+        impl #name {
+            #consts
+        }
+    ))
+}
+
+// produces code from an rsc, see `generate` and `offsets`
+type Generator = fn(&RSC, Ident, &Options, Span) -> syn::Result<TokenStream2>;
+
+// parses the rsc from `reader` and produces the code, `source` describes
+// where the json came from for error messages
+fn expand<R: Read>(
+    reader: R,
+    source: &str,
+    name: Ident,
+    options: &Options,
+    span: Span,
+    generator: Generator,
+) -> TokenStream {
+    let rsc: RSC = match serde_json::from_reader(reader) {
+        Ok(rsc) => rsc,
+        Err(e) => {
+            let msg = format!("can't parse {}: {}", source, e);
+            return syn::Error::new(span, msg).into_compile_error().into();
+        }
+    };
+    match generator(&rsc, name, options, span) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+// opens the rsc file at the path given in `input` and produces the code
+fn expand_file(input: JsonInput, generator: Generator) -> TokenStream {
+    let path = input.lit.value();
+    let span = input.lit.span();
+    match File::open(&path) {
+        Ok(f) => expand(f, &path, input.name, &input.options, span, generator),
+        Err(e) => {
+            let msg = format!("can't open {}: {}", path, e);
+            syn::Error::new(span, msg).into_compile_error().into()
+        }
+    }
 }
 
 /// See the [crate documentation](revpi_macro)
 #[proc_macro]
 pub fn revpi_from_json(stream: TokenStream) -> TokenStream {
+    expand_file(parse_macro_input!(stream as JsonInput), generate)
+}
+
+/// Generates only the addresses of the variables in the rsc file at the given
+/// path, see the [crate documentation](revpi_macro#address-constants)
+#[proc_macro]
+pub fn revpi_offsets(stream: TokenStream) -> TokenStream {
+    expand_file(parse_macro_input!(stream as JsonInput), offsets)
+}
+
+/// Same as [`revpi_from_json!`], but takes the content of the rsc file
+/// instead of its path, e.g. for tests:
+/// ```ignore
+/// revpi_from_json_str!(RevPi r#"{"App": ..., "Devices": [...]}"#);
+/// ```
+#[proc_macro]
+pub fn revpi_from_json_str(stream: TokenStream) -> TokenStream {
     let input = parse_macro_input!(stream as JsonInput);
-    let f = File::open(input.path.value()).unwrap();
-    let rsc: RSC = serde_json::from_reader(f).unwrap();
-    from_json(&rsc, input.name).into()
+    let json = input.lit.value();
+    let span = input.lit.span();
+    expand(
+        json.as_bytes(),
+        "the json",
+        input.name,
+        &input.options,
+        span,
+        generate,
+    )
 }
 
 /// See the [crate documentation](revpi_macro)
 #[proc_macro]
 pub fn revpi(stream: TokenStream) -> TokenStream {
-    let name = parse_macro_input!(stream as Ident);
+    let input = parse_macro_input!(stream as Input);
+    let span = input.name.span();
     // on older models the file can still under /opt so we gotta check for that
-    let f = match File::open("/etc/revpi/config.rsc") {
-        Ok(f) => f,
-        Err(_) => File::open("/opt/KUNBUS/config.rsc").unwrap(),
+    for path in ["/etc/revpi/config.rsc", "/opt/KUNBUS/config.rsc"] {
+        if let Ok(f) = File::open(path) {
+            return expand(f, path, input.name, &input.options, span, generate);
+        }
+    }
+    let msg = "can't open /etc/revpi/config.rsc or /opt/KUNBUS/config.rsc";
+    syn::Error::new(span, msg).into_compile_error().into()
+}
+
+// the items of all `#[pi(...)]` attributes
+fn pi_attrs(attrs: &[Attribute]) -> syn::Result<Vec<NestedMeta>> {
+    let mut items = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("pi")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => items.extend(list.nested),
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[pi(...)]")),
+        }
+    }
+    Ok(items)
+}
+
+// produces the implementation of `ProcessImage` for a struct with named fields
+fn process_image(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut krate: Path = parse_quote!(::revpi);
+    for item in pi_attrs(&input.attrs)? {
+        match item {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => match &nv.lit {
+                Lit::Str(path) => krate = path.parse()?,
+                lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+            },
+            item => return Err(syn::Error::new_spanned(item, "unknown attribute")),
+        }
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ProcessImage can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ProcessImage can only be derived for structs",
+            ))
+        }
     };
-    let rsc: RSC = serde_json::from_reader(f).unwrap();
-    from_json(&rsc, name).into()
+    let mut reads = TokenStream2::default();
+    let mut writes = TokenStream2::default();
+    for field in fields {
+        // named fields always have an identifier
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let mut name = ident.to_string();
+        let (mut skip, mut read_only) = (false, false);
+        for item in pi_attrs(&field.attrs)? {
+            match item {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => {
+                    match &nv.lit {
+                        Lit::Str(lit) => name = lit.value(),
+                        lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                    }
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => skip = true,
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("read_only") => {
+                    read_only = true
+                }
+                item => return Err(syn::Error::new_spanned(item, "unknown attribute")),
+            }
+        }
+        if skip {
+            reads.extend(quote!(#ident: ::std::default::Default::default(),));
+            continue;
+        }
+        reads.extend(quote!(
+            #ident: #krate::picontrol::PiControl::get_value_as::<#ty>(pi, #name)?,
+        ));
+        if !read_only {
+            writes.extend(quote!(
+                #krate::picontrol::PiControl::set_value(
+                    pi,
+                    #name,
+                    ::std::convert::Into::<#krate::picontrol::Value>::into(
+                        ::std::clone::Clone::clone(&self.#ident),
+                    ),
+                )?;
+            ));
+        }
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote!(
+        impl #impl_generics #krate::picontrol::ProcessImage for #ident #ty_generics #where_clause {
+            fn read_from(
+                pi: &#krate::picontrol::PiControl,
+            ) -> ::std::result::Result<Self, #krate::picontrol::PiControlError> {
+                ::std::result::Result::Ok(Self { #reads })
+            }
+
+            fn write_to(
+                &self,
+                pi: &#krate::picontrol::PiControl,
+            ) -> ::std::result::Result<(), #krate::picontrol::PiControlError> {
+                #writes
+                ::std::result::Result::Ok(())
+            }
+        }
+    ))
+}
+
+/// Derives `ProcessImage` for a struct, see the
+/// [crate documentation](revpi_macro#deriving-processimage)
+#[proc_macro_derive(ProcessImage, attributes(pi))]
+pub fn derive_process_image(stream: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(stream as DeriveInput);
+    match process_image(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
 }
@@ -0,0 +1,278 @@
+use super::{field_names, generate, offsets, parse_options, process_image, Options};
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::format_ident;
+use revpi_rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, Rap, RscBuilder, RSC};
+use std::collections::BTreeSet;
+use syn::parse::Parser;
+
+// RSC_JSON and RAP_JSON of the tests of revpi_rsc
+const RSC_JSON: &str = r#"{"App":{"name":"PiCtory","version":"2.0.6","saveTS":"20220523193431","language":"en","layout":{}},"Summary":{"inpTotal":2,"outTotal":0},"Devices":[{"GUID":"80941337-4242-beed-aaaa-d9df13376969","id":"device_RevPiCore_20220123_4_5_006","type":"BASE","productType":"95","position":"0","name":"RevPi Core/3/3+/S","bmk":"RevPi Core/3/3+/S","inpVariant":0,"outVariant":0,"comment":"This is a RevPiCore Device","offset":42,"inp":{"0":["a","0","8","0",true,"0000","",""],"1":["b","0","8","1",true,"0001","",""]},"out":{},"mem":{},"extend":{}}]}"#;
+const RAP_JSON: &str = r#"{"GUID":"adap.","id":"device_RevPiRO_20231018_1_0","type":"LEFT_RIGHT","productType":"137","position":"adap.","name":"RevPi RO","bmk":"RevPi RO","inpVariant":0,"outVariant":0,"comment":"This is a RevPi RO Device","offset":0,"inp":{"0":["Status","0","8","0",true,"0000","",""]},"out":{"0":["RelayOutput_1","0","1","1",true,"0001","","0"],"1":["RelayOutput_2","0","1","1",true,"0002","","1"]},"mem":{"0":["RelayCycleWarningThreshold_1","0","32","2",true,"0003","",""]},"extend":{}}"#;
+
+// the core of RSC_JSON with the RevPi RO of RAP_JSON at position 31, whose
+// second relay isn't exported. Adding the RO moves the core to offset 0, the
+// RO gets offset 2.
+fn fixture() -> RSC {
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    let rap = Rap::from_reader(RAP_JSON.as_bytes()).unwrap();
+    rsc.add_device(rap.device_builder().position(31)).unwrap();
+    rsc.devices[1].out.get_mut(&1).unwrap().exported = false;
+    rsc
+}
+
+fn options(input: &str) -> Options {
+    parse_options.parse_str(input).unwrap()
+}
+
+// all identifiers in `tokens`, including nested ones
+fn idents(tokens: TokenStream2) -> BTreeSet<String> {
+    let mut idents = BTreeSet::new();
+    for tree in tokens {
+        match tree {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => idents.extend(self::idents(group.stream())),
+            _ => (),
+        }
+    }
+    idents
+}
+
+// identifiers of the code generated for `RevPi` from the fixture
+fn expand(options: &str) -> BTreeSet<String> {
+    let tokens = generate(
+        &fixture(),
+        format_ident!("RevPi"),
+        &self::options(options),
+        Span::call_site(),
+    )
+    .unwrap();
+    idents(tokens)
+}
+
+// error message of generating `RevPi` from `rsc`
+fn error(rsc: &RSC, options: &str) -> String {
+    generate(
+        rsc,
+        format_ident!("RevPi"),
+        &self::options(options),
+        Span::call_site(),
+    )
+    .unwrap_err()
+    .to_string()
+}
+
+#[test]
+fn field_names_digits() {
+    let virt = |name, position| {
+        DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), name).position(position)
+    };
+    let rsc = RscBuilder::new()
+        .device(virt("1DIO", 32))
+        .device(virt("DIO 1", 33))
+        .device(virt("2 AIO", 34))
+        .device(virt("2 AIO", 35))
+        .device(virt("type", 36))
+        .device(virt("", 37))
+        .build()
+        .unwrap();
+    let names: Vec<_> = field_names(&rsc.devices)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "device_1_dio",
+            "dio_1",
+            "device_2_aio_34",
+            "device_2_aio_35",
+            "type_36",
+            "device_37"
+        ]
+    );
+}
+
+#[test]
+fn name_const_collision() {
+    let rsc = RscBuilder::new()
+        .device(
+            DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V")
+                .input(InOutMemBuilder::new("RevPiLED", 8))
+                .input(InOutMemBuilder::new("Rev_Pi_LED", 8)),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        error(&rsc, ""),
+        r#"variables "RevPiLED" and "Rev_Pi_LED" both get the constant names::REV_PI_LED"#
+    );
+}
+
+#[test]
+fn methods() {
+    let idents = expand("");
+    for ident in [
+        "get_a",
+        "get_Status",
+        "get_RelayOutput_1",
+        "set_RelayOutput_1",
+        "set_RelayOutput_2",
+        "set_RelayCycleWarningThreshold_1",
+        "RELAY_OUTPUT_1",
+    ] {
+        assert!(idents.contains(ident), "{} is missing", ident);
+    }
+    // inputs have no setters
+    assert!(!idents.contains("set_a"));
+}
+
+#[test]
+fn rename_prefix_suffix() {
+    let idents = expand(r#", rename = "snake_case", prefix = "io_", suffix = "_now""#);
+    assert!(idents.contains("get_io_relay_output_1_now"));
+    assert!(idents.contains("set_io_relay_cycle_warning_threshold_1_now"));
+    assert!(!idents.contains("get_RelayOutput_1"));
+    // the constants in names aren't renamed
+    assert!(idents.contains("RELAY_OUTPUT_1"));
+    // the fields of the batch structs are named like the methods
+    assert!(idents.contains("io_relay_output_1_now"));
+    let err = parse_options.parse_str(r#", rename = "camelCase""#);
+    assert!(err.is_err());
+}
+
+#[test]
+fn skip_unexported() {
+    let methods = expand(", skip_unexported");
+    assert!(methods.contains("get_RelayOutput_1"));
+    assert!(!methods.contains("get_RelayOutput_2"));
+    assert!(!methods.contains("set_RelayOutput_2"));
+    // the name constant is still generated
+    assert!(methods.contains("RELAY_OUTPUT_2"));
+    let tokens = offsets(
+        &fixture(),
+        format_ident!("RevPi"),
+        &options(", skip_unexported"),
+        Span::call_site(),
+    )
+    .unwrap();
+    let constants = idents(tokens);
+    assert!(constants.contains("RELAY_OUTPUT_1_ADDR"));
+    assert!(!constants.contains("RELAY_OUTPUT_2_ADDR"));
+}
+
+#[test]
+fn grouped() {
+    let idents = expand(", grouped");
+    for ident in [
+        "RevPiDevice0",
+        "RevPiDevice31",
+        "rev_pi_core_3_3_s",
+        "rev_pi_ro",
+        "RevPiDevice31Inputs",
+        "RevPiDevice31Outputs",
+        "get_Status",
+    ] {
+        assert!(idents.contains(ident), "{} is missing", ident);
+    }
+    // the batch structs are per device only
+    assert!(!idents.contains("RevPiInputs"));
+}
+
+#[test]
+fn batch_fns() {
+    let idents = expand("");
+    for ident in [
+        "RevPiInputs",
+        "RevPiOutputs",
+        "read_inputs",
+        "write_outputs",
+    ] {
+        assert!(idents.contains(ident), "{} is missing", ident);
+    }
+    let tokens = generate(
+        &fixture(),
+        format_ident!("RevPi"),
+        &options(""),
+        Span::call_site(),
+    )
+    .unwrap()
+    .to_string();
+    // the inputs of both devices are adjacent and read at once
+    let read = "let mut buf = [0u8 ; 3usize] ; unsafe { :: revpi :: picontrol :: Backend :: get_bytes (& self . inner , 0u16 , & mut buf) }";
+    assert!(tokens.contains(read), "{}", tokens);
+    // the byte with the relays is read before it is written
+    let write = "let mut buf = [0u8 ; 1usize] ; unsafe { :: revpi :: picontrol :: Backend :: get_bytes (& self . inner , 3u16 , & mut buf) }";
+    assert!(tokens.contains(write), "{}", tokens);
+    assert_eq!(tokens.matches("get_bytes").count(), 2);
+    assert_eq!(tokens.matches("set_bytes").count(), 1);
+    // memory isn't part of the batch structs
+    assert!(!tokens.contains("values . RelayCycleWarningThreshold_1"));
+}
+
+#[test]
+fn offset_constants() {
+    let tokens = offsets(
+        &fixture(),
+        format_ident!("RevPi"),
+        &options(""),
+        Span::call_site(),
+    )
+    .unwrap()
+    .to_string();
+    for constant in [
+        "B_ADDR : u16 = 1u16",
+        "RELAY_OUTPUT_2_ADDR : u16 = 3u16",
+        "RELAY_OUTPUT_2_BIT : u8 = 1u8",
+        "RELAY_CYCLE_WARNING_THRESHOLD_1_ADDR : u16 = 4u16",
+        "RELAY_CYCLE_WARNING_THRESHOLD_1_LEN : u16 = 32u16",
+    ] {
+        assert!(tokens.contains(constant), "{} is missing", constant);
+    }
+}
+
+#[test]
+fn derive_process_image() {
+    let input = syn::parse_str(
+        r#"
+        struct Machine {
+            #[pi(name = "RevPiLED")]
+            led: u8,
+            #[pi(read_only)]
+            door_open: bool,
+            #[pi(skip)]
+            cycles: u64,
+        }
+        "#,
+    )
+    .unwrap();
+    let tokens = process_image(&input).unwrap().to_string();
+    assert!(tokens.contains("impl :: revpi :: picontrol :: ProcessImage for Machine"));
+    assert!(tokens.contains(r#"get_value_as :: < u8 > (pi , "RevPiLED")"#));
+    assert!(tokens.contains(r#"get_value_as :: < bool > (pi , "door_open")"#));
+    assert!(tokens.contains("cycles : :: std :: default :: Default :: default ()"));
+    // only led is written
+    assert_eq!(tokens.matches("set_value").count(), 1);
+    assert!(tokens.contains("self . led"));
+
+    let krate = syn::parse_str(r#"#[pi(crate = "::my_revpi")] struct A { a: u8 }"#).unwrap();
+    let tokens = process_image(&krate).unwrap().to_string();
+    assert!(tokens.contains(":: my_revpi :: picontrol :: ProcessImage"));
+
+    for (input, message) in [
+        (
+            "enum A { B }",
+            "ProcessImage can only be derived for structs",
+        ),
+        (
+            "struct A(u8);",
+            "ProcessImage can only be derived for structs with named fields",
+        ),
+        ("struct A { #[pi(unknown)] a: u8 }", "unknown attribute"),
+        ("struct A { #[pi(name = 1)] a: u8 }", "expected a string"),
+    ] {
+        let input = syn::parse_str(input).unwrap();
+        assert_eq!(process_image(&input).unwrap_err().to_string(), message);
+    }
+}
@@ -0,0 +1,534 @@
+//! Creation of RSC files in code
+
+use super::{
+    App, Device, DeviceKind, InOutMem, Layout, Limits, ProductType, RscError, Summary, RSC,
+};
+use serde_json::Value;
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, BTreeSet},
+    hash::{BuildHasher, Hasher},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Builder for a single variable, see [`DeviceBuilder`]
+///
+/// The offset is calculated by [`RscBuilder::build`], unless it is set
+/// explicitly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InOutMemBuilder {
+    name: String,
+    bit_length: u8,
+    default: u64,
+    exported: bool,
+    comment: String,
+    offset: Option<u64>,
+    bit_position: Option<u8>,
+}
+
+impl InOutMemBuilder {
+    /// Creates a variable `name` with `bit_length` bits, which is exported
+    /// and defaults to `0`
+    pub fn new<S: Into<String>>(name: S, bit_length: u8) -> Self {
+        Self {
+            name: name.into(),
+            bit_length,
+            default: 0,
+            exported: true,
+            comment: String::new(),
+            offset: None,
+            bit_position: None,
+        }
+    }
+
+    /// Sets the default value
+    pub fn default_value(mut self, default: u64) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Sets whether the variable is exported, i.e. can be found by name
+    pub fn exported(mut self, exported: bool) -> Self {
+        self.exported = exported;
+        self
+    }
+
+    /// Sets the comment
+    pub fn comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Sets the offset relative to the device and the bit position inside
+    /// of it. The following variables are placed behind this one.
+    pub fn offset(mut self, offset: u64, bit_position: Option<u8>) -> Self {
+        self.offset = Some(offset);
+        self.bit_position = bit_position;
+        self
+    }
+}
+
+/// Builder for a single device, see [`RscBuilder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBuilder {
+    kind: DeviceKind,
+    product: ProductType,
+    name: String,
+    position: Option<u64>,
+    guid: Option<String>,
+    id: Option<String>,
+    bmk: Option<String>,
+    comment: String,
+    inp: Vec<InOutMemBuilder>,
+    out: Vec<InOutMemBuilder>,
+    mem: Vec<InOutMemBuilder>,
+    extend: Value,
+}
+
+impl DeviceBuilder {
+    /// Creates a device without any variables
+    pub fn new<S: Into<String>>(kind: DeviceKind, product: ProductType, name: S) -> Self {
+        Self {
+            kind,
+            product,
+            name: name.into(),
+            position: None,
+            guid: None,
+            id: None,
+            bmk: None,
+            comment: String::new(),
+            inp: Vec::new(),
+            out: Vec::new(),
+            mem: Vec::new(),
+            extend: Value::Object(Default::default()),
+        }
+    }
+
+    /// Sets the position, by default the first free position starting at `0`
+    pub fn position(mut self, position: u64) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    /// Sets the GUID, by default a random one is generated
+    pub fn guid<S: Into<String>>(mut self, guid: S) -> Self {
+        self.guid = Some(guid.into());
+        self
+    }
+
+    /// Sets the id, by default it is derived from the name and the position
+    pub fn id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets the equipment identifier, the name by default
+    pub fn bmk<S: Into<String>>(mut self, bmk: S) -> Self {
+        self.bmk = Some(bmk.into());
+        self
+    }
+
+    /// Sets the comment
+    pub fn comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Sets the device specific parameters, an empty object by default, e.g.
+    /// a [`ModbusExtend`](crate::ModbusExtend)
+    pub fn extend<V: Into<Value>>(mut self, extend: V) -> Self {
+        self.extend = extend.into();
+        self
+    }
+
+    /// Adds an input
+    pub fn input(mut self, var: InOutMemBuilder) -> Self {
+        self.inp.push(var);
+        self
+    }
+
+    /// Adds an output
+    pub fn output(mut self, var: InOutMemBuilder) -> Self {
+        self.out.push(var);
+        self
+    }
+
+    /// Adds a memory variable
+    pub fn memory(mut self, var: InOutMemBuilder) -> Self {
+        self.mem.push(var);
+        self
+    }
+}
+
+/// Builder for a whole [`RSC`]
+///
+/// Offsets of devices and variables, sort positions, the summary and GUIDs are
+/// calculated, so only the variables themselves have to be given. Devices
+/// are placed in the processimage in the order of their positions, variables
+/// in the order inputs, outputs, memory. Consecutive single bit variables
+/// share bytes.
+///
+/// # Examples
+/// ```
+/// use revpi_rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, RscBuilder};
+///
+/// let rsc = RscBuilder::new()
+///     .device(
+///         DeviceBuilder::new(DeviceKind::Base, ProductType::Core, "RevPi Core")
+///             .input(InOutMemBuilder::new("RevPiStatus", 8))
+///             .output(InOutMemBuilder::new("RevPiLED", 8)),
+///     )
+///     .device(
+///         DeviceBuilder::new(DeviceKind::LeftRight, ProductType::Dio, "DIO")
+///             .position(32)
+///             .input(InOutMemBuilder::new("I_1", 1))
+///             .input(InOutMemBuilder::new("I_2", 1))
+///             .output(InOutMemBuilder::new("O_1", 1)),
+///     )
+///     .build()
+///     .unwrap();
+/// assert_eq!(rsc.devices[1].offset, 2);
+/// assert_eq!(rsc.summary.inp_total, 2);
+/// assert_eq!(rsc.devices[1].out[&0].offset, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RscBuilder {
+    app: Option<App>,
+    devices: Vec<DeviceBuilder>,
+}
+
+impl Default for RscBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RscBuilder {
+    /// Creates an empty config
+    pub fn new() -> Self {
+        Self {
+            app: None,
+            devices: Vec::new(),
+        }
+    }
+
+    /// Sets the app section, by default it looks like one written by PiCtory
+    /// at the time of [`build`](Self::build)
+    pub fn app(mut self, app: App) -> Self {
+        self.app = Some(app);
+        self
+    }
+
+    /// Adds a device
+    pub fn device(mut self, device: DeviceBuilder) -> Self {
+        self.devices.push(device);
+        self
+    }
+
+    /// Creates the [`RSC`]
+    ///
+    /// # Errors
+    /// Returns a [`RscError::DuplicatePosition`] or
+    /// [`RscError::DuplicateName`] if positions or variable names aren't
+    /// unique and the error of [`Limits::check`] with the default limits if
+    /// e.g. the devices don't fit into the processimage.
+    pub fn build(self) -> Result<RSC, RscError> {
+        let mut positions = BTreeSet::new();
+        for device in self.devices.iter() {
+            if let Some(position) = device.position {
+                if !positions.insert(position) {
+                    return Err(RscError::DuplicatePosition(position));
+                }
+            }
+        }
+        let mut next_position = 0;
+        let mut devices = Vec::new();
+        let mut names = BTreeSet::new();
+        for builder in self.devices {
+            let position = match builder.position {
+                Some(position) => position,
+                None => {
+                    while positions.contains(&next_position) {
+                        next_position += 1;
+                    }
+                    positions.insert(next_position);
+                    next_position
+                }
+            };
+            let device = build_device(builder, position)?;
+            for var in device.variables() {
+                if !names.insert(var.name.clone()) {
+                    return Err(RscError::DuplicateName(var.name.clone()));
+                }
+            }
+            devices.push(device);
+        }
+        devices.sort_by_key(|d| d.position);
+        let mut rsc = RSC {
+            app: self.app.unwrap_or_else(default_app),
+            summary: Summary {
+                inp_total: 0,
+                out_total: 0,
+                extra: Default::default(),
+            },
+            devices,
+            connections: Vec::new(),
+            extra: Default::default(),
+        };
+        rsc.relayout();
+        Limits::default().check(&rsc)?;
+        Ok(rsc)
+    }
+}
+
+impl RSC {
+    /// Recalculates the offsets of the devices and the summary, e.g. after
+    /// devices were added or removed or variables changed.
+    ///
+    /// Like PiCtory, the devices are placed one after another by position,
+    /// each taking up the bytes up to the end of its last variable. The
+    /// offsets of the variables inside the devices are kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use revpi_rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, RscBuilder};
+    ///
+    /// let dio = |name| {
+    ///     DeviceBuilder::new(DeviceKind::LeftRight, ProductType::Dio, name)
+    ///         .input(InOutMemBuilder::new(format!("{}_in", name), 16))
+    /// };
+    /// let mut rsc = RscBuilder::new()
+    ///     .device(dio("a"))
+    ///     .device(dio("b"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(rsc.devices[1].offset, 2);
+    /// rsc.devices.remove(0);
+    /// rsc.relayout();
+    /// assert_eq!(rsc.devices[0].offset, 0);
+    /// assert_eq!(rsc.summary.inp_total, 2);
+    /// ```
+    pub fn relayout(&mut self) {
+        let mut order: Vec<_> = (0..self.devices.len()).collect();
+        order.sort_by_key(|&i| self.devices[i].position);
+        let mut offset = 0;
+        self.summary.inp_total = 0;
+        self.summary.out_total = 0;
+        for i in order {
+            let device = &mut self.devices[i];
+            device.offset = offset;
+            offset += end(device.variables());
+            self.summary.inp_total += len(device.inp.values()) as usize;
+            self.summary.out_total += len(device.out.values()) as usize;
+        }
+    }
+
+    /// Adds a device to an existing config, e.g. a virtual device used by an
+    /// application, and recalculates the offsets, see
+    /// [`relayout`](Self::relayout). Without a position, the device gets the
+    /// first free one starting at `0`. Devices behind the new one move, so it
+    /// should get a position behind the hardware, like PiCtory's virtual
+    /// devices from `64` onwards.
+    ///
+    /// # Errors
+    /// Same as [`RscBuilder::build`], the config is unchanged then.
+    ///
+    /// # Examples
+    /// ```
+    /// use revpi_rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, RscBuilder};
+    ///
+    /// let mut rsc = RscBuilder::new()
+    ///     .device(
+    ///         DeviceBuilder::new(DeviceKind::Base, ProductType::Core, "RevPi Core")
+    ///             .input(InOutMemBuilder::new("RevPiStatus", 8)),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// let device = rsc
+    ///     .add_device(
+    ///         DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "Shared")
+    ///             .position(64)
+    ///             .input(InOutMemBuilder::new("Setpoint", 16)),
+    ///     )
+    ///     .unwrap();
+    /// assert_eq!(device.offset, 1);
+    /// let taken = DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V");
+    /// assert!(rsc.add_device(taken.position(64)).is_err());
+    /// ```
+    pub fn add_device(&mut self, builder: DeviceBuilder) -> Result<&Device, RscError> {
+        let position = match builder.position {
+            Some(position) if self.devices.iter().any(|d| d.position == position) => {
+                return Err(RscError::DuplicatePosition(position))
+            }
+            Some(position) => position,
+            None => (0..)
+                .find(|p| self.devices.iter().all(|d| d.position != *p))
+                .unwrap_or_default(),
+        };
+        let device = build_device(builder, position)?;
+        let names: BTreeSet<_> = self
+            .devices
+            .iter()
+            .flat_map(|d| d.variables())
+            .map(|v| &v.name)
+            .collect();
+        if let Some(var) = device.variables().find(|var| names.contains(&var.name)) {
+            return Err(RscError::DuplicateName(var.name.clone()));
+        }
+        self.devices.push(device);
+        self.relayout();
+        if let Err(e) = Limits::default().check(self) {
+            self.devices.pop();
+            self.relayout();
+            return Err(e);
+        }
+        Ok(self.devices.last().unwrap())
+    }
+}
+
+// bytes from the start of the device up to the end of the last variable
+fn end<'a>(vars: impl Iterator<Item = &'a InOutMem>) -> u64 {
+    vars.map(|var| {
+        var.offset + (var.bit_position.unwrap_or(0) as u64 + var.bit_length as u64).div_ceil(8)
+    })
+    .max()
+    .unwrap_or(0)
+}
+
+// bytes from the first to the end of the last variable
+fn len<'a>(vars: impl Iterator<Item = &'a InOutMem> + Clone) -> u64 {
+    let start = vars.clone().map(|var| var.offset).min().unwrap_or(0);
+    end(vars) - start
+}
+
+fn build_device(builder: DeviceBuilder, position: u64) -> Result<Device, RscError> {
+    let mut sort_pos = 0;
+    let mut offset = 0;
+    let mut sections = [BTreeMap::new(), BTreeMap::new(), BTreeMap::new()];
+    for (section, vars) in sections
+        .iter_mut()
+        .zip([builder.inp, builder.out, builder.mem])
+    {
+        // single bits share a byte until another variable comes
+        let mut bit = None;
+        for (i, var) in vars.into_iter().enumerate() {
+            let is_bit = var.bit_length == 1;
+            let (var_offset, bit_position) = match (var.offset, bit) {
+                (Some(o), _) => (o, var.bit_position),
+                (None, Some(b)) if is_bit && b < 7 => (offset, Some(b + 1)),
+                // the byte of the previous bits is full or used up
+                (None, Some(_)) => (offset + 1, is_bit.then_some(0)),
+                (None, None) => (offset, is_bit.then_some(0)),
+            };
+            let bits = bit_position.unwrap_or(0) as u64;
+            (offset, bit) = match is_bit {
+                true => (var_offset + bits / 8, Some((bits % 8) as u8)),
+                false => (
+                    var_offset + (bits + var.bit_length as u64).div_ceil(8),
+                    None,
+                ),
+            };
+            let sort_pos_u16 = u16::try_from(sort_pos).map_err(|_| RscError::TooManyVariables {
+                position,
+                count: sort_pos,
+            })?;
+            section.insert(
+                i as u64,
+                InOutMem {
+                    name: var.name,
+                    default: var.default,
+                    bit_length: var.bit_length,
+                    offset: var_offset,
+                    exported: var.exported,
+                    sort_pos: sort_pos_u16,
+                    comment: var.comment,
+                    bit_position,
+                },
+            );
+            sort_pos += 1;
+        }
+        // a section ends behind the byte of its last bit
+        if bit.is_some() {
+            offset += 1;
+        }
+    }
+    let [inp, out, mem] = sections;
+    let id = builder.id.unwrap_or_else(|| {
+        let name: String = builder
+            .name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect();
+        format!("device_{}_{:03}", name, position)
+    });
+    Ok(Device {
+        guid: builder.guid.unwrap_or_else(guid),
+        id,
+        dev_type: builder.kind,
+        product_type: u16::from(builder.product) as u64,
+        position,
+        bmk: builder.bmk.unwrap_or_else(|| builder.name.clone()),
+        name: builder.name,
+        inp_variant: 0,
+        out_variant: 0,
+        comment: builder.comment,
+        offset: 0,
+        inp,
+        out,
+        mem,
+        extend: builder.extend,
+        active: None,
+        extra: Default::default(),
+    })
+}
+
+// random version 4 UUID
+fn guid() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    let (a, b) = (random(), random());
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        0x8000 | (b >> 48) & 0x3fff,
+        b & 0xffff_ffff_ffff
+    )
+}
+
+fn default_app() -> App {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    App {
+        name: "PiCtory".to_string(),
+        version: "2.0.6".to_string(),
+        save_ts: save_ts(secs),
+        language: "en".to_string(),
+        layout: Layout::default(),
+        extra: Default::default(),
+    }
+}
+
+// formats unix time in UTC like PiCtory, e.g. "20220523193431"
+pub(crate) fn save_ts(secs: u64) -> String {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,46 @@
+//! Connections between variables
+
+use super::{RscError, RSC};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// Link between two variables drawn in PiCtory
+///
+/// That means this is a struct for ID D in the [documentation](https://revolutionpi.de/tabellarische-auflistung-aller-json-attribute-einer-rsc-datei/)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Connection {
+    /// Name of the variable the value is taken from
+    pub source: String,
+    /// Name of the variable the value is written to
+    pub target: String,
+    /// Attributes without a field in this struct, which are written back
+    /// unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl RSC {
+    /// Checks that the source and target of every connection are variables
+    /// of a device.
+    ///
+    /// # Errors
+    /// Returns a [`RscError::UnknownVariable`] with the first name that
+    /// doesn't exist.
+    pub fn check_connections(&self) -> Result<(), RscError> {
+        let names: HashSet<&str> = self
+            .devices
+            .iter()
+            .flat_map(|d| d.variables())
+            .map(|var| var.name.as_str())
+            .collect();
+        for connection in self.connections.iter() {
+            for name in [&connection.source, &connection.target] {
+                if !names.contains(name.as_str()) {
+                    return Err(RscError::UnknownVariable(name.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
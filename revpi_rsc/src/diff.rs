@@ -0,0 +1,244 @@
+//! Differences between two RSC files
+
+use super::{Device, InOutMem, ProductType, RSC};
+use std::{collections::BTreeMap, fmt};
+
+/// A single difference found by [`diff`]
+///
+/// Devices are identified by their position and variables by their name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// There is a new device at `position`
+    DeviceAdded { position: u64, product: ProductType },
+    /// The device at `position` was removed
+    DeviceRemoved { position: u64, product: ProductType },
+    /// The device at `position` was replaced by one of another product type.
+    /// Its variables aren't compared.
+    DeviceReplaced {
+        position: u64,
+        old: ProductType,
+        new: ProductType,
+    },
+    /// The variable `name` was added
+    VariableAdded { position: u64, name: String },
+    /// The variable `name` was removed
+    VariableRemoved { position: u64, name: String },
+    /// The variable at the same place of the same device got another name
+    VariableRenamed {
+        position: u64,
+        old: String,
+        new: String,
+    },
+    /// The variable `name` got another address or bit position in the
+    /// processimage, e.g. because a device in front of it was added
+    VariableMoved {
+        name: String,
+        old: (u64, u8),
+        new: (u64, u8),
+    },
+    /// The length of the variable `name` in bits changed
+    LengthChanged { name: String, old: u8, new: u8 },
+    /// The default value of the variable `name` changed
+    DefaultChanged { name: String, old: u64, new: u64 },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Change::*;
+        match self {
+            DeviceAdded { position, product } => {
+                write!(f, "added {:?} at position {}", product, position)
+            }
+            DeviceRemoved { position, product } => {
+                write!(f, "removed {:?} at position {}", product, position)
+            }
+            DeviceReplaced { position, old, new } => write!(
+                f,
+                "replaced {:?} with {:?} at position {}",
+                old, new, position
+            ),
+            VariableAdded { position, name } => {
+                write!(f, "added {} to device {}", name, position)
+            }
+            VariableRemoved { position, name } => {
+                write!(f, "removed {} from device {}", name, position)
+            }
+            VariableRenamed { position, old, new } => {
+                write!(f, "renamed {} to {} in device {}", old, new, position)
+            }
+            VariableMoved { name, old, new } => write!(
+                f,
+                "moved {} from {}.{} to {}.{}",
+                name, old.0, old.1, new.0, new.1
+            ),
+            LengthChanged { name, old, new } => {
+                write!(f, "changed length of {} from {} to {}", name, old, new)
+            }
+            DefaultChanged { name, old, new } => {
+                write!(f, "changed default of {} from {} to {}", name, old, new)
+            }
+        }
+    }
+}
+
+/// Result of [`diff`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RscDiff {
+    /// All differences, sorted by the position of the device
+    pub changes: Vec<Change>,
+}
+
+impl RscDiff {
+    /// Returns whether both configs are equal as far as the processimage is
+    /// concerned
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for RscDiff {
+    /// Writes one change per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+// absolute address and bit of a variable, bit positions can be larger than 7
+fn location(device: &Device, var: &InOutMem) -> (u64, u8) {
+    let address = device.address_of(var).unwrap_or(u64::MAX);
+    (address, var.bit_position.unwrap_or(0) % 8)
+}
+
+fn diff_variables(old: &Device, new: &Device, changes: &mut Vec<Change>) {
+    let position = new.position;
+    let old_vars: BTreeMap<_, _> = old.variables().map(|v| (v.name.as_str(), v)).collect();
+    let new_vars: BTreeMap<_, _> = new.variables().map(|v| (v.name.as_str(), v)).collect();
+    // variables at the same place of a section that only exist in one of the
+    // configs were renamed
+    let mut renamed = Vec::new();
+    for (old_section, new_section) in [
+        (&old.inp, &new.inp),
+        (&old.out, &new.out),
+        (&old.mem, &new.mem),
+    ] {
+        for (key, old_var) in old_section.iter() {
+            match new_section.get(key) {
+                Some(new_var)
+                    if old_var.name != new_var.name
+                        && !new_vars.contains_key(old_var.name.as_str())
+                        && !old_vars.contains_key(new_var.name.as_str()) =>
+                {
+                    renamed.push((old_var, new_var));
+                }
+                _ => (),
+            }
+        }
+    }
+    let is_renamed_from = |name: &str| renamed.iter().any(|(o, _)| o.name == name);
+    let is_renamed_to = |name: &str| renamed.iter().any(|(_, n)| n.name == name);
+    for name in old_vars.keys().filter(|n| !new_vars.contains_key(*n)) {
+        if !is_renamed_from(name) {
+            changes.push(Change::VariableRemoved {
+                position,
+                name: name.to_string(),
+            });
+        }
+    }
+    for name in new_vars.keys().filter(|n| !old_vars.contains_key(*n)) {
+        if !is_renamed_to(name) {
+            changes.push(Change::VariableAdded {
+                position,
+                name: name.to_string(),
+            });
+        }
+    }
+    let pairs = old_vars
+        .iter()
+        .filter_map(|(name, o)| Some((*o, *new_vars.get(name)?)))
+        .chain(renamed.iter().copied());
+    for (o, n) in pairs {
+        if o.name != n.name {
+            changes.push(Change::VariableRenamed {
+                position,
+                old: o.name.clone(),
+                new: n.name.clone(),
+            });
+        }
+        let (old_location, new_location) = (location(old, o), location(new, n));
+        if old_location != new_location {
+            changes.push(Change::VariableMoved {
+                name: n.name.clone(),
+                old: old_location,
+                new: new_location,
+            });
+        }
+        if o.bit_length != n.bit_length {
+            changes.push(Change::LengthChanged {
+                name: n.name.clone(),
+                old: o.bit_length,
+                new: n.bit_length,
+            });
+        }
+        if o.default != n.default {
+            changes.push(Change::DefaultChanged {
+                name: n.name.clone(),
+                old: o.default,
+                new: n.default,
+            });
+        }
+    }
+}
+
+/// Compares two configs as far as the processimage is concerned, e.g. to find
+/// out whether the running config still matches the one an application was
+/// built for.
+///
+/// Comments, names of devices and the like are ignored.
+///
+/// # Examples
+/// ```no_run
+/// use revpi_rsc::{diff, RSC};
+/// use std::fs::File;
+///
+/// let expected: RSC = serde_json::from_reader(File::open("expected.rsc").unwrap()).unwrap();
+/// let running: RSC =
+///     serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+/// let diff = diff(&expected, &running);
+/// if !diff.is_empty() {
+///     eprintln!("the config has changed:\n{}", diff);
+/// }
+/// ```
+pub fn diff(old: &RSC, new: &RSC) -> RscDiff {
+    let old_devices: BTreeMap<_, _> = old.devices.iter().map(|d| (d.position, d)).collect();
+    let new_devices: BTreeMap<_, _> = new.devices.iter().map(|d| (d.position, d)).collect();
+    let mut positions: Vec<_> = old_devices.keys().chain(new_devices.keys()).collect();
+    positions.sort_unstable();
+    positions.dedup();
+    let mut changes = Vec::new();
+    for position in positions {
+        let position = *position;
+        match (old_devices.get(&position), new_devices.get(&position)) {
+            (Some(o), None) => changes.push(Change::DeviceRemoved {
+                position,
+                product: o.product(),
+            }),
+            (None, Some(n)) => changes.push(Change::DeviceAdded {
+                position,
+                product: n.product(),
+            }),
+            (Some(o), Some(n)) if o.product_type != n.product_type => {
+                changes.push(Change::DeviceReplaced {
+                    position,
+                    old: o.product(),
+                    new: n.product(),
+                })
+            }
+            (Some(o), Some(n)) => diff_variables(o, n, &mut changes),
+            (None, None) => (),
+        }
+    }
+    RscDiff { changes }
+}
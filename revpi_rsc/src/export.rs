@@ -0,0 +1,162 @@
+//! Flat address map of all variables
+
+use super::{RscError, RSC};
+use std::io::Write;
+
+/// Section of a device a variable is configured in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// `inp`
+    Input,
+    /// `out`
+    Output,
+    /// `mem`
+    Memory,
+}
+
+impl Section {
+    /// Returns the name used in exports, `"input"`, `"output"` or `"memory"`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::Input => "input",
+            Section::Output => "output",
+            Section::Memory => "memory",
+        }
+    }
+}
+
+/// A variable with its absolute address, returned by [`RSC::export_symbols`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    /// Name of the variable
+    pub name: String,
+    /// Position of the device
+    pub position: u64,
+    /// Name of the device
+    pub device: String,
+    /// Section of the device the variable is in
+    pub section: Section,
+    /// Address of the first byte in the processimage
+    pub address: u64,
+    /// Bit inside the byte at `address` for variables of a single bit
+    pub bit: Option<u8>,
+    /// Length in bits
+    pub bit_length: u8,
+    /// Default value
+    pub default: u64,
+    /// Whether the variable is exported
+    pub exported: bool,
+    /// Comment of the variable
+    pub comment: String,
+}
+
+// quotes a CSV field if needed, see RFC 4180
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl RSC {
+    /// Returns all variables of all devices with their absolute address,
+    /// sorted by address and bit.
+    ///
+    /// # Errors
+    /// Returns a [`RscError::OffsetOutOfRange`] if the address of a variable
+    /// overflows.
+    pub fn export_symbols(&self) -> Result<Vec<Symbol>, RscError> {
+        let mut symbols = Vec::new();
+        for device in self.devices.iter() {
+            let sections = [
+                (Section::Input, &device.inp),
+                (Section::Output, &device.out),
+                (Section::Memory, &device.mem),
+            ];
+            for (section, vars) in sections {
+                for var in vars.values() {
+                    let address =
+                        device
+                            .address_of(var)
+                            .ok_or_else(|| RscError::OffsetOutOfRange {
+                                position: device.position,
+                                name: var.name.clone(),
+                            })?;
+                    symbols.push(Symbol {
+                        name: var.name.clone(),
+                        position: device.position,
+                        device: device.name.clone(),
+                        section,
+                        address,
+                        bit: (var.bit_length == 1).then(|| var.bit_position.unwrap_or(0) % 8),
+                        bit_length: var.bit_length,
+                        default: var.default,
+                        exported: var.exported,
+                        comment: var.comment.clone(),
+                    });
+                }
+            }
+        }
+        symbols.sort_by_key(|s| (s.address, s.bit));
+        Ok(symbols)
+    }
+
+    /// Writes all variables as CSV with a header line, see
+    /// [`RSC::export_symbols`]
+    ///
+    /// # Errors
+    /// Returns an [`RscError`] if exporting or writing fails.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi_rsc::RSC;
+    /// # use std::{fs::File, io};
+    /// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+    /// rsc.write_csv(io::stdout()).unwrap();
+    /// ```
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<(), RscError> {
+        writeln!(
+            writer,
+            "name,position,device,section,address,bit,bit_length,default,exported,comment"
+        )?;
+        for s in self.export_symbols()? {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                csv_field(&s.name),
+                s.position,
+                csv_field(&s.device),
+                s.section.as_str(),
+                s.address,
+                s.bit.map_or(String::new(), |b| b.to_string()),
+                s.bit_length,
+                s.default,
+                s.exported,
+                csv_field(&s.comment)
+            )?;
+        }
+        Ok(writer.flush()?)
+    }
+
+    /// Writes all variables as a symbol table, one per line with address,
+    /// bit, length in bits, section and name, e.g.
+    /// `    6.0   8 output RevPiLED`. See [`RSC::export_symbols`].
+    ///
+    /// # Errors
+    /// Returns an [`RscError`] if exporting or writing fails.
+    pub fn write_symbol_table<W: Write>(&self, mut writer: W) -> Result<(), RscError> {
+        for s in self.export_symbols()? {
+            writeln!(
+                writer,
+                "{:>5}.{} {:>3} {:<6} {}",
+                s.address,
+                s.bit.unwrap_or(0),
+                s.bit_length,
+                s.section.as_str(),
+                s.name
+            )?;
+        }
+        Ok(writer.flush()?)
+    }
+}
@@ -0,0 +1,63 @@
+//! Layout of the PiCtory window
+
+use super::util::de_present;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Layout of the PiCtory window, ID A.5
+///
+/// PiCtory stores the state of its panes here, the sizes and whether they are
+/// collapsed or hidden. It only affects how PiCtory is displayed, the
+/// position of a device is [`Device::position`](crate::Device::position).
+///
+/// Panes that are missing are left out when writing, any other attributes are
+/// kept in [`Layout::extra`].
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Layout {
+    /// Top pane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub north: Option<Pane>,
+    /// Bottom pane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub south: Option<Pane>,
+    /// Right pane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub east: Option<Pane>,
+    /// Left pane
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub west: Option<Pane>,
+    /// Attributes without a field in this struct, which are written back
+    /// unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// State of a pane of the [`Layout`]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Pane {
+    /// Size in pixels, the height for north and south and the width for east
+    /// and west
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u32>,
+    /// Whether the pane starts collapsed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_closed: Option<bool>,
+    /// Whether the pane starts hidden
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init_hidden: Option<bool>,
+    /// Layouts nested in the pane by name, usually `"layout1"`
+    ///
+    /// PiCtory writes `null` for some panes, which is kept as `Some(None)`.
+    #[serde(
+        default,
+        deserialize_with = "de_present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub children: Option<Option<BTreeMap<String, Layout>>>,
+    /// Attributes without a field in this struct, which are written back
+    /// unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -18,17 +18,51 @@
 //! let rsc: RSC = serde_json::from_reader(f).unwrap();
 //! println!("{:?}", rsc);
 //! ```
+//!
+//! Attributes that aren't modeled are kept in the `extra` maps of the structs,
+//! so reading and writing a config back doesn't lose any of them.
+//!
+//! [`RSC::write_to`] replaces a config file atomically and keeps a backup of
+//! the old one.
+//!
+//! New configs can be created in code with the [`RscBuilder`], which
+//! calculates offsets and the summary. Two configs can be compared with
+//! [`diff`]. [`RSC::export_symbols`] lists all variables with their absolute
+//! address, which can be written as CSV or a symbol table for other tools.
+//! Device blocks for any module can be created from its description in the
+//! PiCtory catalog, see [`Rap`] and [`Catalog`].
+//!
+//! Files from untrusted sources should be read with
+//! [`RSC::from_reader_with_limits`], which rejects absurd values, see
+//! [`Limits`].
 
+mod builder;
+mod connection;
+mod diff;
+mod export;
+mod layout;
+mod limits;
+mod modbus;
+mod rap;
 #[cfg(test)]
 mod tests;
 mod util;
+mod write;
 
+pub use self::builder::{DeviceBuilder, InOutMemBuilder, RscBuilder};
+pub use self::connection::Connection;
+pub use self::diff::{diff, Change, RscDiff};
+pub use self::export::{Section, Symbol};
+pub use self::layout::{Layout, Pane};
+pub use self::limits::{Limits, RscError};
+pub use self::modbus::{ModbusAction, ModbusExtend};
+pub use self::rap::{Catalog, Rap, CATALOG_DIR};
 use self::util::{de_str_i, de_str_opt_i, ser_str_i};
 use serde::{
     ser::{Error as SerError, SerializeTuple},
     Deserialize, Serialize,
 };
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::BTreeMap;
 
 // unfortunately we have to implement custom serializers and deserializers because
@@ -50,9 +84,11 @@ pub struct App {
     /// ID A.4
     pub language: String,
     /// ID A.5
-    ///
-    /// Lower layers are omitted due to there being no need for them as of yet
-    pub layout: Value,
+    pub layout: Layout,
+    /// Attributes without a field in this struct, e.g. ones added by newer
+    /// versions of PiCtory, which are written back unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// Representing the summary
@@ -65,6 +101,10 @@ pub struct Summary {
     pub inp_total: usize,
     /// ID B.2
     pub out_total: usize,
+    /// Attributes without a field in this struct, e.g. ones added by newer
+    /// versions of PiCtory, which are written back unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 /// Representing the list found under `inp`, `out` and `mem`
@@ -124,6 +164,180 @@ impl Serialize for InOutMem {
     }
 }
 
+/// Role of a device, the `type` field of a device
+///
+/// Unknown types are kept as [`DeviceKind::Other`], so files written by newer
+/// versions of PiCtory can still be read and written back unchanged.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum DeviceKind {
+    /// Base module, e.g. a RevPi Core or Connect, `"BASE"`
+    Base,
+    /// Module that can be attached on both sides, e.g. IO modules,
+    /// `"LEFT_RIGHT"`
+    LeftRight,
+    /// Module that can only be attached on the right, e.g. gateways, `"RIGHT"`
+    Right,
+    /// Virtual device without hardware, `"VIRTUAL"`
+    Virtual,
+    /// Edge device, `"EDGE"`
+    Edge,
+    /// Any other type
+    Other(String),
+}
+
+impl DeviceKind {
+    /// Returns the string used for this kind in RSC files
+    pub fn as_str(&self) -> &str {
+        match self {
+            DeviceKind::Base => "BASE",
+            DeviceKind::LeftRight => "LEFT_RIGHT",
+            DeviceKind::Right => "RIGHT",
+            DeviceKind::Virtual => "VIRTUAL",
+            DeviceKind::Edge => "EDGE",
+            DeviceKind::Other(s) => s,
+        }
+    }
+}
+
+impl From<String> for DeviceKind {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "BASE" => DeviceKind::Base,
+            "LEFT_RIGHT" => DeviceKind::LeftRight,
+            "RIGHT" => DeviceKind::Right,
+            "VIRTUAL" => DeviceKind::Virtual,
+            "EDGE" => DeviceKind::Edge,
+            _ => DeviceKind::Other(s),
+        }
+    }
+}
+
+impl From<DeviceKind> for String {
+    fn from(kind: DeviceKind) -> Self {
+        match kind {
+            DeviceKind::Other(s) => s,
+            kind => kind.as_str().to_string(),
+        }
+    }
+}
+
+/// Product type of a module, the `productType` field of a device
+///
+/// The same numbers are reported by the driver as module type, so configured
+/// and detected modules can be compared directly:
+/// ```
+/// use revpi_rsc::ProductType;
+///
+/// assert_eq!(ProductType::from(96), ProductType::Dio);
+/// // the driver sets the highest bit for configured but missing modules
+/// assert_eq!(ProductType::from(0x8000 | 96), ProductType::Dio);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProductType {
+    GatewayCanOpen,
+    GatewayCcLink,
+    GatewayDeviceNet,
+    GatewayEtherCat,
+    GatewayEtherNetIp,
+    GatewayPowerlink,
+    GatewayProfibus,
+    GatewayProfinet,
+    GatewaySercos3,
+    GatewaySerial,
+    GatewayModbusRtu,
+    GatewayModbusTcp,
+    GatewayDmx,
+    Core,
+    Dio,
+    Di,
+    Do,
+    Aio,
+    Compact,
+    Connect,
+    ConCan,
+    ConMbus,
+    ConBt,
+    Mio,
+    Flat,
+    Ro,
+    /// Any other product type
+    Unknown(u16),
+}
+
+// set by the driver for configured modules that aren't connected
+const NOT_CONNECTED: u16 = 0x8000;
+
+impl From<u16> for ProductType {
+    fn from(v: u16) -> Self {
+        use ProductType::*;
+        match v & !NOT_CONNECTED {
+            71 => GatewayCanOpen,
+            72 => GatewayCcLink,
+            73 => GatewayDeviceNet,
+            74 => GatewayEtherCat,
+            75 => GatewayEtherNetIp,
+            76 => GatewayPowerlink,
+            77 => GatewayProfibus,
+            78 => GatewayProfinet,
+            81 => GatewaySercos3,
+            82 => GatewaySerial,
+            92 => GatewayModbusRtu,
+            93 => GatewayModbusTcp,
+            100 => GatewayDmx,
+            95 => Core,
+            96 => Dio,
+            97 => Di,
+            98 => Do,
+            103 => Aio,
+            104 => Compact,
+            105 => Connect,
+            109 => ConCan,
+            110 => ConMbus,
+            111 => ConBt,
+            118 => Mio,
+            135 => Flat,
+            137 => Ro,
+            v => Unknown(v),
+        }
+    }
+}
+
+impl From<ProductType> for u16 {
+    fn from(t: ProductType) -> Self {
+        use ProductType::*;
+        match t {
+            GatewayCanOpen => 71,
+            GatewayCcLink => 72,
+            GatewayDeviceNet => 73,
+            GatewayEtherCat => 74,
+            GatewayEtherNetIp => 75,
+            GatewayPowerlink => 76,
+            GatewayProfibus => 77,
+            GatewayProfinet => 78,
+            GatewaySercos3 => 81,
+            GatewaySerial => 82,
+            GatewayModbusRtu => 92,
+            GatewayModbusTcp => 93,
+            GatewayDmx => 100,
+            Core => 95,
+            Dio => 96,
+            Di => 97,
+            Do => 98,
+            Aio => 103,
+            Compact => 104,
+            Connect => 105,
+            ConCan => 109,
+            ConMbus => 110,
+            ConBt => 111,
+            Mio => 118,
+            Flat => 135,
+            Ro => 137,
+            Unknown(v) => v,
+        }
+    }
+}
+
 /// Representing a singular device
 ///
 /// That means this is a struct for section C in the [documentation](https://revolutionpi.de/tabellarische-auflistung-aller-json-attribute-einer-rsc-datei/)
@@ -136,7 +350,7 @@ pub struct Device {
     pub id: String,
     /// ID C.4
     #[serde(rename = "type")]
-    pub dev_type: String,
+    pub dev_type: DeviceKind,
     /// ID C.5
     #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
     #[serde(rename = "productType")]
@@ -166,11 +380,23 @@ pub struct Device {
     pub mem: BTreeMap<u64, InOutMem>,
     /// ID C.16
     ///
-    /// Lower layers are omitted due to there being no documentation for them
+    /// The layout depends on the device, for Modbus masters see
+    /// [`Device::modbus`]
     pub extend: Value,
     /// has no id
     #[serde(skip_serializing_if = "Option::is_none")]
     pub active: Option<bool>,
+    /// Attributes without a field in this struct, e.g. ones added by newer
+    /// versions of PiCtory, which are written back unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Device {
+    /// Returns `product_type` as [`ProductType`]
+    pub fn product(&self) -> ProductType {
+        u16::try_from(self.product_type).map_or(ProductType::Unknown(u16::MAX), ProductType::from)
+    }
 }
 
 /// Struct of the whole RSC file
@@ -183,4 +409,11 @@ pub struct RSC {
     pub summary: Summary,
     /// ID C
     pub devices: Vec<Device>,
+    /// ID D, empty if the file has none, see [`RSC::check_connections`]
+    #[serde(default)]
+    pub connections: Vec<Connection>,
+    /// Attributes without a field in this struct, e.g. ones added by newer
+    /// versions of PiCtory, which are written back unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
@@ -0,0 +1,207 @@
+//! Limits for RSC files from untrusted sources
+
+use super::{Device, InOutMem, RSC};
+use std::io::Read;
+use thiserror::Error;
+
+/// Error returned when reading an RSC file with [`Limits`], writing or
+/// building one
+#[derive(Debug, Error)]
+pub enum RscError {
+    /// The input was larger than [`Limits::max_size`]
+    #[error("RSC file is larger than {0} bytes")]
+    TooLarge(usize),
+    /// There were more than [`Limits::max_devices`] devices
+    #[error("RSC file has {0} devices, which is too many")]
+    TooManyDevices(usize),
+    /// The device at `position` had more than [`Limits::max_variables`]
+    /// variables
+    #[error("Device at position {position} has {count} variables, which is too many")]
+    TooManyVariables { position: u64, count: usize },
+    /// A variable of the device at `position` ended behind
+    /// [`Limits::max_offset`] or its offset overflowed
+    #[error("Variable {name} of device at position {position} lies outside of the processimage")]
+    OffsetOutOfRange { position: u64, name: String },
+    /// A string was longer than [`Limits::max_string_len`]
+    #[error("String starting with {0:?} is too long")]
+    StringTooLong(String),
+    /// Returned by [`RscBuilder::build`](crate::RscBuilder::build) if two
+    /// devices have the same position
+    #[error("There are multiple devices at position {0}")]
+    DuplicatePosition(u64),
+    /// Returned by [`RscBuilder::build`](crate::RscBuilder::build) if two
+    /// variables have the same name
+    #[error("There are multiple variables named {0}")]
+    DuplicateName(String),
+    /// Returned by [`RSC::check_connections`](crate::RSC::check_connections)
+    /// if a connection refers to a variable that doesn't exist
+    #[error("There is no variable named {0}")]
+    UnknownVariable(String),
+    /// Returned by [`ModbusExtend::validate`](crate::ModbusExtend::validate)
+    /// for the action with `id`
+    #[error("Modbus action {id} is invalid: {reason}")]
+    InvalidModbusAction { id: u16, reason: &'static str },
+    /// Wrapper around [`std::io::Error`]
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    /// Wrapper around [`serde_json::Error`]
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+}
+
+/// Limits an RSC file has to stay within
+///
+/// The defaults are generous for any real configuration, but keep a corrupted
+/// or malicious file from causing huge allocations or offsets outside of the
+/// processimage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Maximum size of the file in bytes, 16 MiB by default
+    pub max_size: usize,
+    /// Maximum number of devices, 64 by default like the driver
+    pub max_devices: usize,
+    /// Maximum number of variables of a single device, summed over `inp`,
+    /// `out` and `mem`, 1024 by default
+    pub max_variables: usize,
+    /// Maximum address any variable may end at, 4096 by default, the size of
+    /// the processimage
+    pub max_offset: u64,
+    /// Maximum length of any string in bytes, 1024 by default
+    pub max_string_len: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_size: 16 * 1024 * 1024,
+            max_devices: 64,
+            max_variables: 1024,
+            max_offset: 4096,
+            max_string_len: 1024,
+        }
+    }
+}
+
+impl Limits {
+    fn check_str(&self, s: &str) -> Result<(), RscError> {
+        if s.len() > self.max_string_len {
+            let start: String = s.chars().take(32).collect();
+            return Err(RscError::StringTooLong(start));
+        }
+        Ok(())
+    }
+
+    fn check_device(&self, device: &Device) -> Result<(), RscError> {
+        for s in [
+            device.guid.as_str(),
+            &device.id,
+            device.dev_type.as_str(),
+            &device.name,
+            &device.bmk,
+            &device.comment,
+        ] {
+            self.check_str(s)?;
+        }
+        let count = device.inp.len() + device.out.len() + device.mem.len();
+        if count > self.max_variables {
+            return Err(RscError::TooManyVariables {
+                position: device.position,
+                count,
+            });
+        }
+        for var in device.variables() {
+            self.check_str(&var.name)?;
+            self.check_str(&var.comment)?;
+            match device.end_of(var) {
+                Some(end) if end <= self.max_offset => (),
+                _ => {
+                    return Err(RscError::OffsetOutOfRange {
+                        position: device.position,
+                        name: var.name.clone(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `rsc` stays within these limits
+    ///
+    /// # Errors
+    /// Returns the first violated limit.
+    pub fn check(&self, rsc: &RSC) -> Result<(), RscError> {
+        for s in [
+            &rsc.app.name,
+            &rsc.app.version,
+            &rsc.app.save_ts,
+            &rsc.app.language,
+        ] {
+            self.check_str(s)?;
+        }
+        for connection in rsc.connections.iter() {
+            self.check_str(&connection.source)?;
+            self.check_str(&connection.target)?;
+        }
+        if rsc.devices.len() > self.max_devices {
+            return Err(RscError::TooManyDevices(rsc.devices.len()));
+        }
+        rsc.devices.iter().try_for_each(|d| self.check_device(d))
+    }
+}
+
+impl Device {
+    /// Returns all variables of the device, i.e. `inp`, `out` and `mem`
+    pub fn variables(&self) -> impl Iterator<Item = &InOutMem> {
+        self.inp
+            .values()
+            .chain(self.out.values())
+            .chain(self.mem.values())
+    }
+
+    /// Returns the address of the first byte of `var` in the processimage, or
+    /// `None` if it overflows.
+    pub fn address_of(&self, var: &InOutMem) -> Option<u64> {
+        self.offset
+            .checked_add(var.offset)?
+            .checked_add(var.bit_position.unwrap_or(0) as u64 / 8)
+    }
+
+    /// Returns the address behind the last byte of `var` in the processimage,
+    /// or `None` if it overflows.
+    pub fn end_of(&self, var: &InOutMem) -> Option<u64> {
+        self.address_of(var)?
+            .checked_add((var.bit_length as u64).div_ceil(8))
+    }
+}
+
+impl RSC {
+    /// Reads an RSC file from `reader` and checks it against `limits`.
+    ///
+    /// At most [`Limits::max_size`] bytes are read, so the allocations while
+    /// parsing stay bounded as well.
+    ///
+    /// # Errors
+    /// Returns an [`RscError`] if reading or parsing fails or if a limit is
+    /// violated.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use revpi_rsc::{Limits, RSC};
+    /// use std::fs::File;
+    ///
+    /// let f = File::open("/etc/revpi/config.rsc").unwrap();
+    /// let rsc = RSC::from_reader_with_limits(f, &Limits::default()).unwrap();
+    /// ```
+    pub fn from_reader_with_limits<R: Read>(reader: R, limits: &Limits) -> Result<Self, RscError> {
+        let mut bytes = Vec::new();
+        reader
+            .take(limits.max_size as u64 + 1)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() > limits.max_size {
+            return Err(RscError::TooLarge(limits.max_size));
+        }
+        let rsc: RSC = serde_json::from_slice(&bytes)?;
+        limits.check(&rsc)?;
+        Ok(rsc)
+    }
+}
@@ -0,0 +1,141 @@
+//! Parameters of the Modbus master devices
+
+use super::{
+    util::{de_str_i, ser_str_i},
+    Device, RscError,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+/// The `extend` of a Modbus TCP or RTU master, see [`Device::modbus`]
+///
+/// The connection parameters, e.g. the IP address of the slave, are memory
+/// variables of the device, only the actions are stored here.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct ModbusExtend {
+    /// Actions the master executes cyclically
+    #[serde(rename = "ModbusActions", default)]
+    pub actions: Vec<ModbusAction>,
+    /// Attributes without a field in this struct, which are written back
+    /// unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A row of the action table of a Modbus master
+///
+/// Like in the rest of the file, the numbers are wrapped in strings.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ModbusAction {
+    /// Number of the action, unique within the device
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub action_id: u16,
+    /// Unit identifier of the slave, `0` broadcasts a write to all slaves
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub slave_address: u8,
+    /// Modbus function code, one of 1, 2, 3, 4, 5, 6, 15 and 16
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub function_code: u8,
+    /// Address of the first register or coil in the slave
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub register_address: u16,
+    /// Number of registers or coils
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub quantity_of_registers: u16,
+    /// Interval in milliseconds
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    pub action_interval: u32,
+    /// Name of the first variable of the device that is read into or written
+    /// from
+    pub device_value: String,
+    /// Attributes without a field in this struct, which are written back
+    /// unchanged
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl ModbusAction {
+    /// Returns whether the function code reads from the slave
+    pub fn is_read(&self) -> bool {
+        matches!(self.function_code, 1..=4)
+    }
+
+    // the most registers or coils a single request can transfer
+    fn max_quantity(&self) -> Option<u16> {
+        match self.function_code {
+            1 | 2 => Some(2000),
+            3 | 4 => Some(125),
+            5 | 6 => Some(1),
+            15 => Some(1968),
+            16 => Some(123),
+            _ => None,
+        }
+    }
+}
+
+impl ModbusExtend {
+    /// Checks that the actions are valid for `device`: the ids are unique,
+    /// function codes, addresses and quantities are allowed by Modbus, the
+    /// interval isn't `0` and reads go to an input and writes come from an
+    /// output of `device`.
+    ///
+    /// # Errors
+    /// Returns a [`RscError::InvalidModbusAction`] for the first invalid
+    /// action.
+    pub fn validate(&self, device: &Device) -> Result<(), RscError> {
+        let mut ids = HashSet::new();
+        for action in self.actions.iter() {
+            let invalid = |reason| RscError::InvalidModbusAction {
+                id: action.action_id,
+                reason,
+            };
+            if !ids.insert(action.action_id) {
+                return Err(invalid("duplicate action id"));
+            }
+            let max = action
+                .max_quantity()
+                .ok_or_else(|| invalid("unsupported function code"))?;
+            if !(1..=max).contains(&action.quantity_of_registers) {
+                return Err(invalid("quantity out of range"));
+            }
+            if action.slave_address > 247 || (action.slave_address == 0 && action.is_read()) {
+                return Err(invalid("invalid slave address"));
+            }
+            if action.action_interval == 0 {
+                return Err(invalid("interval must not be 0"));
+            }
+            let vars = match action.is_read() {
+                true => &device.inp,
+                false => &device.out,
+            };
+            if !vars.values().any(|var| var.name == action.device_value) {
+                return Err(invalid(match action.is_read() {
+                    true => "device value is not an input of the device",
+                    false => "device value is not an output of the device",
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<ModbusExtend> for Value {
+    fn from(extend: ModbusExtend) -> Self {
+        // all keys are strings, so this can't fail
+        serde_json::to_value(extend).unwrap()
+    }
+}
+
+impl Device {
+    /// Parses `extend` as the parameters of a Modbus master. An empty
+    /// `extend` has no actions.
+    ///
+    /// # Errors
+    /// Returns a [`RscError::JsonError`] if `extend` doesn't have the layout
+    /// of a Modbus master.
+    pub fn modbus(&self) -> Result<ModbusExtend, RscError> {
+        Ok(ModbusExtend::deserialize(&self.extend)?)
+    }
+}
@@ -0,0 +1,198 @@
+//! Device descriptions of the PiCtory catalog
+
+use super::{DeviceBuilder, DeviceKind, InOutMem, InOutMemBuilder, ProductType, RscError};
+use crate::util::{de_str_i, ser_str_i};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// Directory PiCtory reads its catalog from
+pub const CATALOG_DIR: &str = "/var/www/revpi/pictory/resources/data/devices";
+
+/// Description of a module from the PiCtory catalog, a RAP file
+///
+/// A RAP file looks like a device of an RSC file, with the default names,
+/// offsets and parameters of the variables, but without a position. Placed
+/// into a config with [`Rap::device_builder`], it gives the device block
+/// PiCtory would create for the module.
+///
+/// # Examples
+/// ```
+/// use revpi_rsc::{Rap, RscBuilder};
+///
+/// let rap = Rap::from_reader(
+///     r#"{"GUID":"adap.","id":"device_RevPiDIO_20160818_1_0","type":"LEFT_RIGHT",
+///     "productType":"96","position":"adap.","name":"RevPi DIO","bmk":"RevPi DIO",
+///     "inpVariant":0,"outVariant":0,"comment":"This is a RevPiDIO Device","offset":0,
+///     "inp":{"0":["I_1","0","1","0",true,"0000","",""],
+///            "1":["I_2","0","1","0",true,"0001","","1"]},
+///     "out":{"0":["O_1","0","1","70",true,"0002","",""]},
+///     "mem":{},"extend":{}}"#
+///         .as_bytes(),
+/// )
+/// .unwrap();
+/// assert_eq!(rap.version(), Some((20160818, 1, 0)));
+/// let rsc = RscBuilder::new()
+///     .device(rap.device_builder().position(32))
+///     .build()
+///     .unwrap();
+/// assert_eq!(rsc.devices[0].id, "device_RevPiDIO_20160818_1_0");
+/// assert_eq!(rsc.devices[0].out[&0].offset, 70);
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Rap {
+    /// Catalog id, which devices created from it get as `id`
+    pub id: String,
+    #[serde(rename = "type")]
+    pub dev_type: DeviceKind,
+    #[serde(deserialize_with = "de_str_i", serialize_with = "ser_str_i")]
+    #[serde(rename = "productType")]
+    pub product_type: u64,
+    pub name: String,
+    pub bmk: String,
+    pub comment: String,
+    pub inp: BTreeMap<u64, InOutMem>,
+    pub out: BTreeMap<u64, InOutMem>,
+    pub mem: BTreeMap<u64, InOutMem>,
+    #[serde(default)]
+    pub extend: Value,
+    /// Attributes without a field in this struct, e.g. the placeholders
+    /// for the GUID and the position
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Rap {
+    /// Parses a RAP file
+    ///
+    /// # Errors
+    /// Returns a [`RscError::IoError`] if it can't be read and a
+    /// [`RscError::JsonError`] if it is malformed.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, RscError> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Returns `product_type` as [`ProductType`]
+    pub fn product(&self) -> ProductType {
+        u16::try_from(self.product_type).map_or(ProductType::Unknown(u16::MAX), ProductType::from)
+    }
+
+    /// Returns the date and the version of the description, taken from the
+    /// end of its id, e.g. `(20160818, 1, 0)` for
+    /// `"device_RevPiDIO_20160818_1_0"`
+    pub fn version(&self) -> Option<(u32, u32, u32)> {
+        let mut parts = self.id.rsplitn(4, '_');
+        let minor = parts.next()?.parse().ok()?;
+        let major = parts.next()?.parse().ok()?;
+        let date = parts.next()?.parse().ok()?;
+        Some((date, major, minor))
+    }
+
+    /// Renames all variables, e.g. to append a suffix when a config has
+    /// several modules of the same kind
+    pub fn rename_variables<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+        for var in self
+            .inp
+            .values_mut()
+            .chain(self.out.values_mut())
+            .chain(self.mem.values_mut())
+        {
+            var.name = f(&var.name);
+        }
+    }
+
+    /// Returns a builder for a device of this module, with the variables at
+    /// the offsets of the description, see [`RscBuilder`](crate::RscBuilder)
+    pub fn device_builder(&self) -> DeviceBuilder {
+        let mut builder = DeviceBuilder::new(self.dev_type.clone(), self.product(), &*self.name)
+            .id(&*self.id)
+            .bmk(&*self.bmk)
+            .comment(&*self.comment)
+            .extend(self.extend.clone());
+        for var in sorted(&self.inp) {
+            builder = builder.input(var);
+        }
+        for var in sorted(&self.out) {
+            builder = builder.output(var);
+        }
+        for var in sorted(&self.mem) {
+            builder = builder.memory(var);
+        }
+        builder
+    }
+}
+
+// the variables of a section in the order PiCtory shows them
+fn sorted(section: &BTreeMap<u64, InOutMem>) -> Vec<InOutMemBuilder> {
+    let mut vars: Vec<_> = section.values().collect();
+    vars.sort_by_key(|var| var.sort_pos);
+    vars.into_iter()
+        .map(|var| {
+            InOutMemBuilder::new(&*var.name, var.bit_length)
+                .default_value(var.default)
+                .exported(var.exported)
+                .comment(&*var.comment)
+                .offset(var.offset, var.bit_position)
+        })
+        .collect()
+}
+
+/// All RAP files of a directory, e.g. [`CATALOG_DIR`]
+///
+/// # Examples
+/// ```no_run
+/// use revpi_rsc::{Catalog, ProductType, RscBuilder, CATALOG_DIR};
+///
+/// let catalog = Catalog::from_dir(CATALOG_DIR).unwrap();
+/// let dio = catalog.find(ProductType::Dio).unwrap();
+/// let rsc = RscBuilder::new().device(dio.device_builder()).build().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Catalog {
+    /// The descriptions, sorted by id
+    pub raps: Vec<Rap>,
+}
+
+impl Catalog {
+    /// Reads all files ending in `.rap` in `dir` and its subdirectories
+    ///
+    /// # Errors
+    /// Returns a [`RscError::IoError`] if the directory or a file can't be
+    /// read and a [`RscError::JsonError`] if a file is malformed.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, RscError> {
+        let mut raps = Vec::new();
+        read_dir(dir.as_ref(), &mut raps)?;
+        raps.sort_by(|a: &Rap, b| a.id.cmp(&b.id));
+        Ok(Self { raps })
+    }
+
+    /// Returns the description with the id `id`
+    pub fn get(&self, id: &str) -> Option<&Rap> {
+        self.raps.iter().find(|rap| rap.id == id)
+    }
+
+    /// Returns the newest description of `product`
+    pub fn find(&self, product: ProductType) -> Option<&Rap> {
+        self.raps
+            .iter()
+            .filter(|rap| rap.product() == product)
+            .max_by_key(|rap| rap.version())
+    }
+}
+
+fn read_dir(dir: &Path, raps: &mut Vec<Rap>) -> Result<(), RscError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            read_dir(&path, raps)?;
+        } else if path.extension().is_some_and(|e| e == "rap") {
+            raps.push(Rap::from_reader(BufReader::new(File::open(&path)?))?);
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,8 @@
-use super::{App, Device, InOutMem, Summary};
+use super::{
+    builder::save_ts, diff, App, Catalog, Change, Connection, Device, DeviceBuilder, DeviceKind,
+    InOutMem, InOutMemBuilder, Layout, Limits, ModbusAction, ModbusExtend, ProductType, Rap,
+    RscBuilder, RscError, Section, Summary, RSC,
+};
 use std::collections::BTreeMap;
 
 #[test]
@@ -9,7 +13,8 @@ fn app_de() {
         version: "2.0.6".to_string(),
         save_ts: "20220523193431".to_string(),
         language: "en".to_string(),
-        layout: serde_json::Value::Object(serde_json::Map::<String, serde_json::Value>::new()),
+        layout: Layout::default(),
+        extra: Default::default(),
     };
     let app: App = serde_json::from_str(app_json).unwrap();
     assert_eq!(app, reference);
@@ -23,7 +28,8 @@ fn app_ser() {
         version: "2.0.6".to_string(),
         save_ts: "20220523193431".to_string(),
         language: "en".to_string(),
-        layout: serde_json::Value::Object(serde_json::Map::<String, serde_json::Value>::new()),
+        layout: Layout::default(),
+        extra: Default::default(),
     };
     let app_json = serde_json::to_string(&app).unwrap();
     assert_eq!(app_json, reference);
@@ -35,6 +41,7 @@ fn summary_de() {
     let reference = Summary {
         inp_total: 96,
         out_total: 27,
+        extra: Default::default(),
     };
     let summary: Summary = serde_json::from_str(summary_json).unwrap();
     assert_eq!(summary, reference);
@@ -46,6 +53,7 @@ fn summary_ser() {
     let summary = Summary {
         inp_total: 96,
         out_total: 27,
+        extra: Default::default(),
     };
     let summary_json = serde_json::to_string(&summary).unwrap();
     assert_eq!(summary_json, reference);
@@ -152,7 +160,7 @@ fn device_de() {
     let reference = Device {
         guid: "80941337-4242-beed-aaaa-d9df13376969".to_string(),
         id: "device_RevPiCore_20220123_4_5_006".to_string(),
-        dev_type: "BASE".to_string(),
+        dev_type: DeviceKind::Base,
         product_type: 95,
         position: 0,
         name: "RevPi Core/3/3+/S".to_string(),
@@ -166,6 +174,7 @@ fn device_de() {
         mem: BTreeMap::new(),
         extend: serde_json::Value::Object(serde_json::Map::<String, serde_json::Value>::new()),
         active: None,
+        extra: Default::default(),
     };
     let device: Device = serde_json::from_str(device_json).unwrap();
     assert_eq!(device, reference);
@@ -204,7 +213,7 @@ fn device_ser() {
     let device = Device {
         guid: "80941337-4242-beed-aaaa-d9df13376969".to_string(),
         id: "device_RevPiCore_20220123_4_5_006".to_string(),
-        dev_type: "BASE".to_string(),
+        dev_type: DeviceKind::Base,
         product_type: 95,
         position: 0,
         name: "RevPi Core/3/3+/S".to_string(),
@@ -218,7 +227,422 @@ fn device_ser() {
         mem: BTreeMap::new(),
         extend: serde_json::Value::Object(serde_json::Map::<String, serde_json::Value>::new()),
         active: None,
+        extra: Default::default(),
     };
     let device_json = serde_json::to_string(&device).unwrap();
     assert_eq!(device_json, reference);
 }
+
+const RSC_JSON: &str = r#"{"App":{"name":"PiCtory","version":"2.0.6","saveTS":"20220523193431","language":"en","layout":{}},"Summary":{"inpTotal":2,"outTotal":0},"Devices":[{"GUID":"80941337-4242-beed-aaaa-d9df13376969","id":"device_RevPiCore_20220123_4_5_006","type":"BASE","productType":"95","position":"0","name":"RevPi Core/3/3+/S","bmk":"RevPi Core/3/3+/S","inpVariant":0,"outVariant":0,"comment":"This is a RevPiCore Device","offset":42,"inp":{"0":["a","0","8","0",true,"0000","",""],"1":["b","0","8","1",true,"0001","",""]},"out":{},"mem":{},"extend":{}}]}"#;
+
+#[test]
+fn limits_ok() {
+    RSC::from_reader_with_limits(RSC_JSON.as_bytes(), &Limits::default()).unwrap();
+}
+
+#[test]
+fn limits_too_large() {
+    let limits = Limits {
+        max_size: 100,
+        ..Default::default()
+    };
+    let err = RSC::from_reader_with_limits(RSC_JSON.as_bytes(), &limits).unwrap_err();
+    assert!(matches!(err, RscError::TooLarge(100)));
+}
+
+#[test]
+fn limits_too_many_variables() {
+    let limits = Limits {
+        max_variables: 1,
+        ..Default::default()
+    };
+    let err = RSC::from_reader_with_limits(RSC_JSON.as_bytes(), &limits).unwrap_err();
+    assert!(matches!(
+        err,
+        RscError::TooManyVariables {
+            position: 0,
+            count: 2
+        }
+    ));
+}
+
+#[test]
+fn limits_offset_overflow() {
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    rsc.devices[0].offset = u64::MAX;
+    let err = Limits::default().check(&rsc).unwrap_err();
+    assert!(matches!(
+        err,
+        RscError::OffsetOutOfRange { position: 0, .. }
+    ));
+    rsc.devices[0].offset = 4095;
+    let err = Limits::default().check(&rsc).unwrap_err();
+    assert!(matches!(
+        err,
+        RscError::OffsetOutOfRange { position: 0, .. }
+    ));
+}
+
+#[test]
+fn limits_string_too_long() {
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    rsc.devices[0].comment = "a".repeat(2000);
+    let err = Limits::default().check(&rsc).unwrap_err();
+    assert!(matches!(err, RscError::StringTooLong(_)));
+}
+
+#[test]
+fn device_kind_other() {
+    let kind: DeviceKind = serde_json::from_str(r#""SOMETHING_NEW""#).unwrap();
+    assert_eq!(kind, DeviceKind::Other("SOMETHING_NEW".to_string()));
+    assert_eq!(serde_json::to_string(&kind).unwrap(), r#""SOMETHING_NEW""#);
+}
+
+#[test]
+fn product_type_roundtrip() {
+    let device: Device = serde_json::from_str::<RSC>(RSC_JSON).unwrap().devices[0].clone();
+    assert_eq!(device.product(), ProductType::Core);
+    assert_eq!(u16::from(ProductType::Core), 95);
+    assert_eq!(ProductType::from(4242), ProductType::Unknown(4242));
+}
+
+#[test]
+fn write_to_with_backup() {
+    let dir = std::env::temp_dir().join(format!("revpi_rsc_write_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.rsc");
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    assert_eq!(rsc.write_to(&path).unwrap(), None);
+    rsc.devices[0].comment = "changed".to_string();
+    let backup = rsc.write_to(&path).unwrap().unwrap();
+    let old: RSC = serde_json::from_reader(std::fs::File::open(backup).unwrap()).unwrap();
+    let new: RSC = serde_json::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(old.devices[0].comment, "This is a RevPiCore Device");
+    assert_eq!(new, rsc);
+    assert!(!dir.join("config.rsc.tmp").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn save_ts_format() {
+    assert_eq!(save_ts(0), "19700101000000");
+    assert_eq!(save_ts(1653334471), "20220523193431");
+}
+
+#[test]
+fn builder_offsets() {
+    let mut dio = DeviceBuilder::new(DeviceKind::LeftRight, ProductType::Dio, "DIO");
+    for i in 0..10 {
+        dio = dio.input(InOutMemBuilder::new(format!("I_{}", i), 1));
+    }
+    let rsc = RscBuilder::new()
+        .device(dio.output(InOutMemBuilder::new("O", 16)).position(32))
+        .device(
+            DeviceBuilder::new(DeviceKind::Base, ProductType::Core, "Core")
+                .input(InOutMemBuilder::new("Status", 8))
+                .memory(InOutMemBuilder::new("Mem", 32)),
+        )
+        .build()
+        .unwrap();
+    let core = &rsc.devices[0];
+    assert_eq!((core.position, core.offset), (0, 0));
+    assert_eq!(core.mem[&0].offset, 1);
+    assert_eq!(core.mem[&0].sort_pos, 1);
+    let dio = &rsc.devices[1];
+    assert_eq!((dio.position, dio.offset), (32, 5));
+    assert_eq!(dio.inp[&7].offset, 0);
+    assert_eq!(dio.inp[&7].bit_position, Some(7));
+    assert_eq!(dio.inp[&8].offset, 1);
+    assert_eq!(dio.inp[&8].bit_position, Some(0));
+    assert_eq!(dio.out[&0].offset, 2);
+    assert_eq!((rsc.summary.inp_total, rsc.summary.out_total), (3, 2));
+    // the result can be read back
+    let json = serde_json::to_string(&rsc).unwrap();
+    RSC::from_reader_with_limits(json.as_bytes(), &Limits::default()).unwrap();
+}
+
+#[test]
+fn builder_duplicates() {
+    let device = || DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V");
+    let err = RscBuilder::new()
+        .device(device().position(64))
+        .device(device().position(64))
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, RscError::DuplicatePosition(64)));
+    let err = RscBuilder::new()
+        .device(device().memory(InOutMemBuilder::new("a", 8)))
+        .device(device().memory(InOutMemBuilder::new("a", 8)))
+        .build()
+        .unwrap_err();
+    assert!(matches!(err, RscError::DuplicateName(name) if name == "a"));
+}
+
+#[test]
+fn add_device() {
+    let virt = |name| DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), name);
+    let mut rsc = RscBuilder::new()
+        .device(
+            DeviceBuilder::new(DeviceKind::Base, ProductType::Core, "Core")
+                .input(InOutMemBuilder::new("Status", 8)),
+        )
+        .device(virt("V").position(65).input(InOutMemBuilder::new("a", 8)))
+        .build()
+        .unwrap();
+    let added = rsc
+        .add_device(virt("W").position(64).input(InOutMemBuilder::new("b", 16)))
+        .unwrap();
+    assert_eq!((added.position, added.offset), (64, 1));
+    // the device behind moved
+    assert_eq!(rsc.devices[1].offset, 3);
+    assert_eq!(rsc.summary.inp_total, 4);
+    // without a position the first free one is taken
+    assert_eq!(rsc.add_device(virt("X")).unwrap().position, 1);
+    let before = rsc.clone();
+    let err = rsc
+        .add_device(virt("Y").input(InOutMemBuilder::new("a", 8)))
+        .unwrap_err();
+    assert!(matches!(err, RscError::DuplicateName(name) if name == "a"));
+    let err = rsc.add_device(virt("Z").position(64)).unwrap_err();
+    assert!(matches!(err, RscError::DuplicatePosition(64)));
+    assert_eq!(rsc, before);
+}
+
+#[test]
+fn diff_configs() {
+    let core = |status: &str| {
+        DeviceBuilder::new(DeviceKind::Base, ProductType::Core, "Core")
+            .input(InOutMemBuilder::new(status, 8))
+            .memory(InOutMemBuilder::new("Mem", 8))
+    };
+    let dio = |default| {
+        DeviceBuilder::new(DeviceKind::LeftRight, ProductType::Dio, "DIO")
+            .position(32)
+            .input(InOutMemBuilder::new("I_1", 1))
+            .output(InOutMemBuilder::new("O_1", 16).default_value(default))
+    };
+    let old = RscBuilder::new()
+        .device(core("Status"))
+        .device(dio(0))
+        .device(
+            DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V").position(64),
+        )
+        .build()
+        .unwrap();
+    assert!(diff(&old, &old).is_empty());
+    let new = RscBuilder::new()
+        .device(core("RevPiStatus"))
+        .device(
+            DeviceBuilder::new(DeviceKind::LeftRight, ProductType::Aio, "AIO")
+                .position(31)
+                .input(InOutMemBuilder::new("AIn_1", 16)),
+        )
+        .device(dio(1))
+        .build()
+        .unwrap();
+    let changes = diff(&old, &new).changes;
+    assert_eq!(
+        changes,
+        vec![
+            Change::VariableRenamed {
+                position: 0,
+                old: "Status".to_string(),
+                new: "RevPiStatus".to_string(),
+            },
+            Change::DeviceAdded {
+                position: 31,
+                product: ProductType::Aio,
+            },
+            Change::VariableMoved {
+                name: "I_1".to_string(),
+                old: (2, 0),
+                new: (4, 0),
+            },
+            Change::VariableMoved {
+                name: "O_1".to_string(),
+                old: (3, 0),
+                new: (5, 0),
+            },
+            Change::DefaultChanged {
+                name: "O_1".to_string(),
+                old: 0,
+                new: 1,
+            },
+            Change::DeviceRemoved {
+                position: 64,
+                product: ProductType::Unknown(102),
+            },
+        ]
+    );
+}
+
+#[test]
+fn unknown_fields_roundtrip() {
+    let json = RSC_JSON.strip_suffix('}').unwrap().to_string() + r#","Connections":[]}"#;
+    let json = json
+        .replace(r#""layout":{}"#, r#""layout":{},"theme":"dark""#)
+        .replace(r#""outTotal":0"#, r#""outTotal":0,"memTotal":4"#)
+        .replace(r#""extend":{}"#, r#""extend":{},"firmware":[1,2]"#);
+    let rsc: RSC = serde_json::from_str(&json).unwrap();
+    assert_eq!(rsc.app.extra["theme"], "dark");
+    assert_eq!(rsc.summary.extra["memTotal"], 4);
+    assert_eq!(rsc.devices[0].extra["firmware"], serde_json::json!([1, 2]));
+    let original: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(serde_json::to_value(&rsc).unwrap(), original);
+}
+
+#[test]
+fn layout_roundtrip() {
+    let json = r#"{"north":{"size":70,"initClosed":false,"initHidden":false},"south":{"size":386,"initClosed":false,"initHidden":false,"children":{"layout1":{"east":{"size":70,"initClosed":false,"initHidden":true}}}},"east":{"size":70,"initClosed":false,"initHidden":true,"children":null},"west":{"size":173,"initClosed":false,"initHidden":false,"children":{"layout1":{}}},"zoom":2}"#;
+    let mut layout: Layout = serde_json::from_str(json).unwrap();
+    assert_eq!(layout.north.as_ref().unwrap().size, Some(70));
+    assert_eq!(layout.north.as_ref().unwrap().children, None);
+    assert_eq!(layout.east.as_ref().unwrap().children, Some(None));
+    let south = layout
+        .south
+        .as_ref()
+        .unwrap()
+        .children
+        .clone()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        south["layout1"].east.as_ref().unwrap().init_hidden,
+        Some(true)
+    );
+    assert_eq!(layout.extra["zoom"], 2);
+    assert_eq!(serde_json::to_string(&layout).unwrap(), json);
+    layout.west.as_mut().unwrap().size = Some(200);
+    assert!(serde_json::to_string(&layout)
+        .unwrap()
+        .contains(r#""west":{"size":200,"#));
+}
+
+#[test]
+fn modbus_actions() {
+    let action = ModbusAction {
+        action_id: 1,
+        slave_address: 1,
+        function_code: 3,
+        register_address: 100,
+        quantity_of_registers: 1,
+        action_interval: 1000,
+        device_value: "Input_Word_1".to_string(),
+        extra: Default::default(),
+    };
+    let extend = ModbusExtend {
+        actions: vec![action.clone()],
+        extra: Default::default(),
+    };
+    let rsc = RscBuilder::new()
+        .device(
+            DeviceBuilder::new(
+                DeviceKind::Virtual,
+                ProductType::Unknown(24577),
+                "ModbusTCP Master",
+            )
+            .input(InOutMemBuilder::new("Input_Word_1", 16))
+            .output(InOutMemBuilder::new("Output_Word_1", 16))
+            .extend(extend.clone()),
+        )
+        .build()
+        .unwrap();
+    let device = &rsc.devices[0];
+    assert_eq!(device.extend["ModbusActions"][0]["FunctionCode"], "3");
+    assert_eq!(device.modbus().unwrap(), extend);
+    extend.validate(device).unwrap();
+
+    let write = ModbusAction {
+        action_id: 2,
+        function_code: 16,
+        ..action.clone()
+    };
+    let invalid = ModbusExtend {
+        actions: vec![action, write],
+        extra: Default::default(),
+    };
+    let err = invalid.validate(device).unwrap_err();
+    assert!(matches!(err, RscError::InvalidModbusAction { id: 2, .. }));
+}
+
+#[test]
+fn connections() {
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    assert!(rsc.connections.is_empty());
+    rsc.connections.push(Connection {
+        source: "a".to_string(),
+        target: "b".to_string(),
+        extra: Default::default(),
+    });
+    rsc.check_connections().unwrap();
+    let json = serde_json::to_string(&rsc).unwrap();
+    assert!(json.ends_with(r#""Connections":[{"source":"a","target":"b"}]}"#));
+    rsc.connections[0].target = "c".to_string();
+    let err = rsc.check_connections().unwrap_err();
+    assert!(matches!(err, RscError::UnknownVariable(name) if name == "c"));
+}
+
+#[test]
+fn export_symbols() {
+    let mut rsc: RSC = serde_json::from_str(RSC_JSON).unwrap();
+    rsc.devices[0].inp.get_mut(&1).unwrap().comment = "say \"hi\", twice".to_string();
+    let symbols = rsc.export_symbols().unwrap();
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(symbols[1].address, 43);
+    assert_eq!(symbols[1].section, Section::Input);
+    assert_eq!(symbols[1].bit, None);
+    let mut csv = Vec::new();
+    rsc.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[1], "a,0,RevPi Core/3/3+/S,input,42,,8,0,true,");
+    assert_eq!(
+        lines[2],
+        r#"b,0,RevPi Core/3/3+/S,input,43,,8,0,true,"say ""hi"", twice""#
+    );
+    let mut table = Vec::new();
+    rsc.write_symbol_table(&mut table).unwrap();
+    assert_eq!(
+        String::from_utf8(table).unwrap().lines().next(),
+        Some("   42.0   8 input  a")
+    );
+}
+
+const RAP_JSON: &str = r#"{"GUID":"adap.","id":"device_RevPiRO_20231018_1_0","type":"LEFT_RIGHT","productType":"137","position":"adap.","name":"RevPi RO","bmk":"RevPi RO","inpVariant":0,"outVariant":0,"comment":"This is a RevPi RO Device","offset":0,"inp":{"0":["Status","0","8","0",true,"0000","",""]},"out":{"0":["RelayOutput_1","0","1","1",true,"0001","","0"],"1":["RelayOutput_2","0","1","1",true,"0002","","1"]},"mem":{"0":["RelayCycleWarningThreshold_1","0","32","2",true,"0003","",""]},"extend":{}}"#;
+
+#[test]
+fn rap() {
+    let rap: Rap = serde_json::from_str(RAP_JSON).unwrap();
+    assert_eq!(rap.product(), ProductType::Ro);
+    assert_eq!(rap.extra["position"], "adap.");
+    let json = serde_json::to_string(&rap).unwrap();
+    assert_eq!(serde_json::from_str::<Rap>(&json).unwrap(), rap);
+
+    let dir = std::env::temp_dir().join("revpi_rsc-rap-test");
+    std::fs::create_dir_all(dir.join("ro")).unwrap();
+    std::fs::write(dir.join("ro/RevPiRO_20231018_1_0.rap"), RAP_JSON).unwrap();
+    let older = RAP_JSON.replace("20231018", "20220101");
+    std::fs::write(dir.join("RevPiRO_20220101_1_0.rap"), older).unwrap();
+    std::fs::write(dir.join("readme.txt"), "not a rap").unwrap();
+    let catalog = Catalog::from_dir(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(catalog.raps.len(), 2);
+    assert!(catalog.get("device_RevPiRO_20220101_1_0").is_some());
+    let mut ro = catalog.find(ProductType::Ro).unwrap().clone();
+    assert_eq!(ro.version(), Some((20231018, 1, 0)));
+    assert!(catalog.find(ProductType::Dio).is_none());
+
+    ro.rename_variables(|name| format!("{name}_i02"));
+    let rsc = RscBuilder::new()
+        .device(rap.device_builder())
+        .device(ro.device_builder())
+        .build()
+        .unwrap();
+    let device = &rsc.devices[1];
+    assert_eq!(device.offset, 6);
+    assert_eq!(device.out[&1].name, "RelayOutput_2_i02");
+    assert_eq!(device.out[&1].offset, 1);
+    assert_eq!(device.out[&1].bit_position, Some(1));
+    assert_eq!(device.mem[&0].offset, 2);
+    assert_eq!(device.id, rap.id);
+}
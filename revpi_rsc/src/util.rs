@@ -1,6 +1,6 @@
 use serde::{
     de::{Error as DeError, Visitor},
-    Deserializer, Serializer,
+    Deserialize, Deserializer, Serializer,
 };
 use std::{fmt::Display, marker::PhantomData, str::FromStr};
 
@@ -88,3 +88,13 @@ where
 {
     serializer.serialize_str(&format!("{}", i))
 }
+
+// only called for fields that are present, so together with `default` a
+// `null` can be told apart from a missing field
+pub fn de_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
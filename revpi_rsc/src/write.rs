@@ -0,0 +1,77 @@
+//! Safe replacement of RSC files
+
+use super::{RscError, RSC};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// appends `suffix` to the file name of `path`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+impl RSC {
+    /// Writes the config to `path` without ever leaving a half written file
+    /// behind.
+    ///
+    /// The config is written to `<path>.tmp` and synced to disk first. If
+    /// `path` already exists, it is copied to `<path>.<unix time>.bak`. Then
+    /// the temporary file is renamed to `path`, which replaces the old file
+    /// atomically.
+    ///
+    /// Returns the path of the backup, if one was made.
+    ///
+    /// # Errors
+    /// Returns an [`RscError`] if serializing or any file operation fails. The
+    /// file at `path` is unchanged in that case.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use revpi_rsc::RSC;
+    /// use std::fs::File;
+    ///
+    /// let f = File::open("/etc/revpi/config.rsc").unwrap();
+    /// let mut rsc: RSC = serde_json::from_reader(f).unwrap();
+    /// rsc.devices[0].comment = "changed".to_string();
+    /// rsc.write_to("/etc/revpi/config.rsc").unwrap();
+    /// ```
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> Result<Option<PathBuf>, RscError> {
+        let path = path.as_ref();
+        let tmp = with_suffix(path, ".tmp");
+        let result = self.write_tmp(&tmp).and_then(|_| {
+            let backup = if path.exists() {
+                let secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                let backup = with_suffix(path, &format!(".{}.bak", secs));
+                fs::copy(path, &backup)?;
+                Some(backup)
+            } else {
+                None
+            };
+            fs::rename(&tmp, path)?;
+            // make the rename itself durable
+            if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+                File::open(dir)?.sync_all()?;
+            }
+            Ok(backup)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result
+    }
+
+    fn write_tmp(&self, tmp: &Path) -> Result<(), RscError> {
+        let mut writer = BufWriter::new(File::create(tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
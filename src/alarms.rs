@@ -0,0 +1,275 @@
+//! Limit monitoring of variables
+//!
+//! [`Alarms`] evaluates [`Rule`]s against variables whenever it is polled and
+//! reports when an alarm is raised or cleared. A rule has a deadband, so a
+//! value hovering around the limit doesn't raise and clear the alarm over and
+//! over:
+//! ```no_run
+//! use revpi::alarms::{Alarms, Rule};
+//! use revpi::picontrol::PiControl;
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut alarms = Alarms::new(Duration::from_millis(100))
+//!     .rule("overtemp", Rule::high("Core_Temperature", 80.0).deadband(5.0))
+//!     .rule("pressure_drop", Rule::rate_of_change("AIn_1", 500.0));
+//! alarms
+//!     .run(&pi, |event| {
+//!         println!("{} {:?} at {}", event.alarm, event.state, event.value);
+//!         true
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! Values are compared in engineering units if the variable has a
+//! [`Scaling`](crate::picontrol::Scaling), otherwise as raw unsigned numbers.
+
+use crate::{
+    clock::{Clock, SystemClock, Timestamp},
+    cycle::{Cycle, OverrunPolicy},
+    picontrol::{PiControl, PiControlError},
+};
+use std::{fmt, time::Duration};
+
+/// Condition under which an alarm is raised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    /// Raised while the value is above the limit
+    High(f64),
+    /// Raised while the value is below the limit
+    Low(f64),
+    /// Raised while the value changes faster than the limit, in units per
+    /// second and either direction
+    RateOfChange(f64),
+}
+
+/// A limit for a variable, added with [`Alarms::rule`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    variable: String,
+    limit: Limit,
+    deadband: f64,
+}
+
+impl Rule {
+    /// Creates a rule with the limit `limit` for the variable `variable` and
+    /// no deadband
+    pub fn new(variable: &str, limit: Limit) -> Self {
+        Self {
+            variable: variable.to_string(),
+            limit,
+            deadband: 0.0,
+        }
+    }
+
+    /// Creates a [`Limit::High`] rule
+    pub fn high(variable: &str, limit: f64) -> Self {
+        Self::new(variable, Limit::High(limit))
+    }
+
+    /// Creates a [`Limit::Low`] rule
+    pub fn low(variable: &str, limit: f64) -> Self {
+        Self::new(variable, Limit::Low(limit))
+    }
+
+    /// Creates a [`Limit::RateOfChange`] rule
+    pub fn rate_of_change(variable: &str, limit: f64) -> Self {
+        Self::new(variable, Limit::RateOfChange(limit))
+    }
+
+    /// Sets the deadband: a raised alarm is only cleared once the value is
+    /// back inside the limit by at least `deadband`
+    pub fn deadband(mut self, deadband: f64) -> Self {
+        self.deadband = deadband;
+        self
+    }
+
+    /// Returns the variable
+    pub fn variable(&self) -> &str {
+        &self.variable
+    }
+
+    /// Returns the limit
+    pub fn limit(&self) -> Limit {
+        self.limit
+    }
+
+    // whether the alarm is raised for `value`, given that it is `active`
+    fn violated(&self, value: f64, active: bool) -> bool {
+        // once raised, the value has to get past the deadband
+        let deadband = if active { self.deadband } else { 0.0 };
+        match self.limit {
+            Limit::High(limit) | Limit::RateOfChange(limit) => value > limit - deadband,
+            Limit::Low(limit) => value < limit + deadband,
+        }
+    }
+}
+
+/// Whether an alarm was raised or cleared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlarmState {
+    Raised,
+    Cleared,
+}
+
+/// A raised or cleared alarm, reported by [`Alarms::poll`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlarmEvent {
+    /// Name of the alarm
+    pub alarm: String,
+    /// Name of the variable
+    pub variable: String,
+    pub state: AlarmState,
+    /// The value that raised or cleared the alarm, the rate for
+    /// [`Limit::RateOfChange`]
+    pub value: f64,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug)]
+struct Alarm {
+    name: String,
+    rule: Rule,
+    active: bool,
+    // value and time of the previous poll, for the rate of change
+    previous: Option<(f64, Duration)>,
+}
+
+/// Evaluates limits of variables and reports alarms, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::alarms::{AlarmState, Alarms, Rule};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let mock = Arc::new(MockBackend::new().variable("Level", 0, 0, 16));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut alarms = Alarms::new(Duration::from_millis(100))
+///     .rule("level_high", Rule::high("Level", 900.0).deadband(50.0));
+/// mock.write(0, &950u16.to_le_bytes()).unwrap();
+/// let events = alarms.poll(&pi).unwrap();
+/// assert_eq!(events[0].state, AlarmState::Raised);
+/// assert_eq!(alarms.active().collect::<Vec<_>>(), ["level_high"]);
+/// // still inside the deadband
+/// mock.write(0, &880u16.to_le_bytes()).unwrap();
+/// assert!(alarms.poll(&pi).unwrap().is_empty());
+/// mock.write(0, &840u16.to_le_bytes()).unwrap();
+/// assert_eq!(alarms.poll(&pi).unwrap()[0].state, AlarmState::Cleared);
+/// ```
+pub struct Alarms {
+    interval: Duration,
+    alarms: Vec<Alarm>,
+    clock: Box<dyn Clock>,
+}
+
+impl fmt::Debug for Alarms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alarms")
+            .field("interval", &self.interval)
+            .field("alarms", &self.alarms)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Alarms {
+    /// Creates an empty set of alarms, polled every `interval` in
+    /// [`Alarms::run`] and stamped with a [`SystemClock`]
+    pub fn new(interval: Duration) -> Self {
+        Self::with_clock(interval, SystemClock::new())
+    }
+
+    /// Creates an empty set of alarms like [`Alarms::new`], but stamped with
+    /// `clock`, which also measures the time for rates of change
+    pub fn with_clock<C: Clock + 'static>(interval: Duration, clock: C) -> Self {
+        Self {
+            interval,
+            alarms: Vec::new(),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Adds the alarm `name` for `rule`. An alarm with the same name is
+    /// replaced.
+    pub fn rule(mut self, name: &str, rule: Rule) -> Self {
+        self.alarms.retain(|alarm| alarm.name != name);
+        self.alarms.push(Alarm {
+            name: name.to_string(),
+            rule,
+            active: false,
+            previous: None,
+        });
+        self
+    }
+
+    /// Returns the names of the raised alarms
+    pub fn active(&self) -> impl Iterator<Item = &str> {
+        self.alarms
+            .iter()
+            .filter(|alarm| alarm.active)
+            .map(|alarm| alarm.name.as_str())
+    }
+
+    /// Reads all variables and returns the alarms that were raised or cleared
+    /// since the last poll
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a variable can't be
+    /// found or is a byte array.
+    pub fn poll(&mut self, pi: &PiControl) -> Result<Vec<AlarmEvent>, PiControlError> {
+        let timestamp = self.clock.now();
+        let mut events = Vec::new();
+        for alarm in self.alarms.iter_mut() {
+            let value = pi.get_number(&alarm.rule.variable)?;
+            let value = match alarm.rule.limit {
+                Limit::RateOfChange(_) => {
+                    let previous = alarm.previous.replace((value, timestamp.monotonic));
+                    match previous {
+                        Some((v, t)) if timestamp.monotonic > t => {
+                            (value - v).abs() / (timestamp.monotonic - t).as_secs_f64()
+                        }
+                        // no rate without a previous value
+                        _ => continue,
+                    }
+                }
+                _ => value,
+            };
+            let violated = alarm.rule.violated(value, alarm.active);
+            if violated != alarm.active {
+                alarm.active = violated;
+                events.push(AlarmEvent {
+                    alarm: alarm.name.clone(),
+                    variable: alarm.rule.variable.clone(),
+                    state: match violated {
+                        true => AlarmState::Raised,
+                        false => AlarmState::Cleared,
+                    },
+                    value,
+                    timestamp,
+                });
+            }
+        }
+        Ok(events)
+    }
+
+    /// Polls every interval and calls `f` with every event until `f` returns
+    /// `false`.
+    ///
+    /// # Errors
+    /// Returns the first error of [`Alarms::poll`].
+    pub fn run<F>(&mut self, pi: &PiControl, mut f: F) -> Result<(), PiControlError>
+    where
+        F: FnMut(&AlarmEvent) -> bool,
+    {
+        let mut cycle = Cycle::new(self.interval).overrun_policy(OverrunPolicy::Skip);
+        loop {
+            cycle.wait()?;
+            for event in self.poll(pi)? {
+                if !f(&event) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! The piTest-like subcommands that work without a config
+
+use revpi::{
+    monitor::Monitor,
+    picontrol::{raw::PiControlRaw, PiControl, Value},
+};
+use std::time::Duration;
+
+/// Parses `s` as a number, decimal or hexadecimal with a leading `0x`
+pub fn number(s: &str) -> Result<u32, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("invalid number {}", s))
+}
+
+// parses `s` as a value of the same kind as `old`
+fn value(old: Value, s: &str) -> Result<Value, String> {
+    let n = number(s)?;
+    let value = match old {
+        Value::Bit(_) => (n <= 1).then_some(Value::Bit(n == 1)),
+        Value::Byte(_) => u8::try_from(n).ok().map(Value::Byte),
+        Value::Word(_) => u16::try_from(n).ok().map(Value::Word),
+        Value::DWord(_) => Some(Value::DWord(n)),
+        // get_value only returns the unsigned variants
+        _ => None,
+    };
+    value.ok_or_else(|| format!("{} is out of range", s))
+}
+
+/// `revpictl read NAME`: prints the value of a variable
+pub fn read(pi: &PiControl, name: &str) -> Result<(), String> {
+    let value = pi.get_value(name).map_err(|e| format!("{}: {}", name, e))?;
+    println!("{}", value);
+    Ok(())
+}
+
+/// `revpictl write NAME VALUE`: sets a variable, the value must fit its length
+pub fn write(pi: &PiControl, name: &str, s: &str) -> Result<(), String> {
+    // the current value tells the length of the variable
+    let old = pi.get_value(name).map_err(|e| format!("{}: {}", name, e))?;
+    pi.set_value(name, value(old, s)?)
+        .map_err(|e| format!("{}: {}", name, e))
+}
+
+/// `revpictl dump`: prints the whole processimage as hex, 16 bytes per line
+pub fn dump(raw: &PiControlRaw) -> Result<(), String> {
+    let image = raw.dump_image().map_err(|e| e.to_string())?;
+    for (i, line) in image.chunks(16).enumerate() {
+        let bytes: Vec<_> = line.iter().map(|b| format!("{:02x}", b)).collect();
+        println!("{:04x}: {}", i * 16, bytes.join(" "));
+    }
+    Ok(())
+}
+
+/// `revpictl list`: prints all devices known to the driver
+pub fn list(raw: &PiControlRaw) -> Result<(), String> {
+    for dev in raw.get_device_info_list().map_err(|e| e.to_string())? {
+        println!(
+            "{:>3} {:<24} in {:?} out {:?} serial {}{}",
+            dev.address(),
+            dev.module_type().to_string(),
+            dev.inputs(),
+            dev.outputs(),
+            dev.serial_number(),
+            if dev.is_connected() {
+                ""
+            } else {
+                " (not connected)"
+            },
+        );
+    }
+    Ok(())
+}
+
+/// `revpictl reset`: restarts the driver, which rereads the config
+pub fn reset(raw: &PiControlRaw) -> Result<(), String> {
+    // an operator asking for it is the one case where a reset is intended
+    unsafe { raw.reset() }.map_err(|e| e.to_string())
+}
+
+/// `revpictl reset-counter ADDRESS BITFIELD`: resets the counters of a DIO
+pub fn reset_counter(raw: &PiControlRaw, address: &str, bitfield: &str) -> Result<(), String> {
+    let address = u8::try_from(number(address)?).map_err(|_| "invalid address")?;
+    let bitfield = u16::try_from(number(bitfield)?).map_err(|_| "invalid bitfield")?;
+    raw.dio_reset_counter(address, bitfield)
+        .map_err(|e| e.to_string())
+}
+
+/// `revpictl watch NAME`: prints the value of a variable on every change
+pub fn watch(pi: &PiControl, name: &str, interval: Duration) -> Result<(), String> {
+    println!(
+        "{}",
+        pi.get_value(name).map_err(|e| format!("{}: {}", name, e))?
+    );
+    Monitor::new(interval)
+        .watch(name)
+        .run(pi, |change| {
+            println!("{}", change.new);
+            true
+        })
+        .map_err(|e| format!("{}: {}", name, e))
+}
@@ -0,0 +1,104 @@
+//! Command line tool for the RevPi, mostly like piTest
+//!
+//! ```text
+//! revpictl read NAME
+//! revpictl write NAME VALUE
+//! revpictl dump
+//! revpictl list
+//! revpictl reset
+//! revpictl reset-counter ADDRESS BITFIELD
+//! revpictl watch NAME [--interval MS]
+//! revpictl watch [--tui] [--config PATH] [--interval MS]
+//! ```
+//!
+//! Numbers can be given decimal or hexadecimal with a leading `0x`.
+
+mod commands;
+mod watch;
+
+use revpi::{
+    picontrol::{
+        raw::{raw::PICONFIG_FILE, PiControlRaw},
+        PiControl,
+    },
+    rsc::{Limits, RSC},
+};
+use std::{env, fs::File, process, time::Duration};
+
+const USAGE: &str = "usage:
+    revpictl read NAME
+    revpictl write NAME VALUE
+    revpictl dump
+    revpictl list
+    revpictl reset
+    revpictl reset-counter ADDRESS BITFIELD
+    revpictl watch NAME [--interval MS]
+    revpictl watch [--tui] [--config PATH] [--interval MS]";
+
+// options shared by all subcommands
+struct Options {
+    config: String,
+    interval: Duration,
+    tui: bool,
+    args: Vec<String>,
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        config: PICONFIG_FILE.to_string(),
+        interval: Duration::from_millis(100),
+        tui: false,
+        args: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tui" => options.tui = true,
+            "--config" => options.config = args.next().ok_or("--config needs a path")?,
+            "--interval" => {
+                let ms = args.next().ok_or("--interval needs a value")?;
+                let ms = ms.parse().map_err(|_| format!("invalid interval {}", ms))?;
+                options.interval = Duration::from_millis(ms);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown argument {}", arg)),
+            _ => options.args.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+fn config(path: &str) -> Result<RSC, String> {
+    let f = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    RSC::from_reader_with_limits(f, &Limits::default()).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn run() -> Result<(), String> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or(USAGE)?;
+    let options = parse(args)?;
+    let args: Vec<_> = options.args.iter().map(String::as_str).collect();
+    let pi = || PiControl::new().map_err(|e| e.to_string());
+    let raw = || PiControlRaw::new().map_err(|e| e.to_string());
+    match (command.as_str(), args.as_slice()) {
+        ("read", [name]) => commands::read(&pi()?, name),
+        ("write", [name, value]) => commands::write(&pi()?, name, value),
+        ("dump", []) => commands::dump(&raw()?),
+        ("list", []) => commands::list(&raw()?),
+        ("reset", []) => commands::reset(&raw()?),
+        ("reset-counter", [address, bitfield]) => {
+            commands::reset_counter(&raw()?, address, bitfield)
+        }
+        ("watch", [name]) => commands::watch(&pi()?, name, options.interval),
+        ("watch", []) if options.tui => {
+            watch::tui(&pi()?, &config(&options.config)?, options.interval)
+        }
+        ("watch", []) => watch::print(&pi()?, &config(&options.config)?, options.interval),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("revpictl: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,121 @@
+//! `revpictl watch`: shows the values of all variables and their changes
+
+#[cfg(feature = "tui")]
+mod tui;
+
+#[cfg(feature = "tui")]
+pub use self::tui::tui;
+use revpi::{
+    picontrol::{DeviceImage, PiControl, Value},
+    rsc::{Device, RSC},
+};
+use std::{thread, time::Duration};
+
+/// Kind of a variable, i.e. which map of the [`Device`] it is from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Input,
+    Output,
+    Memory,
+}
+
+impl Kind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Input => "in",
+            Kind::Output => "out",
+            Kind::Memory => "mem",
+        }
+    }
+}
+
+/// One variable of the config and its last value
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub device: usize,
+    pub name: String,
+    pub kind: Kind,
+    // only needed for writing
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub bit_length: u8,
+    pub value: Option<Value>,
+}
+
+/// Values of all variables of a config, which can be updated from the
+/// processimage
+#[derive(Debug)]
+pub struct Watch<'a> {
+    pub devices: Vec<&'a Device>,
+    pub rows: Vec<Row>,
+}
+
+impl<'a> Watch<'a> {
+    pub fn new(rsc: &'a RSC) -> Self {
+        let mut devices: Vec<_> = rsc.devices.iter().collect();
+        devices.sort_by_key(|d| d.position);
+        let mut rows = Vec::new();
+        for (i, device) in devices.iter().enumerate() {
+            let vars = [
+                (Kind::Input, &device.inp),
+                (Kind::Output, &device.out),
+                (Kind::Memory, &device.mem),
+            ];
+            for (kind, vars) in vars {
+                rows.extend(vars.values().map(|var| Row {
+                    device: i,
+                    name: var.name.clone(),
+                    kind,
+                    bit_length: var.bit_length,
+                    value: None,
+                }));
+            }
+        }
+        Self { devices, rows }
+    }
+
+    /// Reads the regions of all devices and returns the indices of the rows
+    /// whose value changed. Devices that can't be read are skipped.
+    pub fn update(&mut self, pi: &PiControl) -> Vec<usize> {
+        let images: Vec<_> = self
+            .devices
+            .iter()
+            .map(|d| DeviceImage::read(pi, d).ok())
+            .collect();
+        let mut changed = Vec::new();
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            let value = images[row.device].as_ref().and_then(|i| i.get(&row.name));
+            if value != row.value {
+                row.value = value;
+                changed.push(i);
+            }
+        }
+        changed
+    }
+}
+
+/// Formats `value` like piTest does, `-` if it is unknown
+pub fn format(value: Option<&Value>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+/// Prints every change of a value as a line
+pub fn print(pi: &PiControl, rsc: &RSC, interval: Duration) -> Result<(), String> {
+    let mut watch = Watch::new(rsc);
+    loop {
+        for i in watch.update(pi) {
+            let row = &watch.rows[i];
+            println!(
+                "{:<3} {} = {}",
+                row.kind.as_str(),
+                row.name,
+                format(row.value.as_ref())
+            );
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn tui(_: &PiControl, _: &RSC, _: Duration) -> Result<(), String> {
+    Err("revpictl was built without the tui feature".to_string())
+}
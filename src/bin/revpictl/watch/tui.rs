@@ -0,0 +1,253 @@
+//! Terminal UI of `revpictl watch --tui`
+//!
+//! Shows all variables grouped by device and highlights changed values.
+//! Outputs and memory variables can be written inline.
+
+use super::{format, Kind, Watch};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use revpi::{
+    picontrol::{PiControl, Value},
+    rsc::RSC,
+};
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+// how long a changed value stays highlighted
+const HIGHLIGHT: Duration = Duration::from_secs(1);
+const HELP: &str = "up/down: select  enter: write  q: quit";
+
+enum Line {
+    Device(usize),
+    Row(usize),
+}
+
+// restores the terminal even if drawing fails
+struct Terminal(Stdout);
+
+impl Terminal {
+    fn new() -> io::Result<Self> {
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self(out))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(self.0, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+struct State<'a> {
+    watch: Watch<'a>,
+    lines: Vec<Line>,
+    changed: Vec<Option<Instant>>,
+    selected: usize,
+    scroll: usize,
+    input: Option<String>,
+    status: String,
+}
+
+impl<'a> State<'a> {
+    fn new(rsc: &'a RSC) -> Self {
+        let watch = Watch::new(rsc);
+        let mut lines = Vec::new();
+        for (i, row) in watch.rows.iter().enumerate() {
+            if i == 0 || watch.rows[i - 1].device != row.device {
+                lines.push(Line::Device(row.device));
+            }
+            lines.push(Line::Row(i));
+        }
+        Self {
+            changed: vec![None; watch.rows.len()],
+            watch,
+            lines,
+            selected: 0,
+            scroll: 0,
+            input: None,
+            status: HELP.to_string(),
+        }
+    }
+
+    fn update(&mut self, pi: &PiControl) {
+        let now = Instant::now();
+        for i in self.watch.update(pi) {
+            self.changed[i] = Some(now);
+        }
+    }
+
+    // returns false if the tui should quit
+    fn key(&mut self, pi: &PiControl, key: KeyEvent) -> bool {
+        if let Some(input) = self.input.as_mut() {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Enter => {
+                    let input = self.input.take().unwrap_or_default();
+                    self.status = match self.write(pi, &input) {
+                        Ok(()) => HELP.to_string(),
+                        Err(e) => e,
+                    };
+                }
+                _ => (),
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.watch.rows.len() => self.selected += 1,
+            KeyCode::Enter => match self.watch.rows.get(self.selected) {
+                Some(row) if row.kind == Kind::Input => {
+                    self.status = "inputs are written by the devices".to_string()
+                }
+                Some(_) => self.input = Some(String::new()),
+                None => (),
+            },
+            _ => (),
+        }
+        true
+    }
+
+    fn write(&self, pi: &PiControl, input: &str) -> Result<(), String> {
+        let row = &self.watch.rows[self.selected];
+        let input = input.trim();
+        let v = match input.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => input.parse(),
+        }
+        .map_err(|_| format!("invalid number {:?}", input))?;
+        let value = match row.bit_length {
+            1 if v <= 1 => Value::Bit(v == 1),
+            8 => Value::Byte(u8::try_from(v).map_err(|e| e.to_string())?),
+            16 => Value::Word(u16::try_from(v).map_err(|e| e.to_string())?),
+            32 => Value::DWord(v),
+            _ => return Err(format!("{} doesn't fit into {}", v, row.name)),
+        };
+        pi.set_value(&row.name, value).map_err(|e| e.to_string())
+    }
+
+    fn draw(&mut self, out: &mut Stdout) -> io::Result<()> {
+        let (cols, rows) = terminal::size()?;
+        // the last line shows the status or the input
+        let height = rows.saturating_sub(1) as usize;
+        let selected = self
+            .lines
+            .iter()
+            .position(|l| matches!(l, Line::Row(i) if *i == self.selected))
+            .unwrap_or(0);
+        if selected < self.scroll {
+            // keep the device header visible when scrolling up
+            self.scroll = selected.saturating_sub(1);
+        } else if selected >= self.scroll + height {
+            self.scroll = selected + 1 - height;
+        }
+        let width = self
+            .watch
+            .rows
+            .iter()
+            .map(|r| r.name.len())
+            .max()
+            .unwrap_or(0);
+        queue!(out, cursor::MoveTo(0, 0))?;
+        for (y, line) in self.lines.iter().skip(self.scroll).take(height).enumerate() {
+            queue!(out, cursor::MoveTo(0, y as u16))?;
+            match *line {
+                Line::Device(d) => {
+                    let device = self.watch.devices[d];
+                    queue!(
+                        out,
+                        SetAttribute(Attribute::Bold),
+                        Print(format!(
+                            "[{}] {} ({})",
+                            device.position,
+                            device.name,
+                            device.dev_type.as_str()
+                        )),
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                }
+                Line::Row(i) => {
+                    let row = &self.watch.rows[i];
+                    if i == self.selected {
+                        queue!(out, SetAttribute(Attribute::Reverse))?;
+                    }
+                    if self.changed[i].is_some_and(|t| t.elapsed() < HIGHLIGHT) {
+                        queue!(out, SetForegroundColor(Color::Yellow))?;
+                    }
+                    let text = format!(
+                        "  {:<3} {:<width$}  {}",
+                        row.kind.as_str(),
+                        row.name,
+                        format(row.value.as_ref()),
+                        width = width
+                    );
+                    queue!(
+                        out,
+                        Print(truncate(&text, cols)),
+                        ResetColor,
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                }
+            }
+            queue!(out, terminal::Clear(ClearType::UntilNewLine))?;
+        }
+        queue!(out, terminal::Clear(ClearType::FromCursorDown))?;
+        let status = match &self.input {
+            Some(input) => format!("{} = {}_", self.watch.rows[self.selected].name, input),
+            None => self.status.clone(),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, rows.saturating_sub(1)),
+            Print(truncate(&status, cols)),
+            terminal::Clear(ClearType::UntilNewLine),
+        )?;
+        out.flush()
+    }
+}
+
+fn truncate(s: &str, cols: u16) -> &str {
+    match s.char_indices().nth(cols as usize) {
+        Some((i, _)) => &s[..i],
+        None => s,
+    }
+}
+
+/// Runs the terminal UI until `q` is pressed
+pub fn tui(pi: &PiControl, rsc: &RSC, interval: Duration) -> Result<(), String> {
+    let mut terminal = Terminal::new().map_err(|e| e.to_string())?;
+    let mut state = State::new(rsc);
+    let result = (|| -> io::Result<()> {
+        let mut next = Instant::now();
+        loop {
+            if Instant::now() >= next {
+                state.update(pi);
+                next = Instant::now() + interval;
+            }
+            state.draw(&mut terminal.0)?;
+            if event::poll(next.saturating_duration_since(Instant::now()))? {
+                if let Event::Key(key) = event::read()? {
+                    if !state.key(pi, key) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    })();
+    result.map_err(|e| e.to_string())
+}
@@ -0,0 +1,113 @@
+//! Timestamps for recorded samples
+//!
+//! Samples are stamped with a monotonic and a wall-clock time. The monotonic
+//! time is never affected by changes of the system time and should be used to
+//! order samples and calculate durations, the wall-clock time relates samples
+//! to the outside world:
+//! ```
+//! use revpi::clock::{Clock, SystemClock};
+//!
+//! let mut clock = SystemClock::new();
+//! let ts = clock.now();
+//! println!("{:?} since start, at {:?}", ts.monotonic, ts.wall);
+//! ```
+//!
+//! If the system time is stepped, e.g. by NTP, the wall-clock times of
+//! consecutive samples jump. [`SystemClock`] detects this and marks the first
+//! sample after the step with [`Timestamp::step`], so recorded data can be
+//! corrected or at least flagged.\
+//! Everything that needs timestamps takes a [`Clock`], so e.g. tests can
+//! provide their own time.
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// Jump of the wall-clock time that wasn't caused by time passing
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Step {
+    Forward(Duration),
+    Backward(Duration),
+}
+
+/// Point in time, given by both a monotonic and a wall-clock time
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Timestamp {
+    /// Time since the creation of the clock
+    pub monotonic: Duration,
+    /// System time
+    pub wall: SystemTime,
+    /// Set if the system time was stepped since the previous timestamp of the
+    /// same clock
+    pub step: Option<Step>,
+}
+
+impl Timestamp {
+    /// Returns the wall-clock time as [`chrono::DateTime`]
+    #[cfg(feature = "chrono")]
+    pub fn wall_utc(&self) -> chrono::DateTime<chrono::Utc> {
+        self.wall.into()
+    }
+}
+
+/// Source of [`Timestamp`]s
+pub trait Clock: Send {
+    /// Returns the current time
+    fn now(&mut self) -> Timestamp;
+}
+
+/// [`Clock`] using the time of the system
+///
+/// A step is detected if the wall-clock time deviates from the monotonic time
+/// by more than the tolerance, 100ms by default. Slewing, the way NTP usually
+/// adjusts the time, is not detected as step.
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    start: Instant,
+    // wall-clock time at `start`, adjusted after every step
+    base: SystemTime,
+    tolerance: Duration,
+}
+
+impl SystemClock {
+    /// Creates a new clock, its monotonic time starts at zero
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            base: SystemTime::now(),
+            tolerance: Duration::from_millis(100),
+        }
+    }
+
+    /// Sets the deviation from which on a change of the wall-clock time is
+    /// considered a step
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> Timestamp {
+        let monotonic = self.start.elapsed();
+        let wall = SystemTime::now();
+        let expected = self.base + monotonic;
+        let step = match wall.duration_since(expected) {
+            Ok(ahead) if ahead > self.tolerance => Some(Step::Forward(ahead)),
+            Err(e) if e.duration() > self.tolerance => Some(Step::Backward(e.duration())),
+            _ => None,
+        };
+        if step.is_some() {
+            self.base = wall - monotonic;
+        }
+        Timestamp {
+            monotonic,
+            wall,
+            step,
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Single-writer command queue
+//!
+//! A [`Commander`] moves a [`PiControl`] into a thread of its own, which then
+//! executes the write [`Command`]s sent to it through a bounded queue. Any
+//! number of threads can hold a [`CommandSender`] and get notified once their
+//! command was executed, without having to share the [`PiControl`] behind a
+//! lock:
+//! ```no_run
+//! use revpi::commander::{Command, Commander};
+//! use revpi::picontrol::{PiControl, Value};
+//! use std::thread;
+//!
+//! let commander = Commander::spawn(PiControl::new().unwrap(), 16);
+//! let sender = commander.sender();
+//! thread::spawn(move || {
+//!     let done = sender
+//!         .send(Command::SetValue {
+//!             name: "RevPiLED".to_string(),
+//!             value: Value::Byte(1),
+//!         })
+//!         .unwrap();
+//!     done.wait().unwrap();
+//! });
+//! // returns the PiControl once every sender is gone
+//! let pi = commander.join();
+//! ```
+//!
+//! # Merging
+//! Every time the commander picks up work, it takes all commands queued at
+//! that moment as one batch. If several commands in a batch write the same
+//! variable, the [`MergePolicy`] decides which of them is executed. Commands
+//! writing different variables are executed in the order they were sent.
+
+use crate::picontrol::{PiControl, PiControlError, Value};
+use std::{
+    collections::BTreeMap,
+    io,
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+
+/// Write command executed by a [`Commander`]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum Command {
+    /// Same as [`PiControl::set_value`]
+    SetValue { name: String, value: Value },
+}
+
+impl Command {
+    // commands with the same key write the same target
+    fn key(&self) -> &str {
+        match self {
+            Command::SetValue { name, .. } => name,
+        }
+    }
+
+    fn execute(&self, pi: &PiControl) -> Result<(), PiControlError> {
+        match self {
+            Command::SetValue { name, value } => pi.set_value(name, value.clone()),
+        }
+    }
+}
+
+/// Decides which command is executed if several commands in a batch write the
+/// same variable
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum MergePolicy {
+    /// The command sent last is executed. The others get its result, since
+    /// their value would have been overwritten anyway.
+    #[default]
+    LastWriteWins,
+    /// The command with the highest priority is executed, if there are several
+    /// the one sent last. The others fail with [`PiControlError::Conflict`].
+    HighestPriorityWins,
+    /// If all commands are the same, it is executed once and all get its
+    /// result. Otherwise all of them fail with [`PiControlError::Conflict`].
+    RejectConflicting,
+}
+
+impl MergePolicy {
+    // returns the index of the command to execute, if any, and whether the
+    // others get its result instead of a conflict
+    fn winner(&self, group: &[(usize, &Envelope)]) -> (Option<usize>, bool) {
+        // groups are never empty
+        let (last, _) = group[group.len() - 1];
+        match self {
+            MergePolicy::LastWriteWins => (Some(last), true),
+            MergePolicy::HighestPriorityWins => {
+                // max_by_key returns the last maximum
+                let (winner, _) = group.iter().max_by_key(|(_, e)| e.priority).unwrap();
+                (Some(*winner), false)
+            }
+            MergePolicy::RejectConflicting => {
+                let (_, first) = group[0];
+                if group.iter().all(|(_, e)| e.command == first.command) {
+                    (Some(last), true)
+                } else {
+                    (None, false)
+                }
+            }
+        }
+    }
+
+    fn execute(&self, pi: &PiControl, batch: Vec<Envelope>) {
+        let mut groups: BTreeMap<&str, Vec<(usize, &Envelope)>> = BTreeMap::new();
+        for (i, envelope) in batch.iter().enumerate() {
+            groups
+                .entry(envelope.command.key())
+                .or_default()
+                .push((i, envelope));
+        }
+        let decisions: Vec<_> = groups.values().map(|group| self.winner(group)).collect();
+        let mut winners: Vec<_> = decisions.iter().filter_map(|(w, _)| *w).collect();
+        winners.sort_unstable();
+        // executed before anyone is notified, so the merged commands get the
+        // actual result
+        let mut results: BTreeMap<_, _> = winners
+            .into_iter()
+            .map(|i| (i, batch[i].command.execute(pi)))
+            .collect();
+        for (group, (winner, shared)) in groups.values().zip(decisions) {
+            for (i, envelope) in group.iter() {
+                if Some(*i) == winner {
+                    continue;
+                }
+                let result = match winner.map(|winner| &results[&winner]) {
+                    Some(Ok(())) if shared => Ok(()),
+                    Some(Err(e)) if shared => Err(duplicate(e)),
+                    _ => Err(PiControlError::Conflict),
+                };
+                // the sender might not be interested in the result
+                let _ = envelope.done.send(result);
+            }
+            if let Some(winner) = winner {
+                let _ = batch[winner].done.send(results.remove(&winner).unwrap());
+            }
+        }
+    }
+}
+
+// copy of the error of a command for the commands merged into it. Errors
+// that can't be copied are passed on as I/O errors with the same message.
+fn duplicate(e: &PiControlError) -> PiControlError {
+    use PiControlError::*;
+    match e {
+        InvalidArgument(arg) => InvalidArgument(arg),
+        DeviceNotFound(address) => DeviceNotFound(*address),
+        NoVarEntries => NoVarEntries,
+        Timeout => Timeout,
+        BridgeNotRunning => BridgeNotRunning,
+        OutOfMemory => OutOfMemory,
+        NotSupportedOnThisModel => NotSupportedOnThisModel,
+        NotSupportedByDriver => NotSupportedByDriver,
+        ReadOnly(name) => ReadOnly(name.clone()),
+        IoError(e) => io::Error::new(e.kind(), e.to_string()).into(),
+        e => io::Error::other(e.to_string()).into(),
+    }
+}
+
+#[derive(Debug)]
+struct Envelope {
+    command: Command,
+    priority: u8,
+    done: mpsc::Sender<Result<(), PiControlError>>,
+}
+
+/// Notification that a command was executed
+#[derive(Debug)]
+pub struct Completion(Receiver<Result<(), PiControlError>>);
+
+impl Completion {
+    /// Blocks until the command was executed and returns its result.
+    ///
+    /// # Errors
+    /// Returns the error the command produced or [`PiControlError::Disconnected`]
+    /// if the [`Commander`] stopped before executing it.
+    pub fn wait(self) -> Result<(), PiControlError> {
+        self.0.recv().unwrap_or(Err(PiControlError::Disconnected))
+    }
+
+    /// Returns the result of the command if it was already executed.
+    pub fn try_wait(&self) -> Option<Result<(), PiControlError>> {
+        match self.0.try_recv() {
+            Ok(r) => Some(r),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(PiControlError::Disconnected)),
+        }
+    }
+}
+
+/// Sends commands to a [`Commander`]
+///
+/// Can be cloned and sent to other threads freely.
+#[derive(Debug, Clone)]
+pub struct CommandSender(SyncSender<Envelope>);
+
+impl CommandSender {
+    /// Queues `command` with priority `0`, blocking while the queue is full.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::Disconnected`] if the [`Commander`] stopped.
+    pub fn send(&self, command: Command) -> Result<Completion, PiControlError> {
+        self.send_with_priority(command, 0)
+    }
+
+    /// Queues `command` with the given priority, blocking while the queue is
+    /// full. The priority is only taken into account with
+    /// [`MergePolicy::HighestPriorityWins`].
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::Disconnected`] if the [`Commander`] stopped.
+    pub fn send_with_priority(
+        &self,
+        command: Command,
+        priority: u8,
+    ) -> Result<Completion, PiControlError> {
+        let (done, completion) = mpsc::channel();
+        self.0
+            .send(Envelope {
+                command,
+                priority,
+                done,
+            })
+            .map_err(|_| PiControlError::Disconnected)?;
+        Ok(Completion(completion))
+    }
+
+    /// Queues `command` with priority `0` without blocking.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::QueueFull`] if the queue is full and
+    /// [`PiControlError::Disconnected`] if the [`Commander`] stopped.
+    pub fn try_send(&self, command: Command) -> Result<Completion, PiControlError> {
+        let (done, completion) = mpsc::channel();
+        self.0
+            .try_send(Envelope {
+                command,
+                priority: 0,
+                done,
+            })
+            .map_err(|e| match e {
+                TrySendError::Full(_) => PiControlError::QueueFull,
+                TrySendError::Disconnected(_) => PiControlError::Disconnected,
+            })?;
+        Ok(Completion(completion))
+    }
+}
+
+/// Owns a [`PiControl`] and executes the commands sent to it in its own thread
+#[derive(Debug)]
+pub struct Commander {
+    sender: CommandSender,
+    handle: JoinHandle<PiControl>,
+}
+
+impl Commander {
+    /// Moves `pi` into a new thread executing commands with
+    /// [`MergePolicy::LastWriteWins`]. `capacity` is the number of commands
+    /// that can be queued before senders block.
+    pub fn spawn(pi: PiControl, capacity: usize) -> Self {
+        Self::spawn_with_policy(pi, capacity, MergePolicy::default())
+    }
+
+    /// Same as [`Commander::spawn`], but merges writes to the same variable
+    /// according to `policy`.
+    pub fn spawn_with_policy(pi: PiControl, capacity: usize, policy: MergePolicy) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Envelope>(capacity);
+        let handle = thread::spawn(move || {
+            while let Ok(first) = receiver.recv() {
+                let mut batch = vec![first];
+                batch.extend(receiver.try_iter());
+                policy.execute(&pi, batch);
+            }
+            pi
+        });
+        Self {
+            sender: CommandSender(sender),
+            handle,
+        }
+    }
+
+    /// Returns a new sender for this commander
+    pub fn sender(&self) -> CommandSender {
+        self.sender.clone()
+    }
+
+    /// Waits until every [`CommandSender`] was dropped and all queued commands
+    /// were executed, then returns the [`PiControl`].
+    ///
+    /// # Panics
+    /// Panics if the thread executing the commands panicked.
+    pub fn join(self) -> PiControl {
+        drop(self.sender);
+        self.handle.join().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::MockBackend;
+    use std::sync::Arc;
+
+    fn pi() -> (Arc<MockBackend>, PiControl) {
+        let mock = Arc::new(
+            MockBackend::new()
+                .variable("a", 0, 0, 8)
+                .variable("b", 1, 0, 8),
+        );
+        let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+        (mock, pi)
+    }
+
+    // executes `commands` as one batch and returns their results
+    fn execute(
+        policy: MergePolicy,
+        pi: &PiControl,
+        commands: &[(&str, u8, u8)],
+    ) -> Vec<Result<(), PiControlError>> {
+        let (batch, completions): (Vec<_>, Vec<_>) = commands
+            .iter()
+            .map(|&(name, value, priority)| {
+                let (done, completion) = mpsc::channel();
+                let command = Command::SetValue {
+                    name: name.to_string(),
+                    value: Value::Byte(value),
+                };
+                let envelope = Envelope {
+                    command,
+                    priority,
+                    done,
+                };
+                (envelope, Completion(completion))
+            })
+            .unzip();
+        policy.execute(pi, batch);
+        completions.into_iter().map(Completion::wait).collect()
+    }
+
+    #[test]
+    fn last_write_wins() {
+        let (mock, pi) = pi();
+        let results = execute(
+            MergePolicy::LastWriteWins,
+            &pi,
+            &[("a", 1, 0), ("b", 2, 0), ("a", 3, 0)],
+        );
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(mock.read(0, 2).unwrap(), vec![3, 2]);
+    }
+
+    #[test]
+    fn highest_priority_wins() {
+        let (mock, pi) = pi();
+        let results = execute(
+            MergePolicy::HighestPriorityWins,
+            &pi,
+            &[("a", 1, 5), ("a", 2, 1), ("a", 3, 5), ("b", 4, 0)],
+        );
+        assert!(matches!(results[0], Err(PiControlError::Conflict)));
+        assert!(matches!(results[1], Err(PiControlError::Conflict)));
+        assert!(results[2].is_ok());
+        assert!(results[3].is_ok());
+        assert_eq!(mock.read(0, 2).unwrap(), vec![3, 4]);
+    }
+
+    #[test]
+    fn reject_conflicting() {
+        let (mock, pi) = pi();
+        let results = execute(
+            MergePolicy::RejectConflicting,
+            &pi,
+            &[("a", 1, 0), ("b", 2, 0), ("a", 3, 0), ("b", 2, 0)],
+        );
+        assert!(matches!(results[0], Err(PiControlError::Conflict)));
+        assert!(results[1].is_ok());
+        assert!(matches!(results[2], Err(PiControlError::Conflict)));
+        assert!(results[3].is_ok());
+        // the conflicting writes aren't executed at all
+        assert_eq!(mock.read(0, 2).unwrap(), vec![0, 2]);
+    }
+
+    #[test]
+    fn failing_command() {
+        let (_, pi) = pi();
+        let results = execute(
+            MergePolicy::LastWriteWins,
+            &pi,
+            &[("unknown", 1, 0), ("unknown", 2, 0)],
+        );
+        // the overwritten command gets the error of the executed one
+        for result in results {
+            assert!(matches!(result, Err(PiControlError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn commander() {
+        let (mock, pi) = pi();
+        let commander = Commander::spawn_with_policy(pi, 4, MergePolicy::RejectConflicting);
+        let sender = commander.sender();
+        let command = Command::SetValue {
+            name: "a".to_string(),
+            value: Value::Byte(7),
+        };
+        sender.send(command).unwrap().wait().unwrap();
+        assert_eq!(mock.read(0, 1).unwrap(), vec![7]);
+        drop(sender);
+        let pi = commander.join();
+        assert_eq!(pi.get_value("a").unwrap(), Value::Byte(7));
+    }
+
+    #[test]
+    fn dropped_command() {
+        let (done, completion) = mpsc::channel();
+        drop(done);
+        assert!(matches!(
+            Completion(completion).try_wait(),
+            Some(Err(PiControlError::Disconnected))
+        ));
+    }
+}
@@ -0,0 +1,261 @@
+//! Application configuration from a single TOML file
+//!
+//! An [`AppConfig`] bundles everything a typical application sets up before
+//! doing IO, so that only a few lines of setup code are needed:
+//! ```no_run
+//! use revpi::config::AppConfig;
+//!
+//! let config = AppConfig::from_file("/etc/myapp.toml").unwrap();
+//! let pi = config.build().unwrap();
+//! pi.set_value("status_led", 1u8.into()).unwrap();
+//! ```
+//!
+//! A config file looks like this, every section is optional:
+//! ```toml
+//! [picontrol]
+//! path = "/dev/piControl0"
+//! watchdog_ms = 500
+//! cache = true
+//!
+//! [cycle]
+//! period_ms = 10
+//!
+//! # alias = name of the variable in PiCtory
+//! [aliases]
+//! status_led = "RevPiLED"
+//!
+//! # written by PiControl::apply_safe_state
+//! [safe_state]
+//! RevPiLED = 0
+//! O_1 = false
+//!
+//! [scaling.AIn_1]
+//! raw_min = 0
+//! raw_max = 10000
+//! min = 0.0
+//! max = 10.0
+//! offset = -0.1
+//! unit = "V"
+//!
+//! [deadband]
+//! AIn_1 = 5
+//!
+//! [control.fan]
+//! type = "hysteresis"
+//! input = "Core_Temperature"
+//! output = "O_1"
+//! on = 70.0
+//! off = 65.0
+//!
+//! [[logging]]
+//! sink = "file"
+//! path = "/var/log/myapp.log"
+//! variables = ["AIn_1", "RevPiLED"]
+//! interval_ms = 1000
+//! ```
+//! The `picontrol`, `aliases`, `safe_state` and `scaling` sections configure
+//! the [`PiControl`] returned by [`AppConfig::build`]. The other sections are
+//! provided as typed structs for the subsystems using them, e.g. the
+//! `control` section for a [`Controller`](crate::control::Controller):
+//! ```no_run
+//! # use revpi::config::AppConfig;
+//! use revpi::control::Controller;
+//!
+//! # let config = AppConfig::from_file("/etc/myapp.toml").unwrap();
+//! # let pi = config.build().unwrap();
+//! let period = config.cycle.as_ref().unwrap().period();
+//! let mut controller = Controller::new(period).blocks(config.control.clone());
+//! controller.run(&pi, || true).unwrap();
+//! ```
+
+use crate::control::Block;
+use crate::picontrol::{PiControl, PiControlBuilder, PiControlError, Scaling, Value};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path, path::PathBuf, time::Duration};
+
+/// The `[picontrol]` section, see [`PiControlBuilder`]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PiControlConfig {
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub watchdog_ms: u32,
+    #[serde(default)]
+    pub cache: bool,
+}
+
+/// The `[cycle]` section
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CycleConfig {
+    pub period_ms: u64,
+}
+
+impl CycleConfig {
+    /// Returns the cycle period
+    pub fn period(&self) -> Duration {
+        Duration::from_millis(self.period_ms)
+    }
+}
+
+/// A value in the `[safe_state]` section
+///
+/// The width of the value is only known once the variable is looked up, so
+/// bools and integers are accepted for all variables and converted when the
+/// [`PiControl`] is built.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    Bool(bool),
+    Int(u32),
+}
+
+impl ConfigValue {
+    // converts to the variant fitting a variable of the given bitlength
+    fn to_value(self, bitlength: u16) -> Option<Value> {
+        let int = match self {
+            ConfigValue::Bool(b) => b as u32,
+            ConfigValue::Int(i) => i,
+        };
+        match bitlength {
+            1 if int <= 1 => Some(Value::Bit(int == 1)),
+            8 => u8::try_from(int).ok().map(Value::Byte),
+            16 => u16::try_from(int).ok().map(Value::Word),
+            32 => Some(Value::DWord(int)),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the `[scaling]` section, mapping the raw range of a variable
+/// linearly onto a range in engineering units, see [`Scaling`]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ScalingConfig {
+    pub raw_min: i64,
+    pub raw_max: i64,
+    pub min: f64,
+    pub max: f64,
+    #[serde(default)]
+    pub offset: f64,
+    pub unit: Option<String>,
+}
+
+impl ScalingConfig {
+    /// Creates the [`Scaling`] described by this entry
+    ///
+    /// # Errors
+    /// Same as [`Scaling::new`].
+    pub fn to_scaling(&self) -> Result<Scaling, PiControlError> {
+        let scaling =
+            Scaling::new(self.raw_min, self.raw_max, self.min, self.max)?.with_offset(self.offset);
+        Ok(match &self.unit {
+            Some(unit) => scaling.with_unit(unit),
+            None => scaling,
+        })
+    }
+}
+
+/// Where a logging sink writes to
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind {
+    Stdout,
+    File,
+}
+
+/// An entry of the `[[logging]]` array
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SinkConfig {
+    pub sink: SinkKind,
+    /// Only used by [`SinkKind::File`]
+    pub path: Option<PathBuf>,
+    pub variables: Vec<String>,
+    pub interval_ms: u64,
+}
+
+/// The whole application config, see the [module documentation](self)
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    #[serde(default)]
+    pub picontrol: PiControlConfig,
+    pub cycle: Option<CycleConfig>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default)]
+    pub safe_state: BTreeMap<String, ConfigValue>,
+    #[serde(default)]
+    pub scaling: BTreeMap<String, ScalingConfig>,
+    /// Changes smaller than the deadband, in raw units, are ignored
+    #[serde(default)]
+    pub deadband: BTreeMap<String, u32>,
+    /// Blocks of a [`Controller`](crate::control::Controller) by name
+    #[serde(default)]
+    pub control: BTreeMap<String, Block>,
+    #[serde(default)]
+    pub logging: Vec<SinkConfig>,
+}
+
+impl AppConfig {
+    /// Parses a config from a TOML document.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::TomlError`] if the document is malformed or
+    /// contains unknown keys.
+    pub fn from_toml(s: &str) -> Result<Self, PiControlError> {
+        toml::from_str(s).map_err(PiControlError::from)
+    }
+
+    /// Reads and parses the config file at `path`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file can't be read and a
+    /// [`PiControlError::TomlError`] if it is malformed.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PiControlError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Returns a [`PiControlBuilder`] configured with the `picontrol` and
+    /// `aliases` sections. The safe state is only applied by
+    /// [`AppConfig::build`], since the variables have to be looked up for it.
+    pub fn builder(&self) -> PiControlBuilder {
+        let mut builder = PiControl::builder()
+            .watchdog_ms(self.picontrol.watchdog_ms)
+            .cache(self.picontrol.cache);
+        if let Some(path) = &self.picontrol.path {
+            builder = builder.path(path);
+        }
+        for (alias, name) in self.aliases.iter() {
+            builder = builder.alias(alias, name);
+        }
+        builder
+    }
+
+    /// Creates a [`PiControl`] configured with the `picontrol`, `aliases`,
+    /// `safe_state` and `scaling` sections.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the processimage can't be
+    /// opened and a [`PiControlError::InvalidArgument`] if a variable of the
+    /// safe state can't be found or its value doesn't fit into it, or if a
+    /// raw range of the scaling is empty.
+    pub fn build(&self) -> Result<PiControl, PiControlError> {
+        let mut builder = self.builder();
+        for (name, scaling) in self.scaling.iter() {
+            builder = builder.scaling(name, scaling.to_scaling()?);
+        }
+        let pi = builder.build()?;
+        let mut safe_state = BTreeMap::new();
+        for (name, value) in self.safe_state.iter() {
+            let bitlength = pi.find_variable(name)?.length;
+            let value = value
+                .to_value(bitlength)
+                .ok_or(PiControlError::InvalidArgument("safe_state"))?;
+            safe_state.insert(name.clone(), value);
+        }
+        pi.set_safe_state(safe_state);
+        Ok(pi)
+    }
+}
@@ -0,0 +1,102 @@
+//! The relay and the hardware watchdog of the RevPi Connect
+//!
+//! Besides the LEDs, see [`leds`](crate::leds), the Connect has two more bits
+//! in the `RevPiLED` output: bit 6 switches the relay output on X2 (X2 DO)
+//! and bit 7 triggers the hardware watchdog. Once the watchdog is activated
+//! with the jumper on X2, the bit has to be toggled at least every 60
+//! seconds, otherwise the Connect restarts:
+//! ```no_run
+//! use revpi::connect::Connect;
+//! use revpi::picontrol::{raw::PiControlRaw, PiControl};
+//! use std::{thread, time::Duration};
+//!
+//! let connect = Connect::detect(&PiControlRaw::new().unwrap()).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! connect.set_relay(&pi, true).unwrap();
+//! loop {
+//!     connect.trigger_watchdog(&pi).unwrap();
+//!     thread::sleep(Duration::from_secs(10));
+//! }
+//! ```
+
+use crate::leds::{Model, LED_VARIABLE};
+use crate::picontrol::{raw::PiControlRaw, PiControl, PiControlError};
+use crate::util::ensure;
+
+/// Bit of the relay output on X2 inside `RevPiLED`
+pub const RELAY_BIT: u8 = 6;
+
+/// Bit of the hardware watchdog inside `RevPiLED`
+pub const WATCHDOG_BIT: u8 = 7;
+
+/// Access to the relay and the hardware watchdog of a RevPi Connect, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::connect::Connect;
+/// use revpi::leds::{Model, LED_VARIABLE};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new().variable(LED_VARIABLE, 6, 0, 8));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// assert!(Connect::new(Model::Core).is_err());
+/// let connect = Connect::new(Model::Connect).unwrap();
+/// connect.set_relay(&pi, true).unwrap();
+/// connect.trigger_watchdog(&pi).unwrap();
+/// assert_eq!(mock.read(6, 1).unwrap(), vec![0b1100_0000]);
+/// connect.trigger_watchdog(&pi).unwrap();
+/// assert_eq!(mock.read(6, 1).unwrap(), vec![0b0100_0000]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Connect(());
+
+impl Connect {
+    /// Creates the access if `model` is a [`Model::Connect`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] for other
+    /// models, since their bits have other meanings.
+    pub fn new(model: Model) -> Result<Self, PiControlError> {
+        ensure!(
+            model == Model::Connect,
+            PiControlError::NotSupportedOnThisModel
+        );
+        Ok(Self(()))
+    }
+
+    /// Asks the driver whether the base module is a Connect, see
+    /// [`Model::detect`]
+    ///
+    /// # Errors
+    /// Same as [`Model::detect`] and [`Connect::new`].
+    pub fn detect(raw: &PiControlRaw) -> Result<Self, PiControlError> {
+        Self::new(Model::detect(raw)?)
+    }
+
+    /// Returns whether the relay on X2 is closed
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_flag`].
+    pub fn relay(&self, pi: &PiControl) -> Result<bool, PiControlError> {
+        pi.get_flag(LED_VARIABLE, RELAY_BIT)
+    }
+
+    /// Closes or opens the relay on X2
+    ///
+    /// # Errors
+    /// Same as [`PiControl::set_flag`].
+    pub fn set_relay(&self, pi: &PiControl, closed: bool) -> Result<(), PiControlError> {
+        pi.set_flag(LED_VARIABLE, RELAY_BIT, closed)
+    }
+
+    /// Toggles the watchdog bit, which resets the hardware watchdog
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_flag`] and [`PiControl::set_flag`].
+    pub fn trigger_watchdog(&self, pi: &PiControl) -> Result<(), PiControlError> {
+        let bit = pi.get_flag(LED_VARIABLE, WATCHDOG_BIT)?;
+        pi.set_flag(LED_VARIABLE, WATCHDOG_BIT, !bit)
+    }
+}
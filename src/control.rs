@@ -0,0 +1,428 @@
+//! Ramps, hysteresis and PID control of variables
+//!
+//! A [`Controller`] runs a set of named [`Block`]s, each reading and writing
+//! variables whenever it is polled, for simple closed loops without a full
+//! PLC runtime:
+//! ```no_run
+//! use revpi::control::{Controller, Hysteresis, Pid, Ramp};
+//! use revpi::picontrol::PiControl;
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut controller = Controller::new(Duration::from_millis(100))
+//!     // heat to the setpoint entered on the HMI
+//!     .block(
+//!         "heater",
+//!         Pid::new("Temperature", "Setpoint", "Heater", 2.0, 0.1, 0.0).limits(0.0, 100.0),
+//!     )
+//!     .block("fan", Hysteresis::new("Temperature", "Fan", 70.0, 65.0))
+//!     // open the valve by at most 5% per second
+//!     .block("valve", Ramp::new("Valve_Setpoint", "Valve", 5.0));
+//! controller.run(&pi, || true).unwrap();
+//! ```
+//!
+//! Values are in engineering units if the variable has a
+//! [`Scaling`](crate::picontrol::Scaling), otherwise raw unsigned numbers.
+//!
+//! With the `toml` feature, blocks can also be read from a TOML document,
+//! e.g. the `[control]` section of an
+//! [`AppConfig`](crate::config::AppConfig):
+//! ```toml
+//! [control.heater]
+//! type = "pid"
+//! input = "Temperature"
+//! setpoint = 60.0
+//! output = "Heater"
+//! kp = 2.0
+//! ki = 0.1
+//! min = 0.0
+//! max = 100.0
+//!
+//! [control.fan]
+//! type = "hysteresis"
+//! input = "Temperature"
+//! output = "Fan"
+//! on = 70.0
+//! off = 65.0
+//!
+//! [control.valve]
+//! type = "ramp"
+//! target = "Valve_Setpoint"
+//! output = "Valve"
+//! rate = 5.0
+//! ```
+
+use crate::{
+    clock::{Clock, SystemClock},
+    cycle::{Cycle, OverrunPolicy},
+    picontrol::{PiControl, PiControlError},
+    util::ensure,
+};
+#[cfg(feature = "toml")]
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+/// Where a block takes a setpoint from
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(untagged))]
+pub enum Source {
+    /// The value of a variable
+    Variable(String),
+    /// A fixed value
+    Constant(f64),
+}
+
+impl Source {
+    fn get(&self, pi: &PiControl) -> Result<f64, PiControlError> {
+        match self {
+            Source::Variable(name) => pi.get_number(name),
+            Source::Constant(value) => Ok(*value),
+        }
+    }
+}
+
+impl From<&str> for Source {
+    /// Returns a [`Source::Variable`]
+    fn from(name: &str) -> Self {
+        Source::Variable(name.to_string())
+    }
+}
+
+impl From<f64> for Source {
+    /// Returns a [`Source::Constant`]
+    fn from(value: f64) -> Self {
+        Source::Constant(value)
+    }
+}
+
+/// Moves `output` towards `target` by at most `rate` units per second
+///
+/// The ramp starts at the value `output` has when it is first polled.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(deny_unknown_fields))]
+pub struct Ramp {
+    pub target: Source,
+    pub output: String,
+    pub rate: f64,
+    #[cfg_attr(feature = "toml", serde(skip))]
+    current: Option<f64>,
+}
+
+impl Ramp {
+    /// Creates a ramp of `output` towards `target`
+    pub fn new<S: Into<Source>>(target: S, output: &str, rate: f64) -> Self {
+        Self {
+            target: target.into(),
+            output: output.to_string(),
+            rate,
+            current: None,
+        }
+    }
+
+    fn step(&mut self, pi: &PiControl, dt: Duration) -> Result<(), PiControlError> {
+        ensure!(
+            self.rate.is_finite() && self.rate > 0.0,
+            PiControlError::InvalidArgument("rate")
+        );
+        let target = self.target.get(pi)?;
+        let current = match self.current {
+            Some(current) => current,
+            None => pi.get_number(&self.output)?,
+        };
+        let delta = self.rate * dt.as_secs_f64();
+        let next = target.clamp(current - delta, current + delta);
+        pi.set_number(&self.output, next)?;
+        self.current = Some(next);
+        Ok(())
+    }
+}
+
+/// Switches the bit `output` on once `input` reaches `on` and off once it
+/// reaches `off`, and leaves it in between
+///
+/// If `on` is above `off`, the output is on for high values, e.g. for a fan,
+/// otherwise for low values, e.g. for a heater.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(deny_unknown_fields))]
+pub struct Hysteresis {
+    pub input: String,
+    pub output: String,
+    pub on: f64,
+    pub off: f64,
+    #[cfg_attr(feature = "toml", serde(skip))]
+    state: bool,
+}
+
+impl Hysteresis {
+    /// Creates a hysteresis switching `output` depending on `input`
+    pub fn new(input: &str, output: &str, on: f64, off: f64) -> Self {
+        Self {
+            input: input.to_string(),
+            output: output.to_string(),
+            on,
+            off,
+            state: false,
+        }
+    }
+
+    fn step(&mut self, pi: &PiControl) -> Result<(), PiControlError> {
+        let value = pi.get_number(&self.input)?;
+        let (on, off) = match self.on > self.off {
+            true => (value >= self.on, value <= self.off),
+            false => (value <= self.on, value >= self.off),
+        };
+        if on {
+            self.state = true;
+        } else if off {
+            self.state = false;
+        }
+        pi.set_number(&self.output, self.state as u8 as f64)
+    }
+}
+
+/// Controls `output` so that `input` follows `setpoint`
+///
+/// The integral is limited to the output limits, so it doesn't wind up while
+/// the output is saturated, and the derivative is taken of the input, so
+/// changes of the setpoint don't kick the output.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(deny_unknown_fields))]
+pub struct Pid {
+    pub input: String,
+    pub setpoint: Source,
+    pub output: String,
+    pub kp: f64,
+    #[cfg_attr(feature = "toml", serde(default))]
+    pub ki: f64,
+    #[cfg_attr(feature = "toml", serde(default))]
+    pub kd: f64,
+    /// Lower limit of the output
+    pub min: Option<f64>,
+    /// Upper limit of the output
+    pub max: Option<f64>,
+    #[cfg_attr(feature = "toml", serde(skip))]
+    integral: f64,
+    #[cfg_attr(feature = "toml", serde(skip))]
+    previous: Option<f64>,
+}
+
+impl Pid {
+    /// Creates a PID controller with the gains `kp`, `ki` and `kd` and no
+    /// output limits
+    pub fn new<S: Into<Source>>(
+        input: &str,
+        setpoint: S,
+        output: &str,
+        kp: f64,
+        ki: f64,
+        kd: f64,
+    ) -> Self {
+        Self {
+            input: input.to_string(),
+            setpoint: setpoint.into(),
+            output: output.to_string(),
+            kp,
+            ki,
+            kd,
+            min: None,
+            max: None,
+            integral: 0.0,
+            previous: None,
+        }
+    }
+
+    /// Limits the output to `min..=max`
+    pub fn limits(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    fn limit(&self, value: f64) -> f64 {
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+
+    fn step(&mut self, pi: &PiControl, dt: Duration) -> Result<(), PiControlError> {
+        let input = pi.get_number(&self.input)?;
+        let error = self.setpoint.get(pi)? - input;
+        let dt = dt.as_secs_f64();
+        let mut derivative = 0.0;
+        if dt > 0.0 {
+            self.integral = self.limit(self.integral + self.ki * error * dt);
+            if let Some(previous) = self.previous {
+                derivative = -(input - previous) / dt;
+            }
+        }
+        self.previous = Some(input);
+        let output = self.limit(self.kp * error + self.integral + self.kd * derivative);
+        pi.set_number(&self.output, output)
+    }
+}
+
+/// A block run by a [`Controller`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(tag = "type", rename_all = "lowercase"))]
+pub enum Block {
+    Ramp(Ramp),
+    Hysteresis(Hysteresis),
+    Pid(Pid),
+}
+
+impl Block {
+    fn step(&mut self, pi: &PiControl, dt: Duration) -> Result<(), PiControlError> {
+        match self {
+            Block::Ramp(ramp) => ramp.step(pi, dt),
+            Block::Hysteresis(hysteresis) => hysteresis.step(pi),
+            Block::Pid(pid) => pid.step(pi, dt),
+        }
+    }
+}
+
+impl From<Ramp> for Block {
+    fn from(ramp: Ramp) -> Self {
+        Block::Ramp(ramp)
+    }
+}
+
+impl From<Hysteresis> for Block {
+    fn from(hysteresis: Hysteresis) -> Self {
+        Block::Hysteresis(hysteresis)
+    }
+}
+
+impl From<Pid> for Block {
+    fn from(pid: Pid) -> Self {
+        Block::Pid(pid)
+    }
+}
+
+/// Runs control blocks, see the [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::control::{Controller, Pid, Ramp};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let mock = Arc::new(
+///     MockBackend::new()
+///         .variable("Level", 0, 0, 16)
+///         .variable("Pump", 2, 0, 16)
+///         .variable("Valve", 4, 0, 16),
+/// );
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut controller = Controller::new(Duration::from_millis(100))
+///     .block("pump", Pid::new("Level", 500.0, "Pump", 2.0, 1.0, 0.0).limits(0.0, 1000.0))
+///     .block("valve", Ramp::new(100.0, "Valve", 50.0));
+/// mock.write(0, &400u16.to_le_bytes()).unwrap();
+/// controller.step(&pi, Duration::from_secs(1)).unwrap();
+/// // 2 * 100 + 1 * 100 * 1s
+/// assert_eq!(mock.read(2, 2).unwrap(), 300u16.to_le_bytes());
+/// assert_eq!(mock.read(4, 2).unwrap(), 50u16.to_le_bytes());
+/// controller.step(&pi, Duration::from_secs(1)).unwrap();
+/// assert_eq!(mock.read(4, 2).unwrap(), 100u16.to_le_bytes());
+/// ```
+pub struct Controller {
+    interval: Duration,
+    blocks: BTreeMap<String, Block>,
+    clock: Box<dyn Clock>,
+    // time of the previous poll
+    previous: Option<Duration>,
+}
+
+impl fmt::Debug for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Controller")
+            .field("interval", &self.interval)
+            .field("blocks", &self.blocks)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Controller {
+    /// Creates a controller without blocks, polled every `interval` in
+    /// [`Controller::run`]
+    pub fn new(interval: Duration) -> Self {
+        Self::with_clock(interval, SystemClock::new())
+    }
+
+    /// Creates a controller like [`Controller::new`], but measuring the time
+    /// between polls with `clock`
+    pub fn with_clock<C: Clock + 'static>(interval: Duration, clock: C) -> Self {
+        Self {
+            interval,
+            blocks: BTreeMap::new(),
+            clock: Box::new(clock),
+            previous: None,
+        }
+    }
+
+    /// Adds the block `name`. A block with the same name is replaced.
+    pub fn block<B: Into<Block>>(mut self, name: &str, block: B) -> Self {
+        self.blocks.insert(name.to_string(), block.into());
+        self
+    }
+
+    /// Adds all `blocks`, e.g. from the `[control]` section of an
+    /// [`AppConfig`](crate::config::AppConfig)
+    pub fn blocks<I: IntoIterator<Item = (String, Block)>>(mut self, blocks: I) -> Self {
+        self.blocks.extend(blocks);
+        self
+    }
+
+    /// Returns the block `name`
+    pub fn get(&self, name: &str) -> Option<&Block> {
+        self.blocks.get(name)
+    }
+
+    /// Runs every block once, ordered by name, as if `dt` passed since the
+    /// last step
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a variable can't be
+    /// found, a value doesn't fit into an output or the rate of a ramp isn't
+    /// positive and finite, and the errors of reading and writing the
+    /// variables. The remaining blocks aren't run then.
+    pub fn step(&mut self, pi: &PiControl, dt: Duration) -> Result<(), PiControlError> {
+        for block in self.blocks.values_mut() {
+            block.step(pi, dt)?;
+        }
+        Ok(())
+    }
+
+    /// Runs every block once with the time since the last poll, none in the
+    /// first poll
+    ///
+    /// # Errors
+    /// Same as [`Controller::step`].
+    pub fn poll(&mut self, pi: &PiControl) -> Result<(), PiControlError> {
+        let now = self.clock.now().monotonic;
+        let dt = match self.previous.replace(now) {
+            Some(previous) => now.saturating_sub(previous),
+            None => Duration::ZERO,
+        };
+        self.step(pi, dt)
+    }
+
+    /// Polls every interval until `f` returns `false`
+    ///
+    /// # Errors
+    /// Returns the first error of [`Controller::poll`].
+    pub fn run<F>(&mut self, pi: &PiControl, mut f: F) -> Result<(), PiControlError>
+    where
+        F: FnMut() -> bool,
+    {
+        let mut cycle = Cycle::new(self.interval).overrun_policy(OverrunPolicy::Skip);
+        while f() {
+            cycle.wait()?;
+            self.poll(pi)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,418 @@
+//! Fixed-period control loops
+//!
+//! A [`Cycle`] paces a loop to a fixed period and records how long every
+//! cycle took and how late it started, so it can be shown that the loop
+//! meets its timing requirements:
+//! ```
+//! use revpi::cycle::Cycle;
+//! use std::time::Duration;
+//!
+//! let mut cycle = Cycle::new(Duration::from_millis(1));
+//! for _ in 0..10 {
+//!     cycle.wait().unwrap();
+//!     // read inputs, compute, write outputs
+//! }
+//! let stats = cycle.stats();
+//! println!(
+//!     "{} cycles, max {:?}, p99 {:?}, max jitter {:?}",
+//!     stats.count(),
+//!     stats.max(),
+//!     stats.percentile(99.0),
+//!     stats.max_jitter(),
+//! );
+//! ```
+//!
+//! What happens if a cycle takes longer than the period is decided by the
+//! [`OverrunPolicy`]. Additionally a callback can be registered to e.g. log
+//! overruns or bring the outputs into a safe state:
+//! ```no_run
+//! use revpi::cycle::{Cycle, OverrunPolicy};
+//! use std::time::Duration;
+//!
+//! let mut cycle = Cycle::new(Duration::from_millis(10))
+//!     .overrun_policy(OverrunPolicy::Abort)
+//!     .on_overrun(|stats| eprintln!("overrun, longest cycle: {:?}", stats.max()));
+//! while cycle.wait().is_ok() {
+//!     // read inputs, compute, write outputs
+//! }
+//! ```
+//!
+//! A [`CycleRunner`] does the reading and writing as well, like the scan
+//! cycle of a PLC.
+
+mod runner;
+
+pub use self::runner::{CycleRunner, Snapshot};
+use crate::metrics::Metrics;
+use crate::picontrol::PiControlError;
+use std::{
+    collections::VecDeque,
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+// number of durations kept for calculating percentiles
+const WINDOW: usize = 1024;
+
+/// Timing statistics of a [`Cycle`]
+///
+/// The duration of a cycle is the time from its start until the next call of
+/// [`Cycle::wait`], i.e. the time spent working. The jitter of a cycle is how
+/// much later than scheduled it started.\
+/// Minimum, maximum and means cover all cycles since the last reset, while
+/// percentiles are calculated from the last 1024 cycles.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CycleStats {
+    count: u64,
+    overruns: u64,
+    min: Option<Duration>,
+    max: Duration,
+    total: Duration,
+    recent: VecDeque<Duration>,
+    jitter_count: u64,
+    max_jitter: Duration,
+    total_jitter: Duration,
+}
+
+impl CycleStats {
+    fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.min = Some(self.min.map_or(duration, |min| min.min(duration)));
+        self.max = self.max.max(duration);
+        self.total += duration;
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+    }
+
+    fn record_jitter(&mut self, jitter: Duration) {
+        self.jitter_count += 1;
+        self.max_jitter = self.max_jitter.max(jitter);
+        self.total_jitter += jitter;
+    }
+
+    /// Returns the number of completed cycles
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the number of cycles that didn't end before the next cycle was
+    /// due. With [`OverrunPolicy::CatchUp`], the cycles started late to catch
+    /// up aren't counted, only the one that delayed them.
+    pub fn overruns(&self) -> u64 {
+        self.overruns
+    }
+
+    /// Returns the shortest cycle duration, `None` if no cycle completed yet
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// Returns the longest cycle duration
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns the mean cycle duration, `None` if no cycle completed yet
+    pub fn mean(&self) -> Option<Duration> {
+        mean(self.total, self.count)
+    }
+
+    /// Returns the cycle duration that `p` percent of the recent cycles didn't
+    /// exceed, `None` if no cycle completed yet. `p` is clamped to `0..=100`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.recent.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64).round();
+        Some(sorted[rank as usize])
+    }
+
+    /// Returns the largest delay of the start of a cycle
+    pub fn max_jitter(&self) -> Duration {
+        self.max_jitter
+    }
+
+    /// Returns the mean delay of the start of a cycle, `None` if no cycle
+    /// started yet
+    pub fn mean_jitter(&self) -> Option<Duration> {
+        mean(self.total_jitter, self.jitter_count)
+    }
+}
+
+fn mean(total: Duration, count: u64) -> Option<Duration> {
+    (count != 0).then(|| Duration::from_secs_f64(total.as_secs_f64() / count as f64))
+}
+
+/// Decides what a [`Cycle`] does if a cycle took longer than the period
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub enum OverrunPolicy {
+    /// The missed cycles are dropped, the next cycle starts at the next point
+    /// of the original schedule.
+    Skip,
+    /// The missed cycles start immediately one after another until the
+    /// schedule is met again. Only the first late cycle counts as an overrun,
+    /// the next overrun is counted once the schedule was met.
+    #[default]
+    CatchUp,
+    /// [`Cycle::wait`] returns [`PiControlError::Overrun`]. If the loop is
+    /// continued anyway, the missed cycles are dropped like with
+    /// [`OverrunPolicy::Skip`].
+    Abort,
+}
+
+/// Callback of [`Cycle::on_overrun`]
+pub struct OverrunHook(pub Box<dyn FnMut(&CycleStats) + Send>);
+
+impl fmt::Debug for OverrunHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OverrunHook")
+    }
+}
+
+/// Paces a loop to a fixed period
+///
+/// Cycles are scheduled relative to the first one, so delays don't accumulate.
+/// If a cycle takes longer than the period, the [`OverrunPolicy`] decides how
+/// the schedule continues.
+#[derive(Debug)]
+pub struct Cycle {
+    period: Duration,
+    policy: OverrunPolicy,
+    on_overrun: Option<OverrunHook>,
+    deadline: Option<Instant>,
+    start: Option<Instant>,
+    // whether late cycles are catching up with an overrun counted already
+    catching_up: bool,
+    stats: CycleStats,
+    metrics: Metrics,
+}
+
+impl Cycle {
+    /// Creates a new cycle with the given period and
+    /// [`OverrunPolicy::CatchUp`]
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            policy: OverrunPolicy::default(),
+            on_overrun: None,
+            deadline: None,
+            start: None,
+            catching_up: false,
+            stats: CycleStats::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Sets what happens if a cycle takes longer than the period
+    pub fn overrun_policy(mut self, policy: OverrunPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets a callback that is called with the updated statistics every time
+    /// an overrun is counted, see [`CycleStats::overruns`], before the
+    /// [`OverrunPolicy`] is applied.
+    pub fn on_overrun<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&CycleStats) + Send + 'static,
+    {
+        self.on_overrun = Some(OverrunHook(Box::new(hook)));
+        self
+    }
+
+    /// Returns the period
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    // first point of the schedule after `now`
+    fn next_after(&self, deadline: Instant, now: Instant) -> Instant {
+        let period = self.period.as_nanos();
+        let missed = (now - deadline).as_nanos() / period + 1;
+        deadline + Duration::from_nanos((missed * period) as u64)
+    }
+
+    /// Ends the current cycle and blocks until the next one is due. The first
+    /// call returns immediately and starts the schedule.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::Overrun`] if the next cycle was already due
+    /// and the policy is [`OverrunPolicy::Abort`].
+    pub fn wait(&mut self) -> Result<(), PiControlError> {
+        let now = Instant::now();
+        if let Some(start) = self.start {
+            self.stats.record(now - start);
+            self.metrics.cycle_duration.record(now - start);
+        }
+        let mut deadline = self.deadline.map_or(now, |d| d + self.period);
+        if now <= deadline || self.period.is_zero() {
+            self.catching_up = false;
+        } else {
+            if !self.catching_up {
+                self.stats.overruns += 1;
+                self.metrics.missed_deadlines += 1;
+                if let Some(hook) = self.on_overrun.as_mut() {
+                    (hook.0)(&self.stats);
+                }
+            }
+            self.catching_up = self.policy == OverrunPolicy::CatchUp;
+            if self.policy != OverrunPolicy::CatchUp {
+                deadline = self.next_after(deadline, now);
+            }
+            if self.policy == OverrunPolicy::Abort {
+                // the next call of wait starts the next cycle at the deadline
+                self.deadline = Some(deadline - self.period);
+                self.start = None;
+                return Err(PiControlError::Overrun);
+            }
+        }
+        if let Some(remaining) = deadline.checked_duration_since(now) {
+            thread::sleep(remaining);
+        }
+        let start = Instant::now();
+        let jitter = start.saturating_duration_since(deadline);
+        self.stats.record_jitter(jitter);
+        self.metrics.jitter.record(jitter);
+        self.deadline = Some(deadline);
+        self.start = Some(start);
+        Ok(())
+    }
+
+    /// Returns the statistics of all cycles since the creation or the last
+    /// reset
+    pub fn stats(&self) -> &CycleStats {
+        &self.stats
+    }
+
+    /// Resets the statistics, the schedule and the metrics are kept
+    pub fn reset_stats(&mut self) {
+        self.stats = CycleStats::default();
+    }
+
+    /// Returns the metrics of all cycles since the creation, see
+    /// [`metrics`](crate::metrics)
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    const PERIOD: Duration = Duration::from_millis(10);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn empty_stats() {
+        let stats = CycleStats::default();
+        assert_eq!(stats.count(), 0);
+        assert_eq!(stats.min(), None);
+        assert_eq!(stats.max(), Duration::ZERO);
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.percentile(50.0), None);
+        assert_eq!(stats.mean_jitter(), None);
+    }
+
+    #[test]
+    fn durations() {
+        let mut stats = CycleStats::default();
+        for millis in [5, 1, 9, 3, 7, 2, 8, 4, 6] {
+            stats.record(ms(millis));
+        }
+        assert_eq!(stats.count(), 9);
+        assert_eq!(stats.min(), Some(ms(1)));
+        assert_eq!(stats.max(), ms(9));
+        assert_eq!(stats.mean(), Some(ms(5)));
+        assert_eq!(stats.percentile(0.0), Some(ms(1)));
+        assert_eq!(stats.percentile(50.0), Some(ms(5)));
+        assert_eq!(stats.percentile(100.0), Some(ms(9)));
+        // out of range percentiles are clamped
+        assert_eq!(stats.percentile(-1.0), Some(ms(1)));
+        assert_eq!(stats.percentile(200.0), Some(ms(9)));
+    }
+
+    #[test]
+    fn percentile_window() {
+        let mut stats = CycleStats::default();
+        stats.record(ms(1));
+        for _ in 0..WINDOW {
+            stats.record(ms(2));
+        }
+        // the first duration dropped out of the window, but not of the minimum
+        assert_eq!(stats.percentile(0.0), Some(ms(2)));
+        assert_eq!(stats.min(), Some(ms(1)));
+        assert_eq!(stats.count(), WINDOW as u64 + 1);
+    }
+
+    #[test]
+    fn jitter() {
+        let mut stats = CycleStats::default();
+        for millis in [2, 6, 1] {
+            stats.record_jitter(ms(millis));
+        }
+        assert_eq!(stats.max_jitter(), ms(6));
+        assert_eq!(stats.mean_jitter(), Some(ms(3)));
+        // jitter doesn't count as a completed cycle
+        assert_eq!(stats.count(), 0);
+    }
+
+    #[test]
+    fn skip() {
+        let mut cycle = Cycle::new(PERIOD).overrun_policy(OverrunPolicy::Skip);
+        cycle.wait().unwrap();
+        let first = Instant::now();
+        thread::sleep(PERIOD * 5 / 2);
+        cycle.wait().unwrap();
+        // the cycles at 10 and 20 ms are dropped
+        assert!(first.elapsed() >= PERIOD * 3 - Duration::from_millis(1));
+        cycle.wait().unwrap();
+        assert_eq!(cycle.stats().overruns(), 1);
+        assert_eq!(cycle.metrics().missed_deadlines(), 1);
+    }
+
+    #[test]
+    fn catch_up() {
+        let hooks = Arc::new(AtomicU64::new(0));
+        let calls = hooks.clone();
+        let mut cycle = Cycle::new(PERIOD).on_overrun(move |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+        });
+        cycle.wait().unwrap();
+        thread::sleep(PERIOD * 7 / 2);
+        // the cycles at 10, 20 and 30 ms start at once, the one at 40 ms is
+        // on time again
+        for _ in 0..4 {
+            cycle.wait().unwrap();
+        }
+        assert_eq!(cycle.stats().overruns(), 1);
+        thread::sleep(PERIOD * 3 / 2);
+        cycle.wait().unwrap();
+        cycle.wait().unwrap();
+        assert_eq!(cycle.stats().overruns(), 2);
+        assert_eq!(hooks.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn abort() {
+        let mut cycle = Cycle::new(PERIOD).overrun_policy(OverrunPolicy::Abort);
+        cycle.wait().unwrap();
+        thread::sleep(PERIOD * 3 / 2);
+        assert!(matches!(cycle.wait(), Err(PiControlError::Overrun)));
+        // continuing skips the missed cycles
+        cycle.wait().unwrap();
+        cycle.wait().unwrap();
+        assert_eq!(cycle.stats().overruns(), 1);
+    }
+}
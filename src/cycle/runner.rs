@@ -0,0 +1,302 @@
+//! PLC-style scan cycles
+
+use super::{Cycle, CycleStats};
+use crate::metrics::{Histogram, Metrics};
+use crate::picontrol::{raw::Bit, PiControl, PiControlError};
+use std::{ops::Range, time::Instant};
+
+/// Copy of regions of the processimage, addressed like the processimage
+/// itself
+///
+/// A [`CycleRunner`] passes one with the inputs read at the start of the
+/// cycle and one with the outputs to write at its end.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Snapshot {
+    regions: Vec<(Range<u16>, Vec<u8>)>,
+}
+
+impl Snapshot {
+    fn add(&mut self, range: Range<u16>) {
+        let len = range.len();
+        self.regions.push((range, vec![0; len]));
+    }
+
+    // the region containing `len` bytes at `address` and the index of the
+    // first of them
+    fn find(&self, address: u16, len: usize) -> Result<(usize, usize), PiControlError> {
+        self.regions
+            .iter()
+            .position(|(r, _)| r.start <= address && address as usize + len <= r.end as usize)
+            .map(|i| (i, (address - self.regions[i].0.start) as usize))
+            .ok_or(PiControlError::InvalidArgument("address"))
+    }
+
+    /// Returns the regions, i.e. their addresses and bytes
+    pub fn regions(&self) -> impl Iterator<Item = (Range<u16>, &[u8])> {
+        self.regions.iter().map(|(r, b)| (r.clone(), b.as_slice()))
+    }
+
+    /// Returns `len` bytes at `address`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside a single region.
+    pub fn get_bytes(&self, address: u16, len: usize) -> Result<&[u8], PiControlError> {
+        let (i, start) = self.find(address, len)?;
+        Ok(&self.regions[i].1[start..start + len])
+    }
+
+    /// Overwrites the bytes at `address` with `bytes`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn set_bytes(&mut self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        let (i, start) = self.find(address, bytes.len())?;
+        self.regions[i].1[start..start + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Returns the bit `bit` of the byte at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        Ok((self.get_byte(address)? >> bit as u8) & 1 == 1)
+    }
+
+    /// Sets the bit `bit` of the byte at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn set_bit(&mut self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        let (i, start) = self.find(address, 1)?;
+        let byte = &mut self.regions[i].1[start];
+        match value {
+            true => *byte |= 1 << bit as u8,
+            false => *byte &= !(1 << bit as u8),
+        }
+        Ok(())
+    }
+
+    /// Returns the byte at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn get_byte(&self, address: u16) -> Result<u8, PiControlError> {
+        Ok(self.get_bytes(address, 1)?[0])
+    }
+
+    /// Sets the byte at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn set_byte(&mut self, address: u16, value: u8) -> Result<(), PiControlError> {
+        self.set_bytes(address, &[value])
+    }
+
+    /// Returns the little endian word at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn get_word(&self, address: u16) -> Result<u16, PiControlError> {
+        let bytes = self.get_bytes(address, 2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Sets the little endian word at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn set_word(&mut self, address: u16, value: u16) -> Result<(), PiControlError> {
+        self.set_bytes(address, &value.to_le_bytes())
+    }
+
+    /// Returns the little endian double word at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn get_dword(&self, address: u16) -> Result<u32, PiControlError> {
+        let bytes = self.get_bytes(address, 4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Sets the little endian double word at `address`
+    ///
+    /// # Errors
+    /// Same as [`Snapshot::get_bytes`].
+    pub fn set_dword(&mut self, address: u16, value: u32) -> Result<(), PiControlError> {
+        self.set_bytes(address, &value.to_le_bytes())
+    }
+}
+
+/// Runs a scan cycle at a fixed period: read the inputs, compute, write the
+/// outputs
+///
+/// Every cycle reads each input region with a single read, calls the user
+/// function with the inputs and the outputs and writes each output region
+/// that changed with a single write. The outputs keep their values between
+/// cycles and start out as read from the processimage in the first cycle.
+/// Timing and overruns are handled by the [`Cycle`].
+///
+/// # Example
+/// ```
+/// use revpi::cycle::{Cycle, CycleRunner};
+/// use revpi::picontrol::{backend::MockBackend, raw::Bit, PiControl};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// mock.write(11, &[0b1]).unwrap();
+/// let mut runner = CycleRunner::new(Cycle::new(Duration::from_millis(1)))
+///     .input(11..81)
+///     .output(81..99);
+/// let mut cycles = 0;
+/// runner
+///     .run(&pi, |inputs, outputs| {
+///         // copy input 1 of the DIO to output 1
+///         let on = inputs.get_bit(11, Bit::Zero)?;
+///         outputs.set_bit(81, Bit::Zero, on)?;
+///         cycles += 1;
+///         Ok(cycles < 10)
+///     })
+///     .unwrap();
+/// assert_eq!(mock.read(81, 1).unwrap(), vec![0b1]);
+/// assert_eq!(runner.stats().count(), 9);
+/// ```
+#[derive(Debug)]
+pub struct CycleRunner {
+    cycle: Cycle,
+    inputs: Snapshot,
+    outputs: Snapshot,
+    // the outputs as last written, None before the first cycle
+    written: Option<Snapshot>,
+}
+
+impl CycleRunner {
+    /// Creates a runner without any regions, paced by `cycle`
+    pub fn new(cycle: Cycle) -> Self {
+        Self {
+            cycle,
+            inputs: Snapshot::default(),
+            outputs: Snapshot::default(),
+            written: None,
+        }
+    }
+
+    /// Adds a region that is read at the start of every cycle
+    pub fn input(mut self, range: Range<u16>) -> Self {
+        self.inputs.add(range);
+        self
+    }
+
+    /// Adds a region that is written at the end of every cycle it changed in
+    pub fn output(mut self, range: Range<u16>) -> Self {
+        self.outputs.add(range);
+        self.written = None;
+        self
+    }
+
+    /// Creates a runner paced by `cycle` with the inputs and the outputs of
+    /// every device configured in `rsc` as regions, one per device and
+    /// direction
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a device lies outside
+    /// of the processimage.
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(cycle: Cycle, rsc: &crate::rsc::RSC) -> Result<Self, PiControlError> {
+        let mut runner = Self::new(cycle);
+        for device in rsc.devices.iter() {
+            for (vars, outputs) in [(&device.inp, false), (&device.out, true)] {
+                let start = vars.values().filter_map(|v| device.address_of(v)).min();
+                let end = vars.values().filter_map(|v| device.end_of(v)).max();
+                if let (Some(start), Some(end)) = (start, end) {
+                    let invalid = |_| PiControlError::InvalidArgument("device");
+                    let range = u16::try_from(start).map_err(invalid)?
+                        ..u16::try_from(end).map_err(invalid)?;
+                    runner = match outputs {
+                        false => runner.input(range),
+                        true => runner.output(range),
+                    };
+                }
+            }
+        }
+        Ok(runner)
+    }
+
+    /// Returns the cycle
+    pub fn cycle(&self) -> &Cycle {
+        &self.cycle
+    }
+
+    /// Returns the timing statistics, see [`Cycle::stats`]
+    pub fn stats(&self) -> &CycleStats {
+        self.cycle.stats()
+    }
+
+    /// Returns the metrics, including the latency of reading and writing the
+    /// regions, see [`Cycle::metrics`]
+    pub fn metrics(&self) -> &Metrics {
+        self.cycle.metrics()
+    }
+
+    /// Waits for the next cycle and runs it, `f` gets the inputs and the
+    /// outputs. Returns what `f` returned.
+    ///
+    /// # Errors
+    /// Returns the error of [`Cycle::wait`], of `f` and of the backend if
+    /// reading or writing fails. If `f` fails, no outputs are written.
+    pub fn step<F>(&mut self, pi: &PiControl, f: F) -> Result<bool, PiControlError>
+    where
+        F: FnOnce(&Snapshot, &mut Snapshot) -> Result<bool, PiControlError>,
+    {
+        self.cycle.wait()?;
+        let latency = &mut self.cycle.metrics.io_latency;
+        for (range, bytes) in self.inputs.regions.iter_mut() {
+            timed(latency, || unsafe {
+                pi.inner.get_bytes(range.start, bytes)
+            })?;
+        }
+        if self.written.is_none() {
+            for (range, bytes) in self.outputs.regions.iter_mut() {
+                timed(latency, || unsafe {
+                    pi.inner.get_bytes(range.start, bytes)
+                })?;
+            }
+            self.written = Some(self.outputs.clone());
+        }
+        let res = f(&self.inputs, &mut self.outputs)?;
+        let written = self.written.get_or_insert_with(Snapshot::default);
+        for ((range, bytes), (_, old)) in
+            self.outputs.regions.iter().zip(written.regions.iter_mut())
+        {
+            if bytes != old {
+                // the user added the region as output
+                timed(&mut self.cycle.metrics.io_latency, || unsafe {
+                    pi.inner.set_bytes(range.start, bytes)
+                })?;
+                old.copy_from_slice(bytes);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Runs cycles until `f` returns `false` or an error
+    ///
+    /// # Errors
+    /// Same as [`CycleRunner::step`].
+    pub fn run<F>(&mut self, pi: &PiControl, mut f: F) -> Result<(), PiControlError>
+    where
+        F: FnMut(&Snapshot, &mut Snapshot) -> Result<bool, PiControlError>,
+    {
+        while self.step(pi, &mut f)? {}
+        Ok(())
+    }
+}
+
+fn timed<T>(latency: &mut Histogram, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    latency.record(start.elapsed());
+    res
+}
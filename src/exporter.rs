@@ -0,0 +1,202 @@
+//! Prometheus exporter for variables of the processimage
+//!
+//! [`Exporter`] serves the current values of variables as Prometheus gauges
+//! over HTTP, so Grafana and the like can chart them without any further
+//! software on the RevPi:
+//! ```no_run
+//! use revpi::exporter::Exporter;
+//! use revpi::picontrol::PiControl;
+//!
+//! let pi = PiControl::new().unwrap();
+//! Exporter::new()
+//!     .variable("Core_Temperature")
+//!     .variable("I_1")
+//!     .serve(&pi, "0.0.0.0:9100")
+//!     .unwrap();
+//! ```
+//! Every variable is a sample of the gauge `revpi_variable`, labeled with its
+//! name, e.g. `revpi_variable{name="I_1"} 1`. The values are read when
+//! `/metrics` is requested, so the scrape interval determines the resolution.
+//! Bits are exported as `0` or `1`, all other values as unsigned integers.
+//! Variables longer than 32 bits, e.g. byte arrays of gateways, can't be
+//! exported.
+
+use crate::{
+    http,
+    picontrol::{PiControl, PiControlError, Value},
+};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+// version of the Prometheus text format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Serves variables as Prometheus gauges, see the
+/// [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Exporter {
+    prefix: String,
+    variables: Vec<String>,
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// escapes a label value, see the Prometheus text format
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// the value as sample, bits as `0` or `1`
+fn sample(value: Value) -> Result<u32, PiControlError> {
+    match value {
+        Value::Bytes(_) | Value::String(_) => Err(PiControlError::InvalidArgument("bitlength")),
+        v => Ok(v.as_u32()),
+    }
+}
+
+impl Exporter {
+    /// Creates an exporter without any variables and the prefix `revpi`
+    pub fn new() -> Self {
+        Self {
+            prefix: "revpi".to_string(),
+            variables: Vec::new(),
+        }
+    }
+
+    /// Sets the prefix of the metric names
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Adds the variable `name`
+    pub fn variable(mut self, name: &str) -> Self {
+        self.variables.push(name.to_string());
+        self
+    }
+
+    /// Creates an exporter with every exported variable configured in `rsc`,
+    /// except for the ones longer than 32 bits
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(rsc: &crate::rsc::RSC) -> Self {
+        Self {
+            variables: rsc
+                .devices
+                .iter()
+                .flat_map(|device| device.variables())
+                .filter(|var| var.exported && !var.name.is_empty() && var.bit_length <= 32)
+                .map(|var| var.name.clone())
+                .collect(),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the names of the exported variables
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Reads all variables and encodes them in the Prometheus text format
+    ///
+    /// # Errors
+    /// Returns an error if a variable can't be found or read and a
+    /// [`PiControlError::InvalidArgument`] if it is longer than 32 bits.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::exporter::Exporter;
+    /// use revpi::picontrol::{backend::MockBackend, PiControl};
+    /// use std::sync::Arc;
+    ///
+    /// let mock = Arc::new(MockBackend::new().variable("I_1", 0, 3, 1).variable("Temp", 1, 0, 16));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// mock.write(0, &[0b1000, 42, 0]).unwrap();
+    /// let text = Exporter::new().variable("I_1").variable("Temp").encode(&pi).unwrap();
+    /// assert!(text.contains("revpi_variable{name=\"I_1\"} 1\n"));
+    /// assert!(text.contains("revpi_variable{name=\"Temp\"} 42\n"));
+    /// ```
+    pub fn encode(&self, pi: &PiControl) -> Result<String, PiControlError> {
+        let name = format!("{}_variable", self.prefix);
+        let mut out = format!(
+            "# HELP {0} Value of a variable of the processimage\n# TYPE {0} gauge\n",
+            name
+        );
+        for var in self.variables.iter() {
+            let value = sample(pi.get_value(var)?)?;
+            out.push_str(&format!("{}{{name=\"{}\"}} {}\n", name, escape(var), value));
+        }
+        Ok(out)
+    }
+
+    /// Answers a single HTTP request on `stream`. `GET /metrics` gets the
+    /// values, see [`Exporter::encode`], everything else an error status.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the request can't be read or
+    /// the response can't be written. Errors reading the variables are sent
+    /// to the client instead.
+    pub fn handle(&self, pi: &PiControl, mut stream: TcpStream) -> Result<(), PiControlError> {
+        let request = http::read_request(&mut stream)?;
+        let (status, body) = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/metrics") => match self.encode(pi) {
+                Ok(body) => ("200 OK", body),
+                Err(e) => ("500 Internal Server Error", format!("{}\n", e)),
+            },
+            ("GET", _) => ("404 Not Found", "not found\n".to_string()),
+            _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+        };
+        http::respond(&mut stream, status, CONTENT_TYPE, &body)
+    }
+
+    /// Listens on `addr` and answers requests one after another, forever
+    ///
+    /// Failed connections are dropped without affecting the others.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if `addr` can't be bound.
+    pub fn serve<A: ToSocketAddrs>(&self, pi: &PiControl, addr: A) -> Result<(), PiControlError> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming().flatten() {
+            // a broken connection only concerns its client
+            let _ = self.handle(pi, stream);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::MockBackend;
+
+    #[test]
+    fn long_variables() {
+        let mock = MockBackend::new()
+            .variable("Word", 0, 0, 16)
+            .variable("Bytes", 2, 0, 64);
+        let pi = PiControl::builder().backend(mock).build().unwrap();
+        let exporter = Exporter::new().variable("Word").variable("Bytes");
+        assert!(matches!(
+            exporter.encode(&pi),
+            Err(PiControlError::InvalidArgument("bitlength"))
+        ));
+    }
+
+    #[cfg(feature = "rsc")]
+    #[test]
+    fn from_rsc() {
+        use crate::rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, RscBuilder};
+
+        let device = DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V")
+            .memory(InOutMemBuilder::new("DWord", 32))
+            .memory(InOutMemBuilder::new("Bytes", 64))
+            .memory(InOutMemBuilder::new("Hidden", 8).exported(false));
+        let rsc = RscBuilder::new().device(device).build().unwrap();
+        assert_eq!(Exporter::from_rsc(&rsc).variables(), ["DWord"]);
+    }
+}
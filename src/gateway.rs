@@ -0,0 +1,465 @@
+//! Named fields inside the data area of a fieldbus gateway
+//!
+//! Gateway modules (PROFINET, EtherNet/IP, ...) exchange their cyclic data
+//! through a plain block of bytes in the processimage. A [`GatewayMap`] gives
+//! the fields inside such a block a name, a width and an endianness, so the
+//! offsets only have to be defined once:
+//! ```no_run
+//! use revpi::gateway::{Endianness, GatewayMap, Width};
+//! use revpi::picontrol::{PiControl, Value};
+//!
+//! let pi = PiControl::new().unwrap();
+//! let map = GatewayMap::new(75)
+//!     .field("speed", 0, Width::Word, Endianness::Big)
+//!     .field("running", 2, Width::Bit(0), Endianness::Little);
+//! let speed = map.get(&pi, "speed").unwrap();
+//! map.set(&pi, "running", Value::Bit(true)).unwrap();
+//! ```
+//!
+//! With the `toml` feature, maps can also be read from a TOML document:
+//! ```toml
+//! base = 75
+//!
+//! [fields]
+//! speed = { offset = 0, width = "word", endianness = "big" }
+//! running = { offset = 2, width = { bit = 0 } }
+//! ```
+//!
+//! To move whole structures across a gateway, a [`GatewayChannel`] copies the
+//! input and output blocks of the gateway at once and gives access to them
+//! as byte slices:
+//! ```no_run
+//! use revpi::gateway::GatewayChannel;
+//! use revpi::picontrol::{raw::{DeviceInfo, PiControlRaw}, PiControl};
+//!
+//! let raw = PiControlRaw::new().unwrap();
+//! let info = DeviceInfo::from(raw.get_device_info(33).unwrap());
+//! let mut channel = GatewayChannel::from_info(&info).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! let received = channel.read(&pi).unwrap().to_vec();
+//! channel.outputs_mut()[..received.len()].copy_from_slice(&received);
+//! channel.write(&pi).unwrap();
+//! ```
+
+pub use crate::picontrol::raw::Endianness;
+use crate::picontrol::{
+    raw::{raw::KB_PI_LEN, Bit, DeviceInfo, ModuleType},
+    PiControl, PiControlError, Value,
+};
+use crate::util::ensure;
+#[cfg(feature = "toml")]
+use serde::Deserialize;
+use std::{collections::BTreeMap, ops::Range};
+
+/// Width of a field
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(rename_all = "lowercase"))]
+pub enum Width {
+    /// A single bit. The bit number may be larger than 7, in which case it is
+    /// counted from the field's offset onwards.
+    Bit(u8),
+    Byte,
+    Word,
+    DWord,
+}
+
+impl Width {
+    /// Returns the number of bytes a field of this width occupies
+    pub fn byte_len(&self) -> usize {
+        use Width::*;
+        match self {
+            Bit(_) | Byte => 1,
+            Word => 2,
+            DWord => 4,
+        }
+    }
+}
+
+/// A single field inside a gateway's data area
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+pub struct Field {
+    /// Offset in bytes, relative to the start of the data area
+    pub offset: u16,
+    pub width: Width,
+    #[cfg_attr(feature = "toml", serde(default))]
+    pub endianness: Endianness,
+}
+
+/// Maps names to fields inside a gateway's data area
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+pub struct GatewayMap {
+    /// Address of the data area inside the processimage
+    pub base: u16,
+    pub fields: BTreeMap<String, Field>,
+}
+
+impl GatewayMap {
+    /// Creates a new map without any fields. `base` is the address of the
+    /// data area inside the processimage, e.g. `i16uInputOffset` of the
+    /// gateway's [`SDeviceInfo`](crate::picontrol::raw::raw::SDeviceInfo).
+    pub fn new(base: u16) -> Self {
+        Self {
+            base,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Parses a map from a TOML document, see the [module documentation](self)
+    /// for the format.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::TomlError`] if the document is malformed.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(s: &str) -> Result<Self, PiControlError> {
+        toml::from_str(s).map_err(PiControlError::from)
+    }
+
+    /// Adds a field to the map. A field with the same name is replaced.
+    pub fn field(mut self, name: &str, offset: u16, width: Width, endianness: Endianness) -> Self {
+        self.fields.insert(
+            name.to_string(),
+            Field {
+                offset,
+                width,
+                endianness,
+            },
+        );
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Field, PiControlError> {
+        self.fields
+            .get(name)
+            .ok_or(PiControlError::InvalidArgument("name"))
+    }
+
+    // absolute address of the byte containing the field and, for bits, the
+    // bit inside that byte. Fails if the field doesn't fit into the
+    // processimage.
+    fn address(&self, field: &Field) -> Result<(u16, u8), PiControlError> {
+        let (offset, bit) = match field.width {
+            Width::Bit(b) => (field.offset as usize + (b / 8) as usize, b % 8),
+            _ => (field.offset as usize, 0),
+        };
+        let address = self.base as usize + offset;
+        ensure!(
+            address + field.width.byte_len() <= KB_PI_LEN,
+            PiControlError::InvalidArgument("offset")
+        );
+        Ok((address as u16, bit))
+    }
+
+    /// Reads the field `name` from the processimage.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if there is no field named
+    /// `name` or if the field lies outside of the processimage.
+    pub fn get(&self, pi: &PiControl, name: &str) -> Result<Value, PiControlError> {
+        let field = self.lookup(name)?;
+        let (address, bit) = self.address(field)?;
+        if let Width::Bit(_) = field.width {
+            return unsafe { pi.inner.get_bit(address, Bit::from(bit)) }.map(Value::Bit);
+        }
+        let mut bytes = [0u8; 4];
+        let bytes = &mut bytes[..field.width.byte_len()];
+        unsafe { pi.inner.get_bytes(address, bytes) }?;
+        Ok(decode(field, bytes))
+    }
+
+    /// Writes `value` to the field `name` in the processimage.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if there is no field named
+    /// `name`, if the field lies outside of the processimage or if the size
+    /// of `value` doesn't match the width of the field.
+    pub fn set(&self, pi: &PiControl, name: &str, value: Value) -> Result<(), PiControlError> {
+        let field = self.lookup(name)?;
+        let (address, bit) = self.address(field)?;
+        match (field.width, value) {
+            (Width::Bit(_), Value::Bit(b)) => unsafe {
+                pi.inner.set_bit(address, Bit::from(bit), b)
+            },
+            (Width::Bit(_), _) => Err(PiControlError::InvalidArgument("value")),
+            (width, value) => {
+                let len = width.byte_len();
+                ensure!(
+                    value.bitcnt() == len * 8,
+                    PiControlError::InvalidArgument("value")
+                );
+                let bytes = match field.endianness {
+                    Endianness::Little => value.as_u32().to_le_bytes(),
+                    // the value is in the lower bytes
+                    Endianness::Big => (value.as_u32() << (32 - len * 8)).to_be_bytes(),
+                };
+                unsafe { pi.inner.set_bytes(address, &bytes[..len]) }
+            }
+        }
+    }
+}
+
+/// The input and output blocks of a gateway, see the
+/// [module documentation](self)
+///
+/// The blocks are copied into buffers: [`GatewayChannel::read`] fills the
+/// input buffer, [`GatewayChannel::write`] writes the output buffer.
+/// [`GatewayChannel::read_region`] and [`GatewayChannel::write_region`]
+/// access a part of a block directly instead.
+///
+/// # Example
+/// ```
+/// use revpi::gateway::GatewayChannel;
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut channel = GatewayChannel::new(100..164, 164..228);
+/// mock.write(100, &[1, 2, 3]).unwrap();
+/// assert_eq!(&channel.read(&pi).unwrap()[..3], &[1, 2, 3]);
+/// channel.outputs_mut()[..2].copy_from_slice(&[4, 5]);
+/// channel.write(&pi).unwrap();
+/// channel.write_region(&pi, 10, &[6]).unwrap();
+/// assert_eq!(mock.read(164, 2).unwrap(), vec![4, 5]);
+/// assert_eq!(mock.read(174, 1).unwrap(), vec![6]);
+/// assert!(channel.write_region(&pi, 63, &[7, 8]).is_err());
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GatewayChannel {
+    inputs: Range<u16>,
+    outputs: Range<u16>,
+    input_buf: Vec<u8>,
+    output_buf: Vec<u8>,
+}
+
+impl GatewayChannel {
+    /// Creates a channel for the given blocks of the processimage, with
+    /// zeroed buffers
+    pub fn new(inputs: Range<u16>, outputs: Range<u16>) -> Self {
+        Self {
+            input_buf: vec![0; inputs.len()],
+            output_buf: vec![0; outputs.len()],
+            inputs,
+            outputs,
+        }
+    }
+
+    /// Creates a channel for the blocks of the gateway described by `info`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the module isn't a
+    /// gateway.
+    pub fn from_info(info: &DeviceInfo) -> Result<Self, PiControlError> {
+        use ModuleType::*;
+        ensure!(
+            matches!(
+                info.module_type(),
+                GatewayCanOpen
+                    | GatewayCcLink
+                    | GatewayDeviceNet
+                    | GatewayEtherCat
+                    | GatewayEtherNetIp
+                    | GatewayPowerlink
+                    | GatewayProfibus
+                    | GatewayProfinet
+                    | GatewaySercos3
+                    | GatewaySerial
+                    | GatewayModbusRtu
+                    | GatewayModbusTcp
+                    | GatewayDmx
+            ),
+            PiControlError::InvalidArgument("module type")
+        );
+        Ok(Self::new(info.inputs(), info.outputs()))
+    }
+
+    /// Returns the addresses of the input block
+    pub fn input_range(&self) -> Range<u16> {
+        self.inputs.clone()
+    }
+
+    /// Returns the addresses of the output block
+    pub fn output_range(&self) -> Range<u16> {
+        self.outputs.clone()
+    }
+
+    /// Reads the input block into the buffer and returns it
+    ///
+    /// # Errors
+    /// Returns the error of the backend if reading fails.
+    pub fn read(&mut self, pi: &PiControl) -> Result<&[u8], PiControlError> {
+        unsafe { pi.inner.get_bytes(self.inputs.start, &mut self.input_buf) }?;
+        Ok(&self.input_buf)
+    }
+
+    /// Returns the input buffer as of the last [`GatewayChannel::read`]
+    pub fn inputs(&self) -> &[u8] {
+        &self.input_buf
+    }
+
+    /// Returns the output buffer, which is written by
+    /// [`GatewayChannel::write`]
+    pub fn outputs(&self) -> &[u8] {
+        &self.output_buf
+    }
+
+    /// Returns the output buffer for modification
+    pub fn outputs_mut(&mut self) -> &mut [u8] {
+        &mut self.output_buf
+    }
+
+    /// Writes the output buffer to the output block
+    ///
+    /// # Errors
+    /// Returns the error of the backend if writing fails.
+    pub fn write(&self, pi: &PiControl) -> Result<(), PiControlError> {
+        unsafe { pi.inner.set_bytes(self.outputs.start, &self.output_buf) }
+    }
+
+    // absolute address of `len` bytes at `offset` inside `block`
+    fn region(block: &Range<u16>, offset: u16, len: usize) -> Result<u16, PiControlError> {
+        ensure!(
+            offset as usize + len <= block.len(),
+            PiControlError::InvalidArgument("region")
+        );
+        Ok(block.start + offset)
+    }
+
+    /// Reads `bytes.len()` bytes at `offset` inside the input block, without
+    /// touching the buffer
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the region doesn't
+    /// lie inside the input block and the error of the backend if reading
+    /// fails.
+    pub fn read_region(
+        &self,
+        pi: &PiControl,
+        offset: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), PiControlError> {
+        let address = Self::region(&self.inputs, offset, bytes.len())?;
+        unsafe { pi.inner.get_bytes(address, bytes) }
+    }
+
+    /// Writes `bytes` at `offset` inside the output block, without touching
+    /// the buffer
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the region doesn't
+    /// lie inside the output block and the error of the backend if writing
+    /// fails.
+    pub fn write_region(
+        &self,
+        pi: &PiControl,
+        offset: u16,
+        bytes: &[u8],
+    ) -> Result<(), PiControlError> {
+        let address = Self::region(&self.outputs, offset, bytes.len())?;
+        unsafe { pi.inner.set_bytes(address, bytes) }
+    }
+}
+
+// bytes has to be exactly as long as the field
+fn decode(field: &Field, bytes: &[u8]) -> Value {
+    match (field.width, field.endianness) {
+        (Width::Bit(b), _) => Value::Bit(bytes[0] & (1 << (b % 8)) != 0),
+        (Width::Byte, _) => Value::Byte(bytes[0]),
+        (Width::Word, Endianness::Little) => Value::Word(u16::from_le_bytes([bytes[0], bytes[1]])),
+        (Width::Word, Endianness::Big) => Value::Word(u16::from_be_bytes([bytes[0], bytes[1]])),
+        (Width::DWord, Endianness::Little) => {
+            Value::DWord(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        (Width::DWord, Endianness::Big) => {
+            Value::DWord(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::MockBackend;
+    use std::sync::Arc;
+
+    // decodes the start of 0x12345678 as a field of `width`
+    fn decode_as(width: Width, endianness: Endianness) -> Value {
+        let field = Field {
+            offset: 0,
+            width,
+            endianness,
+        };
+        decode(&field, &[0x12, 0x34, 0x56, 0x78][..width.byte_len()])
+    }
+
+    #[test]
+    fn decode_widths() {
+        use Endianness::*;
+        assert_eq!(decode_as(Width::Bit(1), Little), Value::Bit(true));
+        assert_eq!(decode_as(Width::Bit(10), Little), Value::Bit(false));
+        assert_eq!(decode_as(Width::Byte, Big), Value::Byte(0x12));
+        assert_eq!(decode_as(Width::Word, Little), Value::Word(0x3412));
+        assert_eq!(decode_as(Width::Word, Big), Value::Word(0x1234));
+        assert_eq!(decode_as(Width::DWord, Little), Value::DWord(0x78563412));
+        assert_eq!(decode_as(Width::DWord, Big), Value::DWord(0x12345678));
+    }
+
+    #[test]
+    fn get_set() {
+        let mock = Arc::new(MockBackend::new());
+        let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+        let map = GatewayMap::new(100)
+            .field("speed", 0, Width::Word, Endianness::Big)
+            .field("running", 2, Width::Bit(9), Endianness::Little);
+        map.set(&pi, "speed", Value::Word(0x1234)).unwrap();
+        map.set(&pi, "running", Value::Bit(true)).unwrap();
+        assert_eq!(mock.read(100, 4).unwrap(), vec![0x12, 0x34, 0, 0b10]);
+        assert_eq!(map.get(&pi, "speed").unwrap(), Value::Word(0x1234));
+        assert_eq!(map.get(&pi, "running").unwrap(), Value::Bit(true));
+        assert!(map.set(&pi, "speed", Value::Byte(1)).is_err());
+        assert!(map.get(&pi, "unknown").is_err());
+    }
+
+    #[test]
+    fn outside_of_processimage() {
+        let mock = Arc::new(MockBackend::new());
+        let pi = PiControl::builder().backend(mock).build().unwrap();
+        let map = GatewayMap::new(KB_PI_LEN as u16 - 2)
+            .field("fits", 0, Width::Word, Endianness::Little)
+            .field("word", 1, Width::Word, Endianness::Little)
+            .field("bit", 1, Width::Bit(8), Endianness::Little)
+            .field("overflow", u16::MAX, Width::Byte, Endianness::Little);
+        assert!(map.get(&pi, "fits").is_ok());
+        for name in ["word", "bit", "overflow"] {
+            assert!(matches!(
+                map.get(&pi, name),
+                Err(PiControlError::InvalidArgument("offset"))
+            ));
+        }
+        assert!(map.set(&pi, "word", Value::Word(1)).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn from_toml() {
+        let map = GatewayMap::from_toml(
+            r#"
+            base = 75
+
+            [fields]
+            speed = { offset = 0, width = "word", endianness = "big" }
+            running = { offset = 2, width = { bit = 0 } }
+            "#,
+        )
+        .unwrap();
+        let expected = GatewayMap::new(75)
+            .field("speed", 0, Width::Word, Endianness::Big)
+            .field("running", 2, Width::Bit(0), Endianness::Little);
+        assert_eq!(map, expected);
+        let err =
+            GatewayMap::from_toml("base = 75\n[fields]\nx = { offset = 0, width = \"qword\" }");
+        assert!(matches!(err, Err(PiControlError::TomlError(_))));
+    }
+}
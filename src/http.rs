@@ -0,0 +1,169 @@
+//! Minimal HTTP/1.1 server side for the built-in endpoints
+//!
+//! Every connection carries a single request and is closed after the
+//! response, which is all a scraper or a simple REST client needs.
+
+use crate::{picontrol::PiControlError, util::ensure};
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+// requests are only a request line, some headers and a short body
+const MAX_REQUEST: usize = 8192;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    // the websocket server accepts any path
+    #[allow(dead_code)]
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of the header `name`, ignoring its case
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn invalid() -> PiControlError {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid request").into()
+}
+
+// reads until `buf` holds at least `len` bytes
+fn fill(stream: &mut TcpStream, buf: &mut Vec<u8>, len: usize) -> Result<(), PiControlError> {
+    let mut chunk = [0u8; 1024];
+    while buf.len() < len {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 || buf.len() + n > MAX_REQUEST {
+            return Err(invalid());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    Ok(())
+}
+
+/// Reads a request from `stream` and sets its timeouts
+pub(crate) fn read_request(stream: &mut TcpStream) -> Result<Request, PiControlError> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut buf = Vec::new();
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let len = buf.len() + 1;
+        fill(stream, &mut buf, len)?;
+    };
+    let head = std::str::from_utf8(&buf[..head_len]).map_err(|_| invalid())?;
+    let mut lines = head.split("\r\n");
+    let mut parts = lines.next().unwrap_or_default().split(' ');
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().ok_or_else(invalid)?.to_string();
+    let headers: Vec<_> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.to_string(), value.trim().to_string()))
+        .collect();
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let len: usize = match request.header("content-length") {
+        Some(len) => len.parse().map_err(|_| invalid())?,
+        None => 0,
+    };
+    // checked before reading, so a huge length can't overflow
+    ensure!(len <= MAX_REQUEST - head_len, invalid());
+    fill(stream, &mut buf, head_len + len)?;
+    request.body = buf[head_len..head_len + len].to_vec();
+    Ok(request)
+}
+
+/// Writes a response with `status`, e.g. `200 OK`. The client expects the
+/// connection to be closed afterwards.
+pub(crate) fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> Result<(), PiControlError> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+/// Converts `value` to JSON, bits as bools, byte arrays as arrays, text as
+/// string and everything else as unsigned number like
+/// [`PiControl::get_value`](crate::picontrol::PiControl::get_value) returns
+/// them
+#[cfg(any(feature = "httpd", feature = "ws"))]
+pub(crate) fn json(value: crate::picontrol::Value) -> serde_json::Value {
+    use crate::picontrol::Value;
+    match value {
+        Value::Bit(b) => b.into(),
+        Value::Bytes(b) => b.into(),
+        Value::String(s) => s.into(),
+        value => value.as_u32().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    // reads a request sent as `raw`
+    fn read(raw: &'static [u8]) -> Result<Request, PiControlError> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            // the server may close before everything was written
+            let _ = stream.write_all(raw);
+            stream
+        });
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = read_request(&mut stream);
+        drop(client.join().unwrap());
+        request
+    }
+
+    #[test]
+    fn request_with_body() {
+        let request =
+            read(b"POST /values/a HTTP/1.1\r\nContent-Length: 2\r\nHost: x\r\n\r\n42").unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/values/a");
+        assert_eq!(request.header("host"), Some("x"));
+        assert_eq!(request.body, b"42");
+    }
+
+    #[test]
+    fn oversized_content_length() {
+        let request = read(b"POST / HTTP/1.1\r\nContent-Length: 8192\r\n\r\n");
+        assert!(matches!(request, Err(PiControlError::IoError(_))));
+    }
+
+    #[test]
+    fn overflowing_content_length() {
+        let request = read(b"POST / HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n");
+        assert!(matches!(request, Err(PiControlError::IoError(_))));
+    }
+}
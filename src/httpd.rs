@@ -0,0 +1,180 @@
+//! HTTP server for variables, devices and the status
+//!
+//! [`Httpd`] makes the processimage available to web HMIs and other
+//! integrations that speak HTTP and JSON:
+//!
+//! | Request                | Response                                              |
+//! |------------------------|-------------------------------------------------------|
+//! | `GET /variables/NAME`  | `{"name": "I_1", "value": 1}`                          |
+//! | `PUT /variables/NAME`  | writes `{"value": 1}` or a bare `1`, bits also accept `true` and `false` |
+//! | `GET /devices`         | the devices of the config, if one was given            |
+//! | `GET /status`          | whether the piBridge is running and the bits of `RevPiStatus` |
+//!
+//! Errors are answered with `{"error": "..."}` and `400` for malformed
+//! values, `404` for unknown variables and `500` for everything else.
+//! ```no_run
+//! use revpi::httpd::Httpd;
+//! use revpi::picontrol::PiControl;
+//! use revpi::rsc::RSC;
+//! use std::fs::File;
+//!
+//! let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! Httpd::new().devices(&rsc).serve(&pi, "0.0.0.0:8080").unwrap();
+//! ```
+//! Requests are answered one after another, so a slow client delays the
+//! others. There is no authentication, bind to a trusted interface only.
+
+use crate::{
+    http,
+    picontrol::{PiControl, PiControlError, Status},
+    rsc::RSC,
+    tags::DataType,
+};
+use serde_json::{json, Value as Json};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+const CONTENT_TYPE: &str = "application/json";
+
+/// HTTP server for the processimage, see the [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Httpd {
+    devices: Vec<Json>,
+}
+
+fn error(status: &'static str, message: &str) -> (&'static str, Json) {
+    (status, json!({ "error": message }))
+}
+
+impl Httpd {
+    /// Creates a server without devices
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists the devices of `rsc` at `/devices`
+    pub fn devices(mut self, rsc: &RSC) -> Self {
+        self.devices = rsc
+            .devices
+            .iter()
+            .map(|device| {
+                json!({
+                    "position": device.position,
+                    "name": device.name,
+                    "type": device.dev_type.as_str(),
+                    "productType": device.product_type,
+                    "offset": device.offset,
+                    "comment": device.comment,
+                })
+            })
+            .collect();
+        self
+    }
+
+    fn get_variable(&self, pi: &PiControl, name: &str) -> Result<Json, PiControlError> {
+        let value = http::json(pi.get_value(name)?);
+        Ok(json!({ "name": name, "value": value }))
+    }
+
+    fn status(&self, pi: &PiControl) -> Result<Json, PiControlError> {
+        let running = pi.inner.bridge_running()?;
+        // not every processimage has a RevPiStatus, e.g. a simulated one
+        let status = pi.status().ok();
+        let flag = |s: Status| status.map(|status| status.contains(s));
+        Ok(json!({
+            "bridgeRunning": running,
+            "status": status.map(|s| s.bits()),
+            "unconfiguredModule": flag(Status::UNCONFIGURED_MODULE),
+            "missingModule": flag(Status::MISSING_MODULE),
+            "imageOverflow": flag(Status::IMAGE_OVERFLOW),
+        }))
+    }
+
+    // status line and body answering `request`
+    fn answer(&self, pi: &PiControl, request: &http::Request) -> (&'static str, Json) {
+        let name = request.path.strip_prefix("/variables/");
+        let res = match (request.method.as_str(), request.path.as_str(), name) {
+            ("GET", "/devices", _) => Ok(("200 OK", Json::from(self.devices.clone()))),
+            ("GET", "/status", _) => self.status(pi).map(|s| ("200 OK", s)),
+            (_, _, Some(name)) if pi.find_variable(name).is_err() => {
+                return error("404 Not Found", "unknown variable");
+            }
+            ("GET", _, Some(name)) => self.get_variable(pi, name).map(|v| ("200 OK", v)),
+            ("PUT", _, Some(name)) => {
+                let data_type = pi
+                    .find_variable(name)
+                    .ok()
+                    .and_then(|var| DataType::from_bit_length(var.length));
+                let value = serde_json::from_slice::<Json>(&request.body)
+                    .ok()
+                    .map(|json| match json {
+                        Json::Object(mut map) => map.remove("value").unwrap_or_default(),
+                        json => json,
+                    })
+                    .zip(data_type)
+                    .and_then(|(json, data_type)| data_type.parse(&json.to_string()));
+                match value {
+                    Some(value) => pi
+                        .set_value(name, value)
+                        .map(|_| ("204 No Content", Json::Null)),
+                    None => return error("400 Bad Request", "invalid value"),
+                }
+            }
+            ("GET", _, _) => return error("404 Not Found", "not found"),
+            _ => return error("405 Method Not Allowed", "method not allowed"),
+        };
+        res.unwrap_or_else(|e| error("500 Internal Server Error", &e.to_string()))
+    }
+
+    /// Answers a single request on `stream`, see the
+    /// [module documentation](self)
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the request can't be read or
+    /// the response can't be written. Errors accessing the processimage are
+    /// sent to the client instead.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::httpd::Httpd;
+    /// use revpi::picontrol::{backend::MockBackend, PiControl};
+    /// use std::io::{Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::sync::Arc;
+    ///
+    /// let mock = Arc::new(MockBackend::new().variable("O_1", 10, 0, 16));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    /// client
+    ///     .write_all(b"PUT /variables/O_1 HTTP/1.1\r\nContent-Length: 12\r\n\r\n{\"value\":42}")
+    ///     .unwrap();
+    /// Httpd::new().handle(&pi, listener.accept().unwrap().0).unwrap();
+    /// let mut response = String::new();
+    /// client.read_to_string(&mut response).unwrap();
+    /// assert!(response.starts_with("HTTP/1.1 204"));
+    /// assert_eq!(mock.read(10, 2).unwrap(), vec![42, 0]);
+    /// ```
+    pub fn handle(&self, pi: &PiControl, mut stream: TcpStream) -> Result<(), PiControlError> {
+        let request = http::read_request(&mut stream)?;
+        let (status, body) = self.answer(pi, &request);
+        let body = match body {
+            Json::Null => String::new(),
+            body => body.to_string(),
+        };
+        http::respond(&mut stream, status, CONTENT_TYPE, &body)
+    }
+
+    /// Listens on `addr` and answers requests one after another, forever
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if `addr` can't be bound.
+    pub fn serve<A: ToSocketAddrs>(&self, pi: &PiControl, addr: A) -> Result<(), PiControlError> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming().flatten() {
+            // a broken connection only concerns its client
+            let _ = self.handle(pi, stream);
+        }
+        Ok(())
+    }
+}
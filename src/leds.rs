@@ -0,0 +1,204 @@
+//! The status LEDs of the base modules
+//!
+//! Every LED takes two bits of the `RevPiLED` output, green and red, both
+//! together light it orange. [`Leds`] knows the LEDs of each model and only
+//! changes the bits of the LED that is set, so the others keep their color:
+//! ```no_run
+//! use revpi::leds::{Color, Led, Leds, Model};
+//! use revpi::picontrol::{raw::PiControlRaw, PiControl};
+//!
+//! let leds = Leds::new(Model::detect(&PiControlRaw::new().unwrap()).unwrap());
+//! let pi = PiControl::new().unwrap();
+//! leds.set(&pi, Led::A1, Color::Green).unwrap();
+//! leds.set(&pi, Led::A2, Color::Orange).unwrap();
+//! ```
+//!
+//! The RGB LEDs of the RevPi Connect 4 aren't supported.
+
+use crate::picontrol::{
+    raw::{DeviceInfo, ModuleType, PiControlRaw},
+    PiControl, PiControlError, Value,
+};
+use crate::util::ensure;
+
+/// Name of the variable holding the LEDs
+pub const LED_VARIABLE: &str = "RevPiLED";
+
+/// An LED on the front of the base module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Led {
+    A1,
+    A2,
+    /// Only on the RevPi Connect and Flat
+    A3,
+    /// Only on the RevPi Flat
+    A4,
+    /// Only on the RevPi Flat
+    A5,
+}
+
+impl Led {
+    // the lowest of the two bits of the LED in RevPiLED
+    fn shift(&self) -> u32 {
+        2 * *self as u32
+    }
+}
+
+/// Color of an [`Led`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    Off,
+    Green,
+    Red,
+    /// Green and red at the same time
+    Orange,
+}
+
+impl Color {
+    fn bits(&self) -> u32 {
+        match self {
+            Color::Off => 0b00,
+            Color::Green => 0b01,
+            Color::Red => 0b10,
+            Color::Orange => 0b11,
+        }
+    }
+
+    fn from_bits(bits: u32) -> Self {
+        match bits & 0b11 {
+            0b01 => Color::Green,
+            0b10 => Color::Red,
+            0b11 => Color::Orange,
+            _ => Color::Off,
+        }
+    }
+}
+
+/// Base module, which determines the available LEDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    /// RevPi Core and Compact, with A1 and A2
+    Core,
+    /// RevPi Connect, with A1 to A3
+    Connect,
+    /// RevPi Flat, with A1 to A5
+    Flat,
+}
+
+impl Model {
+    /// Returns the model of a base module of type `module_type`, `None` if it
+    /// isn't one
+    pub fn from_module_type(module_type: ModuleType) -> Option<Self> {
+        match module_type {
+            ModuleType::Core | ModuleType::Compact => Some(Model::Core),
+            ModuleType::Connect => Some(Model::Connect),
+            ModuleType::Flat => Some(Model::Flat),
+            _ => None,
+        }
+    }
+
+    /// Asks the driver for the type of the base module
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] if the base
+    /// module is none of the models and the error of
+    /// [`PiControlRaw::get_device_info`] if it can't be found.
+    pub fn detect(raw: &PiControlRaw) -> Result<Self, PiControlError> {
+        // the base module always has address 0
+        let base = DeviceInfo::from(raw.get_device_info(0)?);
+        Self::from_module_type(base.module_type()).ok_or(PiControlError::NotSupportedOnThisModel)
+    }
+
+    /// Returns the LEDs of the model
+    pub fn leds(&self) -> &'static [Led] {
+        match self {
+            Model::Core => &[Led::A1, Led::A2],
+            Model::Connect => &[Led::A1, Led::A2, Led::A3],
+            Model::Flat => &[Led::A1, Led::A2, Led::A3, Led::A4, Led::A5],
+        }
+    }
+}
+
+/// Access to the LEDs of a base module, see the [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::leds::{Color, Led, Leds, Model, LED_VARIABLE};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new().variable(LED_VARIABLE, 6, 0, 8));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let leds = Leds::new(Model::Connect);
+/// leds.set(&pi, Led::A1, Color::Green).unwrap();
+/// leds.set(&pi, Led::A3, Color::Red).unwrap();
+/// assert_eq!(mock.read(6, 1).unwrap(), vec![0b10_00_01]);
+/// assert_eq!(leds.get(&pi, Led::A3).unwrap(), Color::Red);
+/// assert!(leds.set(&pi, Led::A4, Color::Red).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Leds {
+    model: Model,
+}
+
+impl Leds {
+    /// Creates the access to the LEDs of `model`
+    pub fn new(model: Model) -> Self {
+        Self { model }
+    }
+
+    /// Returns the model
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    /// Returns the color `led` currently has
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the model doesn't
+    /// have `led` and the error of [`PiControl::get_value`] if reading fails.
+    pub fn get(&self, pi: &PiControl, led: Led) -> Result<Color, PiControlError> {
+        self.check(led)?;
+        let value = pi.get_value(LED_VARIABLE)?;
+        Ok(Color::from_bits(value.as_u32() >> led.shift()))
+    }
+
+    /// Sets `led` to `color`, keeping the color of the other LEDs
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the model doesn't
+    /// have `led` and the error of [`PiControl::update_value`] if writing
+    /// fails.
+    pub fn set(&self, pi: &PiControl, led: Led, color: Color) -> Result<(), PiControlError> {
+        self.check(led)?;
+        let shift = led.shift();
+        pi.update_value(LED_VARIABLE, |old| {
+            let v = (old.as_u32() & !(0b11 << shift)) | (color.bits() << shift);
+            // the Flat has a word, the others a byte
+            match old {
+                Value::Word(_) => Value::Word(v as u16),
+                _ => Value::Byte(v as u8),
+            }
+        })?;
+        Ok(())
+    }
+
+    /// Turns off all LEDs of the model
+    ///
+    /// # Errors
+    /// Same as [`Leds::set`].
+    pub fn off(&self, pi: &PiControl) -> Result<(), PiControlError> {
+        for led in self.model.leds() {
+            self.set(pi, *led, Color::Off)?;
+        }
+        Ok(())
+    }
+
+    fn check(&self, led: Led) -> Result<(), PiControlError> {
+        ensure!(
+            self.model.leds().contains(&led),
+            PiControlError::InvalidArgument("led")
+        );
+        Ok(())
+    }
+}
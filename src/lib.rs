@@ -19,15 +19,115 @@
 //! ```
 //! The [`revpi!`](revpi_macro) and [`revpi_from_json!`](revpi_macro) macros
 //! provide the same functionality, but faster because the name doesn't have
-//! to be looked up every time.
+//! to be looked up every time. A
+//! [`NameTable`](picontrol::NameTable) gets close to that with a config that
+//! is only read at runtime.
+//!
+//! [`gateway`] gives names to the fields inside the data area of fieldbus
+//! gateways or copies the whole area at once, while [`modbus`] exchanges variables with an external PLC over
+//! Modbus TCP.
+//!
+//! [`shared`] lets applications exchange data through virtual devices.
+//!
+//! [`commander`] lets many threads write to the processimage through a single
+//! owner of the [`PiControl`](picontrol::PiControl).
+//! [`pwm`] generates PWM and pulses on digital outputs.
+//!
+//! [`cycle`] paces control loops to a fixed period, records their timing and
+//! runs PLC-style scan cycles, [`metrics`] keeps histograms of their timing,
+//! [`clock`] provides timestamps for recorded samples.
+//!
+//! [`trace`] records the processimage to a file and replays it, e.g. into a
+//! mock, to debug problems from the field offline.
+//!
+//! [`exporter`] serves variables as Prometheus gauges over HTTP, [`tags`]
+//! offers them to protocol layers like OPC UA, MQTT or REST and [`httpd`]
+//! reads and writes them as JSON for web HMIs.
+//!
+//! [`monitor`] reports changes of variables without every application
+//! writing its own polling loop, [`ws`] streams them to browsers.
+//! [`alarms`] raises and clears alarms when variables exceed their limits,
+//! [`control`] runs ramps, hysteresis and PID controllers on them.
+//!
+//! [`config`] sets up an application from a single TOML file, [`retain`]
+//! keeps setpoints across power cycles.
+//!
+//! [`model()`] tells which RevPi the application runs on, [`leds`] sets the
+//! colors of the status LEDs of the base module,
+//! [`connect`] switches the relay and triggers the hardware watchdog of the
+//! RevPi Connect.
+//!
+//! [`modules`] knows the processimage layout of I/O modules, e.g. the counters
+//! and PWM outputs of a DIO, the analog values of an AIO in mV, µA and °C or
+//! the relays of an RO, as well as the on-board I/O of the Compact and Flat.
 //!
 //! # Features
 //! This crate has features to enable or disable the [macros](revpi_macro) and
-//! [rsc]. [rsc] is enabled by default, while [macro](revpi_macro) is not.
+//! [rsc]. [rsc] is enabled by default, while [macro](revpi_macro) is not.\
+//! The `toml` feature enables the [`config`] module and allows reading e.g.
+//! [`gateway::GatewayMap`]s from TOML, `modbus` enables the [`modbus`] module.\
+//! With `events`, a [`PiControl`](picontrol::PiControl) can handle driver
+//! resets automatically, see
+//! [`PiControlBuilder::watch_resets`](picontrol::PiControlBuilder).\
+//! `chrono` adds conversions of [`clock::Timestamp`]s to `chrono` types.\
+//! `metrics` adds [`Metrics::encode_prometheus`](metrics::Metrics::encode_prometheus)
+//! to export the timing of control loops, `exporter` enables the
+//! [`exporter`] module.\
+//! `rest` adds [`tags::rest`], a plain text REST interface for tags,
+//! `httpd` enables the [`httpd`] module and `ws` the [`ws`] module.\
+//! `tokio` adds [`AsyncPiControl`](picontrol::AsyncPiControl), an async
+//! version of [`PiControl`](picontrol::PiControl), and async streams of
+//! changes, see `monitor::WatchStream`.\
+//! `tracing` instruments [`PiControl`](picontrol::PiControl) and
+//! [`PiControlRaw`](picontrol::raw::PiControlRaw) with `tracing` spans carrying
+//! the addresses, names and values, reads on level trace and writes on level
+//! debug, so the writes before a fault can be captured.\
+//! `simulation` lets [`PiControl`](picontrol::PiControl) use a plain file as
+//! processimage if there is no driver, see `picontrol::backend::FileBackend`.\
+//! `cli` builds the `revpictl` tool, which reads and writes variables by
+//! name, dumps the processimage, lists the devices and resets the driver or
+//! the counters of a DIO like piTest, and whose `watch` command prints every
+//! change in the processimage. With `tui`, `revpictl watch --tui` shows all
+//! variables grouped by device in a terminal UI and allows writing outputs.
 
+pub mod alarms;
+pub mod clock;
+pub mod commander;
+#[cfg(feature = "toml")]
+pub mod config;
+pub mod connect;
+pub mod control;
+pub mod cycle;
+#[cfg(feature = "exporter")]
+pub mod exporter;
+pub mod gateway;
+#[cfg(any(
+    feature = "exporter",
+    feature = "httpd",
+    feature = "rest",
+    feature = "ws"
+))]
+mod http;
+#[cfg(feature = "httpd")]
+pub mod httpd;
+pub mod leds;
+pub mod metrics;
+#[cfg(feature = "modbus")]
+pub mod modbus;
+pub mod model;
+pub mod modules;
+pub mod monitor;
 pub mod picontrol;
+pub mod pwm;
+pub mod retain;
+pub mod shared;
+pub mod tags;
+pub mod trace;
+#[cfg(feature = "ws")]
+pub mod ws;
+pub use self::model::model;
 #[cfg(feature = "macro")]
-pub use revpi_macro::{revpi, revpi_from_json};
+pub use revpi_macro::{revpi, revpi_from_json, revpi_from_json_str, revpi_offsets};
 #[cfg(feature = "rsc")]
 pub use revpi_rsc as rsc;
 pub(crate) mod util;
@@ -0,0 +1,205 @@
+//! Timing metrics of control loops
+//!
+//! Every [`Cycle`](crate::cycle::Cycle) records its [`Metrics`]: histograms
+//! of the cycle durations and the jitter, the missed deadlines and, if run
+//! by a [`CycleRunner`](crate::cycle::CycleRunner), the latency of the reads
+//! and writes of the processimage. Unlike the
+//! [`CycleStats`](crate::cycle::CycleStats), histograms cover all cycles with
+//! constant memory, so they are suited for long running applications.
+//!
+//! With the `metrics` feature, the metrics can be encoded in the Prometheus
+//! text format, see [`Metrics::encode_prometheus`].
+
+use std::time::Duration;
+
+/// Upper bounds of the buckets of a [`Histogram`] by default, from 100 µs to
+/// 1 s
+pub const DEFAULT_BOUNDS: [Duration; 13] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+];
+
+/// Histogram of durations with fixed buckets
+///
+/// # Example
+/// ```
+/// use revpi::metrics::Histogram;
+/// use std::time::Duration;
+///
+/// let mut h = Histogram::new(&[Duration::from_millis(1), Duration::from_millis(10)]);
+/// h.record(Duration::from_micros(500));
+/// h.record(Duration::from_millis(5));
+/// h.record(Duration::from_secs(1));
+/// let buckets: Vec<_> = h.buckets().collect();
+/// assert_eq!(buckets, vec![
+///     (Some(Duration::from_millis(1)), 1),
+///     (Some(Duration::from_millis(10)), 2),
+///     (None, 3),
+/// ]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Histogram {
+    bounds: Vec<Duration>,
+    // one more than bounds, the last one counts everything above
+    counts: Vec<u64>,
+    sum: Duration,
+}
+
+impl Default for Histogram {
+    /// Histogram with the [`DEFAULT_BOUNDS`]
+    fn default() -> Self {
+        Self::new(&DEFAULT_BOUNDS)
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram with buckets up to the given bounds and
+    /// one for everything above. The bounds are sorted.
+    pub fn new(bounds: &[Duration]) -> Self {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        Self {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: Duration::ZERO,
+        }
+    }
+
+    /// Adds a duration
+    pub fn record(&mut self, duration: Duration) {
+        let idx = self.bounds.partition_point(|b| *b < duration);
+        self.counts[idx] += 1;
+        self.sum += duration;
+    }
+
+    /// Returns the number of recorded durations
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the sum of all recorded durations
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// Returns the upper bound of every bucket, `None` for the last one, and
+    /// the number of durations up to it, i.e. including the smaller buckets
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        let bounds = self.bounds.iter().copied().map(Some).chain([None]);
+        bounds.zip(self.counts.iter().scan(0, |total, count| {
+            *total += count;
+            Some(*total)
+        }))
+    }
+}
+
+/// Metrics of a [`Cycle`](crate::cycle::Cycle), see the
+/// [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Metrics {
+    pub(crate) cycle_duration: Histogram,
+    pub(crate) jitter: Histogram,
+    pub(crate) io_latency: Histogram,
+    pub(crate) missed_deadlines: u64,
+}
+
+impl Metrics {
+    /// Returns the histogram of the time spent working per cycle
+    pub fn cycle_duration(&self) -> &Histogram {
+        &self.cycle_duration
+    }
+
+    /// Returns the histogram of the delays of the start of the cycles
+    pub fn jitter(&self) -> &Histogram {
+        &self.jitter
+    }
+
+    /// Returns the histogram of the durations of single reads and writes of
+    /// the processimage
+    pub fn io_latency(&self) -> &Histogram {
+        &self.io_latency
+    }
+
+    /// Returns the number of cycles that didn't end before the next one was
+    /// due, counted like [`CycleStats::overruns`](crate::cycle::CycleStats::overruns)
+    pub fn missed_deadlines(&self) -> u64 {
+        self.missed_deadlines
+    }
+
+    /// Encodes the metrics in the Prometheus text format, with every name
+    /// starting with `prefix`, e.g. `plc_cycle_duration_seconds` for `plc`
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::cycle::Cycle;
+    /// use std::time::Duration;
+    ///
+    /// let mut cycle = Cycle::new(Duration::from_millis(1));
+    /// cycle.wait().unwrap();
+    /// cycle.wait().unwrap();
+    /// let text = cycle.metrics().encode_prometheus("plc");
+    /// assert!(text.contains("# TYPE plc_cycle_duration_seconds histogram\n"));
+    /// assert!(text.contains("plc_cycle_duration_seconds_count 1\n"));
+    /// assert!(text.contains("plc_missed_deadlines_total 0\n"));
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn encode_prometheus(&self, prefix: &str) -> String {
+        let mut out = String::new();
+        for (name, help, histogram) in [
+            (
+                "cycle_duration_seconds",
+                "Time spent working per cycle",
+                &self.cycle_duration,
+            ),
+            (
+                "cycle_jitter_seconds",
+                "Delay of the start of a cycle",
+                &self.jitter,
+            ),
+            (
+                "io_latency_seconds",
+                "Duration of a read or write of the processimage",
+                &self.io_latency,
+            ),
+        ] {
+            encode_histogram(&mut out, &format!("{}_{}", prefix, name), help, histogram);
+        }
+        let name = format!("{}_missed_deadlines_total", prefix);
+        out.push_str(&format!(
+            "# HELP {0} Cycles that didn't end before the next one was due\n\
+             # TYPE {0} counter\n{0} {1}\n",
+            name, self.missed_deadlines
+        ));
+        out
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn encode_histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    out.push_str(&format!(
+        "# HELP {0} {1}\n# TYPE {0} histogram\n",
+        name, help
+    ));
+    for (bound, count) in histogram.buckets() {
+        let le = bound.map_or("+Inf".to_string(), |b| b.as_secs_f64().to_string());
+        out.push_str(&format!("{}_bucket{{le=\"{}\"}} {}\n", name, le, count));
+    }
+    out.push_str(&format!(
+        "{0}_sum {1}\n{0}_count {2}\n",
+        name,
+        histogram.sum().as_secs_f64(),
+        histogram.count()
+    ));
+}
@@ -0,0 +1,563 @@
+//! Modbus TCP client for exchanging variables with an external PLC
+//!
+//! [`ModbusClient`] is a minimal Modbus TCP master supporting the holding
+//! register functions. On top of that, [`Publisher`] periodically writes
+//! variables of the processimage into registers of the PLC and reads setpoints
+//! back:
+//! ```no_run
+//! use revpi::modbus::Publisher;
+//! use revpi::picontrol::{PiControl, Value};
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut publisher = Publisher::new("192.168.0.10:502", 1, Duration::from_millis(100))
+//!     .publish("Core_Temperature", 0)
+//!     .subscribe(10, "Setpoint", Some(Value::Word(0)));
+//! loop {
+//!     publisher.cycle(&pi).unwrap();
+//! }
+//! ```
+//! If the connection to the PLC is lost, every subscribed variable that has a
+//! fallback value is set to it until the connection is reestablished, the
+//! others keep their last value. Exceptions of the PLC, e.g. for a wrong
+//! register, are returned instead.
+
+use crate::{
+    picontrol::{PiControl, PiControlError, Value},
+    util::ensure,
+};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread,
+    time::{Duration, Instant},
+};
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+// limits given by the modbus specification
+const MAX_READ: usize = 125;
+const MAX_WRITE: usize = 123;
+
+/// Minimal Modbus TCP client
+#[derive(Debug)]
+pub struct ModbusClient {
+    stream: TcpStream,
+    unit: u8,
+    transaction: u16,
+}
+
+impl ModbusClient {
+    /// Connects to the Modbus TCP server at `addr`. `unit` is the unit
+    /// identifier sent with every request, `timeout` is used for connecting as
+    /// well as for every read and write on the connection.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the connection couldn't be
+    /// established.
+    pub fn connect<A: ToSocketAddrs>(
+        addr: A,
+        unit: u8,
+        timeout: Duration,
+    ) -> Result<Self, PiControlError> {
+        let mut last = io::Error::new(io::ErrorKind::InvalidInput, "no address given");
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(timeout))?;
+                    stream.set_write_timeout(Some(timeout))?;
+                    stream.set_nodelay(true)?;
+                    return Ok(Self {
+                        stream,
+                        unit,
+                        transaction: 0,
+                    });
+                }
+                Err(e) => last = e,
+            }
+        }
+        Err(last.into())
+    }
+
+    // sends the pdu and returns the pdu of the response
+    fn request(&mut self, pdu: &[u8]) -> Result<Vec<u8>, PiControlError> {
+        self.transaction = self.transaction.wrapping_add(1);
+        let mut frame = Vec::with_capacity(7 + pdu.len());
+        frame.extend_from_slice(&self.transaction.to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+        frame.push(self.unit);
+        frame.extend_from_slice(pdu);
+        self.stream.write_all(&frame)?;
+
+        let mut header = [0u8; 7];
+        self.stream.read_exact(&mut header)?;
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid modbus response");
+        if header[0..2] != self.transaction.to_be_bytes() || header[2..4] != [0, 0] || len < 2 {
+            return Err(invalid().into());
+        }
+        let mut response = vec![0u8; len - 1];
+        self.stream.read_exact(&mut response)?;
+        if response[0] == pdu[0] | 0x80 {
+            ensure!(response.len() >= 2, invalid().into());
+            return Err(PiControlError::ModbusException {
+                function: pdu[0],
+                code: response[1],
+            });
+        }
+        if response[0] != pdu[0] {
+            return Err(invalid().into());
+        }
+        Ok(response)
+    }
+
+    /// Reads `count` holding registers starting at `start`.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if `count` is `0` or larger
+    /// than 125, a [`PiControlError::ModbusException`] if the server answered
+    /// with an exception and a [`PiControlError::IoError`] if the
+    /// communication failed.
+    pub fn read_holding_registers(
+        &mut self,
+        start: u16,
+        count: u16,
+    ) -> Result<Vec<u16>, PiControlError> {
+        ensure!(
+            count != 0 && count as usize <= MAX_READ,
+            PiControlError::InvalidArgument("count")
+        );
+        let mut pdu = vec![READ_HOLDING_REGISTERS];
+        pdu.extend_from_slice(&start.to_be_bytes());
+        pdu.extend_from_slice(&count.to_be_bytes());
+        let response = self.request(&pdu)?;
+        let len = count as usize * 2;
+        if response.len() != 2 + len || response[1] as usize != len {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidData, "invalid modbus response").into(),
+            );
+        }
+        Ok(response[2..]
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect())
+    }
+
+    /// Writes `values` into the holding registers starting at `start`.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if `values` is empty or
+    /// longer than 123, a [`PiControlError::ModbusException`] if the server
+    /// answered with an exception and a [`PiControlError::IoError`] if the
+    /// communication failed.
+    pub fn write_multiple_registers(
+        &mut self,
+        start: u16,
+        values: &[u16],
+    ) -> Result<(), PiControlError> {
+        ensure!(
+            !values.is_empty() && values.len() <= MAX_WRITE,
+            PiControlError::InvalidArgument("values")
+        );
+        let mut pdu = vec![WRITE_MULTIPLE_REGISTERS];
+        pdu.extend_from_slice(&start.to_be_bytes());
+        pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        pdu.push(values.len() as u8 * 2);
+        for v in values {
+            pdu.extend_from_slice(&v.to_be_bytes());
+        }
+        let response = self.request(&pdu)?;
+        // the server echoes the start and the number of registers
+        if response[1..] != pdu[1..5] {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidData, "invalid modbus response").into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+// registers a value occupies, dwords are sent high word first
+fn to_registers(value: Value) -> Result<Vec<u16>, PiControlError> {
+    if let Value::Bytes(_) | Value::String(_) = value {
+        return Err(PiControlError::InvalidArgument("bitlength"));
+    }
+    match value.bitcnt() {
+        1 | 8 | 16 => Ok(vec![value.as_u32() as u16]),
+        32 => {
+            let v = value.as_u32();
+            Ok(vec![(v >> 16) as u16, v as u16])
+        }
+        _ => Err(PiControlError::InvalidArgument("bitlength")),
+    }
+}
+
+// number of registers a variable with the given bitlength occupies
+fn register_count(bitlength: u16) -> u16 {
+    if bitlength > 16 {
+        2
+    } else {
+        1
+    }
+}
+
+fn from_registers(bitlength: u16, regs: &[u16]) -> Result<Value, PiControlError> {
+    match bitlength {
+        1 => Ok(Value::Bit(regs[0] != 0)),
+        8 => Ok(Value::Byte(regs[0] as u8)),
+        16 => Ok(Value::Word(regs[0])),
+        32 => Ok(Value::DWord((regs[0] as u32) << 16 | regs[1] as u32)),
+        _ => Err(PiControlError::InvalidArgument("bitlength")),
+    }
+}
+
+#[derive(Debug)]
+struct Subscription {
+    register: u16,
+    variable: String,
+    fallback: Option<Value>,
+}
+
+/// Periodically exchanges variables with the holding registers of a PLC
+///
+/// Published variables are written to the PLC, subscribed ones are read from
+/// it. The connection is supervised: if the communication fails, the
+/// connection is dropped, the fallback values of the subscriptions are
+/// applied and a new connection is attempted in the next cycle.
+#[derive(Debug)]
+pub struct Publisher<A: ToSocketAddrs> {
+    addr: A,
+    unit: u8,
+    period: Duration,
+    client: Option<ModbusClient>,
+    publications: Vec<(String, u16)>,
+    subscriptions: Vec<Subscription>,
+    last_cycle: Option<Instant>,
+}
+
+impl<A: ToSocketAddrs> Publisher<A> {
+    /// Creates a new publisher for the PLC at `addr` with the unit identifier
+    /// `unit`. `period` is the time between two exchanges, it is also used as
+    /// timeout for the connection.
+    pub fn new(addr: A, unit: u8, period: Duration) -> Self {
+        Self {
+            addr,
+            unit,
+            period,
+            client: None,
+            publications: Vec::new(),
+            subscriptions: Vec::new(),
+            last_cycle: None,
+        }
+    }
+
+    /// Writes the variable `variable` to the holding register `register`
+    /// every cycle. Dwords occupy two registers, high word first, variables
+    /// of other lengths than 1, 8, 16 and 32 bits can't be published.
+    pub fn publish(mut self, variable: &str, register: u16) -> Self {
+        self.publications.push((variable.to_string(), register));
+        self
+    }
+
+    /// Reads the holding register `register` into the variable `variable`
+    /// every cycle. Dwords occupy two registers, high word first.
+    ///
+    /// If `fallback` is given, it is written to the variable while the
+    /// connection to the PLC is lost.
+    pub fn subscribe(mut self, register: u16, variable: &str, fallback: Option<Value>) -> Self {
+        self.subscriptions.push(Subscription {
+            register,
+            variable: variable.to_string(),
+            fallback,
+        });
+        self
+    }
+
+    /// Returns whether the connection to the PLC is currently established
+    pub fn is_connected(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // only communicates with the plc, so the errors are either exceptions or
+    // a broken connection
+    fn exchange(
+        &mut self,
+        outputs: &[(u16, Vec<u16>)],
+        inputs: &[(u16, u16)],
+    ) -> Result<Vec<Vec<u16>>, PiControlError> {
+        if self.client.is_none() {
+            self.client = Some(ModbusClient::connect(&self.addr, self.unit, self.period)?);
+        }
+        // checked above
+        let client = self.client.as_mut().unwrap();
+        for (register, regs) in outputs {
+            client.write_multiple_registers(*register, regs)?;
+        }
+        inputs
+            .iter()
+            .map(|(register, count)| client.read_holding_registers(*register, *count))
+            .collect()
+    }
+
+    fn apply_fallbacks(&self, pi: &PiControl) -> Result<(), PiControlError> {
+        for sub in self.subscriptions.iter() {
+            if let Some(fallback) = &sub.fallback {
+                pi.set_value(&sub.variable, fallback.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits until the period since the last cycle is over and then exchanges
+    /// all variables with the PLC.
+    ///
+    /// Communication errors don't get returned, instead the connection is
+    /// dropped and the fallback values are applied, see [`Publisher`].
+    ///
+    /// # Errors
+    /// Returns an error if a variable couldn't be read or written, e.g.
+    /// because it doesn't exist, and a [`PiControlError::ModbusException`]
+    /// if the PLC rejected a request. The connection is kept then, the
+    /// requests after the rejected one aren't sent in this cycle.
+    pub fn cycle(&mut self, pi: &PiControl) -> Result<(), PiControlError> {
+        if let Some(last) = self.last_cycle {
+            if let Some(remaining) = self.period.checked_sub(last.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+        self.last_cycle = Some(Instant::now());
+        let outputs = self
+            .publications
+            .iter()
+            .map(|(variable, register)| Ok((*register, to_registers(pi.get_value(variable)?)?)))
+            .collect::<Result<Vec<_>, PiControlError>>()?;
+        let bitlengths = self
+            .subscriptions
+            .iter()
+            .map(|sub| Ok(pi.find_variable(&sub.variable)?.length))
+            .collect::<Result<Vec<_>, PiControlError>>()?;
+        let inputs: Vec<_> = self
+            .subscriptions
+            .iter()
+            .zip(bitlengths.iter())
+            .map(|(sub, bitlength)| (sub.register, register_count(*bitlength)))
+            .collect();
+        match self.exchange(&outputs, &inputs) {
+            Ok(values) => {
+                for ((sub, bitlength), regs) in
+                    self.subscriptions.iter().zip(bitlengths).zip(values)
+                {
+                    pi.set_value(&sub.variable, from_registers(bitlength, &regs)?)?;
+                }
+                Ok(())
+            }
+            Err(PiControlError::IoError(_)) => {
+                self.client = None;
+                self.apply_fallbacks(pi)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::MockBackend;
+    use std::{net::TcpListener, sync::Arc};
+
+    // reads a request frame
+    fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).unwrap();
+        let len = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let mut frame = header.to_vec();
+        frame.resize(7 + len - 1, 0);
+        stream.read_exact(&mut frame[7..]).unwrap();
+        frame
+    }
+
+    // serves a single request with `reply`, which gets the request frame
+    // and returns the raw response, and returns the request frame
+    fn serve<F>(reply: F) -> (ModbusClient, thread::JoinHandle<Vec<u8>>)
+    where
+        F: FnOnce(&[u8]) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let frame = read_frame(&mut stream);
+            stream.write_all(&reply(&frame)).unwrap();
+            frame
+        });
+        let client = ModbusClient::connect(addr, 7, Duration::from_secs(1)).unwrap();
+        (client, server)
+    }
+
+    // an MBAP header answering `request` with a pdu of `pdu_len` bytes
+    fn header(request: &[u8], pdu_len: u16) -> Vec<u8> {
+        let mut frame = request[0..4].to_vec();
+        frame.extend_from_slice(&(pdu_len + 1).to_be_bytes());
+        frame.push(request[6]);
+        frame
+    }
+
+    #[test]
+    fn read_holding_registers() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 6);
+            frame.extend_from_slice(&[READ_HOLDING_REGISTERS, 4, 0x12, 0x34, 0xab, 0xcd]);
+            frame
+        });
+        let regs = client.read_holding_registers(0x0102, 2).unwrap();
+        assert_eq!(regs, [0x1234, 0xabcd]);
+        let request = server.join().unwrap();
+        // transaction 1, protocol 0, length 6, unit 7
+        assert_eq!(request[..7], [0, 1, 0, 0, 0, 6, 7]);
+        assert_eq!(request[7..], [READ_HOLDING_REGISTERS, 1, 2, 0, 2]);
+    }
+
+    #[test]
+    fn write_multiple_registers() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 5);
+            frame.extend_from_slice(&request[7..12]);
+            frame
+        });
+        client.write_multiple_registers(10, &[1, 0x0203]).unwrap();
+        let request = server.join().unwrap();
+        assert_eq!(
+            request[7..],
+            [WRITE_MULTIPLE_REGISTERS, 0, 10, 0, 2, 4, 0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn wrong_echo() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 5);
+            frame.extend_from_slice(&[WRITE_MULTIPLE_REGISTERS, 0, 11, 0, 2]);
+            frame
+        });
+        let err = client.write_multiple_registers(10, &[1, 2]).unwrap_err();
+        assert!(matches!(err, PiControlError::IoError(_)));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn publisher_exception() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_frame(&mut stream);
+            let mut frame = header(&request, 2);
+            frame.extend_from_slice(&[WRITE_MULTIPLE_REGISTERS | 0x80, 2]);
+            stream.write_all(&frame).unwrap();
+            stream
+        });
+        let mock = Arc::new(MockBackend::new().variable("a", 0, 0, 16));
+        let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+        mock.write(0, &[3, 0]).unwrap();
+        let mut publisher = Publisher::new(addr, 7, Duration::from_secs(1))
+            .publish("a", 9999)
+            .subscribe(10, "a", Some(Value::Word(5)));
+        let err = publisher.cycle(&pi).unwrap_err();
+        assert!(matches!(
+            err,
+            PiControlError::ModbusException {
+                function: WRITE_MULTIPLE_REGISTERS,
+                code: 2
+            }
+        ));
+        // neither reconnecting nor falling back
+        assert!(publisher.is_connected());
+        assert_eq!(mock.read(0, 2).unwrap(), vec![3, 0]);
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn exception() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 2);
+            frame.extend_from_slice(&[READ_HOLDING_REGISTERS | 0x80, 2]);
+            frame
+        });
+        let err = client.read_holding_registers(0, 1).unwrap_err();
+        assert!(matches!(
+            err,
+            PiControlError::ModbusException {
+                function: READ_HOLDING_REGISTERS,
+                code: 2
+            }
+        ));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn truncated_exception() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 1);
+            frame.push(READ_HOLDING_REGISTERS | 0x80);
+            frame
+        });
+        let err = client.read_holding_registers(0, 1).unwrap_err();
+        assert!(
+            matches!(err, PiControlError::IoError(e) if e.kind() == io::ErrorKind::InvalidData)
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn truncated_response() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 4);
+            frame.extend_from_slice(&[READ_HOLDING_REGISTERS, 4, 0, 1]);
+            frame
+        });
+        let err = client.read_holding_registers(0, 2).unwrap_err();
+        assert!(
+            matches!(err, PiControlError::IoError(e) if e.kind() == io::ErrorKind::InvalidData)
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn wrong_transaction() {
+        let (mut client, server) = serve(|request| {
+            let mut frame = header(request, 4);
+            frame[1] ^= 0xff;
+            frame.extend_from_slice(&[READ_HOLDING_REGISTERS, 2, 0, 1]);
+            frame
+        });
+        assert!(client.read_holding_registers(0, 1).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn registers() {
+        assert_eq!(to_registers(Value::Bit(true)).unwrap(), [1]);
+        assert_eq!(to_registers(Value::Byte(0xab)).unwrap(), [0xab]);
+        assert_eq!(to_registers(Value::Word(0x1234)).unwrap(), [0x1234]);
+        let dword = to_registers(Value::DWord(0x1234_5678)).unwrap();
+        assert_eq!(dword, [0x1234, 0x5678]);
+        assert!(to_registers(Value::Bytes(vec![1, 2, 3])).is_err());
+        assert!(to_registers(Value::String("ab".to_string())).is_err());
+        for length in [1, 8, 16, 32] {
+            assert_eq!(
+                register_count(length) as usize,
+                to_registers(Value::decode(&[1, 0, 0, 0], 0, length as usize).unwrap())
+                    .unwrap()
+                    .len()
+            );
+        }
+        assert_eq!(
+            from_registers(32, &dword).unwrap(),
+            Value::DWord(0x1234_5678)
+        );
+        assert_eq!(from_registers(1, &[2]).unwrap(), Value::Bit(true));
+        assert!(from_registers(24, &[0, 0]).is_err());
+    }
+}
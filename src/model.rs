@@ -0,0 +1,213 @@
+//! Detection of the RevPi model
+//!
+//! Some requests of the driver only work on some models, e.g. the piBridge
+//! only exists on the RevPi Core and Connect, and the driver just answers
+//! with `EPERM` on the others. [`model()`] tells beforehand which model the
+//! application is running on:
+//! ```no_run
+//! use revpi::model::Model;
+//!
+//! match revpi::model() {
+//!     Some(model) if model.has_pibridge() => println!("{} with piBridge", model),
+//!     Some(model) => println!("{} without piBridge", model),
+//!     None => println!("not a RevPi"),
+//! }
+//! ```
+
+use crate::leds;
+use crate::picontrol::raw::ModuleType;
+use std::{fmt, fs};
+
+const DEVICE_TREE_MODEL: &str = "/proc/device-tree/model";
+#[cfg(feature = "rsc")]
+const CPUINFO: &str = "/proc/cpuinfo";
+
+/// Model of a RevPi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Model {
+    /// The first RevPi Core, with a Compute Module 1
+    Core,
+    Core3,
+    Core3Plus,
+    CoreS,
+    CoreSE,
+    /// RevPi Connect, Connect+, Connect S and Connect SE
+    Connect,
+    Connect4,
+    Compact,
+    Flat,
+}
+
+// Compute Module of the RevPi, from the model in /proc/cpuinfo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ComputeModule {
+    Cm1,
+    Cm3,
+    Cm3Plus,
+    Cm4S,
+    Cm4,
+}
+
+impl ComputeModule {
+    // parses e.g. "Raspberry Pi Compute Module 3 Plus Rev 1.0"
+    fn parse(model: &str) -> Option<Self> {
+        let model = model.to_ascii_lowercase();
+        let (_, rest) = model.split_once("compute module")?;
+        let words: Vec<_> = rest.split_whitespace().collect();
+        match words.as_slice() {
+            ["rev", ..] | [] => Some(ComputeModule::Cm1),
+            ["3", "plus", ..] | ["3+", ..] => Some(ComputeModule::Cm3Plus),
+            ["3", ..] => Some(ComputeModule::Cm3),
+            ["4s", ..] => Some(ComputeModule::Cm4S),
+            ["4", ..] => Some(ComputeModule::Cm4),
+            _ => None,
+        }
+    }
+}
+
+impl Model {
+    /// Parses the name of a model as in the device tree, e.g.
+    /// `"Revolution Pi Core 3+"` or `"RevPi Connect 4"`. Returns `None` if it
+    /// isn't a RevPi.
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::model::Model;
+    /// assert_eq!(Model::from_name("Revolution Pi Core 3+"), Some(Model::Core3Plus));
+    /// assert_eq!(Model::from_name("Revolution Pi Connect SE"), Some(Model::Connect));
+    /// assert_eq!(Model::from_name("Raspberry Pi 4 Model B Rev 1.4"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim_end_matches('\0').to_ascii_lowercase();
+        let rest = name
+            .strip_prefix("revolution pi")
+            .or_else(|| name.strip_prefix("revpi"))?;
+        let words: Vec<_> = rest.split_whitespace().collect();
+        match words.as_slice() {
+            ["core", "3+", ..] | ["core", "3", "plus", ..] => Some(Model::Core3Plus),
+            ["core", "3", ..] => Some(Model::Core3),
+            ["core", "s", ..] => Some(Model::CoreS),
+            ["core", "se", ..] => Some(Model::CoreSE),
+            ["core", ..] => Some(Model::Core),
+            ["connect", "4", ..] => Some(Model::Connect4),
+            ["connect", ..] | ["connect+", ..] => Some(Model::Connect),
+            ["compact", ..] => Some(Model::Compact),
+            ["flat", ..] => Some(Model::Flat),
+            _ => None,
+        }
+    }
+
+    /// Determines the model from the type of the base module and the model
+    /// line of `/proc/cpuinfo`, which tells the Compute Module. Returns
+    /// `None` if `module_type` isn't a base module.
+    ///
+    /// The RevPi Core S and SE have the same Compute Module, so both are
+    /// reported as [`Model::CoreS`].
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::model::Model;
+    /// # use revpi::picontrol::raw::ModuleType;
+    /// let cm = "Raspberry Pi Compute Module 4 Rev 1.0";
+    /// assert_eq!(Model::from_base(ModuleType::Connect, Some(cm)), Some(Model::Connect4));
+    /// assert_eq!(Model::from_base(ModuleType::Core, None), Some(Model::Core));
+    /// ```
+    pub fn from_base(module_type: ModuleType, cpu_model: Option<&str>) -> Option<Self> {
+        let cm = cpu_model.and_then(ComputeModule::parse);
+        match module_type {
+            ModuleType::Core => Some(match cm {
+                Some(ComputeModule::Cm3) => Model::Core3,
+                Some(ComputeModule::Cm3Plus) => Model::Core3Plus,
+                Some(ComputeModule::Cm4S) => Model::CoreS,
+                _ => Model::Core,
+            }),
+            ModuleType::Connect if cm == Some(ComputeModule::Cm4) => Some(Model::Connect4),
+            ModuleType::Connect => Some(Model::Connect),
+            ModuleType::Compact => Some(Model::Compact),
+            ModuleType::Flat => Some(Model::Flat),
+            _ => None,
+        }
+    }
+
+    /// Determines the model from the base device configured in `rsc`, see
+    /// [`Model::from_base`]
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(rsc: &crate::rsc::RSC, cpu_model: Option<&str>) -> Option<Self> {
+        let base = rsc
+            .devices
+            .iter()
+            .find(|dev| dev.dev_type == crate::rsc::DeviceKind::Base)?;
+        Self::from_base(ModuleType::from_u16(base.product().into()), cpu_model)
+    }
+
+    /// Returns whether the model has a piBridge for I/O modules and gateways
+    pub fn has_pibridge(&self) -> bool {
+        !matches!(self, Model::Compact | Model::Flat)
+    }
+
+    /// Returns the LEDs of the model, `None` for the RGB LEDs of the
+    /// RevPi Connect 4
+    pub fn leds(&self) -> Option<leds::Model> {
+        match self {
+            Model::Connect4 => None,
+            Model::Connect => Some(leds::Model::Connect),
+            Model::Flat => Some(leds::Model::Flat),
+            _ => Some(leds::Model::Core),
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    /// Writes the product name, e.g. `RevPi Core 3+`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Model::Core => "RevPi Core",
+            Model::Core3 => "RevPi Core 3",
+            Model::Core3Plus => "RevPi Core 3+",
+            Model::CoreS => "RevPi Core S",
+            Model::CoreSE => "RevPi Core SE",
+            Model::Connect => "RevPi Connect",
+            Model::Connect4 => "RevPi Connect 4",
+            Model::Compact => "RevPi Compact",
+            Model::Flat => "RevPi Flat",
+        };
+        f.write_str(name)
+    }
+}
+
+// the model line of /proc/cpuinfo
+#[cfg(feature = "rsc")]
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string(CPUINFO).ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "Model").then(|| value.trim().to_string())
+    })
+}
+
+/// Detects the model of the RevPi this is running on, `None` if it isn't one
+/// or the model can't be told
+///
+/// The model in the device tree is used if it names a RevPi. Otherwise, with
+/// the `rsc` feature, the base device of `/etc/revpi/config.rsc` and the
+/// Compute Module in `/proc/cpuinfo` are combined, see [`Model::from_rsc`].
+pub fn model() -> Option<Model> {
+    if let Some(model) = fs::read_to_string(DEVICE_TREE_MODEL)
+        .ok()
+        .and_then(|name| Model::from_name(&name))
+    {
+        return Some(model);
+    }
+    #[cfg(feature = "rsc")]
+    {
+        use crate::{
+            picontrol::raw::raw::PICONFIG_FILE,
+            rsc::{Limits, RSC},
+        };
+        let f = fs::File::open(PICONFIG_FILE).ok()?;
+        let rsc = RSC::from_reader_with_limits(f, &Limits::default()).ok()?;
+        Model::from_rsc(&rsc, cpu_model().as_deref())
+    }
+    #[cfg(not(feature = "rsc"))]
+    None
+}
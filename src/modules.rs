@@ -0,0 +1,24 @@
+//! Typed access to the I/O modules
+//!
+//! The processimage of a module is a plain block of bytes, whose layout is
+//! described in the documentation of the module. The types in here know these
+//! layouts, so e.g. the counters of a DIO can be read by channel instead of by
+//! address:
+//! ```no_run
+//! use revpi::modules::dio::{Channel, Dio};
+//! use revpi::picontrol::{raw::{DeviceInfo, PiControlRaw}, PiControl};
+//!
+//! let raw = PiControlRaw::new().unwrap();
+//! let dio = Dio::from_info(&DeviceInfo::from(raw.get_device_info(32).unwrap())).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! let count = dio.counter(&pi, Channel::new(1).unwrap()).unwrap();
+//! ```
+//!
+//! The on-board I/O of the RevPi Compact and Flat is accessed by the PiCtory
+//! names of its variables instead, see [`compact`] and [`flat`].
+
+pub mod aio;
+pub mod compact;
+pub mod dio;
+pub mod flat;
+pub mod ro;
@@ -0,0 +1,539 @@
+//! RevPi AIO
+//!
+//! The AIO has 4 analog inputs, 2 RTD inputs and 2 analog outputs. It scales
+//! the values itself: an input shows up in the processimage as
+//! `raw * factor / divisor + offset`, where `raw` is in mV or µA depending on
+//! the range of the input, or in 0.1 °C for the RTDs. An output value `v`
+//! leads to `v * factor / divisor + offset` mV or µA on the output. Ranges and
+//! scaling are configured in PiCtory and end up in the `mem` area of the
+//! device, see [`AioConfig`]. [`Aio`] undoes the scaling, so values are always
+//! in mV, µA or °C:
+//! ```no_run
+//! use revpi::modules::aio::Aio;
+//! use revpi::picontrol::PiControl;
+//! use revpi::rsc::RSC;
+//! use std::fs::File;
+//!
+//! let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+//! let device = rsc.devices.iter().find(|d| d.name == "AIO").unwrap();
+//! let aio = Aio::from_device(device).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! println!("{} {}", aio.input(&pi, 1).unwrap(), aio.config().inputs[0].range.unit());
+//! println!("{} °C", aio.rtd(&pi, 1).unwrap());
+//! aio.set_output(&pi, 1, 2500.0).unwrap();
+//! ```
+
+use crate::picontrol::{PiControl, PiControlError};
+use crate::util::ensure;
+use std::fmt;
+
+/// Number of analog inputs
+pub const INPUTS: u8 = 4;
+/// Number of RTD inputs
+pub const RTDS: u8 = 2;
+/// Number of analog outputs
+pub const OUTPUTS: u8 = 2;
+
+// offsets relative to the first input
+const INPUT_VALUES: u16 = 0;
+const INPUT_STATUS: u16 = 8;
+const RTD_VALUES: u16 = 12;
+const RTD_STATUS: u16 = 16;
+const OUTPUT_STATUS: u16 = 18;
+// offsets relative to the first output
+const OUTPUT_VALUES: u16 = 0;
+
+/// Unit of an analog value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Millivolt,
+    Microampere,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unit::Millivolt => "mV",
+            Unit::Microampere => "µA",
+        })
+    }
+}
+
+/// Range of an analog input, the `InputXRange` of PiCtory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputRange {
+    /// -10 V to 10 V
+    #[default]
+    Bipolar10V,
+    /// 0 V to 10 V
+    Unipolar10V,
+    /// 0 V to 5 V
+    Unipolar5V,
+    /// -5 V to 5 V
+    Bipolar5V,
+    /// 0 mA to 20 mA
+    Current0To20mA,
+    /// 0 mA to 24 mA
+    Current0To24mA,
+    /// 4 mA to 20 mA
+    Current4To20mA,
+    /// -25 mA to 25 mA
+    Current25mA,
+}
+
+impl InputRange {
+    /// Returns the range for the value PiCtory stores, `None` if unknown
+    pub fn from_code(code: u8) -> Option<Self> {
+        use InputRange::*;
+        Some(match code {
+            1 => Bipolar10V,
+            2 => Unipolar10V,
+            3 => Unipolar5V,
+            4 => Bipolar5V,
+            5 => Current0To20mA,
+            6 => Current0To24mA,
+            7 => Current4To20mA,
+            8 => Current25mA,
+            _ => return None,
+        })
+    }
+
+    /// Returns the unit of the values
+    pub fn unit(&self) -> Unit {
+        use InputRange::*;
+        match self {
+            Bipolar10V | Unipolar10V | Unipolar5V | Bipolar5V => Unit::Millivolt,
+            _ => Unit::Microampere,
+        }
+    }
+}
+
+/// Range of an analog output, the `OutputXRange` of PiCtory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OutputRange {
+    /// The output is disabled
+    #[default]
+    Off,
+    /// 0 V to 5 V
+    Unipolar5V,
+    /// 0 V to 10 V
+    Unipolar10V,
+    /// -5 V to 5 V
+    Bipolar5V,
+    /// -10 V to 10 V
+    Bipolar10V,
+    /// 0 V to 5.5 V
+    Unipolar5_5V,
+    /// 0 V to 11 V
+    Unipolar11V,
+    /// -5.5 V to 5.5 V
+    Bipolar5_5V,
+    /// -11 V to 11 V
+    Bipolar11V,
+    /// 4 mA to 20 mA
+    Current4To20mA,
+    /// 0 mA to 20 mA
+    Current0To20mA,
+    /// 0 mA to 24 mA
+    Current0To24mA,
+}
+
+impl OutputRange {
+    /// Returns the range for the value PiCtory stores, `None` if unknown
+    pub fn from_code(code: u8) -> Option<Self> {
+        use OutputRange::*;
+        Some(match code {
+            0 => Off,
+            1 => Unipolar5V,
+            2 => Unipolar10V,
+            3 => Bipolar5V,
+            4 => Bipolar10V,
+            5 => Unipolar5_5V,
+            6 => Unipolar11V,
+            7 => Bipolar5_5V,
+            8 => Bipolar11V,
+            9 => Current4To20mA,
+            10 => Current0To20mA,
+            11 => Current0To24mA,
+            _ => return None,
+        })
+    }
+
+    /// Returns the unit of the values
+    pub fn unit(&self) -> Unit {
+        use OutputRange::*;
+        match self {
+            Current4To20mA | Current0To20mA | Current0To24mA => Unit::Microampere,
+            _ => Unit::Millivolt,
+        }
+    }
+
+    /// Returns the smallest and largest value of the range in its unit,
+    /// `None` if the output is off
+    pub fn limits(&self) -> Option<(f64, f64)> {
+        use OutputRange::*;
+        Some(match self {
+            Off => return None,
+            Unipolar5V => (0.0, 5000.0),
+            Unipolar10V => (0.0, 10000.0),
+            Bipolar5V => (-5000.0, 5000.0),
+            Bipolar10V => (-10000.0, 10000.0),
+            Unipolar5_5V => (0.0, 5500.0),
+            Unipolar11V => (0.0, 11000.0),
+            Bipolar5_5V => (-5500.0, 5500.0),
+            Bipolar11V => (-11000.0, 11000.0),
+            Current4To20mA => (4000.0, 20000.0),
+            Current0To20mA => (0.0, 20000.0),
+            Current0To24mA => (0.0, 24000.0),
+        })
+    }
+}
+
+/// Sensor connected to an RTD input, the `RTDXType` of PiCtory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RtdType {
+    #[default]
+    Pt100,
+    Pt1000,
+}
+
+/// Scaling applied by the module, the `Factor`, `Divisor` and `Offset` of a
+/// channel in PiCtory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Scaling {
+    factor: i16,
+    divisor: u16,
+    offset: i32,
+}
+
+impl Default for Scaling {
+    /// No scaling, i.e. factor and divisor 1 and offset 0
+    fn default() -> Self {
+        Self {
+            factor: 1,
+            divisor: 1,
+            offset: 0,
+        }
+    }
+}
+
+impl Scaling {
+    /// Creates the scaling `value * factor / divisor + offset`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `factor` or `divisor`
+    /// is 0, since the scaling couldn't be undone.
+    pub fn new(factor: i16, divisor: u16, offset: i32) -> Result<Self, PiControlError> {
+        ensure!(factor != 0, PiControlError::InvalidArgument("factor"));
+        ensure!(divisor != 0, PiControlError::InvalidArgument("divisor"));
+        Ok(Self {
+            factor,
+            divisor,
+            offset,
+        })
+    }
+
+    /// Returns the factor
+    pub fn factor(&self) -> i16 {
+        self.factor
+    }
+
+    /// Returns the divisor
+    pub fn divisor(&self) -> u16 {
+        self.divisor
+    }
+
+    /// Returns the offset
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Applies the scaling to `value`
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.factor as f64 / self.divisor as f64 + self.offset as f64
+    }
+
+    /// Undoes the scaling of `value`
+    pub fn undo(&self, value: f64) -> f64 {
+        (value - self.offset as f64) * self.divisor as f64 / self.factor as f64
+    }
+}
+
+/// Configuration of an analog input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct InputConfig {
+    pub range: InputRange,
+    pub scaling: Scaling,
+}
+
+/// Configuration of an RTD input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RtdConfig {
+    pub sensor: RtdType,
+    pub scaling: Scaling,
+}
+
+/// Configuration of an analog output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct OutputConfig {
+    pub range: OutputRange,
+    pub scaling: Scaling,
+}
+
+/// Configuration of all channels of an AIO, the default is the one of a new
+/// AIO in PiCtory except for the outputs, which are off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AioConfig {
+    pub inputs: [InputConfig; INPUTS as usize],
+    pub rtds: [RtdConfig; RTDS as usize],
+    pub outputs: [OutputConfig; OUTPUTS as usize],
+}
+
+impl AioConfig {
+    /// Reads the configuration from the `mem` variables of `device`, e.g.
+    /// `Input1Range`, `RTD2Type` or `Output1Factor`. Missing variables keep
+    /// their default.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a range or type is
+    /// unknown or a scaling can't be undone, see [`Scaling::new`].
+    #[cfg(feature = "rsc")]
+    pub fn from_device(device: &crate::rsc::Device) -> Result<Self, PiControlError> {
+        let mut config = Self::default();
+        let mem = |name: String| {
+            device.mem.values().find(|var| var.name == name).map(|var| {
+                // PiCtory stores negative values as two's complement
+                let shift = 64 - var.bit_length.clamp(1, 64) as u32;
+                ((var.default << shift) as i64) >> shift
+            })
+        };
+        let scaling = |prefix: String| {
+            Scaling::new(
+                mem(format!("{}Factor", prefix)).unwrap_or(1) as i16,
+                mem(format!("{}Divisor", prefix)).unwrap_or(1) as u16,
+                mem(format!("{}Offset", prefix)).unwrap_or(0) as i32,
+            )
+        };
+        for (i, input) in config.inputs.iter_mut().enumerate() {
+            let prefix = format!("Input{}", i + 1);
+            if let Some(code) = mem(format!("{}Range", prefix)) {
+                input.range = InputRange::from_code(code as u8)
+                    .ok_or(PiControlError::InvalidArgument("input range"))?;
+            }
+            input.scaling = scaling(prefix)?;
+        }
+        for (i, rtd) in config.rtds.iter_mut().enumerate() {
+            let prefix = format!("RTD{}", i + 1);
+            rtd.sensor = match mem(format!("{}Type", prefix)) {
+                None | Some(0) => RtdType::Pt100,
+                Some(1) => RtdType::Pt1000,
+                Some(_) => return Err(PiControlError::InvalidArgument("RTD type")),
+            };
+            rtd.scaling = scaling(prefix)?;
+        }
+        for (i, output) in config.outputs.iter_mut().enumerate() {
+            let prefix = format!("Output{}", i + 1);
+            if let Some(code) = mem(format!("{}Range", prefix)) {
+                output.range = OutputRange::from_code(code as u8)
+                    .ok_or(PiControlError::InvalidArgument("output range"))?;
+            }
+            output.scaling = scaling(prefix)?;
+        }
+        Ok(config)
+    }
+}
+
+/// An AIO, see the [module documentation](self)
+///
+/// Inputs and outputs are numbered from 1 like on the module.
+///
+/// # Example
+/// ```
+/// use revpi::modules::aio::{Aio, AioConfig, OutputRange, Scaling};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut config = AioConfig::default();
+/// config.inputs[1].scaling = Scaling::new(1, 10, 0).unwrap();
+/// config.outputs[0].range = OutputRange::Unipolar10V;
+/// let aio = Aio::new(11, 31, config);
+/// // input 2 shows 450, i.e. 4500 mV divided by 10
+/// mock.write(13, &450i16.to_le_bytes()).unwrap();
+/// assert_eq!(aio.input(&pi, 2).unwrap(), 4500.0);
+/// // RTD 1 shows 0.1 °C
+/// mock.write(23, &215i16.to_le_bytes()).unwrap();
+/// assert_eq!(aio.rtd(&pi, 1).unwrap(), 21.5);
+/// aio.set_output(&pi, 1, 2500.0).unwrap();
+/// assert_eq!(mock.read(31, 2).unwrap(), 2500i16.to_le_bytes());
+/// assert!(aio.set_output(&pi, 1, -1.0).is_err());
+/// assert!(aio.set_output(&pi, 2, 0.0).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aio {
+    inputs: u16,
+    outputs: u16,
+    config: AioConfig,
+}
+
+impl Aio {
+    /// Creates the access to the module whose inputs and outputs start at the
+    /// given addresses of the processimage, configured as `config`
+    pub fn new(inputs: u16, outputs: u16, config: AioConfig) -> Self {
+        Self {
+            inputs,
+            outputs,
+            config,
+        }
+    }
+
+    /// Creates the access to the module configured as `device`, see
+    /// [`AioConfig::from_device`]. The outputs are expected to start at the
+    /// first output variable.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the device isn't an
+    /// AIO, lies outside of the processimage or its configuration is invalid.
+    #[cfg(feature = "rsc")]
+    pub fn from_device(device: &crate::rsc::Device) -> Result<Self, PiControlError> {
+        ensure!(
+            device.product() == crate::rsc::ProductType::Aio,
+            PiControlError::InvalidArgument("product type")
+        );
+        let invalid = |_| PiControlError::InvalidArgument("device");
+        let inputs = u16::try_from(device.offset).map_err(invalid)?;
+        let outputs = device
+            .out
+            .values()
+            .filter_map(|var| device.address_of(var))
+            .min()
+            .ok_or(PiControlError::InvalidArgument("device"))?;
+        let outputs = u16::try_from(outputs).map_err(invalid)?;
+        Ok(Self::new(inputs, outputs, AioConfig::from_device(device)?))
+    }
+
+    /// Returns the configuration
+    pub fn config(&self) -> &AioConfig {
+        &self.config
+    }
+
+    fn index(channel: u8, count: u8) -> Result<u16, PiControlError> {
+        ensure!(
+            (1..=count).contains(&channel),
+            PiControlError::InvalidArgument("channel")
+        );
+        Ok((channel - 1) as u16)
+    }
+
+    fn get_i16(pi: &PiControl, address: u16) -> Result<i16, PiControlError> {
+        let mut bytes = [0u8; 2];
+        unsafe { pi.inner.get_bytes(address, &mut bytes) }?;
+        Ok(i16::from_le_bytes(bytes))
+    }
+
+    fn get_u8(pi: &PiControl, address: u16) -> Result<u8, PiControlError> {
+        let mut byte = [0u8];
+        unsafe { pi.inner.get_bytes(address, &mut byte) }?;
+        Ok(byte[0])
+    }
+
+    /// Returns the value of the analog input `channel` in the unit of its
+    /// range, see [`InputRange::unit`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `channel` isn't
+    /// between 1 and 4 and the error of the backend if reading fails.
+    pub fn input(&self, pi: &PiControl, channel: u8) -> Result<f64, PiControlError> {
+        let idx = Self::index(channel, INPUTS)?;
+        let value = Self::get_i16(pi, self.inputs + INPUT_VALUES + 2 * idx)?;
+        Ok(self.config.inputs[idx as usize].scaling.undo(value as f64))
+    }
+
+    /// Returns the status of the analog input `channel`, anything but 0 is an
+    /// error, e.g. a value out of range
+    ///
+    /// # Errors
+    /// Same as [`Aio::input`].
+    pub fn input_status(&self, pi: &PiControl, channel: u8) -> Result<u8, PiControlError> {
+        let idx = Self::index(channel, INPUTS)?;
+        Self::get_u8(pi, self.inputs + INPUT_STATUS + idx)
+    }
+
+    /// Returns the temperature of the RTD input `channel` in °C
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `channel` isn't 1 or
+    /// 2 and the error of the backend if reading fails.
+    pub fn rtd(&self, pi: &PiControl, channel: u8) -> Result<f64, PiControlError> {
+        let idx = Self::index(channel, RTDS)?;
+        let value = Self::get_i16(pi, self.inputs + RTD_VALUES + 2 * idx)?;
+        Ok(self.config.rtds[idx as usize].scaling.undo(value as f64) / 10.0)
+    }
+
+    /// Returns the status of the RTD input `channel`, anything but 0 is an
+    /// error, e.g. a missing sensor
+    ///
+    /// # Errors
+    /// Same as [`Aio::rtd`].
+    pub fn rtd_status(&self, pi: &PiControl, channel: u8) -> Result<u8, PiControlError> {
+        let idx = Self::index(channel, RTDS)?;
+        Self::get_u8(pi, self.inputs + RTD_STATUS + idx)
+    }
+
+    /// Returns the status of the analog output `channel`, anything but 0 is
+    /// an error, e.g. an overload
+    ///
+    /// # Errors
+    /// Same as [`Aio::rtd`].
+    pub fn output_status(&self, pi: &PiControl, channel: u8) -> Result<u8, PiControlError> {
+        let idx = Self::index(channel, OUTPUTS)?;
+        Self::get_u8(pi, self.inputs + OUTPUT_STATUS + idx)
+    }
+
+    /// Returns the value written to the analog output `channel` in the unit
+    /// of its range, see [`OutputRange::unit`]
+    ///
+    /// # Errors
+    /// Same as [`Aio::rtd`].
+    pub fn output(&self, pi: &PiControl, channel: u8) -> Result<f64, PiControlError> {
+        let idx = Self::index(channel, OUTPUTS)?;
+        let value = Self::get_i16(pi, self.outputs + OUTPUT_VALUES + 2 * idx)?;
+        Ok(self.config.outputs[idx as usize]
+            .scaling
+            .apply(value as f64))
+    }
+
+    /// Sets the analog output `channel` to `value` in the unit of its range,
+    /// see [`OutputRange::unit`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `channel` isn't 1 or
+    /// 2, the output is off, `value` lies outside of its range or doesn't fit
+    /// into the processimage after scaling, and the error of the backend if
+    /// writing fails.
+    pub fn set_output(
+        &self,
+        pi: &PiControl,
+        channel: u8,
+        value: f64,
+    ) -> Result<(), PiControlError> {
+        let idx = Self::index(channel, OUTPUTS)?;
+        let config = &self.config.outputs[idx as usize];
+        let (min, max) = config
+            .range
+            .limits()
+            .ok_or(PiControlError::InvalidArgument("output off"))?;
+        ensure!(
+            (min..=max).contains(&value),
+            PiControlError::InvalidArgument("value")
+        );
+        let raw = config.scaling.undo(value).round();
+        ensure!(
+            (i16::MIN as f64..=i16::MAX as f64).contains(&raw),
+            PiControlError::InvalidArgument("value")
+        );
+        let address = self.outputs + OUTPUT_VALUES + 2 * idx;
+        unsafe { pi.inner.set_bytes(address, &(raw as i16).to_le_bytes()) }
+    }
+}
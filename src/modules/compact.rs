@@ -0,0 +1,147 @@
+//! On-board I/O of the RevPi Compact
+//!
+//! The Compact has 8 digital inputs, 8 digital outputs, 8 analog inputs and
+//! 2 analog outputs on board, which PiCtory names `DIn_1` to `DIn_8`,
+//! `DOut_1` to `DOut_8`, `AIn_1` to `AIn_8` and `AOut_1` and `AOut_2`.
+//! [`CompactIo`] accesses them by channel number through these names. If the
+//! variables were renamed, the default names can be set as
+//! [aliases](crate::picontrol::PiControlBuilder::alias):
+//! ```no_run
+//! use revpi::model::Model;
+//! use revpi::modules::compact::CompactIo;
+//! use revpi::picontrol::PiControl;
+//!
+//! let io = CompactIo::new(revpi::model().unwrap()).unwrap();
+//! let pi = PiControl::builder().alias("DIn_1", "Start").build().unwrap();
+//! if io.input(&pi, 1).unwrap() {
+//!     io.set_analog_output(&pi, 1, 5000).unwrap();
+//! }
+//! ```
+
+use crate::model::Model;
+use crate::picontrol::{PiControl, PiControlError, Value};
+use crate::util::ensure;
+
+/// Number of digital inputs
+pub const DIGITAL_INPUTS: u8 = 8;
+/// Number of digital outputs
+pub const DIGITAL_OUTPUTS: u8 = 8;
+/// Number of analog inputs
+pub const ANALOG_INPUTS: u8 = 8;
+/// Number of analog outputs
+pub const ANALOG_OUTPUTS: u8 = 2;
+
+// name of `channel` of `count` channels with the prefix `prefix`
+fn name(prefix: &str, channel: u8, count: u8) -> Result<String, PiControlError> {
+    ensure!(
+        (1..=count).contains(&channel),
+        PiControlError::InvalidArgument("channel")
+    );
+    Ok(format!("{}_{}", prefix, channel))
+}
+
+/// The on-board I/O of a RevPi Compact, see the [module documentation](self)
+///
+/// Channels are numbered from 1 like on the front of the Compact. Analog
+/// values are in the unit configured in PiCtory, mV by default.
+///
+/// # Example
+/// ```
+/// use revpi::model::Model;
+/// use revpi::modules::compact::CompactIo;
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(
+///     MockBackend::new()
+///         .variable("DIn_3", 2, 2, 1)
+///         .variable("AIn_1", 4, 0, 16)
+///         .variable("DOut_8", 40, 7, 1)
+///         .variable("AOut_2", 44, 0, 16),
+/// );
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// assert!(CompactIo::new(Model::Flat).is_err());
+/// let io = CompactIo::new(Model::Compact).unwrap();
+/// mock.write(2, &[0b100]).unwrap();
+/// mock.write(4, &(-1200i16).to_le_bytes()).unwrap();
+/// assert!(io.input(&pi, 3).unwrap());
+/// assert_eq!(io.analog_input(&pi, 1).unwrap(), -1200);
+/// io.set_output(&pi, 8, true).unwrap();
+/// io.set_analog_output(&pi, 2, 2500).unwrap();
+/// assert_eq!(mock.read(40, 1).unwrap(), vec![0b1000_0000]);
+/// assert_eq!(mock.read(44, 2).unwrap(), 2500i16.to_le_bytes());
+/// assert!(io.input(&pi, 9).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompactIo(());
+
+impl CompactIo {
+    /// Creates the access if `model` is a [`Model::Compact`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] for other
+    /// models, which don't have this I/O.
+    pub fn new(model: Model) -> Result<Self, PiControlError> {
+        ensure!(
+            model == Model::Compact,
+            PiControlError::NotSupportedOnThisModel
+        );
+        Ok(Self(()))
+    }
+
+    /// Returns the state of the digital input `channel`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if there is no such
+    /// channel and the error of [`PiControl::get_value_as`] if reading fails.
+    pub fn input(&self, pi: &PiControl, channel: u8) -> Result<bool, PiControlError> {
+        pi.get_value_as(&name("DIn", channel, DIGITAL_INPUTS)?)
+    }
+
+    /// Returns the state of the digital output `channel`
+    ///
+    /// # Errors
+    /// Same as [`CompactIo::input`].
+    pub fn output(&self, pi: &PiControl, channel: u8) -> Result<bool, PiControlError> {
+        pi.get_value_as(&name("DOut", channel, DIGITAL_OUTPUTS)?)
+    }
+
+    /// Switches the digital output `channel`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if there is no such
+    /// channel and the error of [`PiControl::set_value`] if writing fails.
+    pub fn set_output(&self, pi: &PiControl, channel: u8, on: bool) -> Result<(), PiControlError> {
+        pi.set_value(&name("DOut", channel, DIGITAL_OUTPUTS)?, Value::Bit(on))
+    }
+
+    /// Returns the value of the analog input `channel`
+    ///
+    /// # Errors
+    /// Same as [`CompactIo::input`].
+    pub fn analog_input(&self, pi: &PiControl, channel: u8) -> Result<i16, PiControlError> {
+        pi.get_value_as(&name("AIn", channel, ANALOG_INPUTS)?)
+    }
+
+    /// Returns the value of the analog output `channel`
+    ///
+    /// # Errors
+    /// Same as [`CompactIo::input`].
+    pub fn analog_output(&self, pi: &PiControl, channel: u8) -> Result<i16, PiControlError> {
+        pi.get_value_as(&name("AOut", channel, ANALOG_OUTPUTS)?)
+    }
+
+    /// Sets the analog output `channel` to `value`
+    ///
+    /// # Errors
+    /// Same as [`CompactIo::set_output`].
+    pub fn set_analog_output(
+        &self,
+        pi: &PiControl,
+        channel: u8,
+        value: i16,
+    ) -> Result<(), PiControlError> {
+        let name = name("AOut", channel, ANALOG_OUTPUTS)?;
+        pi.set_value(&name, Value::Word(value as u16))
+    }
+}
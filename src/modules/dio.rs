@@ -0,0 +1,309 @@
+//! RevPi DIO, DI and DO
+//!
+//! The inputs of a DIO or DI start with the 16 input bits, followed by the
+//! status words of the inputs and outputs and a 32 bit counter or encoder
+//! value per input. The outputs of a DIO or DO start with the 16 output bits,
+//! followed by a PWM duty cycle in percent per output. The DO has the same
+//! inputs, but only the status words are used, the DI has no outputs.
+
+use crate::picontrol::{
+    raw::{Bit, DeviceInfo, ModuleType, PiControlRaw},
+    PiControl, PiControlError,
+};
+use crate::util::ensure;
+
+/// Number of inputs and outputs
+pub const CHANNELS: u8 = 16;
+
+// offsets relative to the first input
+const INPUTS: u16 = 0;
+const INPUT_STATUS: u16 = 2;
+const OUTPUT_STATUS: u16 = 4;
+const COUNTERS: u16 = 6;
+// offsets relative to the first output
+const OUTPUTS: u16 = 0;
+const PWM: u16 = 2;
+
+/// An input or output, numbered from 1 to 16 like on the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Channel(u8);
+
+impl Channel {
+    /// Returns the channel with the number `n`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `n` isn't between 1
+    /// and 16.
+    pub fn new(n: u8) -> Result<Self, PiControlError> {
+        ensure!(
+            (1..=CHANNELS).contains(&n),
+            PiControlError::InvalidArgument("channel")
+        );
+        Ok(Self(n))
+    }
+
+    /// Returns all channels
+    pub fn all() -> impl Iterator<Item = Channel> {
+        (1..=CHANNELS).map(Channel)
+    }
+
+    /// Returns the number of the channel
+    pub fn number(&self) -> u8 {
+        self.0
+    }
+
+    fn index(&self) -> u16 {
+        (self.0 - 1) as u16
+    }
+}
+
+/// A DIO, DI or DO, see the [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::modules::dio::{Channel, Dio};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// // inputs at 11, outputs at 81, like the first DIO right of a Core
+/// let dio = Dio::new(32, Some(11), Some(81));
+/// mock.write(17, &42u32.to_le_bytes()).unwrap();
+/// assert_eq!(dio.counter(&pi, Channel::new(1).unwrap()).unwrap(), 42);
+/// dio.set_output(&pi, Channel::new(10).unwrap(), true).unwrap();
+/// dio.set_pwm(&pi, Channel::new(2).unwrap(), 50).unwrap();
+/// assert_eq!(mock.read(81, 4).unwrap(), vec![0, 0b10, 0, 50]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Dio {
+    address: u8,
+    inputs: Option<u16>,
+    outputs: Option<u16>,
+}
+
+impl Dio {
+    /// Creates the access to the module at position `address`, whose inputs
+    /// and outputs start at the given addresses of the processimage, `None`
+    /// for a missing part, e.g. the outputs of a DI.
+    pub fn new(address: u8, inputs: Option<u16>, outputs: Option<u16>) -> Self {
+        Self {
+            address,
+            inputs,
+            outputs,
+        }
+    }
+
+    /// Creates the access to the module described by `info`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the module isn't a
+    /// DIO, DI or DO.
+    pub fn from_info(info: &DeviceInfo) -> Result<Self, PiControlError> {
+        let outputs = match info.module_type() {
+            ModuleType::Dio | ModuleType::Do => Some(info.outputs().start),
+            ModuleType::Di => None,
+            _ => return Err(PiControlError::InvalidArgument("module type")),
+        };
+        Ok(Self::new(
+            info.address(),
+            Some(info.inputs().start),
+            outputs,
+        ))
+    }
+
+    /// Creates the access to the module configured as `device`. The outputs
+    /// are expected to start at the first output variable.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the device isn't a
+    /// DIO, DI or DO or lies outside of the processimage.
+    #[cfg(feature = "rsc")]
+    pub fn from_device(device: &crate::rsc::Device) -> Result<Self, PiControlError> {
+        use crate::rsc::ProductType;
+        ensure!(
+            matches!(
+                device.product(),
+                ProductType::Dio | ProductType::Di | ProductType::Do
+            ),
+            PiControlError::InvalidArgument("product type")
+        );
+        let invalid = |_| PiControlError::InvalidArgument("device");
+        let address = u8::try_from(device.position).map_err(invalid)?;
+        let inputs = u16::try_from(device.offset).map_err(invalid)?;
+        let outputs = device
+            .out
+            .values()
+            .filter_map(|var| device.address_of(var))
+            .min()
+            .map(u16::try_from)
+            .transpose()
+            .map_err(invalid)?;
+        Ok(Self::new(address, Some(inputs), outputs))
+    }
+
+    /// Returns the position of the module
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    fn input_address(&self, offset: u16) -> Result<u16, PiControlError> {
+        self.inputs
+            .map(|start| start + offset)
+            .ok_or(PiControlError::NotSupportedOnThisModel)
+    }
+
+    fn output_address(&self, offset: u16) -> Result<u16, PiControlError> {
+        self.outputs
+            .map(|start| start + offset)
+            .ok_or(PiControlError::NotSupportedOnThisModel)
+    }
+
+    fn word(pi: &PiControl, address: u16) -> Result<u16, PiControlError> {
+        let mut bytes = [0u8; 2];
+        unsafe { pi.inner.get_bytes(address, &mut bytes) }?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    /// Returns the state of all inputs, input 1 being the lowest bit
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] for modules
+    /// without inputs, and the error of the backend if reading fails.
+    pub fn inputs(&self, pi: &PiControl) -> Result<u16, PiControlError> {
+        Self::word(pi, self.input_address(INPUTS)?)
+    }
+
+    /// Returns the state of the input `channel`
+    ///
+    /// # Errors
+    /// Same as [`Dio::inputs`].
+    pub fn input(&self, pi: &PiControl, channel: Channel) -> Result<bool, PiControlError> {
+        Ok(self.inputs(pi)? & (1 << channel.index()) != 0)
+    }
+
+    /// Returns the status of the inputs, a set bit marks an error of the
+    /// input, e.g. a missing supply
+    ///
+    /// # Errors
+    /// Same as [`Dio::inputs`].
+    pub fn input_status(&self, pi: &PiControl) -> Result<u16, PiControlError> {
+        Self::word(pi, self.input_address(INPUT_STATUS)?)
+    }
+
+    /// Returns the status of the outputs, a set bit marks an error of the
+    /// output, e.g. an overload
+    ///
+    /// # Errors
+    /// Same as [`Dio::inputs`].
+    pub fn output_status(&self, pi: &PiControl) -> Result<u16, PiControlError> {
+        Self::word(pi, self.input_address(OUTPUT_STATUS)?)
+    }
+
+    /// Returns the counter of the input `channel`, which has to be configured
+    /// as counter in PiCtory
+    ///
+    /// # Errors
+    /// Same as [`Dio::inputs`].
+    pub fn counter(&self, pi: &PiControl, channel: Channel) -> Result<u32, PiControlError> {
+        let address = self.input_address(COUNTERS + 4 * channel.index())?;
+        let mut bytes = [0u8; 4];
+        unsafe { pi.inner.get_bytes(address, &mut bytes) }?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Returns the position of the encoder on the inputs `channel` and
+    /// `channel + 1`, which have to be configured as encoder in PiCtory.
+    /// `channel` is the odd one of the pair.
+    ///
+    /// # Errors
+    /// Same as [`Dio::inputs`].
+    pub fn encoder(&self, pi: &PiControl, channel: Channel) -> Result<i32, PiControlError> {
+        Ok(self.counter(pi, channel)? as i32)
+    }
+
+    /// Resets the counters or encoders of `channels`, see
+    /// [`PiControlRaw::dio_reset_counter`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `channels` is empty
+    /// and the error of [`PiControlRaw::dio_reset_counter`] otherwise.
+    pub fn reset_counters(
+        &self,
+        raw: &PiControlRaw,
+        channels: &[Channel],
+    ) -> Result<(), PiControlError> {
+        let bitfield = channels.iter().fold(0u16, |b, c| b | (1 << c.index()));
+        ensure!(bitfield != 0, PiControlError::InvalidArgument("channels"));
+        raw.dio_reset_counter(self.address, bitfield)
+    }
+
+    /// Returns the state of all outputs, output 1 being the lowest bit
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] for modules
+    /// without outputs, and the error of the backend if reading fails.
+    pub fn outputs(&self, pi: &PiControl) -> Result<u16, PiControlError> {
+        Self::word(pi, self.output_address(OUTPUTS)?)
+    }
+
+    /// Sets all outputs at once, output 1 being the lowest bit
+    ///
+    /// # Errors
+    /// Same as [`Dio::outputs`].
+    pub fn set_outputs(&self, pi: &PiControl, outputs: u16) -> Result<(), PiControlError> {
+        let address = self.output_address(OUTPUTS)?;
+        unsafe { pi.inner.set_bytes(address, &outputs.to_le_bytes()) }
+    }
+
+    /// Returns the state of the output `channel`
+    ///
+    /// # Errors
+    /// Same as [`Dio::outputs`].
+    pub fn output(&self, pi: &PiControl, channel: Channel) -> Result<bool, PiControlError> {
+        Ok(self.outputs(pi)? & (1 << channel.index()) != 0)
+    }
+
+    /// Sets the output `channel`, leaving the others untouched
+    ///
+    /// # Errors
+    /// Same as [`Dio::outputs`].
+    pub fn set_output(
+        &self,
+        pi: &PiControl,
+        channel: Channel,
+        value: bool,
+    ) -> Result<(), PiControlError> {
+        let address = self.output_address(OUTPUTS + channel.index() / 8)?;
+        let bit = Bit::from((channel.index() % 8) as u8);
+        unsafe { pi.inner.set_bit(address, bit, value) }
+    }
+
+    /// Returns the PWM duty cycle of the output `channel` in percent
+    ///
+    /// # Errors
+    /// Same as [`Dio::outputs`].
+    pub fn pwm(&self, pi: &PiControl, channel: Channel) -> Result<u8, PiControlError> {
+        let address = self.output_address(PWM + channel.index())?;
+        let mut duty = [0u8];
+        unsafe { pi.inner.get_bytes(address, &mut duty) }?;
+        Ok(duty[0])
+    }
+
+    /// Sets the PWM duty cycle of the output `channel` in percent. The output
+    /// has to be configured for PWM in PiCtory.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `duty` is larger than
+    /// 100, otherwise the same as [`Dio::outputs`].
+    pub fn set_pwm(
+        &self,
+        pi: &PiControl,
+        channel: Channel,
+        duty: u8,
+    ) -> Result<(), PiControlError> {
+        ensure!(duty <= 100, PiControlError::InvalidArgument("duty"));
+        let address = self.output_address(PWM + channel.index())?;
+        unsafe { pi.inner.set_bytes(address, &[duty]) }
+    }
+}
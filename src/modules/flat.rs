@@ -0,0 +1,111 @@
+//! On-board I/O of the RevPi Flat
+//!
+//! The Flat has a digital input, a digital output, an analog input and an
+//! analog output on board, which PiCtory names `DIn`, `DOut`, `AIn` and
+//! `AOut`. [`FlatIo`] accesses them through these names, renamed variables
+//! can be reached by setting the default names as
+//! [aliases](crate::picontrol::PiControlBuilder::alias). The LEDs are set
+//! with [`leds`](crate::leds).
+
+use crate::model::Model;
+use crate::picontrol::{PiControl, PiControlError, Value};
+use crate::util::ensure;
+
+const INPUT: &str = "DIn";
+const OUTPUT: &str = "DOut";
+const ANALOG_INPUT: &str = "AIn";
+const ANALOG_OUTPUT: &str = "AOut";
+
+/// The on-board I/O of a RevPi Flat, see the [module documentation](self)
+///
+/// Analog values are in the unit configured in PiCtory, mV by default.
+///
+/// # Example
+/// ```
+/// use revpi::model::Model;
+/// use revpi::modules::flat::FlatIo;
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(
+///     MockBackend::new()
+///         .variable("DIn", 2, 0, 1)
+///         .variable("AIn", 4, 0, 16)
+///         .variable("DOut", 20, 0, 1)
+///         .variable("AOut", 22, 0, 16),
+/// );
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// assert!(FlatIo::new(Model::Compact).is_err());
+/// let io = FlatIo::new(Model::Flat).unwrap();
+/// mock.write(4, &4200i16.to_le_bytes()).unwrap();
+/// assert_eq!(io.analog_input(&pi).unwrap(), 4200);
+/// io.set_output(&pi, true).unwrap();
+/// io.set_analog_output(&pi, -300).unwrap();
+/// assert!(io.output(&pi).unwrap());
+/// assert_eq!(mock.read(22, 2).unwrap(), (-300i16).to_le_bytes());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlatIo(());
+
+impl FlatIo {
+    /// Creates the access if `model` is a [`Model::Flat`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::NotSupportedOnThisModel`] for other
+    /// models, which don't have this I/O.
+    pub fn new(model: Model) -> Result<Self, PiControlError> {
+        ensure!(
+            model == Model::Flat,
+            PiControlError::NotSupportedOnThisModel
+        );
+        Ok(Self(()))
+    }
+
+    /// Returns the state of the digital input
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_value_as`].
+    pub fn input(&self, pi: &PiControl) -> Result<bool, PiControlError> {
+        pi.get_value_as(INPUT)
+    }
+
+    /// Returns the state of the digital output
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_value_as`].
+    pub fn output(&self, pi: &PiControl) -> Result<bool, PiControlError> {
+        pi.get_value_as(OUTPUT)
+    }
+
+    /// Switches the digital output
+    ///
+    /// # Errors
+    /// Same as [`PiControl::set_value`].
+    pub fn set_output(&self, pi: &PiControl, on: bool) -> Result<(), PiControlError> {
+        pi.set_value(OUTPUT, Value::Bit(on))
+    }
+
+    /// Returns the value of the analog input
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_value_as`].
+    pub fn analog_input(&self, pi: &PiControl) -> Result<i16, PiControlError> {
+        pi.get_value_as(ANALOG_INPUT)
+    }
+
+    /// Returns the value of the analog output
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_value_as`].
+    pub fn analog_output(&self, pi: &PiControl) -> Result<i16, PiControlError> {
+        pi.get_value_as(ANALOG_OUTPUT)
+    }
+
+    /// Sets the analog output to `value`
+    ///
+    /// # Errors
+    /// Same as [`PiControl::set_value`].
+    pub fn set_analog_output(&self, pi: &PiControl, value: i16) -> Result<(), PiControlError> {
+        pi.set_value(ANALOG_OUTPUT, Value::Word(value as u16))
+    }
+}
@@ -0,0 +1,237 @@
+//! RevPi RO
+//!
+//! The RO has 4 relays, switched by the lowest 4 bits of its first output
+//! byte. The module counts the switching cycles of every relay and reports
+//! them as 32 bit values at the start of its inputs. Relays wear out after a
+//! number of cycles given in their datasheet, so [`Ro`] can warn once a count
+//! exceeds a threshold:
+//! ```no_run
+//! use revpi::modules::ro::Ro;
+//! use revpi::picontrol::{raw::{DeviceInfo, PiControlRaw}, PiControl};
+//!
+//! let raw = PiControlRaw::new().unwrap();
+//! let mut ro = Ro::from_info(&DeviceInfo::from(raw.get_device_info(32).unwrap())).unwrap();
+//! ro.set_threshold(1, Some(100_000)).unwrap();
+//! let pi = PiControl::new().unwrap();
+//! ro.set_relay(&pi, 1, true).unwrap();
+//! for warning in ro.wear_warnings(&pi).unwrap() {
+//!     eprintln!("relay {} switched {} times", warning.relay, warning.cycles);
+//! }
+//! ```
+
+use crate::picontrol::{
+    raw::{Bit, DeviceInfo, ModuleType},
+    PiControl, PiControlError,
+};
+use crate::util::ensure;
+
+/// Number of relays
+pub const RELAYS: u8 = 4;
+
+// offset relative to the first input
+const CYCLES: u16 = 0;
+// offset relative to the first output
+const OUTPUTS: u16 = 0;
+
+/// A relay whose cycles exceed its threshold, returned by
+/// [`Ro::wear_warnings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WearWarning {
+    /// Number of the relay, from 1 to 4
+    pub relay: u8,
+    /// Cycles counted by the module
+    pub cycles: u32,
+    /// The exceeded threshold
+    pub threshold: u32,
+}
+
+/// An RO, see the [module documentation](self)
+///
+/// Relays are numbered from 1 like on the module.
+///
+/// # Example
+/// ```
+/// use revpi::modules::ro::{Ro, WearWarning};
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut ro = Ro::new(11, 27);
+/// ro.set_threshold(2, Some(1000)).unwrap();
+/// ro.set_relay(&pi, 3, true).unwrap();
+/// assert_eq!(mock.read(27, 1).unwrap(), vec![0b100]);
+/// mock.write(15, &1001u32.to_le_bytes()).unwrap();
+/// assert_eq!(ro.cycles(&pi, 2).unwrap(), 1001);
+/// assert_eq!(
+///     ro.wear_warnings(&pi).unwrap(),
+///     vec![WearWarning { relay: 2, cycles: 1001, threshold: 1000 }]
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ro {
+    inputs: u16,
+    outputs: u16,
+    thresholds: [Option<u32>; RELAYS as usize],
+}
+
+impl Ro {
+    /// Creates the access to the module whose inputs and outputs start at the
+    /// given addresses of the processimage, without thresholds
+    pub fn new(inputs: u16, outputs: u16) -> Self {
+        Self {
+            inputs,
+            outputs,
+            thresholds: [None; RELAYS as usize],
+        }
+    }
+
+    /// Creates the access to the module described by `info`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the module isn't an
+    /// RO.
+    pub fn from_info(info: &DeviceInfo) -> Result<Self, PiControlError> {
+        ensure!(
+            info.module_type() == ModuleType::Ro,
+            PiControlError::InvalidArgument("module type")
+        );
+        Ok(Self::new(info.inputs().start, info.outputs().start))
+    }
+
+    /// Creates the access to the module configured as `device`. The outputs
+    /// are expected to start at the first output variable.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the device isn't an
+    /// RO or lies outside of the processimage.
+    #[cfg(feature = "rsc")]
+    pub fn from_device(device: &crate::rsc::Device) -> Result<Self, PiControlError> {
+        ensure!(
+            device.product() == crate::rsc::ProductType::Ro,
+            PiControlError::InvalidArgument("product type")
+        );
+        let invalid = |_| PiControlError::InvalidArgument("device");
+        let inputs = u16::try_from(device.offset).map_err(invalid)?;
+        let outputs = device
+            .out
+            .values()
+            .filter_map(|var| device.address_of(var))
+            .min()
+            .ok_or(PiControlError::InvalidArgument("device"))?;
+        let outputs = u16::try_from(outputs).map_err(invalid)?;
+        Ok(Self::new(inputs, outputs))
+    }
+
+    fn index(relay: u8) -> Result<u16, PiControlError> {
+        ensure!(
+            (1..=RELAYS).contains(&relay),
+            PiControlError::InvalidArgument("relay")
+        );
+        Ok((relay - 1) as u16)
+    }
+
+    /// Returns the threshold of `relay`, `None` if it has none
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `relay` isn't between
+    /// 1 and 4.
+    pub fn threshold(&self, relay: u8) -> Result<Option<u32>, PiControlError> {
+        Ok(self.thresholds[Self::index(relay)? as usize])
+    }
+
+    /// Sets the number of cycles after which [`Ro::wear_warnings`] reports
+    /// `relay`, `None` to never report it
+    ///
+    /// # Errors
+    /// Same as [`Ro::threshold`].
+    pub fn set_threshold(&mut self, relay: u8, cycles: Option<u32>) -> Result<(), PiControlError> {
+        self.thresholds[Self::index(relay)? as usize] = cycles;
+        Ok(())
+    }
+
+    /// Returns the state of all relays, relay 1 being the lowest bit
+    ///
+    /// # Errors
+    /// Returns the error of the backend if reading fails.
+    pub fn relays(&self, pi: &PiControl) -> Result<u8, PiControlError> {
+        let mut byte = [0u8];
+        unsafe { pi.inner.get_bytes(self.outputs + OUTPUTS, &mut byte) }?;
+        Ok(byte[0] & ((1 << RELAYS) - 1))
+    }
+
+    /// Sets all relays at once, relay 1 being the lowest bit
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a bit above the
+    /// relays is set and the error of the backend if writing fails.
+    pub fn set_relays(&self, pi: &PiControl, relays: u8) -> Result<(), PiControlError> {
+        ensure!(
+            relays >> RELAYS == 0,
+            PiControlError::InvalidArgument("relays")
+        );
+        unsafe { pi.inner.set_bytes(self.outputs + OUTPUTS, &[relays]) }
+    }
+
+    /// Returns whether `relay` is closed
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `relay` isn't between
+    /// 1 and 4 and the error of the backend if reading fails.
+    pub fn relay(&self, pi: &PiControl, relay: u8) -> Result<bool, PiControlError> {
+        let idx = Self::index(relay)?;
+        unsafe {
+            pi.inner
+                .get_bit(self.outputs + OUTPUTS, Bit::from(idx as u8))
+        }
+    }
+
+    /// Closes or opens `relay`, leaving the others untouched
+    ///
+    /// # Errors
+    /// Same as [`Ro::relay`].
+    pub fn set_relay(&self, pi: &PiControl, relay: u8, closed: bool) -> Result<(), PiControlError> {
+        let idx = Self::index(relay)?;
+        unsafe {
+            pi.inner
+                .set_bit(self.outputs + OUTPUTS, Bit::from(idx as u8), closed)
+        }
+    }
+
+    /// Returns the switching cycles the module counted for `relay`
+    ///
+    /// # Errors
+    /// Same as [`Ro::relay`].
+    pub fn cycles(&self, pi: &PiControl, relay: u8) -> Result<u32, PiControlError> {
+        let idx = Self::index(relay)?;
+        let mut bytes = [0u8; 4];
+        unsafe {
+            pi.inner
+                .get_bytes(self.inputs + CYCLES + 4 * idx, &mut bytes)
+        }?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Returns the relays whose cycles exceed their threshold
+    ///
+    /// # Errors
+    /// Returns the error of the backend if reading fails.
+    pub fn wear_warnings(&self, pi: &PiControl) -> Result<Vec<WearWarning>, PiControlError> {
+        let mut bytes = [0u8; 4 * RELAYS as usize];
+        unsafe { pi.inner.get_bytes(self.inputs + CYCLES, &mut bytes) }?;
+        Ok(bytes
+            .chunks_exact(4)
+            .zip(self.thresholds)
+            .zip(1..)
+            .filter_map(|((count, threshold), relay)| {
+                let cycles = u32::from_le_bytes(count.try_into().unwrap());
+                let threshold = threshold?;
+                (cycles > threshold).then_some(WearWarning {
+                    relay,
+                    cycles,
+                    threshold,
+                })
+            })
+            .collect())
+    }
+}
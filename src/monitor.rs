@@ -0,0 +1,374 @@
+//! Change notification for variables
+//!
+//! A [`Monitor`] polls a set of variables at a fixed interval and only reports
+//! the ones whose value changed. All variables are read with a single read of
+//! the region they span, and if the region didn't change since the last poll,
+//! nothing else is done:
+//! ```no_run
+//! use revpi::{monitor::Monitor, picontrol::PiControl};
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut monitor = Monitor::new(Duration::from_millis(10))
+//!     .watch("RevPiLED")
+//!     .watch_debounced("I_1", Duration::from_millis(50));
+//! monitor
+//!     .run(&pi, |change| {
+//!         println!("{}: {} -> {}", change.name, change.old, change.new);
+//!         true
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! Instead of [`Monitor::run`], [`Monitor::poll`] can be called from an
+//! existing loop.
+//!
+//! To wait for the changes of a single variable,
+//! [`PiControl::watch`](crate::picontrol::PiControl::watch) returns them as
+//! iterator, or with the `tokio` feature as async stream, see [`Watch`].
+//!
+//! Callbacks for the edges of digital inputs are called by both:
+//! ```no_run
+//! use revpi::{monitor::Monitor, picontrol::PiControl};
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut monitor = Monitor::new(Duration::from_millis(5))
+//!     .watch_debounced("Button1", Duration::from_millis(20))
+//!     .on_rising_edge("Button1", |_| println!("pressed"))
+//!     .on_falling_edge("Button1", |_| println!("released"));
+//! monitor.run(&pi, |_| true).unwrap();
+//! ```
+
+mod watch;
+
+#[cfg(feature = "tokio")]
+pub use self::watch::WatchStream;
+pub use self::watch::{Watch, WatchEvent};
+use crate::{
+    cycle::{Cycle, OverrunPolicy},
+    picontrol::{PiControl, PiControlError, Value, Var},
+    util::ensure,
+};
+use std::{
+    fmt, mem,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+/// A changed variable, reported by [`Monitor::poll`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// Name of the variable
+    pub name: String,
+    /// Last reported value
+    pub old: Value,
+    /// New value
+    pub new: Value,
+}
+
+impl Change {
+    /// Returns the edge if a bit changed, `None` for other values
+    pub fn edge(&self) -> Option<Edge> {
+        edge(&self.old, &self.new)
+    }
+}
+
+fn edge(old: &Value, new: &Value) -> Option<Edge> {
+    match (old, new) {
+        (Value::Bit(false), Value::Bit(true)) => Some(Edge::Rising),
+        (Value::Bit(true), Value::Bit(false)) => Some(Edge::Falling),
+        _ => None,
+    }
+}
+
+/// Edge of a bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Edge {
+    /// From `false` to `true`
+    Rising,
+    /// From `true` to `false`
+    Falling,
+}
+
+type EdgeHandler = Box<dyn FnMut(&Change) + Send>;
+
+// callback for an edge of a variable
+struct Handler {
+    name: String,
+    edge: Edge,
+    f: EdgeHandler,
+}
+
+#[derive(Debug)]
+struct Watched {
+    name: String,
+    debounce: Duration,
+    var: Option<Var>,
+    reported: Option<Value>,
+    // value that differs from the reported one and since when
+    pending: Option<(Value, Instant)>,
+}
+
+/// Polls a set of variables and reports changes, see the
+/// [module documentation](self)
+pub struct Monitor {
+    interval: Duration,
+    watched: Vec<Watched>,
+    handlers: Vec<Handler>,
+    generation: Option<u64>,
+    region: Range<u16>,
+    snapshot: Vec<u8>,
+    buffer: Vec<u8>,
+}
+
+impl fmt::Debug for Monitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let handlers: Vec<_> = self.handlers.iter().map(|h| (&h.name, h.edge)).collect();
+        f.debug_struct("Monitor")
+            .field("interval", &self.interval)
+            .field("watched", &self.watched)
+            .field("handlers", &handlers)
+            .field("generation", &self.generation)
+            .field("region", &self.region)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Monitor {
+    /// Creates a monitor without any variables, polling every `interval` in
+    /// [`Monitor::run`]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            watched: Vec::new(),
+            handlers: Vec::new(),
+            generation: None,
+            region: 0..0,
+            snapshot: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Adds the variable `name`, every change of it is reported
+    pub fn watch(self, name: &str) -> Self {
+        self.watch_debounced(name, Duration::ZERO)
+    }
+
+    /// Adds the variable `name`, but only reports a change once the new value
+    /// was seen for at least `debounce`, so e.g. bouncing contacts don't
+    /// cause a flood of changes. Values that don't last that long are never
+    /// reported.
+    pub fn watch_debounced(mut self, name: &str, debounce: Duration) -> Self {
+        self.watched.push(Watched {
+            name: name.to_string(),
+            debounce,
+            var: None,
+            reported: None,
+            pending: None,
+        });
+        // looked up again on the next poll
+        self.generation = None;
+        self
+    }
+
+    /// Calls `f` whenever the bit `name` changes from `false` to `true`. The
+    /// variable is watched if it isn't yet, to debounce it, watch it with
+    /// [`Monitor::watch_debounced`] first.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::monitor::Monitor;
+    /// use revpi::picontrol::{backend::MockBackend, PiControl};
+    /// use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+    /// use std::time::Duration;
+    ///
+    /// let mock = Arc::new(MockBackend::new().variable("Button1", 0, 0, 1));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// let presses = Arc::new(AtomicUsize::new(0));
+    /// let counter = presses.clone();
+    /// let mut monitor = Monitor::new(Duration::from_millis(10))
+    ///     .on_rising_edge("Button1", move |_| {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     });
+    /// monitor.poll(&pi).unwrap();
+    /// for byte in [1, 0, 1] {
+    ///     mock.write(0, &[byte]).unwrap();
+    ///     monitor.poll(&pi).unwrap();
+    /// }
+    /// assert_eq!(presses.load(Ordering::Relaxed), 2);
+    /// ```
+    pub fn on_rising_edge<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnMut(&Change) + Send + 'static,
+    {
+        self.on_edge(name, Edge::Rising, f)
+    }
+
+    /// Calls `f` whenever the bit `name` changes from `true` to `false`, like
+    /// [`Monitor::on_rising_edge`]
+    pub fn on_falling_edge<F>(self, name: &str, f: F) -> Self
+    where
+        F: FnMut(&Change) + Send + 'static,
+    {
+        self.on_edge(name, Edge::Falling, f)
+    }
+
+    fn on_edge<F>(mut self, name: &str, edge: Edge, f: F) -> Self
+    where
+        F: FnMut(&Change) + Send + 'static,
+    {
+        if !self.watched.iter().any(|w| w.name == name) {
+            self = self.watch(name);
+        }
+        self.handlers.push(Handler {
+            name: name.to_string(),
+            edge,
+            f: Box::new(f),
+        });
+        self
+    }
+
+    /// Returns the names of the watched variables
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.watched.iter().map(|w| w.name.as_str())
+    }
+
+    /// Returns the polling interval of [`Monitor::run`]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // looks up all names and the region they span
+    fn resolve(&mut self, pi: &PiControl) -> Result<(), PiControlError> {
+        // loaded before the lookups, so an invalidation in between is noticed
+        let generation = pi.generation();
+        let mut region: Option<Range<u16>> = None;
+        for watched in self.watched.iter_mut() {
+            let var = pi.find_variable(&watched.name)?;
+            ensure!(
+                var.length == 1 || var.is_bytes(),
+                PiControlError::InvalidArgument("length")
+            );
+            let end = var.address + var.length.div_ceil(8);
+            region = Some(match region {
+                Some(r) => r.start.min(var.address)..r.end.max(end),
+                None => var.address..end,
+            });
+            watched.var = Some(var);
+        }
+        self.region = region.unwrap_or(0..0);
+        self.snapshot.clear();
+        self.generation = Some(generation);
+        Ok(())
+    }
+
+    /// Reads all variables once and returns the ones that changed since they
+    /// were last reported, after calling the callbacks of their edges. The
+    /// first poll only records the current values.
+    ///
+    /// The names are looked up on the first poll and again whenever the cache
+    /// of `pi` gets invalidated, e.g. after a driver reset.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a name can't be found
+    /// and a [`PiControlError::IoError`] if reading fails.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::monitor::{Change, Monitor};
+    /// use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// let mock = Arc::new(MockBackend::new().variable("I_1", 0, 3, 1));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// let mut monitor = Monitor::new(Duration::from_millis(10)).watch("I_1");
+    /// assert!(monitor.poll(&pi).unwrap().is_empty());
+    /// mock.write(0, &[0b1000]).unwrap();
+    /// let change = Change {
+    ///     name: "I_1".to_string(),
+    ///     old: Value::Bit(false),
+    ///     new: Value::Bit(true),
+    /// };
+    /// assert_eq!(monitor.poll(&pi).unwrap(), vec![change]);
+    /// assert!(monitor.poll(&pi).unwrap().is_empty());
+    /// ```
+    pub fn poll(&mut self, pi: &PiControl) -> Result<Vec<Change>, PiControlError> {
+        if self.generation != Some(pi.generation()) {
+            self.resolve(pi)?;
+        }
+        let start = self.region.start;
+        self.buffer.resize(self.region.len(), 0);
+        // the region was given by the driver, so it lies inside the processimage
+        unsafe { pi.inner.get_bytes(start, &mut self.buffer) }?;
+        let pending = self.watched.iter().any(|w| w.pending.is_some());
+        if self.buffer == self.snapshot && !pending {
+            return Ok(Vec::new());
+        }
+        mem::swap(&mut self.buffer, &mut self.snapshot);
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        for watched in self.watched.iter_mut() {
+            // resolved above
+            let var = watched.var.unwrap();
+            let bytes = &self.snapshot[(var.address - start) as usize..];
+            // the length was checked when resolving
+            let value = Value::decode(bytes, var.bit, var.length as usize).unwrap();
+            let old = match &watched.reported {
+                Some(old) if *old != value => old.clone(),
+                Some(_) => {
+                    watched.pending = None;
+                    continue;
+                }
+                None => {
+                    watched.reported = Some(value);
+                    continue;
+                }
+            };
+            let since = match watched.pending {
+                Some((ref pending, since)) if *pending == value => since,
+                _ => now,
+            };
+            if now - since >= watched.debounce {
+                changes.push(Change {
+                    name: watched.name.clone(),
+                    old,
+                    new: value.clone(),
+                });
+                watched.reported = Some(value);
+                watched.pending = None;
+            } else {
+                watched.pending = Some((value, since));
+            }
+        }
+        for change in changes.iter() {
+            let Some(edge) = change.edge() else { continue };
+            for handler in self.handlers.iter_mut() {
+                if handler.edge == edge && handler.name == change.name {
+                    (handler.f)(change);
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Polls every interval and calls `f` with every change until `f` returns
+    /// `false`.
+    ///
+    /// # Errors
+    /// Returns the first error of [`Monitor::poll`].
+    pub fn run<F>(&mut self, pi: &PiControl, mut f: F) -> Result<(), PiControlError>
+    where
+        F: FnMut(&Change) -> bool,
+    {
+        let mut cycle = Cycle::new(self.interval).overrun_policy(OverrunPolicy::Skip);
+        loop {
+            cycle.wait()?;
+            for change in self.poll(pi)? {
+                if !f(&change) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Changes of a single variable as iterator or async stream
+
+use super::{Edge, Monitor};
+use crate::{
+    clock::{Clock, SystemClock, Timestamp},
+    cycle::{Cycle, OverrunPolicy},
+    picontrol::{PiControl, PiControlError, Value},
+};
+use std::{borrow::Borrow, collections::VecDeque, fmt, time::Duration};
+
+/// A change of a watched variable, returned by [`Watch`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchEvent {
+    /// Name of the variable
+    pub name: String,
+    /// Value before the change
+    pub old: Value,
+    /// Value after the change
+    pub new: Value,
+    /// Time the change was noticed
+    pub timestamp: Timestamp,
+}
+
+impl WatchEvent {
+    /// Returns the edge if a bit changed, `None` for other values
+    pub fn edge(&self) -> Option<Edge> {
+        super::edge(&self.old, &self.new)
+    }
+}
+
+/// Blocking iterator over the changes of a variable, created by
+/// [`PiControl::watch`]
+///
+/// The variable is polled with a [`Monitor`] every interval, 10ms by default.
+/// Errors are returned as items, the iterator itself never ends.
+///
+/// # Example
+/// ```
+/// use revpi::monitor::Edge;
+/// use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+/// use std::{sync::Arc, thread, time::Duration};
+///
+/// let mock = Arc::new(MockBackend::new().variable("Button1", 0, 0, 1));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let presser = thread::spawn({
+///     let mock = mock.clone();
+///     move || {
+///         for byte in [1, 0, 1] {
+///             thread::sleep(Duration::from_millis(50));
+///             mock.write(0, &[byte]).unwrap();
+///         }
+///     }
+/// });
+/// let mut presses = pi.watch("Button1").interval(Duration::from_millis(1)).rising();
+/// for _ in 0..2 {
+///     let event = presses.next().unwrap().unwrap();
+///     assert_eq!(event.old, Value::Bit(false));
+///     assert_eq!(event.edge(), Some(Edge::Rising));
+/// }
+/// presser.join().unwrap();
+/// ```
+pub struct Watch<P> {
+    pi: P,
+    name: String,
+    interval: Duration,
+    debounce: Duration,
+    edge: Option<Edge>,
+    clock: Box<dyn Clock>,
+    // created on the first call of next
+    state: Option<(Monitor, Cycle)>,
+    events: VecDeque<WatchEvent>,
+}
+
+impl<P> fmt::Debug for Watch<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watch")
+            .field("name", &self.name)
+            .field("interval", &self.interval)
+            .field("debounce", &self.debounce)
+            .field("edge", &self.edge)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P: Borrow<PiControl>> Watch<P> {
+    /// Creates an iterator over all changes of the variable `name`, with
+    /// `pi` being e.g. a `&PiControl` or an `Arc<PiControl>`
+    pub fn new(pi: P, name: &str) -> Self {
+        Self {
+            pi,
+            name: name.to_string(),
+            interval: Duration::from_millis(10),
+            debounce: Duration::ZERO,
+            edge: None,
+            clock: Box::new(SystemClock::new()),
+            state: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Sets the polling interval
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Only reports values that lasted at least `debounce`, see
+    /// [`Monitor::watch_debounced`]
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Only reports rising edges
+    pub fn rising(mut self) -> Self {
+        self.edge = Some(Edge::Rising);
+        self
+    }
+
+    /// Only reports falling edges
+    pub fn falling(mut self) -> Self {
+        self.edge = Some(Edge::Falling);
+        self
+    }
+
+    /// Stamps the events with `clock` instead of a [`SystemClock`]
+    pub fn with_clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    // waits for the next poll and queues the matching changes
+    fn poll(&mut self) -> Result<(), PiControlError> {
+        let (monitor, cycle) = self.state.get_or_insert_with(|| {
+            let monitor = Monitor::new(self.interval).watch_debounced(&self.name, self.debounce);
+            let cycle = Cycle::new(self.interval).overrun_policy(OverrunPolicy::Skip);
+            (monitor, cycle)
+        });
+        cycle.wait()?;
+        let changes = monitor.poll(self.pi.borrow())?;
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let timestamp = self.clock.now();
+        for change in changes {
+            if self.edge.is_none() || change.edge() == self.edge {
+                self.events.push_back(WatchEvent {
+                    name: change.name,
+                    old: change.old,
+                    new: change.new,
+                    timestamp,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: Borrow<PiControl>> Iterator for Watch<P> {
+    type Item = Result<WatchEvent, PiControlError>;
+
+    /// Blocks until the next change
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Err(e) = self.poll() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl PiControl {
+    /// Watches the variable `name` for changes, see [`Watch`]
+    pub fn watch(&self, name: &str) -> Watch<&PiControl> {
+        Watch::new(self, name)
+    }
+}
+
+/// Async stream of the changes of a variable, created by
+/// [`Watch::into_stream`]
+///
+/// The [`Watch`] runs on tokio's blocking thread pool until the stream is
+/// dropped, noticed with the next change.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::AsyncPiControl;
+/// # async fn f() {
+/// let pi = AsyncPiControl::new().unwrap();
+/// let mut presses = pi.watch("Button1").rising().into_stream();
+/// while let Some(event) = presses.next().await {
+///     println!("pressed at {:?}", event.unwrap().timestamp.wall);
+/// }
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct WatchStream {
+    events: tokio::sync::mpsc::Receiver<Result<WatchEvent, PiControlError>>,
+}
+
+#[cfg(feature = "tokio")]
+impl WatchStream {
+    /// Waits for the next change. Returns `None` if the [`Watch`] stopped,
+    /// e.g. because it panicked.
+    pub async fn next(&mut self) -> Option<Result<WatchEvent, PiControlError>> {
+        self.events.recv().await
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<P: Borrow<PiControl> + Send + 'static> Watch<P> {
+    /// Runs the watch on tokio's blocking thread pool and returns the changes
+    /// as [`WatchStream`]
+    ///
+    /// # Panics
+    /// Panics if not called from a tokio runtime.
+    pub fn into_stream(self) -> WatchStream {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::task::spawn_blocking(move || {
+            for event in self {
+                if tx.blocking_send(event).is_err() {
+                    // the stream was dropped
+                    return;
+                }
+            }
+        });
+        WatchStream { events: rx }
+    }
+}
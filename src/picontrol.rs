@@ -14,23 +14,76 @@
 //! ```no_run
 //! # use revpi::picontrol::raw::{PiControlRaw};
 //! let raw = PiControlRaw::new().unwrap();
-//! unsafe { raw.update_device_firmware(31) };
+//! unsafe { raw.update_device_firmware(31) }.unwrap();
 //! ```
 //! Usually, PiControl is enough though.
 //!
 //! Lastly, [`raw::raw`] provides the raw ioctl bindings needed for IO with the
 //! RevPi.
 
+#[cfg(feature = "tokio")]
+mod asynchronous;
+pub mod backend;
+mod builder;
+mod cache;
+#[cfg(feature = "events")]
+mod events;
+mod handle;
+#[cfg(feature = "rsc")]
+mod image;
+mod mapping;
+mod messages;
+#[cfg(feature = "rsc")]
+mod names;
+mod pool;
 pub mod raw;
+mod replace_io;
+mod retry;
+mod scaling;
+mod status;
 
-use self::raw::{raw::SPIVariable, Bit, PiControlRaw};
+#[cfg(feature = "tokio")]
+pub use self::asynchronous::AsyncPiControl;
+pub use self::backend::Backend;
+pub use self::builder::PiControlBuilder;
+use self::cache::Cache;
+#[cfg(feature = "events")]
+pub use self::events::ResetHook;
+pub use self::handle::VariableHandle;
+#[cfg(feature = "rsc")]
+pub use self::image::DeviceImage;
+pub use self::mapping::ProcessImage;
+pub use self::messages::{Message, MessageLog};
+#[cfg(feature = "rsc")]
+pub use self::names::{NameTable, VariableInfo};
+pub use self::pool::{PiControlPool, PooledPiControl};
+use self::raw::{raw::SPIVariable, Bit, DeviceInfo, FirmwareVersion, ModuleType};
+pub use self::replace_io::{IoFormat, ReplaceIo, ReplaceIoConfig};
+pub use self::retry::RetryPolicy;
+pub use self::scaling::Scaling;
+pub use self::status::{Status, STATUS_VARIABLE};
 use crate::util::ensure;
+#[cfg(feature = "macro")]
+pub use revpi_macro::ProcessImage;
 use std::{
-    ffi::{self, CString},
-    io,
+    collections::{BTreeMap, HashMap},
+    ffi::{self, CStr, CString},
+    fmt,
+    hash::{Hash, Hasher},
+    io, mem,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 use thiserror::Error;
 
+/// How long [`PiControl::apply_new_config`] waits for the piBridge to come up
+/// after a reset
+pub const BRIDGE_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Error)]
 pub enum PiControlError {
     /// If an argument given to a method of PiControlRaw was invalid, e.g. too big
@@ -45,21 +98,118 @@ pub enum PiControlError {
     /// entries at all
     #[error("No variable entries")]
     NoVarEntries,
+    /// Returned by the [`commander`](crate::commander) if the queue was full
+    #[error("Command queue is full")]
+    QueueFull,
+    /// Returned by the [`commander`](crate::commander) if the thread executing
+    /// the commands stopped
+    #[error("Command queue was closed")]
+    Disconnected,
+    /// Returned by the [`commander`](crate::commander) if a write was dropped
+    /// because of a conflicting write to the same variable
+    #[error("Write conflicted with another write")]
+    Conflict,
+    /// Returned by [`Cycle::wait`](crate::cycle::Cycle::wait) if a cycle took
+    /// longer than its period
+    #[error("Cycle took longer than its period")]
+    Overrun,
+    /// Returned if the piBridge didn't come up in time after a driver reset,
+    /// see [`PiControl::apply_new_config`]
+    #[error("Timed out waiting for the piBridge")]
+    Timeout,
+    /// Returned if values couldn't be accessed because the piBridge wasn't
+    /// running, e.g. during a driver reset
+    #[error("piBridge isn't running")]
+    BridgeNotRunning,
+    /// Returned if the kernel module ran out of memory
+    #[error("piControl ran out of memory")]
+    OutOfMemory,
+    /// Returned if the request isn't supported by this RevPi, e.g. resetting
+    /// DIO counters on a RevPi without a piBridge
+    #[error("Not supported on this model")]
+    NotSupportedOnThisModel,
+    /// Returned if the loaded piControl driver is too old for the request,
+    /// see [`PiControlRaw::driver_version`](raw::PiControlRaw::driver_version)
+    #[error("Not supported by this version of piControl")]
+    NotSupportedByDriver,
+    /// Returned by [`FirmwareUpdater::run`](raw::FirmwareUpdater::run) if not
+    /// exactly one module is connected. Contains the number of modules.
+    #[error("Firmware updates need exactly one connected module, found {0}")]
+    ModuleCount(usize),
+    /// Returned by [`PiControlRaw::open`](raw::PiControlRaw::open) if the
+    /// user isn't allowed to open the device. Contains the owner and the
+    /// permission bits of the device, so the missing permission can be found.
+    #[error(
+        "Permission denied opening {path:?} (owner {uid}, group {gid}, mode {mode:o}); \
+         run as root or add the user to group {gid}, e.g. with `usermod -aG`"
+    )]
+    PermissionDenied {
+        path: PathBuf,
+        uid: u32,
+        gid: u32,
+        mode: u32,
+    },
+    /// Returned by [`TagProvider::write`](crate::tags::TagProvider::write)
+    /// if the tag can't be written
+    #[error("Tag {0} is read only")]
+    ReadOnly(String),
     /// Wrapper around [`io::Error`]
     #[error(transparent)]
     IoError(#[from] io::Error),
     /// Wrapper around [`ffi::NulError`]
     #[error(transparent)]
     NulError(#[from] ffi::NulError),
+    /// Wrapper around [`RscError`](crate::rsc::RscError)
+    #[cfg(feature = "rsc")]
+    #[error(transparent)]
+    RscError(#[from] crate::rsc::RscError),
+    /// Wrapper around [`toml::de::Error`]
+    #[cfg(feature = "toml")]
+    #[error(transparent)]
+    TomlError(#[from] toml::de::Error),
+    /// Returned by [`ModbusClient`](crate::modbus::ModbusClient) if the server
+    /// answered with an exception
+    #[cfg(feature = "modbus")]
+    #[error("Modbus function {function:#04x} failed with exception code {code}")]
+    ModbusException { function: u8, code: u8 },
+}
+
+impl From<raw::raw::IoctlError> for PiControlError {
+    /// Wraps the error in a [`PiControlError::IoError`], errors with a
+    /// specific meaning are mapped by the caller.
+    fn from(e: raw::raw::IoctlError) -> Self {
+        PiControlError::IoError(e.into())
+    }
 }
 
 /// Value that can be set or read from the revpi
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+///
+/// The processimage itself only knows bits, so [`PiControl::get_value`]
+/// always returns one of the unsigned variants, or [`Value::Bytes`] for
+/// variables of other lengths. The signed and floating point
+/// variants can be written like the unsigned ones of the same size, see
+/// [`PiControl::get_value_as`] for reading them.\
+/// Values are equal if they have the same variant and the same bits, so a
+/// [`Value::Float32`] NaN equals itself, while `0.0` and `-0.0` differ.
+#[derive(Debug, Clone)]
 pub enum Value {
     Bit(bool),
     Byte(u8),
     Word(u16),
     DWord(u32),
+    /// Signed byte in two's complement
+    Int8(i8),
+    /// Signed word in two's complement
+    Int16(i16),
+    /// Signed double word in two's complement
+    Int32(i32),
+    /// IEEE 754 single precision float, e.g. as used by the AIO modules
+    Float32(f32),
+    /// Bytes of a byte array, as gateways use them e.g. for strings
+    Bytes(Vec<u8>),
+    /// UTF-8 text in a byte array. It may be shorter than the variable, the
+    /// rest is filled with null bytes when written.
+    String(String),
 }
 
 impl Value {
@@ -68,10 +218,147 @@ impl Value {
         use Value::*;
         match self {
             Bit(_) => 1,
-            Byte(_) => u8::BITS as usize,
-            Word(_) => u16::BITS as usize,
-            DWord(_) => u32::BITS as usize,
+            Byte(_) | Int8(_) => u8::BITS as usize,
+            Word(_) | Int16(_) => u16::BITS as usize,
+            DWord(_) | Int32(_) | Float32(_) => u32::BITS as usize,
+            Bytes(b) => b.len() * 8,
+            String(s) => s.len() * 8,
+        }
+    }
+
+    // all variants as u32, so they can be handled alike. Byte arrays are cut
+    // after their first 4 bytes.
+    pub(crate) fn as_u32(&self) -> u32 {
+        match *self {
+            Value::Bit(b) => b as u32,
+            Value::Byte(b) => b as u32,
+            Value::Word(w) => w as u32,
+            Value::DWord(d) => d,
+            Value::Int8(i) => i as u8 as u32,
+            Value::Int16(i) => i as u16 as u32,
+            Value::Int32(i) => i as u32,
+            Value::Float32(f) => f.to_bits(),
+            Value::Bytes(_) | Value::String(_) => {
+                let mut bytes = [0u8; 4];
+                let value = self.to_le_bytes();
+                let len = value.len().min(4);
+                bytes[..len].copy_from_slice(&value[..len]);
+                u32::from_le_bytes(bytes)
+            }
+        }
+    }
+
+    // decodes a value of `length` bits from `bytes`, which start at its
+    // address, a bit is at `bit` of the first byte. `None` if there are too
+    // few bytes or the length is neither 1 nor a multiple of 8.
+    pub(crate) fn decode(bytes: &[u8], bit: u8, length: usize) -> Option<Self> {
+        match length {
+            1 => Some(Value::Bit((bytes.first()? >> bit) & 1 == 1)),
+            8 => Some(Value::Byte(*bytes.first()?)),
+            16 => Some(Value::Word(u16::from_le_bytes(
+                bytes.get(..2)?.try_into().ok()?,
+            ))),
+            32 => Some(Value::DWord(u32::from_le_bytes(
+                bytes.get(..4)?.try_into().ok()?,
+            ))),
+            n if n > 0 && n.is_multiple_of(8) => Some(Value::Bytes(bytes.get(..n / 8)?.to_vec())),
+            _ => None,
+        }
+    }
+
+    // the bytes of the value as stored in the processimage, a bit in the
+    // lowest bit of a byte
+    pub(crate) fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Bytes(b) => b.clone(),
+            Value::String(s) => s.as_bytes().to_vec(),
+            v => v.as_u32().to_le_bytes()[..v.bitcnt().div_ceil(8)].to_vec(),
+        }
+    }
+
+    /// Returns the bits of the value, starting with the least significant one
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::picontrol::Value;
+    /// let bits: Vec<_> = Value::Byte(0b101).bits().collect();
+    /// assert_eq!(bits, [true, false, true, false, false, false, false, false]);
+    /// ```
+    pub fn bits(&self) -> impl Iterator<Item = bool> {
+        let bytes = self.to_le_bytes();
+        (0..self.bitcnt()).map(move |i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+    }
+
+    /// Creates a value from its bits, starting with the least significant one.
+    /// The variant depends on the number of bits like for
+    /// [`PiControl::get_value`].
+    ///
+    /// Returns `None` if the number of bits is neither 1 nor a multiple of 8.
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::picontrol::Value;
+    /// let mut alarms = [false; 16];
+    /// alarms[9] = true;
+    /// assert_eq!(Value::from_bits(alarms), Some(Value::Word(0x200)));
+    /// let mut serial = [false; 64];
+    /// serial[63] = true;
+    /// assert_eq!(Value::from_bits(serial), Some(Value::Bytes(vec![0, 0, 0, 0, 0, 0, 0, 0x80])));
+    /// ```
+    pub fn from_bits<I: IntoIterator<Item = bool>>(bits: I) -> Option<Self> {
+        let mut bytes = Vec::new();
+        let mut cnt = 0;
+        for b in bits {
+            if cnt % 8 == 0 {
+                bytes.push(0);
+            }
+            bytes[cnt / 8] |= (b as u8) << (cnt % 8);
+            cnt += 1;
         }
+        Value::decode(&bytes, 0, cnt)
+    }
+
+    /// Returns the bits of the value together with their label, starting
+    /// with the least significant one, e.g. to show a register of alarms.
+    /// `labels[i]` is the label of bit `i`, bits without a label are named by
+    /// their number.
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::picontrol::Value;
+    /// const ALARMS: [&str; 2] = ["overtemp", "undervoltage"];
+    /// let bits = Value::Word(0b10).named_bits(&ALARMS);
+    /// assert_eq!(bits[0], ("overtemp".to_string(), false));
+    /// assert_eq!(bits[1], ("undervoltage".to_string(), true));
+    /// assert_eq!(bits[15], ("15".to_string(), false));
+    /// ```
+    pub fn named_bits(&self, labels: &[&str]) -> Vec<(String, bool)> {
+        self.bits()
+            .enumerate()
+            .map(|(i, b)| {
+                let label = labels
+                    .get(i)
+                    .map_or_else(|| i.to_string(), |l| l.to_string());
+                (label, b)
+            })
+            .collect()
+    }
+}
+
+// by bits, so Eq and Hash can be implemented despite the float
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+            && self.to_le_bytes() == other.to_le_bytes()
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        self.to_le_bytes().hash(state);
     }
 }
 
@@ -103,18 +390,237 @@ impl From<u32> for Value {
     }
 }
 
+impl From<i8> for Value {
+    /// Returns a [`Value::Int8`] encapsulating the given i8
+    fn from(i: i8) -> Self {
+        Value::Int8(i)
+    }
+}
+
+impl From<i16> for Value {
+    /// Returns a [`Value::Int16`] encapsulating the given i16
+    fn from(i: i16) -> Self {
+        Value::Int16(i)
+    }
+}
+
+impl From<i32> for Value {
+    /// Returns a [`Value::Int32`] encapsulating the given i32
+    fn from(i: i32) -> Self {
+        Value::Int32(i)
+    }
+}
+
+impl From<f32> for Value {
+    /// Returns a [`Value::Float32`] encapsulating the given f32
+    fn from(f: f32) -> Self {
+        Value::Float32(f)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    /// Returns a [`Value::Bytes`] encapsulating the given bytes
+    fn from(b: Vec<u8>) -> Self {
+        Value::Bytes(b)
+    }
+}
+
+impl From<&str> for Value {
+    /// Returns a [`Value::String`] encapsulating the given text
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    /// Returns a [`Value::String`] encapsulating the given text
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value like piTest does, bits as `0` or `1` and bytes in
+    /// hex
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Bit(b) => write!(f, "{}", b as u8),
+            Value::Byte(b) => write!(f, "{}", b),
+            Value::Word(w) => write!(f, "{}", w),
+            Value::DWord(d) => write!(f, "{}", d),
+            Value::Int8(i) => write!(f, "{}", i),
+            Value::Int16(i) => write!(f, "{}", i),
+            Value::Int32(i) => write!(f, "{}", i),
+            Value::Float32(x) => write!(f, "{}", x),
+            Value::Bytes(ref b) => {
+                let hex: Vec<_> = b.iter().map(|b| format!("{:02x}", b)).collect();
+                f.write_str(&hex.join(" "))
+            }
+            Value::String(ref s) => f.write_str(s),
+        }
+    }
+}
+
+/// Types the bits of a [`Value`] can be interpreted as, see
+/// [`PiControl::get_value_as`]
+///
+/// The bits are reinterpreted, so e.g. a [`Value::DWord`] can be read as an
+/// `f32` or an `i32`, but not as a `u16`.
+///
+/// # Example
+/// ```
+/// # use revpi::picontrol::{FromValue, Value};
+/// assert_eq!(f32::from_value(Value::DWord(0x3fc0_0000)), Some(1.5));
+/// assert_eq!(i16::from_value(Value::Word(0xffff)), Some(-1));
+/// assert_eq!(u16::from_value(Value::DWord(1)), None);
+/// ```
+pub trait FromValue: Sized {
+    /// Interprets the bits of `value` as `Self`, `None` if the number of bits
+    /// doesn't match
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+macro_rules! impl_from_value {
+    ($($t:ty: $bits:expr, $conv:expr;)*) => {
+        $(
+            impl FromValue for $t {
+                fn from_value(value: Value) -> Option<Self> {
+                    (value.bitcnt() == $bits).then(|| $conv(value.as_u32()))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_value! {
+    bool: 1, |v| v == 1;
+    u8: 8, |v| v as u8;
+    i8: 8, |v| v as u8 as i8;
+    u16: 16, |v| v as u16;
+    i16: 16, |v| v as u16 as i16;
+    u32: 32, |v| v;
+    i32: 32, |v| v as i32;
+    f32: 32, f32::from_bits;
+}
+
+impl FromValue for Vec<u8> {
+    /// Returns the bytes of a [`Value::Bytes`] or [`Value::String`]
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Bytes(b) => Some(b),
+            Value::String(s) => Some(s.into_bytes()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    /// Returns the text of a [`Value::String`] or of a [`Value::Bytes`]
+    /// holding UTF-8 up to the first null byte
+    fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::Bytes(mut b) => {
+                b.truncate(b.iter().position(|&b| b == 0).unwrap_or(b.len()));
+                String::from_utf8(b).ok()
+            }
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: Value) -> Option<Self> {
+        Some(value)
+    }
+}
+
+// the part of an SPIVariable that is needed for accessing the value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Var {
+    pub(crate) address: u16,
+    pub(crate) bit: u8,
+    pub(crate) length: u16,
+}
+
+impl Var {
+    // whether the variable consists of whole bytes, so it can be read as a
+    // value
+    pub(crate) fn is_bytes(&self) -> bool {
+        self.length > 0 && self.length.is_multiple_of(8)
+    }
+}
+
+impl From<SPIVariable> for Var {
+    fn from(var: SPIVariable) -> Self {
+        Self {
+            address: var.i16uAddress,
+            bit: var.i8uBit,
+            length: var.i16uLength,
+        }
+    }
+}
+
+// looks up `name` without allocating, the driver doesn't accept names longer
+// than 31 bytes anyway
+fn lookup(backend: &dyn Backend, name: &str) -> Result<Var, PiControlError> {
+    let mut buf = [0u8; 32];
+    ensure!(
+        name.len() < buf.len(),
+        PiControlError::InvalidArgument("length of name")
+    );
+    if name.as_bytes().contains(&0) {
+        return Err(CString::new(name).unwrap_err().into());
+    }
+    buf[..name.len()].copy_from_slice(name.as_bytes());
+    // there is exactly one nul byte, see above
+    let name = CStr::from_bytes_with_nul(&buf[..=name.len()]).unwrap();
+    backend.find_variable(name).map(Var::from)
+}
+
 /// Provides safe RevPi IO
+///
+/// PiControl is [`Send`] and [`Sync`], so it can be shared between threads,
+/// e.g. in an [`Arc`]. All calls then go through the same file descriptor
+/// though, see [`try_clone`](Self::try_clone) and [`PiControlPool`] for
+/// giving threads their own.
 #[derive(Debug)]
 pub struct PiControl {
-    inner: PiControlRaw,
+    pub(crate) inner: Arc<dyn Backend>,
+    shared: Arc<Shared>,
+}
+
+// fails to compile if a field stops being thread safe
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<PiControl>();
+};
+
+// everything that isn't bound to a single file descriptor
+#[derive(Debug)]
+struct Shared {
+    // None for custom backends
+    path: Option<PathBuf>,
+    #[cfg(any(feature = "events", feature = "rsc"))]
+    watchdog_ms: u32,
+    safe_state: Mutex<BTreeMap<String, Value>>,
+    cache: Option<Mutex<Cache>>,
+    // incremented whenever the cache is invalidated, so handles notice it
+    generation: AtomicU64,
+    aliases: HashMap<String, String>,
+    #[cfg(feature = "rsc")]
+    names: Option<std::sync::RwLock<NameTable>>,
+    retry: RetryPolicy,
+    messages: Option<Mutex<MessageLog>>,
+    scalings: HashMap<String, Scaling>,
 }
 
 impl PiControl {
     /// Creates a new PiControl object
     ///
     /// # Errors
-    /// Will return a [`PiControlError::IoError`] if the processimage can't be
-    /// opened
+    /// Will return a [`PiControlError::PermissionDenied`] or
+    /// [`PiControlError::IoError`] if the processimage can't be opened
     ///
     /// # Example
     /// ```no_run
@@ -122,19 +628,433 @@ impl PiControl {
     /// let pi = PiControl::new().unwrap();
     /// ```
     pub fn new() -> Result<Self, PiControlError> {
+        Self::builder().build()
+    }
+
+    /// Creates a new PiControl object that accesses the processimage through
+    /// a memory mapping, see [`MappedBackend`](backend::MappedBackend). If
+    /// the driver can't be mapped, every access is a syscall like with
+    /// [`PiControl::new`].
+    ///
+    /// # Errors
+    /// Same as [`PiControl::new`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::open_mapped().unwrap();
+    /// let inputs = pi.read_region(11, 70).unwrap();
+    /// ```
+    pub fn open_mapped() -> Result<Self, PiControlError> {
+        Self::builder()
+            .backend(backend::MappedBackend::open(raw::raw::PICONTROL_DEVICE)?)
+            .build()
+    }
+
+    /// Returns a [`PiControlBuilder`] to configure a new PiControl object
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::builder().cache(true).build().unwrap();
+    /// ```
+    pub fn builder() -> PiControlBuilder {
+        PiControlBuilder::new()
+    }
+
+    /// Returns a new handle with its own file descriptor, which shares the
+    /// cache, the aliases and the safe state with this one.
+    ///
+    /// Calls on one file descriptor are serialized by the driver, so giving
+    /// every thread or async task its own handle keeps e.g. a blocking
+    /// [`PiControlRaw::wait_for_event`](raw::PiControlRaw::wait_for_event)
+    /// from delaying value access elsewhere. The output watchdog isn't armed
+    /// on the new handle, so it doesn't have to be written to regularly.\
+    /// A custom backend, see [`PiControlBuilder::backend`], can't be reopened
+    /// and is shared by both handles instead.
+    ///
+    /// # Errors
+    /// Same as [`PiControl::new`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::{PiControl, Value};
+    /// let pi = PiControl::builder().cache(true).build().unwrap();
+    /// let other = pi.try_clone().unwrap();
+    /// std::thread::spawn(move || other.set_value("RevPiLED", Value::Byte(1)).unwrap());
+    /// println!("{:?}", pi.get_value("RevPiLED").unwrap());
+    /// ```
+    pub fn try_clone(&self) -> Result<Self, PiControlError> {
+        let inner: Arc<dyn Backend> = match &self.shared.path {
+            Some(path) => Arc::new(raw::PiControlRaw::open(path)?),
+            None => self.inner.clone(),
+        };
         Ok(Self {
-            inner: PiControlRaw::new()?,
+            inner,
+            shared: self.shared.clone(),
         })
     }
 
-    fn find_variable(&self, name: &str) -> Result<SPIVariable, PiControlError> {
-        self.inner
-            .find_variable(&CString::new(name).map_err(PiControlError::from)?)
+    pub(crate) fn find_variable(&self, name: &str) -> Result<Var, PiControlError> {
+        let name = self.shared.aliases.get(name).map_or(name, String::as_str);
+        #[cfg(feature = "rsc")]
+        if let Some(names) = &self.shared.names {
+            let names = names.read().unwrap_or_else(|e| e.into_inner());
+            if let Some(var) = names.var(name) {
+                return Ok(var);
+            }
+        }
+        let cache = match &self.shared.cache {
+            Some(cache) => cache,
+            None => return self.retry(|| lookup(&*self.inner, name)),
+        };
+        // a poisoned cache is still consistent, since we only ever insert
+        let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(var) = cache.get(name) {
+            return Ok(var);
+        }
+        let var = self.retry(|| lookup(&*self.inner, name))?;
+        cache.insert(name.to_string(), var);
+        Ok(var)
+    }
+
+    /// Forgets all cached name lookups. Has no effect if caching isn't enabled,
+    /// see [`PiControlBuilder::cache`].
+    ///
+    /// This has to be called whenever the driver gets reset, since the
+    /// addresses of variables might have changed.
+    pub fn invalidate_cache(&self) {
+        if let Some(cache) = &self.shared.cache {
+            cache.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+        self.shared.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Records the last message of the driver in the log and returns the
+    /// recorded messages, the oldest first. Empty if there is no log, see
+    /// [`PiControlBuilder::message_log`].
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::picontrol::{backend::MockBackend, PiControl};
+    /// use std::sync::Arc;
+    ///
+    /// let mock = Arc::new(MockBackend::new());
+    /// let pi = PiControl::builder().backend(mock.clone()).message_log(100).build().unwrap();
+    /// mock.set_message("piGate: module 31 missing");
+    /// assert_eq!(pi.messages()[0].text, "piGate: module 31 missing");
+    /// ```
+    pub fn messages(&self) -> Vec<Message> {
+        match &self.shared.messages {
+            Some(log) => {
+                let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+                log.poll(&*self.inner);
+                log.messages().cloned().collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the connected devices whose firmware is older than the minimum
+    /// version given for their module type. Module types without a minimum
+    /// version are never returned.
+    ///
+    /// # Errors
+    /// Returns the error of the backend if the devices can't be listed.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::picontrol::backend::MockBackend;
+    /// use revpi::picontrol::raw::{raw::SDeviceInfo, FirmwareVersion, ModuleType};
+    /// use revpi::picontrol::PiControl;
+    /// use std::collections::HashMap;
+    ///
+    /// let dio = |address, minor| SDeviceInfo {
+    ///     i8uAddress: address,
+    ///     i16uModuleType: 96,
+    ///     i16uSW_Major: 1,
+    ///     i16uSW_Minor: minor,
+    ///     ..Default::default()
+    /// };
+    /// let mock = MockBackend::new().device(dio(32, 4)).device(dio(33, 6));
+    /// let pi = PiControl::builder().backend(mock).build().unwrap();
+    /// let min_versions = HashMap::from([(ModuleType::Dio, FirmwareVersion::new(1, 5, 0))]);
+    /// let outdated = pi.modules_needing_update(&min_versions).unwrap();
+    /// assert_eq!(outdated.len(), 1);
+    /// assert_eq!(outdated[0].address(), 32);
+    /// ```
+    pub fn modules_needing_update(
+        &self,
+        min_versions: &HashMap<ModuleType, FirmwareVersion>,
+    ) -> Result<Vec<DeviceInfo>, PiControlError> {
+        let devices = self.inner.device_info_list()?;
+        Ok(devices
+            .into_iter()
+            .filter(|dev| dev.is_connected())
+            .filter(|dev| {
+                min_versions
+                    .get(&dev.module_type())
+                    .is_some_and(|min| dev.firmware_version() < *min)
+            })
+            .collect())
+    }
+
+    // changes whenever the cache is invalidated, so lookups done outside of
+    // the cache can be repeated
+    pub(crate) fn generation(&self) -> u64 {
+        self.shared.generation.load(Ordering::Acquire)
+    }
+
+    /// Replaces the safe state given to [`PiControlBuilder::safe_state`].
+    pub fn set_safe_state(&self, safe_state: BTreeMap<String, Value>) {
+        *self
+            .shared
+            .safe_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = safe_state;
+    }
+
+    /// Writes the safe state given to [`PiControlBuilder::safe_state`] to the
+    /// processimage, e.g. before shutting down or after an error.
+    ///
+    /// # Errors
+    /// Every variable is written, even if writing a previous one failed. The
+    /// first error that occured is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// pi.apply_safe_state().unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn apply_safe_state(&self) -> Result<(), PiControlError> {
+        let safe_state = self
+            .shared
+            .safe_state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let mut result = Ok(());
+        for (name, value) in safe_state.iter() {
+            let r = self.set_value(name, value.clone());
+            if result.is_ok() {
+                result = r;
+            }
+        }
+        result
+    }
+
+    /// Reads `len` bytes of the processimage starting at `offset` with a
+    /// single read, e.g. all inputs of a device in a polling loop.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the region doesn't lie
+    /// inside of the processimage and a [`PiControlError::IoError`] if reading
+    /// fails.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// let inputs = pi.read_region(11, 70).unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), err)
+    )]
+    pub fn read_region(&self, offset: u16, len: usize) -> Result<Vec<u8>, PiControlError> {
+        ensure!(
+            offset as usize + len <= raw::raw::KB_PI_LEN,
+            PiControlError::InvalidArgument("offset or len")
+        );
+        let mut bytes = vec![0u8; len];
+        // the region lies inside the processimage, reading it can't harm
+        self.retry(|| unsafe { self.inner.get_bytes(offset, &mut bytes) })?;
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` to the processimage starting at `offset` with a single
+    /// write.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the region doesn't lie
+    /// inside of the processimage and a [`PiControlError::IoError`] if writing
+    /// fails.
+    ///
+    /// # Safety
+    /// You have to ensure that the region only contains values you want to
+    /// write, otherwise you might overwrite something else.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// unsafe { pi.write_region(81, &[0xff, 0x00]) }.unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub unsafe fn write_region(&self, offset: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        ensure!(
+            offset as usize + bytes.len() <= raw::raw::KB_PI_LEN,
+            PiControlError::InvalidArgument("offset or len")
+        );
+        self.retry(|| self.inner.set_bytes(offset, bytes))
+    }
+
+    /// Writes zero to every output variable configured in `rsc`, e.g. as part
+    /// of an emergency stop or shutdown sequence.
+    ///
+    /// Adjacent outputs are combined, so usually there is only one write per
+    /// device.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if an output lies outside
+    /// of the processimage. In that case nothing is written.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+    /// let pi = PiControl::new().unwrap();
+    /// pi.zero_outputs(&rsc).unwrap();
+    /// ```
+    #[cfg(feature = "rsc")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, rsc), err)
+    )]
+    pub fn zero_outputs(&self, rsc: &crate::rsc::RSC) -> Result<(), PiControlError> {
+        let mut ranges = Vec::new();
+        for device in rsc.devices.iter() {
+            for var in device.out.values() {
+                match (device.address_of(var), device.end_of(var)) {
+                    (Some(start), Some(end)) if end <= raw::raw::KB_PI_LEN as u64 => {
+                        ranges.push(start as u16..end as u16)
+                    }
+                    _ => return Err(PiControlError::InvalidArgument("rsc")),
+                }
+            }
+        }
+        ranges.sort_unstable_by_key(|r| r.start);
+        let mut merged: Vec<std::ops::Range<u16>> = Vec::new();
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        let zeros = [0u8; raw::raw::KB_PI_LEN];
+        for range in merged {
+            // the ranges were checked above
+            unsafe { self.inner.set_bytes(range.start, &zeros[..range.len()]) }?;
+        }
+        Ok(())
+    }
+
+    /// Writes `rsc` to `path` and resets the driver, so the new config gets
+    /// applied, see [`RSC::write_to`](crate::rsc::RSC::write_to). Returns the
+    /// path of the backup of the old config, if there was one.
+    ///
+    /// The output watchdog is disabled during the reset. Afterwards this waits
+    /// up to [`BRIDGE_TIMEOUT`] for the piBridge to come up again, re-arms the
+    /// watchdog, replaces the [`NameTable`], resolves all cached variables
+    /// again and applies the safe state. Control loops using this PiControl
+    /// should be paused until this returns, since values can't be accessed
+    /// during the reset.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::RscError`] if the config couldn't be
+    /// written and a [`PiControlError::InvalidArgument`] if a name table is
+    /// used and can't be built from `rsc`. In both cases the driver isn't
+    /// reset. Returns a [`PiControlError::Timeout`] if the piBridge didn't come
+    /// up in time. The watchdog is re-armed even if the reset or the wait
+    /// failed, the name table and the cache are left unchanged then.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let path = "/etc/revpi/config.rsc";
+    /// let mut rsc: RSC = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    /// rsc.devices[0].comment = "changed".to_string();
+    /// let pi = PiControl::builder().cache(true).watchdog_ms(100).build().unwrap();
+    /// pi.apply_new_config(&rsc, path).unwrap();
+    /// ```
+    #[cfg(feature = "rsc")]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn apply_new_config<P: AsRef<std::path::Path>>(
+        &self,
+        rsc: &crate::rsc::RSC,
+        path: P,
+    ) -> Result<Option<std::path::PathBuf>, PiControlError> {
+        // built first, so an invalid config doesn't get applied
+        let table = match self.shared.names {
+            Some(_) => Some(NameTable::from_rsc(rsc)?),
+            None => None,
+        };
+        let backup = rsc.write_to(path)?;
+        if self.shared.watchdog_ms != 0 {
+            self.inner.set_output_watchdog(0)?;
+        }
+        let restarted = (|| {
+            // the driver itself waits for the bridge, but gives up quite early
+            match unsafe { self.inner.reset() } {
+                Ok(()) | Err(PiControlError::Timeout) => (),
+                Err(e) => return Err(e),
+            }
+            let deadline = std::time::Instant::now() + BRIDGE_TIMEOUT;
+            while !self.inner.bridge_running()? {
+                ensure!(
+                    std::time::Instant::now() < deadline,
+                    PiControlError::Timeout
+                );
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Ok(())
+        })();
+        // re-armed on every path, a failed reset mustn't leave the outputs
+        // unguarded
+        let rearmed = match self.shared.watchdog_ms {
+            0 => Ok(()),
+            millis => self.inner.set_output_watchdog(millis),
+        };
+        restarted.and(rearmed)?;
+        if let (Some(names), Some(table)) = (&self.shared.names, table) {
+            *names.write().unwrap_or_else(|e| e.into_inner()) = table;
+        }
+        self.shared.generation.fetch_add(1, Ordering::Release);
+        if let Some(cache) = &self.shared.cache {
+            let mut cache = cache.lock().unwrap_or_else(|e| e.into_inner());
+            let names = cache.names();
+            cache.clear();
+            for name in names {
+                // variables that are gone simply fail on their next access
+                if let Ok(var) = lookup(&*self.inner, &name) {
+                    cache.insert(name, var);
+                }
+            }
+        }
+        self.apply_safe_state()?;
+        Ok(backup)
     }
 
     /// Sets the given value in the processimage. `name` is the name given to the
     /// field that should be written to in PiCtory.
     ///
+    /// A [`Value::String`] may be shorter than the variable, the rest is
+    /// filled with null bytes.
+    ///
     /// # Errors
     /// If the length found in the name lookup and the length of `value` don't
     /// match, a [`PiControlError::InvalidArgument`] is returned. Same thing if
@@ -146,30 +1066,104 @@ impl PiControl {
     /// let pi = PiControl::new().unwrap();
     /// pi.set_value("RevPiLED", Value::Byte(42)).unwrap();
     /// ```
+    ///
+    /// Text for a byte array of a gateway:
+    /// ```
+    /// # use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+    /// # use std::sync::Arc;
+    /// let mock = Arc::new(MockBackend::new().variable("DisplayText", 10, 0, 64));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// pi.set_value("DisplayText", "Hello".into()).unwrap();
+    /// assert_eq!(mock.read(10, 8).unwrap(), b"Hello\0\0\0");
+    /// assert!(pi.set_value("DisplayText", "Hello, World".into()).is_err());
+    /// assert!(pi.set_value("DisplayText", Value::Bytes(vec![1, 2])).is_err());
+    /// pi.set_value("DisplayText", Value::Bytes(vec![1; 8])).unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub fn set_value(&self, name: &str, value: Value) -> Result<(), PiControlError> {
-        let name = self.find_variable(name)?;
+        self.set_var(self.find_variable(name)?, value)
+    }
+
+    /// Replaces the value of the variable `name` with the result of `f`, which
+    /// gets the current value. Returns the new value.
+    ///
+    /// Only the bits that differ between both values are written, each of
+    /// them atomically by the driver. So unlike reading and setting the value
+    /// yourself, bits other processes change in the meantime, e.g. LEDs
+    /// shared with the OS, are kept as long as `f` didn't change them.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the name can't be
+    /// found or if `f` returns a value of another length.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::{PiControl, Value};
+    /// let pi = PiControl::new().unwrap();
+    /// // toggles the lowest bit of the LED byte
+    /// pi.update_value("RevPiLED", |old| match old {
+    ///     Value::Byte(b) => Value::Byte(b ^ 1),
+    ///     other => other,
+    /// })
+    /// .unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, f), ret, err)
+    )]
+    pub fn update_value<F>(&self, name: &str, f: F) -> Result<Value, PiControlError>
+    where
+        F: FnOnce(Value) -> Value,
+    {
+        let var = self.find_variable(name)?;
+        let old = self.get_var(var)?;
+        let new = f(old.clone());
         ensure!(
-            name.i16uLength as usize == value.bitcnt(),
-            PiControlError::InvalidArgument("value or str")
+            var.length as usize == new.bitcnt(),
+            PiControlError::InvalidArgument("value")
         );
-        match value {
-            Value::Bit(b) => unsafe {
+        let changed = old.bits().zip(new.bits()).enumerate();
+        for (i, value) in changed.filter_map(|(i, (o, n))| (o != n).then_some((i as u16, n))) {
+            let bit = var.bit as u16 + i;
+            self.retry(|| unsafe {
                 self.inner
-                    .set_bit(name.i16uAddress, Bit::from(name.i8uBit), b)
-            },
-            Value::Byte(b) => unsafe { self.inner.set_byte(name.i16uAddress, b) },
-            Value::Word(w) => unsafe { self.inner.set_word(name.i16uAddress, w) },
-            Value::DWord(d) => unsafe { self.inner.set_dword(name.i16uAddress, d) },
+                    .set_bit(var.address + bit / 8, Bit::from((bit % 8) as u8), value)
+            })?;
         }
+        Ok(new)
+    }
+
+    pub(crate) fn set_var(&self, var: Var, value: Value) -> Result<(), PiControlError> {
+        let mut bytes = value.to_le_bytes();
+        match value {
+            // shorter text is padded, so it can be written to any larger array
+            Value::String(_) if value.bitcnt() <= var.length as usize => {
+                bytes.resize(var.length as usize / 8, 0)
+            }
+            _ => ensure!(
+                var.length as usize == value.bitcnt(),
+                PiControlError::InvalidArgument("value or str")
+            ),
+        }
+        self.retry(|| match value {
+            Value::Bit(b) => unsafe { self.inner.set_bit(var.address, Bit::from(var.bit), b) },
+            _ => unsafe { self.inner.set_bytes(var.address, &bytes) },
+        })
     }
 
     /// Gets the given value from the processimage. `name` is the name given to the
     /// field that should be written to in PiCtory. The variant of the returned
-    /// [`Value`] depends on the length of the field that is read.
+    /// [`Value`] depends on the length of the field that is read: a
+    /// [`Value::Bit`], [`Value::Byte`], [`Value::Word`] or [`Value::DWord`]
+    /// for 1, 8, 16 or 32 bits and [`Value::Bytes`] for other multiples of 8,
+    /// e.g. the byte arrays of gateways.
     ///
     /// # Errors
-    /// If the name can't be found, a [`PiControlError::InvalidArgument`] is
-    /// returned.
+    /// If the name can't be found or the length of the field is not a
+    /// multiple of 8, a [`PiControlError::InvalidArgument`] is returned.
     ///
     /// # Example
     /// ```no_run
@@ -178,15 +1172,181 @@ impl PiControl {
     /// let val = pi.get_value("Core_Temperature").unwrap();
     /// assert_eq!(val, Value::Byte(42)); // just an example value
     /// ```
+    ///
+    /// Longer fields:
+    /// ```
+    /// # use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+    /// # use std::sync::Arc;
+    /// let mock = Arc::new(
+    ///     MockBackend::new()
+    ///         .variable("Counter64", 0, 0, 64)
+    ///         .variable("DisplayText", 8, 0, 128),
+    /// );
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// mock.write(0, &7u64.to_le_bytes()).unwrap();
+    /// assert_eq!(pi.get_value("Counter64").unwrap(), Value::Bytes(vec![7, 0, 0, 0, 0, 0, 0, 0]));
+    /// pi.set_value("DisplayText", "Hello".into()).unwrap();
+    /// assert_eq!(pi.get_value_as::<String>("DisplayText").unwrap(), "Hello");
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
     pub fn get_value(&self, name: &str) -> Result<Value, PiControlError> {
-        let name = self.find_variable(name)?;
-        match name.i16uLength {
-            1 => unsafe { self.inner.get_bit(name.i16uAddress, Bit::from(name.i8uBit)) }
-                .map(Value::from),
-            8 => unsafe { self.inner.get_byte(name.i16uAddress) }.map(Value::from),
-            16 => unsafe { self.inner.get_word(name.i16uAddress) }.map(Value::from),
-            32 => unsafe { self.inner.get_dword(name.i16uAddress) }.map(Value::from),
-            _ => panic!("invalid bitlength from piControl"),
+        self.get_var(self.find_variable(name)?)
+    }
+
+    /// Gets the given value from the processimage like
+    /// [`get_value`](Self::get_value) and interprets its bits as `T`, e.g. a
+    /// 32 bit variable as a float.
+    ///
+    /// # Errors
+    /// If the name can't be found or if the length of the variable doesn't
+    /// match `T`, a [`PiControlError::InvalidArgument`] is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// let temperature = pi.get_value_as::<f32>("AIO_Input1").unwrap();
+    /// let offset = pi.get_value_as::<i16>("AIO_Offset").unwrap();
+    /// ```
+    pub fn get_value_as<T: FromValue>(&self, name: &str) -> Result<T, PiControlError> {
+        T::from_value(self.get_value(name)?).ok_or(PiControlError::InvalidArgument("type"))
+    }
+
+    pub(crate) fn get_var(&self, var: Var) -> Result<Value, PiControlError> {
+        if var.length == 1 {
+            return self
+                .retry(|| unsafe { self.inner.get_bit(var.address, Bit::from(var.bit)) })
+                .map(Value::Bit);
         }
+        ensure!(var.is_bytes(), PiControlError::InvalidArgument("length"));
+        let mut bytes = vec![0u8; var.length as usize / 8];
+        self.retry(|| unsafe { self.inner.get_bytes(var.address, &mut bytes) })?;
+        Ok(Value::decode(&bytes, var.bit, var.length as usize).unwrap())
+    }
+
+    // address and bit of a single bit inside the variable `name`
+    fn flag_address(&self, name: &str, bit: u8) -> Result<(u16, Bit), PiControlError> {
+        let var = self.find_variable(name)?;
+        ensure!(
+            (bit as u16) < var.length,
+            PiControlError::InvalidArgument("bit")
+        );
+        let bit = var.bit as u16 + bit as u16;
+        Ok((var.address + bit / 8, Bit::from((bit % 8) as u8)))
+    }
+
+    /// Gets a single bit of the variable `name`, e.g. one flag of a status
+    /// byte. `bit` is counted from the least significant bit of the variable.
+    ///
+    /// # Errors
+    /// If the name can't be found or if the variable has less than `bit + 1`
+    /// bits, a [`PiControlError::InvalidArgument`] is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// let flag = pi.get_flag("RevPiStatus", 3).unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
+    pub fn get_flag(&self, name: &str, bit: u8) -> Result<bool, PiControlError> {
+        let (address, bit) = self.flag_address(name, bit)?;
+        self.retry(|| unsafe { self.inner.get_bit(address, bit) })
+    }
+
+    /// Sets a single bit of the variable `name`, leaving the other bits
+    /// untouched. `bit` is counted from the least significant bit of the
+    /// variable.
+    ///
+    /// # Errors
+    /// If the name can't be found or if the variable has less than `bit + 1`
+    /// bits, a [`PiControlError::InvalidArgument`] is returned.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// pi.set_flag("RevPiLED", 0, true).unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn set_flag(&self, name: &str, bit: u8, value: bool) -> Result<(), PiControlError> {
+        let (address, bit) = self.flag_address(name, bit)?;
+        self.retry(|| unsafe { self.inner.set_bit(address, bit, value) })
+    }
+}
+
+#[cfg(all(test, feature = "rsc"))]
+mod tests {
+    use super::*;
+    use backend::MockBackend;
+
+    // a driver whose piBridge never comes up again after a reset
+    #[derive(Debug)]
+    struct BrokenBridge(Arc<MockBackend>);
+
+    impl Backend for BrokenBridge {
+        fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+            self.0.find_variable(name)
+        }
+
+        unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+            self.0.get_bit(address, bit)
+        }
+
+        unsafe fn set_bit(
+            &self,
+            address: u16,
+            bit: Bit,
+            value: bool,
+        ) -> Result<(), PiControlError> {
+            self.0.set_bit(address, bit, value)
+        }
+
+        unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+            self.0.get_bytes(address, bytes)
+        }
+
+        unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+            self.0.set_bytes(address, bytes)
+        }
+
+        fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+            self.0.set_output_watchdog(millis)
+        }
+
+        unsafe fn reset(&self) -> Result<(), PiControlError> {
+            self.0.reset()
+        }
+
+        fn bridge_running(&self) -> Result<bool, PiControlError> {
+            Err(PiControlError::BridgeNotRunning)
+        }
+    }
+
+    #[test]
+    fn failed_config_rearms_watchdog() {
+        let mock = Arc::new(MockBackend::new());
+        let pi = PiControl::builder()
+            .backend(BrokenBridge(mock.clone()))
+            .watchdog_ms(100)
+            .build()
+            .unwrap();
+        let rsc = crate::rsc::RscBuilder::new().build().unwrap();
+        let dir = std::env::temp_dir().join(format!("revpi_apply_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let result = pi.apply_new_config(&rsc, dir.join("config.rsc"));
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(result, Err(PiControlError::BridgeNotRunning)));
+        assert_eq!(mock.resets(), 1);
+        assert_eq!(mock.watchdog_ms(), 100);
     }
 }
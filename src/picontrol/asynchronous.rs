@@ -0,0 +1,121 @@
+//! Async access to the processimage with tokio
+
+use super::{
+    raw::{raw::Event, PiControlRaw},
+    PiControl, PiControlError, Value,
+};
+use crate::monitor::Watch;
+use std::{io, panic, sync::Arc};
+use tokio::task::{self, JoinError};
+
+/// Async version of [`PiControl`] for use with tokio
+///
+/// Every call is run on tokio's blocking thread pool, so the driver never
+/// blocks the runtime. Events are waited for on a separate file descriptor,
+/// so waiting for them doesn't delay the access to values.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::{AsyncPiControl, Value};
+/// # async fn f() {
+/// let pi = AsyncPiControl::new().unwrap();
+/// pi.set_value("RevPiLED", Value::Byte(42)).await.unwrap();
+/// let val = pi.get_value("Core_Temperature").await.unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncPiControl {
+    pi: Arc<PiControl>,
+    // None for custom backends, which can't be reopened
+    events: Option<Arc<PiControlRaw>>,
+}
+
+impl AsyncPiControl {
+    /// Creates a new AsyncPiControl object, see [`PiControl::new`]
+    ///
+    /// # Errors
+    /// Same as [`PiControl::new`].
+    pub fn new() -> Result<Self, PiControlError> {
+        PiControl::new()?.try_into()
+    }
+
+    /// Returns the wrapped [`PiControl`], e.g. for calls from a blocking
+    /// context
+    pub fn blocking(&self) -> &PiControl {
+        &self.pi
+    }
+
+    async fn spawn<T, F>(&self, f: F) -> Result<T, PiControlError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&PiControl) -> Result<T, PiControlError> + Send + 'static,
+    {
+        let pi = self.pi.clone();
+        task::spawn_blocking(move || f(&pi))
+            .await
+            .unwrap_or_else(|e| Err(join_error(e)))
+    }
+
+    /// Watches the variable `name` for changes, like [`PiControl::watch`].
+    /// Use [`Watch::into_stream`] to receive them asynchronously.
+    pub fn watch(&self, name: &str) -> Watch<Arc<PiControl>> {
+        Watch::new(self.pi.clone(), name)
+    }
+
+    /// Async version of [`PiControl::get_value`]
+    pub async fn get_value(&self, name: &str) -> Result<Value, PiControlError> {
+        let name = name.to_string();
+        self.spawn(move |pi| pi.get_value(&name)).await
+    }
+
+    /// Async version of [`PiControl::set_value`]
+    pub async fn set_value(&self, name: &str, value: Value) -> Result<(), PiControlError> {
+        let name = name.to_string();
+        self.spawn(move |pi| pi.set_value(&name, value)).await
+    }
+
+    /// Async version of [`PiControlRaw::try_wait_for_event`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the [`PiControl`] uses
+    /// a custom backend, see
+    /// [`PiControlBuilder::backend`](super::PiControlBuilder::backend), and
+    /// the error of [`PiControlRaw::try_wait_for_event`].
+    pub async fn wait_for_event(&self) -> Result<Event, PiControlError> {
+        let events = self
+            .events
+            .clone()
+            .ok_or(PiControlError::InvalidArgument("backend"))?;
+        task::spawn_blocking(move || events.try_wait_for_event())
+            .await
+            .map_err(join_error)?
+    }
+}
+
+impl TryFrom<PiControl> for AsyncPiControl {
+    type Error = PiControlError;
+
+    /// Wraps `pi` and opens a separate file descriptor for waiting for events
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file descriptor for events
+    /// can't be opened.
+    fn try_from(pi: PiControl) -> Result<Self, Self::Error> {
+        let events = match &pi.shared.path {
+            Some(path) => Some(Arc::new(PiControlRaw::open(path)?)),
+            None => None,
+        };
+        Ok(Self {
+            pi: Arc::new(pi),
+            events,
+        })
+    }
+}
+
+// panics are passed on like they would be in the blocking api
+fn join_error(e: JoinError) -> PiControlError {
+    match e.try_into_panic() {
+        Ok(payload) => panic::resume_unwind(payload),
+        Err(e) => io::Error::other(e).into(),
+    }
+}
@@ -0,0 +1,391 @@
+//! Exchangeable access to the processimage
+//!
+//! [`PiControl`](super::PiControl) doesn't talk to the driver directly, but
+//! through a [`Backend`]. Usually this is [`PiControlRaw`], but other backends
+//! can be set with [`PiControlBuilder::backend`](super::PiControlBuilder::backend),
+//! e.g. a [`FaultInjector`] to test the error handling of an application:
+//! ```no_run
+//! use revpi::picontrol::{backend::FaultInjector, raw::PiControlRaw, PiControl};
+//! use std::sync::Arc;
+//!
+//! let faults = Arc::new(FaultInjector::new(PiControlRaw::new().unwrap()));
+//! let pi = PiControl::builder().backend(faults.clone()).build().unwrap();
+//! // every get_value is a lookup and a read, let the second read fail
+//! faults.fail_nth(4, libc::EIO);
+//! assert!(pi.get_value("RevPiLED").is_ok());
+//! assert!(pi.get_value("RevPiLED").is_err());
+//! ```
+//!
+//! Without a RevPi, a [`MockBackend`] simulates the processimage in memory.
+//! With the `simulation` feature, a `FileBackend` keeps it in a plain file
+//! instead, which other processes can access as well.
+//!
+//! A [`MappedBackend`] maps the processimage into memory, so reading and
+//! writing bytes doesn't need a syscall.
+
+#[cfg(feature = "simulation")]
+mod file;
+mod mapped;
+mod mock;
+
+#[cfg(feature = "simulation")]
+pub(crate) use self::file::simulation;
+#[cfg(feature = "simulation")]
+pub use self::file::{FileBackend, SIMULATION_IMAGE};
+pub use self::mapped::MappedBackend;
+pub use self::mock::MockBackend;
+use super::{
+    raw::{raw::SPIVariable, Bit, DeviceInfo, PiControlRaw},
+    PiControlError,
+};
+use std::{
+    collections::BTreeMap,
+    ffi::CStr,
+    fmt, io,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Operations [`PiControl`](super::PiControl) needs from the processimage
+///
+/// The methods mirror the ones of [`PiControlRaw`].
+pub trait Backend: fmt::Debug + Send + Sync {
+    /// See [`PiControlRaw::find_variable`]
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError>;
+
+    /// See [`PiControlRaw::get_bit`]
+    ///
+    /// # Safety
+    /// You have to ensure that `address` and `bit` are valid and point to the
+    /// right value, otherwise you might get something unexpected.
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError>;
+
+    /// See [`PiControlRaw::set_bit`]
+    ///
+    /// # Safety
+    /// You have to ensure that `address` and `bit` are valid and point to the
+    /// right value, otherwise you might overwrite something else.
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError>;
+
+    /// Reads `bytes.len()` bytes starting at `address`.
+    ///
+    /// # Safety
+    /// You have to ensure that `address` is valid and points to the right
+    /// value, otherwise you might get something unexpected.
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError>;
+
+    /// Writes `bytes` starting at `address`.
+    ///
+    /// # Safety
+    /// You have to ensure that `address` is valid and points to the right
+    /// value, otherwise you might overwrite something else.
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError>;
+
+    /// See [`PiControlRaw::get_byte`]
+    ///
+    /// # Safety
+    /// See [`Backend::get_bytes`].
+    unsafe fn get_byte(&self, address: u16) -> Result<u8, PiControlError> {
+        let mut bytes = [0u8; 1];
+        self.get_bytes(address, &mut bytes)?;
+        Ok(bytes[0])
+    }
+
+    /// See [`PiControlRaw::get_word`]
+    ///
+    /// # Safety
+    /// See [`Backend::get_bytes`].
+    unsafe fn get_word(&self, address: u16) -> Result<u16, PiControlError> {
+        let mut bytes = [0u8; 2];
+        self.get_bytes(address, &mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    /// See [`PiControlRaw::get_dword`]
+    ///
+    /// # Safety
+    /// See [`Backend::get_bytes`].
+    unsafe fn get_dword(&self, address: u16) -> Result<u32, PiControlError> {
+        let mut bytes = [0u8; 4];
+        self.get_bytes(address, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// See [`PiControlRaw::set_byte`]
+    ///
+    /// # Safety
+    /// See [`Backend::set_bytes`].
+    unsafe fn set_byte(&self, address: u16, value: u8) -> Result<(), PiControlError> {
+        self.set_bytes(address, &[value])
+    }
+
+    /// See [`PiControlRaw::set_word`]
+    ///
+    /// # Safety
+    /// See [`Backend::set_bytes`].
+    unsafe fn set_word(&self, address: u16, value: u16) -> Result<(), PiControlError> {
+        self.set_bytes(address, &value.to_le_bytes())
+    }
+
+    /// See [`PiControlRaw::set_dword`]
+    ///
+    /// # Safety
+    /// See [`Backend::set_bytes`].
+    unsafe fn set_dword(&self, address: u16, value: u32) -> Result<(), PiControlError> {
+        self.set_bytes(address, &value.to_le_bytes())
+    }
+
+    /// See [`PiControlRaw::set_output_watchdog`]
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError>;
+
+    /// Resets the driver, returning [`PiControlError::Timeout`] if the
+    /// piBridge didn't come up in time.
+    ///
+    /// # Safety
+    /// See [`PiControlRaw::reset`].
+    unsafe fn reset(&self) -> Result<(), PiControlError>;
+
+    /// See [`PiControlRaw::bridge_running`]
+    fn bridge_running(&self) -> Result<bool, PiControlError>;
+
+    /// See [`PiControlRaw::get_last_message`]. `None` by default, for
+    /// backends without messages.
+    fn last_message(&self) -> Option<String> {
+        None
+    }
+
+    /// See [`PiControlRaw::get_device_info_list`]. Returns
+    /// [`PiControlError::NotSupportedOnThisModel`] by default, for backends
+    /// without devices.
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        Err(PiControlError::NotSupportedOnThisModel)
+    }
+}
+
+impl Backend for PiControlRaw {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        PiControlRaw::find_variable(self, name)
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        PiControlRaw::get_bit(self, address, bit)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        PiControlRaw::set_bit(self, address, bit, value)
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        PiControlRaw::get_bytes(self, address, bytes)
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        PiControlRaw::set_bytes(self, address, bytes)
+    }
+
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+        PiControlRaw::set_output_watchdog(self, millis)
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        PiControlRaw::reset(self)
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        PiControlRaw::bridge_running(self)
+    }
+
+    fn last_message(&self) -> Option<String> {
+        let message = self.get_last_message().ok()?;
+        Some(message.to_string_lossy().into_owned())
+    }
+
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        self.get_device_info_list()
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Arc<B> {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        (**self).find_variable(name)
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        (**self).get_bit(address, bit)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        (**self).set_bit(address, bit, value)
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        (**self).get_bytes(address, bytes)
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        (**self).set_bytes(address, bytes)
+    }
+
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+        (**self).set_output_watchdog(millis)
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        (**self).reset()
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        (**self).bridge_running()
+    }
+
+    fn last_message(&self) -> Option<String> {
+        (**self).last_message()
+    }
+
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        (**self).device_info_list()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    calls: u64,
+    // call number -> errno
+    fail_at: BTreeMap<u64, i32>,
+    delay: Duration,
+    bridge_down: bool,
+}
+
+/// [`Backend`] wrapper that injects faults into the calls to another backend
+///
+/// Every method of [`Backend`] counts as one call, since each of them is one
+/// ioctl or read/write on the real driver. Faults can be programmed while the
+/// backend is in use, so it is usually shared through an [`Arc`], see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct FaultInjector<B> {
+    inner: B,
+    faults: Mutex<Faults>,
+}
+
+impl<B: Backend> FaultInjector<B> {
+    /// Wraps `inner` without injecting any faults yet
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            faults: Mutex::new(Faults::default()),
+        }
+    }
+
+    fn faults(&self) -> std::sync::MutexGuard<'_, Faults> {
+        // the faults stay consistent even if a thread panicked
+        self.faults.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Lets the `n`th call from now on fail with `errno`, `1` being the next
+    /// call. The call doesn't reach the wrapped backend.
+    pub fn fail_nth(&self, n: u64, errno: i32) {
+        let mut faults = self.faults();
+        let at = faults.calls + n;
+        faults.fail_at.insert(at, errno);
+    }
+
+    /// Delays every following call by `delay`, [`Duration::ZERO`] stops the
+    /// delays.
+    pub fn delay(&self, delay: Duration) {
+        self.faults().delay = delay;
+    }
+
+    /// Simulates a stopped piBridge: while `down` is set, every access to a
+    /// value fails with [`PiControlError::BridgeNotRunning`], like it does
+    /// with the driver.
+    pub fn bridge_down(&self, down: bool) {
+        self.faults().bridge_down = down;
+    }
+
+    /// Returns the number of calls so far, including failed ones
+    pub fn calls(&self) -> u64 {
+        self.faults().calls
+    }
+
+    /// Returns the wrapped backend
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    // counts the call and returns the injected fault, if any
+    fn call(&self, value_access: bool) -> Result<(), PiControlError> {
+        let (delay, errno) = {
+            let mut faults = self.faults();
+            faults.calls += 1;
+            let calls = faults.calls;
+            let errno = faults
+                .fail_at
+                .remove(&calls)
+                .or_else(|| (value_access && faults.bridge_down).then_some(libc::EFAULT));
+            (faults.delay, errno)
+        };
+        // sleep without holding the lock, so faults can still be programmed
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+        match errno {
+            // mapped like the driver backend does for value accesses
+            Some(libc::EFAULT) if value_access => Err(PiControlError::BridgeNotRunning),
+            Some(errno) => Err(io::Error::from_raw_os_error(errno).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<B: Backend> Backend for FaultInjector<B> {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        self.call(false)?;
+        self.inner.find_variable(name)
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        self.call(true)?;
+        self.inner.get_bit(address, bit)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        self.call(true)?;
+        self.inner.set_bit(address, bit, value)
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        self.call(true)?;
+        self.inner.get_bytes(address, bytes)
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        self.call(true)?;
+        self.inner.set_bytes(address, bytes)
+    }
+
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+        self.call(false)?;
+        self.inner.set_output_watchdog(millis)
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        self.call(false)?;
+        self.inner.reset()
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        self.call(false)?;
+        Ok(!self.faults().bridge_down && self.inner.bridge_running()?)
+    }
+
+    fn last_message(&self) -> Option<String> {
+        self.inner.last_message()
+    }
+
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        self.call(false)?;
+        self.inner.device_info_list()
+    }
+}
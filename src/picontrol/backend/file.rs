@@ -0,0 +1,181 @@
+//! Processimage in a plain file, for simulating a RevPi
+
+use super::Backend;
+use crate::{
+    picontrol::{
+        raw::{
+            raw::{SPIVariable, KB_PI_LEN, PICONFIG_FILE, PICONTROL_DEVICE},
+            Bit,
+        },
+        NameTable, PiControlError,
+    },
+    rsc::{Limits, RSC},
+    util::ensure,
+};
+use std::{
+    ffi::CStr,
+    fs::{self, File, OpenOptions},
+    io,
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::Mutex,
+};
+
+/// Location of the simulated processimage used by
+/// [`PiControl::new`](crate::picontrol::PiControl::new) if there is no driver
+pub const SIMULATION_IMAGE: &str = "/tmp/piControl.sim";
+
+/// [`Backend`] simulating the driver with a processimage in a plain file
+///
+/// The file is created if it doesn't exist and extended to the size of the
+/// processimage, so other processes, e.g. a simulation of the machine, can
+/// read and write it at the same offsets as the real processimage. Names are
+/// taken from a config, since there is no driver to ask.
+///
+/// With the `simulation` feature, [`PiControlBuilder::build`](crate::picontrol::PiControlBuilder::build)
+/// uses this backend if the path is a plain file, or if the default path
+/// doesn't exist, in which case [`SIMULATION_IMAGE`] and the config at
+/// [`PICONFIG_FILE`](crate::picontrol::raw::raw::PICONFIG_FILE), if any, are
+/// used.
+///
+/// # Example
+/// ```no_run
+/// use revpi::picontrol::{backend::FileBackend, PiControl, Value};
+/// use revpi::rsc::RSC;
+/// use std::fs::File;
+///
+/// let rsc: RSC = serde_json::from_reader(File::open("config.rsc").unwrap()).unwrap();
+/// let sim = FileBackend::open("/tmp/piControl.sim", &rsc).unwrap();
+/// let pi = PiControl::builder().backend(sim).build().unwrap();
+/// pi.set_value("RevPiLED", Value::Byte(1)).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FileBackend {
+    file: File,
+    names: NameTable,
+    // serializes the read-modify-write of bits within this process
+    bits: Mutex<()>,
+}
+
+impl FileBackend {
+    /// Opens or creates the processimage at `path` with the variables
+    /// configured in `rsc`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file can't be opened or
+    /// extended and a [`PiControlError::InvalidArgument`] if a variable of
+    /// `rsc` lies outside of the processimage.
+    pub fn open<P: AsRef<Path>>(path: P, rsc: &RSC) -> Result<Self, PiControlError> {
+        Self::with_names(path, NameTable::from_rsc(rsc)?)
+    }
+
+    pub(crate) fn with_names<P: AsRef<Path>>(
+        path: P,
+        names: NameTable,
+    ) -> Result<Self, PiControlError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() < KB_PI_LEN as u64 {
+            file.set_len(KB_PI_LEN as u64)?;
+        }
+        Ok(Self {
+            file,
+            names,
+            bits: Mutex::new(()),
+        })
+    }
+}
+
+// the simulation to use instead of the driver at `path`, if any
+pub(crate) fn simulation(path: &Path) -> Option<Result<FileBackend, PiControlError>> {
+    let path = match fs::metadata(path) {
+        Ok(meta) if meta.is_file() => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && path == Path::new(PICONTROL_DEVICE) => {
+            Path::new(SIMULATION_IMAGE)
+        }
+        _ => return None,
+    };
+    let names = match File::open(PICONFIG_FILE) {
+        Ok(f) => RSC::from_reader_with_limits(f, &Limits::default())
+            .map_err(PiControlError::from)
+            .and_then(|rsc| NameTable::from_rsc(&rsc)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(NameTable::default()),
+        Err(e) => Err(e.into()),
+    };
+    Some(names.and_then(|names| FileBackend::with_names(path, names)))
+}
+
+fn check(address: u16, len: usize) -> Result<(), PiControlError> {
+    ensure!(
+        address as usize + len <= KB_PI_LEN,
+        PiControlError::InvalidArgument("address")
+    );
+    Ok(())
+}
+
+impl Backend for FileBackend {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        ensure!(!self.names.is_empty(), PiControlError::NoVarEntries);
+        let bytes = name.to_bytes();
+        let (address, bit, length) = name
+            .to_str()
+            .ok()
+            .and_then(|name| self.names.get(name))
+            .ok_or(PiControlError::InvalidArgument("name"))?;
+        let mut var = SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        var.strVarName[..bytes.len()].copy_from_slice(bytes);
+        Ok(var)
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        let mut byte = [0];
+        self.get_bytes(address, &mut byte)?;
+        Ok((byte[0] >> bit as u8) & 1 == 1)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        let _guard = self.bits.lock().unwrap_or_else(|e| e.into_inner());
+        let mut byte = [0];
+        self.get_bytes(address, &mut byte)?;
+        match value {
+            true => byte[0] |= 1 << bit as u8,
+            false => byte[0] &= !(1 << bit as u8),
+        }
+        self.set_bytes(address, &byte)
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        check(address, bytes.len())?;
+        self.file
+            .read_exact_at(bytes, address as u64)
+            .map_err(PiControlError::from)
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        check(address, bytes.len())?;
+        self.file
+            .write_all_at(bytes, address as u64)
+            .map_err(PiControlError::from)
+    }
+
+    fn set_output_watchdog(&self, _millis: u32) -> Result<(), PiControlError> {
+        Ok(())
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        Ok(())
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        Ok(true)
+    }
+}
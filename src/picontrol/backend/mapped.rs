@@ -0,0 +1,181 @@
+//! Processimage mapped into memory
+
+use super::Backend;
+use crate::{
+    picontrol::{
+        raw::{
+            raw::{SPIVariable, KB_PI_LEN},
+            Bit, DeviceInfo, PiControlRaw,
+        },
+        PiControlError,
+    },
+    util::ensure,
+};
+use std::{ffi::CStr, fmt, os::unix::io::AsRawFd, path::Path, ptr::NonNull};
+
+/// [`Backend`] accessing the processimage through a memory mapping
+///
+/// Every access through [`PiControlRaw`] is a syscall, which adds up in fast
+/// control loops. This backend maps the processimage once and reads and
+/// writes it with volatile accesses instead. If the driver doesn't support
+/// mapping, it falls back to [`PiControlRaw`], see
+/// [`is_mapped`](Self::is_mapped). Everything except reading and writing
+/// bytes is always passed on to [`PiControlRaw`], including
+/// [`set_bit`](Backend::set_bit), since the driver changes single bits
+/// atomically, which a read-modify-write of the mapping can't.
+///
+/// Unlike a read through the driver, a mapped read of several bytes isn't
+/// atomic, so it may see the bytes of two different piBridge cycles.
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MappedBackend, raw::{raw::KB_PI_LEN, PiControlRaw}, PiControl};
+///
+/// // a plain file can be mapped just like the driver
+/// let path = std::env::temp_dir().join("revpi-mapped-doctest");
+/// std::fs::write(&path, vec![0u8; KB_PI_LEN]).unwrap();
+/// let mapped = MappedBackend::new(PiControlRaw::open(&path).unwrap());
+/// assert!(mapped.is_mapped());
+/// let pi = PiControl::builder().backend(mapped).build().unwrap();
+/// unsafe { pi.write_region(10, &[1, 2]) }.unwrap();
+/// assert_eq!(std::fs::read(&path).unwrap()[10..12], [1, 2]);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct MappedBackend {
+    raw: PiControlRaw,
+    map: Option<NonNull<u8>>,
+}
+
+// the mapping is only accessed through volatile reads and writes of single
+// bytes, like the driver's read and write, and unmapped on drop
+unsafe impl Send for MappedBackend {}
+unsafe impl Sync for MappedBackend {}
+
+impl fmt::Debug for MappedBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MappedBackend")
+            .field("raw", &self.raw)
+            .field("mapped", &self.is_mapped())
+            .finish()
+    }
+}
+
+impl MappedBackend {
+    /// Maps the processimage of `raw`, falling back to `raw` itself if that
+    /// fails
+    pub fn new(raw: PiControlRaw) -> Self {
+        let map = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                KB_PI_LEN,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                raw.as_raw_fd(),
+                0,
+            )
+        };
+        let map = match map {
+            libc::MAP_FAILED => None,
+            map => NonNull::new(map as *mut u8),
+        };
+        Self { raw, map }
+    }
+
+    /// Opens the driver at `path` and maps its processimage, see
+    /// [`MappedBackend::new`]
+    ///
+    /// # Errors
+    /// Same as [`PiControlRaw::open`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PiControlError> {
+        Ok(Self::new(PiControlRaw::open(path)?))
+    }
+
+    /// Returns whether the processimage is mapped, otherwise every access is
+    /// a syscall
+    pub fn is_mapped(&self) -> bool {
+        self.map.is_some()
+    }
+
+    /// Returns the underlying driver
+    pub fn raw(&self) -> &PiControlRaw {
+        &self.raw
+    }
+}
+
+impl Drop for MappedBackend {
+    fn drop(&mut self) {
+        if let Some(map) = self.map {
+            unsafe { libc::munmap(map.as_ptr() as *mut libc::c_void, KB_PI_LEN) };
+        }
+    }
+}
+
+fn check(address: u16, len: usize) -> Result<(), PiControlError> {
+    ensure!(
+        address as usize + len <= KB_PI_LEN,
+        PiControlError::InvalidArgument("address")
+    );
+    Ok(())
+}
+
+impl Backend for MappedBackend {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        self.raw.find_variable(name)
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        let mut byte = [0];
+        self.get_bytes(address, &mut byte)?;
+        Ok((byte[0] >> bit as u8) & 1 == 1)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        self.raw.set_bit(address, bit, value)
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        let map = match self.map {
+            Some(map) => map,
+            None => return self.raw.get_bytes(address, bytes),
+        };
+        check(address, bytes.len())?;
+        let src = map.as_ptr().add(address as usize);
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = src.add(i).read_volatile();
+        }
+        Ok(())
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        let map = match self.map {
+            Some(map) => map,
+            None => return self.raw.set_bytes(address, bytes),
+        };
+        check(address, bytes.len())?;
+        let dst = map.as_ptr().add(address as usize);
+        for (i, byte) in bytes.iter().enumerate() {
+            dst.add(i).write_volatile(*byte);
+        }
+        Ok(())
+    }
+
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+        Backend::set_output_watchdog(&self.raw, millis)
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        self.raw.reset()
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        self.raw.bridge_running()
+    }
+
+    fn last_message(&self) -> Option<String> {
+        Backend::last_message(&self.raw)
+    }
+
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        self.raw.get_device_info_list()
+    }
+}
@@ -0,0 +1,236 @@
+//! In-memory processimage
+
+use super::Backend;
+use crate::{
+    picontrol::{
+        raw::{
+            raw::{SDeviceInfo, SPIVariable, KB_PI_LEN},
+            Bit, DeviceInfo,
+        },
+        PiControlError,
+    },
+    util::ensure,
+};
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    sync::{Mutex, MutexGuard},
+};
+
+#[derive(Debug)]
+struct State {
+    image: [u8; KB_PI_LEN],
+    watchdog_ms: u32,
+    resets: u64,
+    message: String,
+}
+
+/// [`Backend`] simulating the driver with an in-memory processimage
+///
+/// The processimage starts out zeroed and only knows the variables it was
+/// given, so application logic can be tested without a RevPi. Inputs are
+/// simulated by writing to the image with [`write`](Self::write) while the
+/// backend is in use, so it is usually shared through an
+/// [`Arc`](std::sync::Arc).
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(
+///     MockBackend::new()
+///         .variable("Input", 0, 0, 1)
+///         .variable("RevPiLED", 6, 0, 8),
+/// );
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// mock.write(0, &[1]).unwrap();
+/// assert_eq!(pi.get_value("Input").unwrap(), Value::Bit(true));
+/// pi.set_value("RevPiLED", Value::Byte(3)).unwrap();
+/// assert_eq!(mock.read(6, 1).unwrap(), vec![3]);
+/// ```
+#[derive(Debug)]
+pub struct MockBackend {
+    variables: HashMap<String, SPIVariable>,
+    devices: Vec<DeviceInfo>,
+    state: Mutex<State>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self {
+            variables: HashMap::new(),
+            devices: Vec::new(),
+            state: Mutex::new(State {
+                image: [0; KB_PI_LEN],
+                watchdog_ms: 0,
+                resets: 0,
+                message: String::new(),
+            }),
+        }
+    }
+}
+
+impl MockBackend {
+    /// Creates a zeroed processimage without any variables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a zeroed processimage with all variables configured in `rsc`
+    ///
+    /// # Errors
+    /// Same as [`NameTable::from_rsc`](crate::picontrol::NameTable::from_rsc)
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(rsc: &crate::rsc::RSC) -> Result<Self, PiControlError> {
+        let table = crate::picontrol::NameTable::from_rsc(rsc)?;
+        let mut mock = Self::new();
+        for name in table.names() {
+            // the name comes from the table itself
+            let (address, bit, length) = table.get(name).unwrap();
+            mock = mock.variable(name, address, bit, length);
+        }
+        Ok(mock)
+    }
+
+    /// Adds the variable `name` of `length` bits at `address` and `bit`, like
+    /// they are returned by [`PiControlRaw::find_variable`](crate::picontrol::raw::PiControlRaw::find_variable).
+    /// `bit` is only used if `length` is `1`.
+    ///
+    /// # Panics
+    /// Will panic if `name` is longer than 31 bytes, since the driver couldn't
+    /// find it either.
+    pub fn variable(mut self, name: &str, address: u16, bit: u8, length: u16) -> Self {
+        assert!(name.len() < 32, "name of variable too long");
+        let mut var = SPIVariable {
+            i16uAddress: address,
+            i8uBit: bit,
+            i16uLength: length,
+            ..Default::default()
+        };
+        var.strVarName[..name.len()].copy_from_slice(name.as_bytes());
+        self.variables.insert(name.to_string(), var);
+        self
+    }
+
+    /// Adds a device, which is returned by
+    /// [`Backend::device_info_list`] in the order the devices were added
+    pub fn device(mut self, device: SDeviceInfo) -> Self {
+        self.devices.push(device.into());
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // the image stays consistent even if a thread panicked
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Reads `len` bytes of the processimage starting at `address`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the processimage.
+    pub fn read(&self, address: u16, len: usize) -> Result<Vec<u8>, PiControlError> {
+        let mut bytes = vec![0; len];
+        // the mock has no unsafety, the trait just mirrors the driver
+        unsafe { self.get_bytes(address, &mut bytes) }?;
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` to the processimage starting at `address`, e.g. to
+    /// simulate inputs.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the processimage.
+    pub fn write(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        // the mock has no unsafety, the trait just mirrors the driver
+        unsafe { self.set_bytes(address, bytes) }
+    }
+
+    /// Returns the period of the output watchdog that was set last, `0` if it
+    /// is deactivated
+    pub fn watchdog_ms(&self) -> u32 {
+        self.state().watchdog_ms
+    }
+
+    /// Returns how often the driver was reset
+    pub fn resets(&self) -> u64 {
+        self.state().resets
+    }
+
+    /// Sets the message returned as last message of the driver, e.g. to
+    /// simulate a driver error
+    pub fn set_message(&self, message: &str) {
+        self.state().message = message.to_string();
+    }
+}
+
+fn range(address: u16, len: usize) -> Result<std::ops::Range<usize>, PiControlError> {
+    let address = address as usize;
+    ensure!(
+        address + len <= KB_PI_LEN,
+        PiControlError::InvalidArgument("address")
+    );
+    Ok(address..address + len)
+}
+
+impl Backend for MockBackend {
+    fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
+        ensure!(!self.variables.is_empty(), PiControlError::NoVarEntries);
+        name.to_str()
+            .ok()
+            .and_then(|name| self.variables.get(name))
+            .copied()
+            .ok_or(PiControlError::InvalidArgument("name"))
+    }
+
+    unsafe fn get_bit(&self, address: u16, bit: Bit) -> Result<bool, PiControlError> {
+        let range = range(address, 1)?;
+        Ok((self.state().image[range.start] >> bit as u8) & 1 == 1)
+    }
+
+    unsafe fn set_bit(&self, address: u16, bit: Bit, value: bool) -> Result<(), PiControlError> {
+        let range = range(address, 1)?;
+        let byte = &mut self.state().image[range.start];
+        match value {
+            true => *byte |= 1 << bit as u8,
+            false => *byte &= !(1 << bit as u8),
+        }
+        Ok(())
+    }
+
+    unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        let range = range(address, bytes.len())?;
+        bytes.copy_from_slice(&self.state().image[range]);
+        Ok(())
+    }
+
+    unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        let range = range(address, bytes.len())?;
+        self.state().image[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn set_output_watchdog(&self, millis: u32) -> Result<(), PiControlError> {
+        self.state().watchdog_ms = millis;
+        Ok(())
+    }
+
+    unsafe fn reset(&self) -> Result<(), PiControlError> {
+        self.state().resets += 1;
+        Ok(())
+    }
+
+    fn bridge_running(&self) -> Result<bool, PiControlError> {
+        Ok(true)
+    }
+
+    fn last_message(&self) -> Option<String> {
+        Some(self.state().message.clone())
+    }
+
+    fn device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
+        Ok(self.devices.clone())
+    }
+}
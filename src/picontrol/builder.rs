@@ -0,0 +1,255 @@
+//! Builder for [`PiControl`]
+
+#[cfg(feature = "events")]
+use super::events::{self, ResetHook};
+#[cfg(feature = "rsc")]
+use super::NameTable;
+use super::{
+    cache::Cache, raw::raw::PICONTROL_DEVICE, raw::PiControlRaw, Backend, MessageLog, PiControl,
+    PiControlError, RetryPolicy, Scaling, Shared, Value,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+};
+
+/// Configures and creates a [`PiControl`]
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::{PiControl, Value};
+/// use std::collections::BTreeMap;
+///
+/// let mut safe_state = BTreeMap::new();
+/// safe_state.insert("RevPiLED".to_string(), Value::Byte(0));
+/// let pi = PiControl::builder()
+///     .watchdog_ms(500)
+///     .safe_state(safe_state)
+///     .cache(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PiControlBuilder {
+    path: PathBuf,
+    backend: Option<Arc<dyn Backend>>,
+    watchdog_ms: u32,
+    safe_state: BTreeMap<String, Value>,
+    cache: Option<usize>,
+    aliases: HashMap<String, String>,
+    #[cfg(feature = "rsc")]
+    names: Option<NameTable>,
+    retry: RetryPolicy,
+    messages: Option<usize>,
+    scalings: HashMap<String, Scaling>,
+    #[cfg(feature = "events")]
+    watch_resets: bool,
+    #[cfg(feature = "events")]
+    on_reset: Option<ResetHook>,
+}
+
+impl Default for PiControlBuilder {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(PICONTROL_DEVICE),
+            backend: None,
+            watchdog_ms: 0,
+            safe_state: BTreeMap::new(),
+            cache: None,
+            aliases: HashMap::new(),
+            #[cfg(feature = "rsc")]
+            names: None,
+            retry: RetryPolicy::none(),
+            messages: None,
+            scalings: HashMap::new(),
+            #[cfg(feature = "events")]
+            watch_resets: false,
+            #[cfg(feature = "events")]
+            on_reset: None,
+        }
+    }
+}
+
+impl PiControlBuilder {
+    /// Creates a builder with the default options, same as
+    /// [`PiControl::builder`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the path of the piControl device, `"/dev/piControl0"` by default
+    ///
+    /// With the `simulation` feature, a plain file is opened as simulated
+    /// processimage instead, see `backend::FileBackend`.
+    pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Uses `backend` instead of opening the piControl device, see
+    /// [`backend`](super::backend). The path is ignored then.
+    ///
+    /// Driver resets can't be watched with a custom backend, so
+    /// [`PiControlBuilder::watch_resets`] has no effect.
+    pub fn backend<B: Backend + 'static>(mut self, backend: B) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
+    }
+
+    /// Activates the output watchdog with the given period, see
+    /// [`PiControlRaw::set_output_watchdog`]. `0`, the default, leaves the
+    /// watchdog deactivated.
+    pub fn watchdog_ms(mut self, millis: u32) -> Self {
+        self.watchdog_ms = millis;
+        self
+    }
+
+    /// Sets the values written by [`PiControl::apply_safe_state`], mapping the
+    /// names of variables to their safe value. Empty by default.
+    pub fn safe_state(mut self, safe_state: BTreeMap<String, Value>) -> Self {
+        self.safe_state = safe_state;
+        self
+    }
+
+    /// Enables caching of the name lookups, disabled by default.
+    ///
+    /// With the cache, each name is only looked up once. This is faster, but
+    /// the cache has to be invalidated with [`PiControl::invalidate_cache`]
+    /// when the driver gets reset. With the `events` feature, this can happen
+    /// automatically, see `watch_resets`.\
+    /// The cache isn't limited, see [`cache_capacity`](Self::cache_capacity)
+    /// for limiting it.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache.then_some(usize::MAX);
+        self
+    }
+
+    /// Enables caching of the name lookups like [`cache`](Self::cache), but
+    /// keeps at most `capacity` names. The least recently used name is
+    /// evicted first.
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Some(capacity);
+        self
+    }
+
+    /// Adds an alias, so `alias` can be used instead of `name` wherever
+    /// [`PiControl`] takes the name of a variable.
+    pub fn alias(mut self, alias: &str, name: &str) -> Self {
+        self.aliases.insert(alias.to_string(), name.to_string());
+        self
+    }
+
+    /// Sets the [`Scaling`] of the variable `name`, used by
+    /// [`PiControl::get_scaled`] and [`PiControl::set_scaled`]. A scaling
+    /// set before for the same name is replaced.
+    pub fn scaling(mut self, name: &str, scaling: Scaling) -> Self {
+        self.scalings.insert(name.to_string(), scaling);
+        self
+    }
+
+    /// Takes the names in `table` from it instead of asking the driver, see
+    /// [`NameTable`]. Aliases are resolved before the table is consulted.
+    #[cfg(feature = "rsc")]
+    pub fn name_table(mut self, table: NameTable) -> Self {
+        self.names = Some(table);
+        self
+    }
+
+    /// Sets how calls that failed because the piBridge wasn't running or the
+    /// driver timed out are retried, see [`RetryPolicy`]. By default they
+    /// aren't retried.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Keeps the last `capacity` messages of the driver, see [`MessageLog`]
+    /// and [`PiControl::messages`]. Disabled by default.
+    pub fn message_log(mut self, capacity: usize) -> Self {
+        self.messages = Some(capacity);
+        self
+    }
+
+    /// Enables or disables handling of driver resets, disabled by default.
+    ///
+    /// If enabled, a thread waits for [`Event::Reset`](super::raw::raw::Event::Reset)
+    /// on a separate file descriptor. After every reset, it invalidates the
+    /// cache, reactivates the watchdog, applies the safe state, records the
+    /// message of the driver if there is a [`message_log`](Self::message_log)
+    /// and calls the hook given to [`PiControlBuilder::on_reset`].\
+    /// The thread blocks in the driver, so it only notices that the
+    /// [`PiControl`] and all handles created from it with
+    /// [`PiControl::try_clone`] were dropped at the next reset. Until then,
+    /// it and its file descriptor are kept, so this is meant for a
+    /// [`PiControl`] living as long as the application. Drivers without
+    /// events stop the thread right away, see [`PiControlRaw::capabilities`].
+    #[cfg(feature = "events")]
+    pub fn watch_resets(mut self, watch: bool) -> Self {
+        self.watch_resets = watch;
+        self
+    }
+
+    /// Sets a hook that is called after a driver reset was handled, see
+    /// [`PiControlBuilder::watch_resets`], which has to be enabled for it. The
+    /// hook gets the result of applying the safe state.
+    #[cfg(feature = "events")]
+    pub fn on_reset<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(Result<(), PiControlError>) + Send + 'static,
+    {
+        self.on_reset = Some(ResetHook(Box::new(hook)));
+        self
+    }
+
+    /// Creates the [`PiControl`]
+    ///
+    /// # Errors
+    /// Will return a [`PiControlError::PermissionDenied`] or
+    /// [`PiControlError::IoError`] if the processimage can't be opened or the
+    /// error of the backend if the watchdog can't be activated
+    pub fn build(self) -> Result<PiControl, PiControlError> {
+        // custom backends can't be reopened, see PiControl::try_clone
+        let (inner, path) = match self.backend {
+            Some(backend) => (backend, None),
+            None => open(self.path)?,
+        };
+        if self.watchdog_ms != 0 {
+            inner.set_output_watchdog(self.watchdog_ms)?;
+        }
+        let pi = PiControl {
+            inner,
+            shared: Arc::new(Shared {
+                path,
+                #[cfg(any(feature = "events", feature = "rsc"))]
+                watchdog_ms: self.watchdog_ms,
+                safe_state: Mutex::new(self.safe_state),
+                cache: self.cache.map(|capacity| Mutex::new(Cache::new(capacity))),
+                generation: AtomicU64::new(0),
+                aliases: self.aliases,
+                #[cfg(feature = "rsc")]
+                names: self.names.map(std::sync::RwLock::new),
+                retry: self.retry,
+                messages: self
+                    .messages
+                    .map(|capacity| Mutex::new(MessageLog::new(capacity))),
+                scalings: self.scalings,
+            }),
+        };
+        #[cfg(feature = "events")]
+        if self.watch_resets {
+            events::watch_resets(&pi, self.on_reset)?;
+        }
+        Ok(pi)
+    }
+}
+
+// opens the driver at `path`, or a simulation of it
+fn open(path: PathBuf) -> Result<(Arc<dyn Backend>, Option<PathBuf>), PiControlError> {
+    #[cfg(feature = "simulation")]
+    if let Some(simulation) = super::backend::simulation(&path) {
+        // like custom backends, simulations aren't reopened
+        return Ok((Arc::new(simulation?), None));
+    }
+    Ok((Arc::new(PiControlRaw::open(&path)?), Some(path)))
+}
@@ -0,0 +1,99 @@
+//! Cache of name lookups
+
+use super::Var;
+use std::collections::HashMap;
+
+// least recently used entries are evicted once `capacity` is reached
+#[derive(Debug)]
+pub(crate) struct Cache {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (Var, u64)>,
+}
+
+impl Cache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, name: &str) -> Option<Var> {
+        self.tick += 1;
+        let (var, used) = self.entries.get_mut(name)?;
+        *used = self.tick;
+        Some(*var)
+    }
+
+    pub(crate) fn insert(&mut self, name: String, var: Var) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&name) {
+            // linear, but only on misses of a full cache
+            let oldest = self.entries.iter().min_by_key(|(_, (_, used))| *used);
+            if let Some(oldest) = oldest.map(|(name, _)| name.clone()) {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(name, (var, self.tick));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // names in the order they were last used
+    #[cfg(feature = "rsc")]
+    pub(crate) fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.entries.iter().collect();
+        names.sort_by_key(|(_, (_, used))| *used);
+        names.into_iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(address: u16) -> Var {
+        Var {
+            address,
+            bit: 0,
+            length: 8,
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = Cache::new(2);
+        cache.insert("a".to_string(), var(0));
+        cache.insert("b".to_string(), var(1));
+        // a is used more recently than b now
+        assert_eq!(cache.get("a"), Some(var(0)));
+        cache.insert("c".to_string(), var(2));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(var(0)));
+        assert_eq!(cache.get("c"), Some(var(2)));
+    }
+
+    #[test]
+    fn replaces_without_eviction() {
+        let mut cache = Cache::new(2);
+        cache.insert("a".to_string(), var(0));
+        cache.insert("b".to_string(), var(1));
+        cache.insert("a".to_string(), var(3));
+        assert_eq!(cache.get("a"), Some(var(3)));
+        assert_eq!(cache.get("b"), Some(var(1)));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let mut cache = Cache::new(0);
+        cache.insert("a".to_string(), var(0));
+        assert_eq!(cache.get("a"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Handling of driver resets
+
+use super::{
+    raw::{raw::Event, PiControlRaw},
+    PiControl, PiControlError,
+};
+use std::{fmt, sync::Arc, thread};
+
+/// Hook called after a driver reset was handled, see
+/// [`PiControlBuilder::on_reset`](super::PiControlBuilder::on_reset)
+pub struct ResetHook(pub Box<dyn FnMut(Result<(), PiControlError>) + Send>);
+
+impl fmt::Debug for ResetHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResetHook")
+    }
+}
+
+// waits for resets on its own file descriptor, so value access on the
+// PiControl isn't blocked, and handles them through it. Waiting can't be
+// interrupted, so the thread only notices at the next reset that the state
+// shared by `pi` and its clones was dropped, and keeps the file descriptor
+// open until then. Only the watchdog is re-armed on the file descriptor of
+// `pi`, as long as that one is open, since dropping it is what stops the
+// watchdog. Drivers without events simply aren't watched.
+pub(crate) fn watch_resets(
+    pi: &PiControl,
+    mut hook: Option<ResetHook>,
+) -> Result<(), PiControlError> {
+    // resets of custom backends can't be watched
+    let events = match &pi.shared.path {
+        Some(path) => Arc::new(PiControlRaw::open(path)?),
+        None => return Ok(()),
+    };
+    let watchdog = Arc::downgrade(&pi.inner);
+    let shared = Arc::downgrade(&pi.shared);
+    thread::spawn(move || loop {
+        match events.try_wait_for_event() {
+            Ok(Event::Reset) => (),
+            Err(_) => return,
+        }
+        let pi = match shared.upgrade() {
+            Some(shared) => PiControl {
+                inner: events.clone(),
+                shared,
+            },
+            None => return,
+        };
+        pi.invalidate_cache();
+        let mut result = Ok(());
+        if pi.shared.watchdog_ms != 0 {
+            if let Some(inner) = watchdog.upgrade() {
+                result = inner.set_output_watchdog(pi.shared.watchdog_ms);
+            }
+        }
+        // the safe state is applied even if the watchdog failed
+        result = pi.apply_safe_state().and(result);
+        // catches the reason of the reset before the next message replaces it
+        if let Some(log) = &pi.shared.messages {
+            log.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .poll(&*pi.inner);
+        }
+        if let Some(hook) = hook.as_mut() {
+            (hook.0)(result);
+        }
+    });
+    Ok(())
+}
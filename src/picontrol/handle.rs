@@ -0,0 +1,141 @@
+//! Handles to single variables
+
+use super::{PiControl, PiControlError, Value, Var};
+use std::{cell::Cell, sync::atomic::Ordering};
+
+/// Handle to a single variable, returned by [`PiControl::variable`]
+///
+/// The name is only looked up once, so accessing the value is as fast as if
+/// the address was known beforehand. If the cache of the [`PiControl`] gets
+/// invalidated, e.g. after a driver reset, the name is looked up again on the
+/// next access.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::{PiControl, Value};
+/// let pi = PiControl::new().unwrap();
+/// let led = pi.variable("RevPiLED").unwrap();
+/// for i in 0..=255 {
+///     led.set(Value::Byte(i)).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct VariableHandle<'a> {
+    pi: &'a PiControl,
+    name: String,
+    var: Cell<Var>,
+    generation: Cell<u64>,
+}
+
+impl<'a> VariableHandle<'a> {
+    pub(crate) fn new(pi: &'a PiControl, name: &str) -> Result<Self, PiControlError> {
+        // loaded before the lookup, so an invalidation in between is noticed
+        let generation = pi.shared.generation.load(Ordering::Acquire);
+        Ok(Self {
+            pi,
+            name: name.to_string(),
+            var: Cell::new(pi.find_variable(name)?),
+            generation: Cell::new(generation),
+        })
+    }
+
+    // looks the name up again if the cache was invalidated since the last time
+    fn var(&self) -> Result<Var, PiControlError> {
+        let generation = self.pi.shared.generation.load(Ordering::Acquire);
+        if generation != self.generation.get() {
+            self.var.set(self.pi.find_variable(&self.name)?);
+            self.generation.set(generation);
+        }
+        Ok(self.var.get())
+    }
+
+    /// Returns the name of the variable
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the length of the variable in bits
+    pub fn bitcnt(&self) -> Result<usize, PiControlError> {
+        Ok(self.var()?.length as usize)
+    }
+
+    /// Gets the value of the variable, see [`PiControl::get_value`]
+    pub fn get(&self) -> Result<Value, PiControlError> {
+        self.pi.get_var(self.var()?)
+    }
+
+    /// Sets the value of the variable, see [`PiControl::set_value`]
+    pub fn set(&self, value: Value) -> Result<(), PiControlError> {
+        self.pi.set_var(self.var()?, value)
+    }
+}
+
+impl PiControl {
+    /// Returns a [`VariableHandle`] to the variable `name`, which can be
+    /// accessed without looking up the name every time.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the name can't be
+    /// found.
+    pub fn variable(&self, name: &str) -> Result<VariableHandle<'_>, PiControlError> {
+        VariableHandle::new(self, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::MockBackend;
+    use std::sync::Arc;
+
+    #[test]
+    fn lookup_after_invalidation() {
+        let mock = Arc::new(MockBackend::new().variable("a", 4, 0, 8));
+        let pi = PiControl::builder()
+            .backend(mock.clone())
+            .cache(true)
+            .build()
+            .unwrap();
+        let handle = pi.variable("a").unwrap();
+        handle.set(Value::Byte(1)).unwrap();
+        pi.invalidate_cache();
+        handle.set(Value::Byte(2)).unwrap();
+        assert_eq!(mock.read(4, 1).unwrap(), vec![2]);
+        assert!(pi.variable("b").is_err());
+    }
+
+    #[cfg(feature = "rsc")]
+    #[test]
+    fn stale_handle() {
+        use crate::picontrol::NameTable;
+        use crate::rsc::{DeviceBuilder, DeviceKind, InOutMemBuilder, ProductType, RscBuilder};
+
+        let config = |names: &[&str]| {
+            let mut device =
+                DeviceBuilder::new(DeviceKind::Virtual, ProductType::Unknown(102), "V");
+            for name in names {
+                device = device.memory(InOutMemBuilder::new(*name, 8));
+            }
+            RscBuilder::new().device(device).build().unwrap()
+        };
+        let old = config(&["a", "b"]);
+        // the variables of the config only come from the table
+        let mock = Arc::new(MockBackend::new().variable("other", 100, 0, 8));
+        let pi = PiControl::builder()
+            .backend(mock.clone())
+            .name_table(NameTable::from_rsc(&old).unwrap())
+            .build()
+            .unwrap();
+        let a = pi.variable("a").unwrap();
+        let b = pi.variable("b").unwrap();
+        let dir = std::env::temp_dir().join(format!("revpi_handle_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // a is gone and b moves to its address
+        pi.apply_new_config(&config(&["b"]), dir.join("config.rsc"))
+            .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(matches!(a.get(), Err(PiControlError::InvalidArgument(_))));
+        b.set(Value::Byte(5)).unwrap();
+        assert_eq!(mock.read(0, 1).unwrap(), vec![5]);
+    }
+}
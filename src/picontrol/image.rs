@@ -0,0 +1,225 @@
+//! Snapshots of the processimage region of single devices
+
+use super::{
+    raw::{raw::KB_PI_LEN, Bit},
+    PiControl, PiControlError, Value,
+};
+use crate::rsc::{Device, InOutMem, RSC};
+use crate::util::ensure;
+use std::collections::BTreeMap;
+
+/// Snapshot of the processimage region of one device
+///
+/// The region is read at once, so all values of the snapshot are consistent
+/// with each other. The variables of the device, as configured in the RSC
+/// file, can then be looked up by name.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::PiControl;
+/// # use revpi::rsc::RSC;
+/// # use std::fs::File;
+/// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+/// let pi = PiControl::new().unwrap();
+/// let image = pi.read_device_image(32, &rsc).unwrap();
+/// for (name, value) in image.values() {
+///     println!("{}: {:?}", name, value);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceImage {
+    position: u8,
+    offset: u16,
+    bytes: Vec<u8>,
+    variables: BTreeMap<String, InOutMem>,
+}
+
+impl DeviceImage {
+    /// Reads the region of `device` from the processimage.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the region of the
+    /// device lies outside of the processimage and a
+    /// [`PiControlError::IoError`] if reading fails.
+    pub fn read(pi: &PiControl, device: &Device) -> Result<Self, PiControlError> {
+        let position = u8::try_from(device.position)
+            .map_err(|_| PiControlError::InvalidArgument("position"))?;
+        let offset =
+            u16::try_from(device.offset).map_err(|_| PiControlError::InvalidArgument("offset"))?;
+        let variables: BTreeMap<_, _> = device
+            .variables()
+            .map(|var| (var.name.clone(), var.clone()))
+            .collect();
+        // checked, so absurd offsets can't cause huge allocations
+        let mut len = 0;
+        for var in variables.values() {
+            let end = device
+                .end_of(var)
+                .filter(|end| *end <= KB_PI_LEN as u64)
+                .ok_or(PiControlError::InvalidArgument("offset"))?;
+            len = len.max((end - offset as u64) as usize);
+        }
+        let mut bytes = vec![0u8; len];
+        // the region lies inside the processimage, see above
+        unsafe { pi.inner.get_bytes(offset, &mut bytes) }?;
+        Ok(Self {
+            position,
+            offset,
+            bytes,
+            variables,
+        })
+    }
+
+    /// Returns the position of the device
+    pub fn position(&self) -> u8 {
+        self.position
+    }
+
+    /// Returns the address of the start of the region in the processimage
+    pub fn offset(&self) -> u16 {
+        self.offset
+    }
+
+    /// Returns the raw bytes of the region
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the names of all variables of the device
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.variables.keys().map(String::as_str)
+    }
+
+    /// Returns the value of the variable `name` at the time of the snapshot.
+    ///
+    /// Returns `None` if the device has no such variable or if its bitlength
+    /// can't be represented by a [`Value`].
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.variables
+            .get(name)
+            .and_then(|var| decode(&self.bytes, var))
+    }
+
+    /// Returns all variables that can be represented by a [`Value`], sorted by
+    /// name
+    pub fn values(&self) -> impl Iterator<Item = (&str, Value)> {
+        self.variables
+            .iter()
+            .filter_map(|(name, var)| Some((name.as_str(), decode(&self.bytes, var)?)))
+    }
+}
+
+// first byte of the variable relative to the device, bit positions can be
+// larger than 7
+fn start(var: &InOutMem) -> usize {
+    var.offset as usize + var.bit_position.unwrap_or(0) as usize / 8
+}
+
+fn decode(bytes: &[u8], var: &InOutMem) -> Option<Value> {
+    let bit = var.bit_position.unwrap_or(0) % 8;
+    Value::decode(bytes.get(start(var)..)?, bit, var.bit_length as usize)
+}
+
+impl PiControl {
+    /// Reads a [`DeviceImage`] of the device at `position`, as configured in
+    /// `rsc`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::DeviceNotFound`] if `rsc` contains no device
+    /// at `position`, otherwise see [`DeviceImage::read`].
+    pub fn read_device_image(
+        &self,
+        position: u8,
+        rsc: &RSC,
+    ) -> Result<DeviceImage, PiControlError> {
+        let device = rsc
+            .devices
+            .iter()
+            .find(|d| d.position == position as u64)
+            .ok_or(PiControlError::DeviceNotFound(position))?;
+        DeviceImage::read(self, device)
+    }
+
+    /// Reads all variables of the device at `position`, as configured in
+    /// `rsc`, with a single read of the device region. Variables whose
+    /// bitlength can't be represented by a [`Value`] are left out.
+    ///
+    /// # Errors
+    /// Same as [`PiControl::read_device_image`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+    /// let pi = PiControl::new().unwrap();
+    /// for (name, value) in pi.read_device(32, &rsc).unwrap() {
+    ///     println!("{}: {:?}", name, value);
+    /// }
+    /// ```
+    pub fn read_device(
+        &self,
+        position: u8,
+        rsc: &RSC,
+    ) -> Result<BTreeMap<String, Value>, PiControlError> {
+        let image = self.read_device_image(position, rsc)?;
+        Ok(image
+            .values()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect())
+    }
+
+    /// Writes the exported outputs of all devices in `rsc` from `image`, a
+    /// copy of the whole processimage, e.g. one taken with
+    /// [`read_region`](Self::read_region) before the application was
+    /// restarted. All other bytes of `image` are ignored, so unlike
+    /// [`PiControlRaw::set_exported_outputs`](super::raw::PiControlRaw::set_exported_outputs)
+    /// this can't overwrite anything else, and bits sharing a byte with other
+    /// variables are written one by one.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `image` isn't as large
+    /// as the processimage or an exported output lies outside of it, in which
+    /// case nothing is written. Otherwise returns the first error of writing.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::{raw::raw::KB_PI_LEN, PiControl};
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+    /// let pi = PiControl::new().unwrap();
+    /// let image = pi.read_region(0, KB_PI_LEN).unwrap();
+    /// // ...
+    /// pi.restore_exported_outputs(&image, &rsc).unwrap();
+    /// ```
+    pub fn restore_exported_outputs(&self, image: &[u8], rsc: &RSC) -> Result<(), PiControlError> {
+        ensure!(
+            image.len() == KB_PI_LEN,
+            PiControlError::InvalidArgument("image")
+        );
+        let mut outputs = Vec::new();
+        for device in rsc.devices.iter() {
+            for var in device.out.values().filter(|var| var.exported) {
+                let start = device.address_of(var);
+                let end = device.end_of(var).filter(|end| *end <= KB_PI_LEN as u64);
+                match (start, end) {
+                    (Some(start), Some(end)) => outputs.push((start as usize, end as usize, var)),
+                    _ => return Err(PiControlError::InvalidArgument("offset")),
+                }
+            }
+        }
+        for (start, end, var) in outputs {
+            // checked above, so the addresses lie inside the processimage
+            if var.bit_length == 1 {
+                let bit = var.bit_position.unwrap_or(0) % 8;
+                let value = (image[start] >> bit) & 1 == 1;
+                unsafe { self.inner.set_bit(start as u16, Bit::from(bit), value) }?;
+            } else {
+                unsafe { self.inner.set_bytes(start as u16, &image[start..end]) }?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+//! Binding of user structs to variables
+
+use super::{PiControl, PiControlError};
+
+/// A struct whose fields are bound to variables of the processimage
+///
+/// With the `macro` feature, this can be derived, binding every field to the
+/// variable named like it or given with `#[pi(name = "...")]`, see the
+/// [macro documentation](revpi_macro#deriving-processimage):
+/// ```ignore
+/// use revpi::picontrol::{PiControl, ProcessImage};
+///
+/// #[derive(ProcessImage)]
+/// struct Machine {
+///     #[pi(name = "RevPiLED")]
+///     led: u8,
+///     #[pi(name = "I_1", read_only)]
+///     door_open: bool,
+/// }
+///
+/// let pi = PiControl::new().unwrap();
+/// let mut machine = Machine::read_from(&pi).unwrap();
+/// machine.led = machine.door_open as u8;
+/// machine.write_to(&pi).unwrap();
+/// ```
+pub trait ProcessImage: Sized {
+    /// Reads all bound variables from `pi`
+    ///
+    /// # Errors
+    /// Returns the first error of [`PiControl::get_value_as`].
+    fn read_from(pi: &PiControl) -> Result<Self, PiControlError>;
+
+    /// Writes all bound variables that aren't read only to `pi`
+    ///
+    /// # Errors
+    /// Returns the first error of [`PiControl::set_value`]. The variables
+    /// before it have been written already.
+    fn write_to(&self, pi: &PiControl) -> Result<(), PiControlError>;
+}
@@ -0,0 +1,119 @@
+//! History of the driver's messages
+
+use super::Backend;
+use crate::clock::{Clock, SystemClock, Timestamp};
+use std::{collections::VecDeque, fmt};
+
+/// Message of the driver with the time it was noticed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Message {
+    /// Time the message was first seen, not when the driver issued it
+    pub timestamp: Timestamp,
+    /// Text of the message
+    pub text: String,
+}
+
+/// Keeps the last messages of the driver
+///
+/// The driver only remembers its last message, see
+/// [`PiControlRaw::get_last_message`](super::raw::PiControlRaw::get_last_message).
+/// Polling it regularly with [`poll`](Self::poll) builds a history, where a
+/// message is only recorded if it differs from the previous one. At most
+/// `capacity` messages are kept, the oldest is dropped first.
+///
+/// With [`PiControlBuilder::message_log`](super::PiControlBuilder::message_log),
+/// [`PiControl`](super::PiControl) keeps a log that is also polled after
+/// every driver reset when resets are watched with the `events` feature.
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, MessageLog};
+///
+/// let mock = MockBackend::new();
+/// let mut log = MessageLog::new(10);
+/// mock.set_message("piGate: module 31 missing");
+/// assert!(log.poll(&mock).is_some());
+/// assert!(log.poll(&mock).is_none());
+/// mock.set_message("piBridge running");
+/// log.poll(&mock);
+/// let texts: Vec<_> = log.messages().map(|m| m.text.as_str()).collect();
+/// assert_eq!(texts, ["piGate: module 31 missing", "piBridge running"]);
+/// ```
+pub struct MessageLog {
+    capacity: usize,
+    messages: VecDeque<Message>,
+    // also kept if the message was dropped, so it isn't recorded twice
+    last: String,
+    clock: Box<dyn Clock>,
+}
+
+impl fmt::Debug for MessageLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageLog")
+            .field("capacity", &self.capacity)
+            .field("messages", &self.messages)
+            .field("last", &self.last)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MessageLog {
+    /// Creates an empty log keeping at most `capacity` messages, stamped with
+    /// a [`SystemClock`]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, SystemClock::new())
+    }
+
+    /// Creates an empty log like [`MessageLog::new`], but stamped with `clock`
+    pub fn with_clock<C: Clock + 'static>(capacity: usize, clock: C) -> Self {
+        Self {
+            capacity,
+            messages: VecDeque::new(),
+            last: String::new(),
+            clock: Box::new(clock),
+        }
+    }
+
+    /// Records `text` unless it is empty or the same as the previous text.
+    /// Returns the recorded message.
+    pub fn record(&mut self, text: &str) -> Option<&Message> {
+        if text.is_empty() || text == self.last {
+            return None;
+        }
+        self.last = text.to_string();
+        if self.capacity == 0 {
+            return None;
+        }
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            timestamp: self.clock.now(),
+            text: text.to_string(),
+        });
+        self.messages.back()
+    }
+
+    /// Records the last message of `backend`, see [`MessageLog::record`].
+    /// Returns `None` as well if the backend has no messages.
+    pub fn poll<B: Backend + ?Sized>(&mut self, backend: &B) -> Option<&Message> {
+        let text = backend.last_message()?;
+        self.record(&text)
+    }
+
+    /// Returns the recorded messages, the oldest first
+    pub fn messages(&self) -> impl Iterator<Item = &Message> + '_ {
+        self.messages.iter()
+    }
+
+    /// Returns the most recent message
+    pub fn latest(&self) -> Option<&Message> {
+        self.messages.back()
+    }
+
+    /// Forgets all recorded messages. The last message isn't recorded again
+    /// until it changed.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
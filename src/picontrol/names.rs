@@ -0,0 +1,187 @@
+//! Name lookups from an RSC file instead of the driver
+
+use super::{
+    raw::raw::{EntryInfoType, KB_PI_LEN, PICONFIG_FILE, PICONFIG_FILE_WHEEZY},
+    PiControl, PiControlBuilder, PiControlError, Var,
+};
+use crate::rsc::{Limits, RSC};
+use std::{collections::HashMap, fs::File, io};
+
+/// Maps the names of variables to their place in the processimage, as
+/// configured in an RSC file
+///
+/// A [`PiControl`] with a name table doesn't ask the driver for the names in
+/// it, which is about as fast as the accessors generated by the `revpi!`
+/// macro, but only needs the config at runtime. Names that aren't in the
+/// table are still looked up by the driver.
+///
+/// The table has to match the config the driver was started with.
+/// [`PiControl::apply_new_config`] replaces it with one built from the new
+/// config.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::{NameTable, PiControl};
+/// # use revpi::rsc::RSC;
+/// # use std::fs::File;
+/// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+/// let pi = PiControl::with_name_table(NameTable::from_rsc(&rsc).unwrap()).unwrap();
+/// println!("{:?}", pi.get_value("RevPiLED").unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NameTable {
+    vars: HashMap<String, Var>,
+}
+
+impl NameTable {
+    /// Builds the table from all variables of all devices in `rsc`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a variable lies
+    /// outside of the processimage.
+    pub fn from_rsc(rsc: &RSC) -> Result<Self, PiControlError> {
+        let mut vars = HashMap::new();
+        for device in rsc.devices.iter() {
+            for var in device.variables() {
+                let address = device
+                    .address_of(var)
+                    .filter(|_| {
+                        device
+                            .end_of(var)
+                            .is_some_and(|end| end <= KB_PI_LEN as u64)
+                    })
+                    .ok_or(PiControlError::InvalidArgument("offset"))?;
+                vars.insert(
+                    var.name.clone(),
+                    Var {
+                        // checked above
+                        address: address as u16,
+                        bit: var.bit_position.unwrap_or(0) % 8,
+                        length: var.bit_length as u16,
+                    },
+                );
+            }
+        }
+        Ok(Self { vars })
+    }
+
+    /// Returns the address, the bit and the length in bits of the variable
+    /// `name`, like [`SPIVariable`](super::raw::raw::SPIVariable)
+    pub fn get(&self, name: &str) -> Option<(u16, u8, u16)> {
+        self.vars
+            .get(name)
+            .map(|var| (var.address, var.bit, var.length))
+    }
+
+    /// Returns the number of variables in the table
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// Returns whether the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Returns the names of all variables in the table, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(String::as_str)
+    }
+
+    pub(crate) fn var(&self, name: &str) -> Option<Var> {
+        self.vars.get(name).copied()
+    }
+
+    pub(crate) fn insert(&mut self, name: String, var: Var) {
+        self.vars.insert(name, var);
+    }
+}
+
+/// A variable of the running config, returned by
+/// [`PiControl::list_variables`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VariableInfo {
+    /// Name given in PiCtory
+    pub name: String,
+    /// Position of the device the variable belongs to
+    pub position: u64,
+    /// Address of the first byte in the processimage
+    pub address: u16,
+    /// Bit inside the byte at `address`, only meaningful if `length` is `1`
+    pub bit: u8,
+    /// Length in bits
+    pub length: u16,
+    /// Whether the variable is an input, an output or memory
+    pub direction: EntryInfoType,
+}
+
+// opens the config the driver was started with
+fn running_config() -> Result<File, PiControlError> {
+    match File::open(PICONFIG_FILE) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(File::open(PICONFIG_FILE_WHEEZY)?),
+        f => Ok(f?),
+    }
+}
+
+impl PiControl {
+    /// Returns all variables of the running config, sorted by address and bit.
+    ///
+    /// The driver can't enumerate its variables, so the names are taken from
+    /// the config it was started with, `"/etc/revpi/config.rsc"` or
+    /// `"/opt/KUNBUS/config.rsc"`, while addresses and lengths are looked up
+    /// like for any other access. Variables the driver doesn't know are left
+    /// out.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] or [`PiControlError::RscError`]
+    /// if the config can't be read.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// for var in pi.list_variables().unwrap() {
+    ///     println!("{:>4}.{} {:?} {}", var.address, var.bit, var.direction, var.name);
+    /// }
+    /// ```
+    pub fn list_variables(&self) -> Result<Vec<VariableInfo>, PiControlError> {
+        let rsc = RSC::from_reader_with_limits(running_config()?, &Limits::default())?;
+        let mut vars = Vec::new();
+        for device in rsc.devices.iter() {
+            let sections = [
+                (&device.inp, EntryInfoType::Input),
+                (&device.out, EntryInfoType::Output),
+                (&device.mem, EntryInfoType::Memory),
+            ];
+            for (section, direction) in sections {
+                for var in section.values() {
+                    let found = match self.find_variable(&var.name) {
+                        Ok(found) => found,
+                        Err(PiControlError::InvalidArgument(_)) => continue,
+                        Err(e) => return Err(e),
+                    };
+                    vars.push(VariableInfo {
+                        name: var.name.clone(),
+                        position: device.position,
+                        address: found.address,
+                        bit: found.bit,
+                        length: found.length,
+                        direction,
+                    });
+                }
+            }
+        }
+        vars.sort_by_key(|var| (var.address, var.bit));
+        Ok(vars)
+    }
+
+    /// Creates a new PiControl object that takes the names in `table` from it
+    /// instead of the driver, see [`NameTable`]. Same as
+    /// `PiControl::builder().name_table(table).build()`.
+    ///
+    /// # Errors
+    /// Same as [`PiControl::new`]
+    pub fn with_name_table(table: NameTable) -> Result<Self, PiControlError> {
+        PiControlBuilder::new().name_table(table).build()
+    }
+}
@@ -0,0 +1,122 @@
+//! Pool of handles for concurrent access
+
+use super::{PiControl, PiControlError};
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug)]
+struct Inner {
+    pi: PiControl,
+    idle: Mutex<Vec<PiControl>>,
+    max_idle: usize,
+}
+
+/// Pool of [`PiControl`] handles, each with its own file descriptor
+///
+/// Calls on one file descriptor are serialized by the driver, so threads
+/// sharing a single [`PiControl`] wait for each other. [`get`](Self::get)
+/// hands out a handle created with [`PiControl::try_clone`], which goes back
+/// to the pool when it is dropped, so handles are only opened when all others
+/// are in use. Like with `try_clone`, the handles share the cache, aliases and
+/// safe state, but the output watchdog is only armed on the original one.\
+/// The pool is cheap to clone, all clones share the same handles.
+///
+/// # Example
+/// ```no_run
+/// # use revpi::picontrol::{PiControl, PiControlPool, Value};
+/// let pool = PiControlPool::new(PiControl::new().unwrap(), 4);
+/// let threads: Vec<_> = (0..4)
+///     .map(|i| {
+///         let pool = pool.clone();
+///         std::thread::spawn(move || {
+///             let pi = pool.get().unwrap();
+///             pi.set_flag("RevPiLED", i, true).unwrap();
+///         })
+///     })
+///     .collect();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PiControlPool {
+    inner: Arc<Inner>,
+}
+
+impl PiControlPool {
+    /// Creates a pool of handles to `pi`, which keeps at most `max_idle`
+    /// unused handles open. More handles can be in use at the same time.
+    pub fn new(pi: PiControl, max_idle: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                pi,
+                idle: Mutex::new(Vec::new()),
+                max_idle,
+            }),
+        }
+    }
+
+    /// Returns the [`PiControl`] the handles are cloned from
+    pub fn pi(&self) -> &PiControl {
+        &self.inner.pi
+    }
+
+    /// Returns an unused handle, opening a new one if there is none
+    ///
+    /// # Errors
+    /// Same as [`PiControl::try_clone`].
+    pub fn get(&self) -> Result<PooledPiControl, PiControlError> {
+        let idle = self
+            .inner
+            .idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop();
+        let pi = match idle {
+            Some(pi) => pi,
+            None => self.inner.pi.try_clone()?,
+        };
+        Ok(PooledPiControl {
+            pi: Some(pi),
+            pool: self.inner.clone(),
+        })
+    }
+
+    /// Returns the number of unused handles
+    pub fn idle(&self) -> usize {
+        self.inner
+            .idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// Handle returned by [`PiControlPool::get`], which goes back to the pool
+/// when dropped
+#[derive(Debug)]
+pub struct PooledPiControl {
+    // only None while dropping
+    pi: Option<PiControl>,
+    pool: Arc<Inner>,
+}
+
+impl Deref for PooledPiControl {
+    type Target = PiControl;
+
+    fn deref(&self) -> &PiControl {
+        // only taken in drop
+        self.pi.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledPiControl {
+    fn drop(&mut self) {
+        let mut idle = self.pool.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < self.pool.max_idle {
+            idle.extend(self.pi.take());
+        }
+    }
+}
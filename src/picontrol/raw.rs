@@ -2,22 +2,38 @@
 //!
 //! If you want real raw access, see the [`raw`] module.
 
+mod device;
+mod driver;
+mod endian;
+mod firmware;
+mod gateway;
+#[allow(clippy::module_inception)]
 pub mod raw;
+mod reader;
 
+pub use self::device::{DeviceInfo, FirmwareVersion, ModuleType};
+pub use self::driver::{Capabilities, DriverVersion};
+pub use self::endian::Endianness;
+pub use self::firmware::{FirmwareUpdater, UpdateStep};
+pub use self::gateway::{GatewayConfigSession, Side};
 use self::raw::{
-    Event, SDIOResetCounter, SDeviceInfo, SPIValue, SPIVariable, KB_PI_LEN, REV_PI_DEV_CNT_MAX,
+    Event, IoctlError, RevPiROCounters, SAIOCalibrate, SDIOResetCounter, SDeviceInfo, SPIValue,
+    SPIVariable, KB_PI_LEN, PICONTROL_DEVICE, REVPI_RO_NUM_RELAYS, REV_PI_DEV_CNT_MAX,
     REV_PI_ERROR_MSG_LEN,
 };
+pub use self::reader::ProcessImageReader;
 use super::PiControlError;
 use crate::util::ensure;
 use std::{
     ffi::{CStr, CString},
-    fs::File,
-    os::unix::prelude::{AsRawFd, FileExt},
+    fs::{self, File, OpenOptions},
+    os::unix::prelude::{AsRawFd, FileExt, MetadataExt},
+    path::Path,
+    sync::atomic::{AtomicU8, Ordering},
 };
 
 /// Bit inside a byte which to write to or read from
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Bit {
     Zero = 0,
@@ -51,6 +67,18 @@ impl From<u8> for Bit {
     }
 }
 
+/// State of the I/O communication, see [`PiControlRaw::stop_io`]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum IoState {
+    Running,
+    Stopped,
+}
+
+// encoding of the last known IoState
+const IO_STATE_UNKNOWN: u8 = 0;
+const IO_STATE_RUNNING: u8 = 1;
+const IO_STATE_STOPPED: u8 = 2;
+
 /// Provides semi-raw access to the RevPi
 ///
 /// The focus lies on providing error-checking where possible but not at the
@@ -61,14 +89,22 @@ impl From<u8> for Bit {
 ///
 /// If you don't have to, don't use this directly but rather a wrapper around it.
 #[derive(Debug)]
-pub struct PiControlRaw(File);
+// the second field is the last known IoState, the third the capabilities
+// the driver turned out to lack
+pub struct PiControlRaw(File, AtomicU8, AtomicU8);
+
+impl AsRawFd for PiControlRaw {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.0.as_raw_fd()
+    }
+}
 
 // drop not needed, file closes automatically when out of scope
 impl PiControlRaw {
     /// Constructs a new PiControlRaw object.
     ///
     /// # Errors
-    /// Returns a [`PiControlError::IoError`] if opening `"/dev/piControl0"` fails.
+    /// Same as [`open`](Self::open).
     ///
     /// # Examples
     /// ```no_run
@@ -76,7 +112,42 @@ impl PiControlRaw {
     /// let raw = PiControlRaw::new().unwrap();
     /// ```
     pub fn new() -> Result<Self, PiControlError> {
-        Ok(PiControlRaw(File::open("/dev/piControl0")?))
+        Self::open(PICONTROL_DEVICE)
+    }
+
+    /// Constructs a new PiControlRaw object from the device at `path` instead
+    /// of `"/dev/piControl0"`.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::PermissionDenied`] if the user isn't
+    /// allowed to open `path` and a [`PiControlError::IoError`] if opening
+    /// fails otherwise.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::open("/dev/piControl0").unwrap();
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PiControlError> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|e| match (e.raw_os_error(), fs::metadata(path)) {
+                (Some(libc::EACCES), Ok(meta)) => PiControlError::PermissionDenied {
+                    path: path.to_path_buf(),
+                    uid: meta.uid(),
+                    gid: meta.gid(),
+                    mode: meta.mode() & 0o7777,
+                },
+                _ => e.into(),
+            })?;
+        Ok(PiControlRaw(
+            file,
+            AtomicU8::new(IO_STATE_UNKNOWN),
+            AtomicU8::new(0),
+        ))
     }
 
     // every error could also be EINVAL if argp or request in ioctl is invalid, but that shouldn't be possible
@@ -89,55 +160,125 @@ impl PiControlRaw {
     /// ensure that either that isn't the case or that the changes are taken
     /// into account.
     ///
-    /// # Panics
-    /// Will panic if the bridge restart timed out.
+    /// # Errors
+    /// Returns [`PiControlError::Timeout`] if the bridge didn't come up in
+    /// time after the restart.
     ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// unsafe { raw.reset() };
+    /// unsafe { raw.reset() }.unwrap();
     /// ```
-    pub unsafe fn reset(&self) {
-        raw::reset(self.0.as_raw_fd())
-            .map_err(|e| match e {
-                libc::ETIMEDOUT => {
-                    panic!("couldn't restart because bridge didn't come up; timedout")
-                }
-                _ => unreachable!(),
-            })
-            .unwrap();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub unsafe fn reset(&self) -> Result<(), PiControlError> {
+        raw::reset(self.0.as_raw_fd()).map_err(|e| match e {
+            IoctlError::TimedOut => PiControlError::Timeout,
+            e => e.into(),
+        })?;
+        Ok(())
+    }
+
+    /// Returns whether the piBridge is running, i.e. whether values can be
+    /// accessed.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver failed in any other
+    /// way.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// println!("{}", raw.bridge_running().unwrap());
+    /// ```
+    pub fn bridge_running(&self) -> Result<bool, PiControlError> {
+        let mut val = SPIValue {
+            i16uAddress: 0,
+            i8uBit: 0,
+            i8uValue: 0,
+        };
+        match unsafe { raw::get_value(self.0.as_raw_fd(), &mut val) } {
+            Ok(_) => Ok(true),
+            Err(IoctlError::Fault) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes `rsc` to `path` with [`RSC::write_to`](crate::rsc::RSC::write_to)
+    /// and then resets the driver, so the new config gets applied. If writing
+    /// fails, the driver isn't reset and keeps the old config.
+    ///
+    /// Returns the path of the backup of the old config, if there was one.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::RscError`] if the config couldn't be
+    /// written and [`PiControlError::Timeout`] if the bridge didn't come up
+    /// again in time.
+    ///
+    /// # Safety
+    /// Same as [`reset`](Self::reset).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let path = "/etc/revpi/config.rsc";
+    /// let mut rsc: RSC = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+    /// rsc.devices[0].comment = "changed".to_string();
+    /// let raw = PiControlRaw::new().unwrap();
+    /// unsafe { raw.apply_config(&rsc, path) }.unwrap();
+    /// ```
+    #[cfg(feature = "rsc")]
+    pub unsafe fn apply_config<P: AsRef<Path>>(
+        &self,
+        rsc: &crate::rsc::RSC,
+        path: P,
+    ) -> Result<Option<std::path::PathBuf>, PiControlError> {
+        let backup = rsc.write_to(path)?;
+        self.reset()?;
+        Ok(backup)
     }
 
     /// Returns a vector with the information of all connected devices.
     ///
-    /// # Panics
-    /// Will panic if the kernel module ran out of memory.
+    /// # Errors
+    /// Returns [`PiControlError::OutOfMemory`] if the kernel module ran out of
+    /// memory.
     ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// let devs = raw.get_device_info_list();
-    /// println!("{:?}", devs);
+    /// for dev in raw.get_device_info_list().unwrap() {
+    ///     println!("{}: {}", dev.address(), dev.module_type());
+    /// }
     /// ```
-    pub fn get_device_info_list(&self) -> Vec<SDeviceInfo> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn get_device_info_list(&self) -> Result<Vec<DeviceInfo>, PiControlError> {
         let mut devs = Vec::with_capacity(REV_PI_DEV_CNT_MAX);
         let cnt = unsafe { raw::get_device_info_list(self.0.as_raw_fd(), devs.as_mut_ptr()) }
             .map_err(|e| match e {
-                libc::ENOMEM => panic!("out of memory"),
-                _ => unreachable!(),
-            })
-            .unwrap();
-        // better safe than sorry, although this shouldn't happen as it is actually specified
+                IoctlError::OutOfMemory => PiControlError::OutOfMemory,
+                e => e.into(),
+            })?;
+        // the driver never writes more, otherwise the memory would already be
+        // corrupted
         assert!(
-            cnt > REV_PI_DEV_CNT_MAX as u32,
+            cnt <= REV_PI_DEV_CNT_MAX as u32,
             "cnt was {}, which is larger than REV_PI_DEV_CNT_MAX ({})",
             cnt,
             REV_PI_DEV_CNT_MAX
         );
         unsafe { devs.set_len(cnt as usize) };
-        devs
+        Ok(devs.into_iter().map(DeviceInfo::from).collect())
     }
 
     /// Returns the information of the requested device.
@@ -153,17 +294,27 @@ impl PiControlRaw {
     /// let dev = raw.get_device_info(31).unwrap();
     /// println!("{:?}", dev);
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub fn get_device_info(&self, address: u8) -> Result<SDeviceInfo, PiControlError> {
-        let mut dev = SDeviceInfo::default();
-        dev.i8uAddress = address;
+        let mut dev = SDeviceInfo {
+            i8uAddress: address,
+            ..Default::default()
+        };
         unsafe { raw::get_device_info(self.0.as_raw_fd(), &mut dev) }.map_err(|e| match e {
-            libc::ENXIO => PiControlError::DeviceNotFound(address),
-            _ => unreachable!(),
+            IoctlError::NoDevice => PiControlError::DeviceNotFound(address),
+            e => e.into(),
         })?;
         Ok(dev)
     }
 
     // unsafe due to uncertainty of address
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
     unsafe fn get_value(&self, address: u16, bit: u8) -> Result<u8, PiControlError> {
         ensure!(
             (address as usize) < KB_PI_LEN,
@@ -174,12 +325,7 @@ impl PiControlRaw {
             i8uBit: bit,
             i8uValue: 0,
         };
-        raw::get_value(self.0.as_raw_fd(), &mut val)
-            .map_err(|e| match e {
-                libc::EFAULT => panic!("bridge wasn't running"),
-                _ => unreachable!(),
-            })
-            .unwrap();
+        raw::get_value(self.0.as_raw_fd(), &mut val).map_err(bridge_error)?;
         Ok(val.i8uValue)
     }
 
@@ -187,15 +333,13 @@ impl PiControlRaw {
     ///
     /// # Errors
     /// Returns [`PiControlError::InvalidArgument`] if `address` is larger
-    /// than [`KB_PI_LEN`].
+    /// than [`KB_PI_LEN`] and [`PiControlError::BridgeNotRunning`] if the
+    /// bridge wasn't running.
     ///
     /// # Safety
     /// You have to ensure that `address` and `bit` are valid and point to the
     /// right value, otherwise you might get something unexpected.
     ///
-    /// # Panics
-    /// Will panic if the bridge wasn't running
-    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::{PiControlRaw, Bit};
@@ -211,15 +355,13 @@ impl PiControlRaw {
     ///
     /// # Errors
     /// Returns [`PiControlError::InvalidArgument`] if `address` is larger
-    /// than [`KB_PI_LEN`].
+    /// than [`KB_PI_LEN`] and [`PiControlError::BridgeNotRunning`] if the
+    /// bridge wasn't running.
     ///
     /// # Safety
     /// You have to ensure that `address` is valid and points to the right value,
     /// otherwise you might get something unexpected.
     ///
-    /// # Panics
-    /// Will panic if the bridge wasn't running
-    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
@@ -248,6 +390,10 @@ impl PiControlRaw {
     /// let word = unsafe { raw.get_word(1337) }.unwrap();
     /// println!("{}", word);
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
     pub unsafe fn get_word(&self, address: u16) -> Result<u16, PiControlError> {
         let mut bytes = [0u8; 2];
         self.0.read_exact_at(&mut bytes, address as u64)?;
@@ -271,13 +417,84 @@ impl PiControlRaw {
     /// let dword = unsafe { raw.get_dword(1337) }.unwrap();
     /// println!("{}", dword);
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
     pub unsafe fn get_dword(&self, address: u16) -> Result<u32, PiControlError> {
         let mut bytes = [0u8; 4];
         self.0.read_exact_at(&mut bytes, address as u64)?;
         Ok(u32::from_le_bytes(bytes))
     }
 
+    /// Fills `bytes` from the processimage, starting at `address`. Values
+    /// spanning several bytes can be read at once this way, see
+    /// [`get_i64`](Self::get_i64) and friends for typed access.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the processimage and [`PiControlError::IoError`] if there was an
+    /// error reading the processimage.
+    ///
+    /// # Safety
+    /// You have to ensure that `address` is valid and points to the right value,
+    /// otherwise you might get something unexpected.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// let mut serial = [0u8; 8];
+    /// unsafe { raw.get_bytes(1337, &mut serial) }.unwrap();
+    /// println!("{:x?}", serial);
+    /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, bytes), fields(len = bytes.len()), err))]
+    pub unsafe fn get_bytes(&self, address: u16, bytes: &mut [u8]) -> Result<(), PiControlError> {
+        ensure!(
+            address as usize + bytes.len() <= KB_PI_LEN,
+            PiControlError::InvalidArgument("address")
+        );
+        self.0
+            .read_exact_at(bytes, address as u64)
+            .map_err(PiControlError::from)
+    }
+
+    /// Writes `bytes` to the processimage, starting at `address`.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the processimage and [`PiControlError::IoError`] if there was an
+    /// error writing the processimage.
+    ///
+    /// # Safety
+    /// You have to ensure that `address` is valid and points to the right value,
+    /// otherwise you might write in the wrong place.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// unsafe { raw.set_bytes(1337, &[0xde, 0xad, 0xbe, 0xef]) }.unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub unsafe fn set_bytes(&self, address: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        ensure!(
+            address as usize + bytes.len() <= KB_PI_LEN,
+            PiControlError::InvalidArgument("address")
+        );
+        self.0
+            .write_all_at(bytes, address as u64)
+            .map_err(PiControlError::from)
+    }
+
     // unsafe due to uncertainty of address
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     unsafe fn set_value(&self, address: u16, bit: u8, value: u8) -> Result<(), PiControlError> {
         ensure!(
             (address as usize) < KB_PI_LEN,
@@ -288,12 +505,7 @@ impl PiControlRaw {
             i8uBit: bit,
             i8uValue: value,
         };
-        raw::set_value(self.0.as_raw_fd(), &mut val)
-            .map_err(|e| match e {
-                libc::EFAULT => panic!("bridge wasn't running"),
-                _ => unreachable!(),
-            })
-            .unwrap();
+        raw::set_value(self.0.as_raw_fd(), &mut val).map_err(bridge_error)?;
         Ok(())
     }
 
@@ -301,15 +513,13 @@ impl PiControlRaw {
     ///
     /// # Errors
     /// Returns [`PiControlError::InvalidArgument`] if `address` is larger
-    /// than [`KB_PI_LEN`].
+    /// than [`KB_PI_LEN`] and [`PiControlError::BridgeNotRunning`] if the
+    /// bridge wasn't running.
     ///
     /// # Safety
     /// You have to ensure that `address` and `bit` are valid and point to the
     /// right value, otherwise you might write in the wrong place.
     ///
-    /// # Panics
-    /// Will panic if the bridge wasn't running
-    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::{PiControlRaw, Bit};
@@ -329,15 +539,13 @@ impl PiControlRaw {
     ///
     /// # Errors
     /// Returns [`PiControlError::InvalidArgument`] if `address` is larger
-    /// than [`KB_PI_LEN`].
+    /// than [`KB_PI_LEN`] and [`PiControlError::BridgeNotRunning`] if the
+    /// bridge wasn't running.
     ///
     /// # Safety
     /// You have to ensure that `address` is valid and points to the right value,
     /// otherwise you might write in the wrong place.
     ///
-    /// # Panics
-    /// Will panic if the bridge wasn't running
-    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
@@ -348,6 +556,42 @@ impl PiControlRaw {
         self.set_value(address, 8, value)
     }
 
+    /// Writes the bits of `value` selected by `mask` to the byte at `address`,
+    /// leaving the other bits untouched.
+    ///
+    /// Every selected bit is written with its own [`set_bit`](Self::set_bit),
+    /// which the driver applies atomically, so bits other processes change at
+    /// the same time, e.g. LEDs shared with the OS, aren't lost like with
+    /// reading, changing and writing the whole byte.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if `address` is larger
+    /// than [`KB_PI_LEN`] and [`PiControlError::BridgeNotRunning`] if the
+    /// bridge wasn't running.
+    ///
+    /// # Safety
+    /// You have to ensure that `address` is valid and points to the right value,
+    /// otherwise you might write in the wrong place.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// // sets bit 0 and clears bit 1
+    /// unsafe { raw.modify_byte(6, 0b11, 0b01) }.unwrap();
+    /// ```
+    pub unsafe fn modify_byte(
+        &self,
+        address: u16,
+        mask: u8,
+        value: u8,
+    ) -> Result<(), PiControlError> {
+        for bit in (0..8).filter(|bit| mask & (1 << bit) != 0) {
+            self.set_value(address, bit, (value >> bit) & 1)?;
+        }
+        Ok(())
+    }
+
     /// Writes a word to the processimage.
     ///
     /// # Errors
@@ -364,6 +608,10 @@ impl PiControlRaw {
     /// let raw = PiControlRaw::new().unwrap();
     /// unsafe { raw.set_word(1337, 42) }.unwrap();
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub unsafe fn set_word(&self, address: u16, value: u16) -> Result<(), PiControlError> {
         self.0
             .write_all_at(&value.to_le_bytes(), address as u64)
@@ -386,6 +634,10 @@ impl PiControlRaw {
     /// let raw = PiControlRaw::new().unwrap();
     /// unsafe { raw.set_word(1337, 42) }.unwrap();
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub unsafe fn set_dword(&self, address: u16, value: u32) -> Result<(), PiControlError> {
         self.0
             .write_all_at(&value.to_le_bytes(), address as u64)
@@ -399,10 +651,8 @@ impl PiControlRaw {
     /// Returns [`PiControlError::InvalidArgument`] if `name` is longer than
     /// 31 bytes or if the given name was not found.\
     /// Returns [`PiControlError::NoVarEntries`] if there were not variable
-    /// entries at all.
-    ///
-    /// # Panics
-    /// Will panic if the bridge wasn't running
+    /// entries at all and [`PiControlError::BridgeNotRunning`] if the bridge
+    /// wasn't running.
     ///
     /// # Examples
     /// ```no_run
@@ -412,26 +662,55 @@ impl PiControlRaw {
     /// let var = raw.find_variable(&CString::new("test").unwrap()).unwrap();
     /// println!("{:?}", var)
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
     pub fn find_variable(&self, name: &CStr) -> Result<SPIVariable, PiControlError> {
         let len = name.to_bytes_with_nul().len();
         ensure!(len <= 32, PiControlError::InvalidArgument("length of name"));
         let mut var = SPIVariable::default();
         var.strVarName[0..len].copy_from_slice(name.to_bytes_with_nul());
         unsafe { raw::find_variable(self.0.as_raw_fd(), &mut var) }.map_err(|e| match e {
-            libc::EFAULT => {
+            IoctlError::Fault => {
                 // not specified, helpful tho, see kernel module
                 if var.i16uAddress == 0xffff && var.i8uBit == 0xff && var.i16uLength == 0xffff {
                     PiControlError::InvalidArgument("name")
                 } else {
-                    panic!("bridge wasn't running")
+                    PiControlError::BridgeNotRunning
                 }
             }
-            libc::ENOENT => PiControlError::NoVarEntries,
-            _ => unreachable!(),
+            IoctlError::NoEntry => PiControlError::NoVarEntries,
+            e => e.into(),
         })?;
         Ok(var)
     }
 
+    /// Reads the whole processimage at once, e.g. to restore the outputs later
+    /// with [`PiControl::restore_exported_outputs`](crate::picontrol::PiControl::restore_exported_outputs)
+    /// or [`set_exported_outputs`](Self::set_exported_outputs).
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if reading fails.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// let image = raw.dump_image().unwrap();
+    /// println!("{:?}", &image[..16]);
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), err)
+    )]
+    pub fn dump_image(&self) -> Result<[u8; KB_PI_LEN], PiControlError> {
+        let mut image = [0; KB_PI_LEN];
+        // the image covers exactly the processimage
+        unsafe { self.get_bytes(0, &mut image) }?;
+        Ok(image)
+    }
+
     // unsafe because only one process should call this
     /// Replaces the whole processimage with the given image.
     ///
@@ -439,16 +718,52 @@ impl PiControlRaw {
     /// You have to ensure that there are no other processes that have an open
     /// file descriptor on "/dev/piControl0".
     ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver rejected the
+    /// image.
+    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// use revpi::picontrol::raw::raw::KB_PI_LEN;
     /// let raw = PiControlRaw::new().unwrap();
     /// let image = [0; KB_PI_LEN]; // this would ofc be a bad idea
-    /// unsafe { raw.set_exported_outputs(&image) };
+    /// unsafe { raw.set_exported_outputs(&image) }.unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub unsafe fn set_exported_outputs(
+        &self,
+        image: &[u8; KB_PI_LEN],
+    ) -> Result<(), PiControlError> {
+        raw::set_exported_outputs(self.0.as_raw_fd(), image.as_ptr())?;
+        Ok(())
+    }
+
+    /// Writes zero to all exported outputs, see
+    /// [`set_exported_outputs`](Self::set_exported_outputs).
+    ///
+    /// # Safety
+    /// You have to ensure that there are no other processes that have an open
+    /// file descriptor on "/dev/piControl0".
+    ///
+    /// # Errors
+    /// Same as [`set_exported_outputs`](Self::set_exported_outputs).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// unsafe { raw.zero_exported_outputs() }.unwrap();
     /// ```
-    pub unsafe fn set_exported_outputs(&self, image: &[u8; KB_PI_LEN]) {
-        raw::set_exported_outputs(self.0.as_raw_fd(), image.as_ptr()).unwrap();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub unsafe fn zero_exported_outputs(&self) -> Result<(), PiControlError> {
+        self.set_exported_outputs(&[0; KB_PI_LEN])
     }
 
     // unsafe because device might get bricked
@@ -456,32 +771,33 @@ impl PiControlRaw {
     /// the module that should be updated. If `module` is `0`, the first device
     /// that's found will be updated.
     ///
+    /// [`firmware_updater`](Self::firmware_updater) checks the preconditions
+    /// and stops I/O communication during the update.
+    ///
     /// # Safety
     /// You have to ensure that there is exactly one device connected at the
     /// time of the update. Also, though it is not specified, your device might
     /// get bricked if you lose power during the update.
     ///
-    /// # Panics
-    /// Will panic if the RevPi is not a RevPi Core or RevPi Connect.
-    /// Will also panic if the bridge wasn't running or if too many or too little
-    /// modules were connected.
+    /// # Errors
+    /// Returns [`PiControlError::NotSupportedOnThisModel`] if the RevPi is not
+    /// a RevPi Core or RevPi Connect and [`PiControlError::BridgeNotRunning`]
+    /// if the bridge wasn't running or if too many or too little modules were
+    /// connected, the driver doesn't tell these apart.
     ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// unsafe { raw.update_device_firmware(31) };
+    /// unsafe { raw.update_device_firmware(31) }.unwrap();
     /// ```
-    pub unsafe fn update_device_firmware(&self, module: u32) {
-        raw::update_device_firmware(self.0.as_raw_fd(), module)
-            .map_err(|e| match e {
-                libc::EFAULT => {
-                    panic!("bridge wasn't running or too little or too many modules were connected")
-                }
-                libc::EPERM => panic!("this isn't a revpi core or connect"),
-                _ => unreachable!(),
-            })
-            .unwrap();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub unsafe fn update_device_firmware(&self, module: u32) -> Result<(), PiControlError> {
+        raw::update_device_firmware(self.0.as_raw_fd(), module).map_err(model_error)?;
+        Ok(())
     }
 
     /// Resets the counter of the DIO module with `dio_address`. The counters
@@ -490,11 +806,10 @@ impl PiControlRaw {
     ///
     /// # Errors
     /// Returns [`PiControlError::InvalidArgument`] if `bitfield` was `0` or
-    /// if `dio_address` was not valid.
-    ///
-    /// # Panics
-    /// Will panic if the RevPi is not a RevPi Core or RevPi Connect, or if the
-    /// bridge wasn't running.
+    /// if `dio_address` was not valid,
+    /// [`PiControlError::NotSupportedOnThisModel`] if the RevPi is not a
+    /// RevPi Core or RevPi Connect and [`PiControlError::BridgeNotRunning`] if
+    /// the bridge wasn't running.
     ///
     /// # Examples
     /// ```no_run
@@ -502,6 +817,10 @@ impl PiControlRaw {
     /// let raw = PiControlRaw::new().unwrap();
     /// raw.dio_reset_counter(31, 0b10011001_01100110).unwrap();
     /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
     pub fn dio_reset_counter(&self, dio_address: u8, bitfield: u16) -> Result<(), PiControlError> {
         // this is specified in the kernel module
         ensure!(bitfield != 0, PiControlError::InvalidArgument("bitfield"));
@@ -510,79 +829,234 @@ impl PiControlRaw {
             i16uBitfield: bitfield,
         };
         unsafe { raw::dio_reset_counter(self.0.as_raw_fd(), &mut ctr) }.map_err(|e| match e {
-            libc::EFAULT => panic!("bridge wasn't running"),
-            libc::EPERM => panic!("this isn't a revpi core or connect"),
-            libc::EINVAL => PiControlError::InvalidArgument("dio_address"),
-            _ => unreachable!(),
+            IoctlError::InvalidArgument => PiControlError::InvalidArgument("dio_address"),
+            e => model_error(e),
+        })?;
+        Ok(())
+    }
+
+    /// Calibrates the `channels` of the AIO module at `address`, a bitmap
+    /// with one bit per channel. `x_val` is the value measured at the
+    /// calibration point and `y_val` the value expected there, `mode`
+    /// selects the kind of calibration as defined by the kernel module.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if `channels` was `0` or
+    /// if there is no AIO at `address` and a [`PiControlError::IoError`] e.g.
+    /// if the bridge wasn't running.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// raw.aio_calibrate(32, 0, 0b0001, 9800, 10000).unwrap();
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn aio_calibrate(
+        &self,
+        address: u8,
+        mode: u8,
+        channels: u8,
+        x_val: u16,
+        y_val: u16,
+    ) -> Result<(), PiControlError> {
+        ensure!(channels != 0, PiControlError::InvalidArgument("channels"));
+        let mut cal = SAIOCalibrate {
+            address,
+            mode,
+            channels,
+            reserved: 0,
+            x_val,
+            y_val,
+        };
+        unsafe { raw::aio_calibrate(self.0.as_raw_fd(), &mut cal) }.map_err(|e| match e {
+            IoctlError::InvalidArgument => PiControlError::InvalidArgument("address"),
+            IoctlError::Fault => PiControlError::BridgeNotRunning,
+            e => e.into(),
         })?;
         Ok(())
     }
 
+    /// Returns the switching cycles the relays of the RO at `address`
+    /// counted, relay 1 first.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::InvalidArgument`] if there is no RO at
+    /// `address`, [`PiControlError::BridgeNotRunning`] if the bridge wasn't
+    /// running and [`PiControlError::NotSupportedByDriver`] if the driver is
+    /// too old to read the counters.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// let raw = PiControlRaw::new().unwrap();
+    /// println!("{:?}", raw.ro_counters(32).unwrap());
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip(self), ret, err)
+    )]
+    pub fn ro_counters(&self, address: u8) -> Result<[u32; REVPI_RO_NUM_RELAYS], PiControlError> {
+        self.require(Capabilities::RO_COUNTERS)?;
+        let mut ctr = RevPiROCounters {
+            addr: address,
+            ..Default::default()
+        };
+        unsafe { raw::ro_get_counter(self.0.as_raw_fd(), &mut ctr) }.map_err(|e| match e {
+            IoctlError::InvalidArgument => PiControlError::InvalidArgument("address"),
+            IoctlError::NotTty => self.unsupported(Capabilities::RO_COUNTERS),
+            e => bridge_error(e),
+        })?;
+        Ok(ctr.counter)
+    }
+
+    /// Returns the information of the first device of type `module_type`
+    ///
+    /// # Errors
+    /// If there is no such device, [`PiControlError::DeviceNotFound`] is
+    /// returned with address `0`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::{DeviceInfo, ModuleType, PiControlRaw};
+    /// let raw = PiControlRaw::new().unwrap();
+    /// let dio = DeviceInfo::from(raw.get_device_info_by_type(ModuleType::Dio).unwrap());
+    /// println!("DIO at {}", dio.address());
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn get_device_info_by_type(
+        &self,
+        module_type: ModuleType,
+    ) -> Result<SDeviceInfo, PiControlError> {
+        // the driver searches by type if the type is set
+        let mut dev = SDeviceInfo {
+            i16uModuleType: module_type.into(),
+            ..Default::default()
+        };
+        unsafe { raw::get_device_info(self.0.as_raw_fd(), &mut dev) }.map_err(|e| match e {
+            IoctlError::NoDevice => PiControlError::DeviceNotFound(0),
+            e => e.into(),
+        })?;
+        Ok(dev)
+    }
+
     /// Returns the last error message of the RevPi
     ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver couldn't return
+    /// the message.
+    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// let msg = raw.get_last_message();
+    /// let msg = raw.get_last_message().unwrap();
     /// println!("{}", msg.into_string().unwrap());
     /// ```
-    pub fn get_last_message(&self) -> CString {
+    pub fn get_last_message(&self) -> Result<CString, PiControlError> {
         let mut msg = Vec::with_capacity(REV_PI_ERROR_MSG_LEN);
         unsafe {
-            // no error should occur because we are responsible for all arguments
-            raw::get_last_message(self.0.as_raw_fd(), msg.as_mut_ptr() as *mut i8).unwrap();
+            raw::get_last_message(self.0.as_raw_fd(), msg.as_mut_ptr() as *mut i8)?;
             let len = libc::strlen(msg.as_ptr() as *const libc::c_char);
-            msg.set_len(len + 1);
+            msg.set_len(len);
         }
-        // Should never panic, we trust the api and checked this before
-        CString::new(msg).unwrap()
+        // Should never panic, strlen stopped at the first nul byte
+        Ok(CString::new(msg).unwrap())
     }
 
-    fn inner_stop_io(&self, mut stop: i32) {
-        unsafe { raw::stop_io(self.0.as_raw_fd(), &mut stop) }
-            .map_err(|e| match e {
-                libc::EFAULT => panic!("bridge wasn't running"),
-                _ => unreachable!(),
-            })
-            .unwrap();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), ret, err)
+    )]
+    fn inner_stop_io(&self, mut stop: i32) -> Result<IoState, PiControlError> {
+        let stopped =
+            unsafe { raw::stop_io(self.0.as_raw_fd(), &mut stop) }.map_err(bridge_error)?;
+        // the driver returns whether the io is stopped now
+        let (state, encoded) = match stopped {
+            0 => (IoState::Running, IO_STATE_RUNNING),
+            _ => (IoState::Stopped, IO_STATE_STOPPED),
+        };
+        self.1.store(encoded, Ordering::Relaxed);
+        Ok(state)
     }
 
     /// Stops all I/O communication. piControl will write `0` to all outputs and
     /// inputs won't be updated.
     ///
+    /// Returns the resulting state reported by the driver.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::BridgeNotRunning`] if the bridge wasn't
+    /// running.
+    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// raw.stop_io();
+    /// raw.stop_io().unwrap();
     /// ```
-    pub fn stop_io(&self) {
-        self.inner_stop_io(1);
+    pub fn stop_io(&self) -> Result<IoState, PiControlError> {
+        self.inner_stop_io(1)
     }
 
-    /// Stops I/O communication
+    /// Starts I/O communication
+    ///
+    /// Returns the resulting state reported by the driver.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::BridgeNotRunning`] if the bridge wasn't
+    /// running.
     ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// raw.start_io();
+    /// raw.start_io().unwrap();
     /// ```
-    pub fn start_io(&self) {
-        self.inner_stop_io(0);
+    pub fn start_io(&self) -> Result<IoState, PiControlError> {
+        self.inner_stop_io(0)
     }
 
     /// Toggles I/O communication
     ///
+    /// Returns the resulting state reported by the driver.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::BridgeNotRunning`] if the bridge wasn't
+    /// running.
+    ///
     /// # Examples
     /// ```no_run
-    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// # use revpi::picontrol::raw::{IoState, PiControlRaw};
     /// let raw = PiControlRaw::new().unwrap();
-    /// raw.toggle_io();
+    /// if raw.toggle_io().unwrap() == IoState::Stopped {
+    ///     println!("io stopped");
+    /// }
     /// ```
-    pub fn toggle_io(&self) {
-        self.inner_stop_io(2);
+    pub fn toggle_io(&self) -> Result<IoState, PiControlError> {
+        self.inner_stop_io(2)
+    }
+
+    /// Returns the I/O state the driver reported to the last call of
+    /// [`stop_io`](Self::stop_io), [`start_io`](Self::start_io) or
+    /// [`toggle_io`](Self::toggle_io) on this object, `None` if none of them
+    /// was called yet.
+    ///
+    /// The driver has no request to query the state without changing it, so
+    /// changes made through other file descriptors, e.g. by other processes,
+    /// aren't reflected.
+    pub fn io_state(&self) -> Option<IoState> {
+        match self.1.load(Ordering::Relaxed) {
+            IO_STATE_RUNNING => Some(IoState::Running),
+            IO_STATE_STOPPED => Some(IoState::Stopped),
+            _ => None,
+        }
     }
 
     /// Activates a watchdog. `millis` is the watchdog period in milliseconds.
@@ -590,37 +1064,60 @@ impl PiControlRaw {
     ///
     /// For more information see `man picontrol_ioctl`
     ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver rejected the
+    /// watchdog.
+    ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::PiControlRaw;
     /// let raw = PiControlRaw::new().unwrap();
-    /// raw.set_output_watchdog(20);
+    /// raw.set_output_watchdog(20).unwrap();
     /// ```
-    pub fn set_output_watchdog(&self, mut millis: u32) {
-        unsafe { raw::set_output_watchdog(self.0.as_raw_fd(), &mut millis) }.unwrap();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn set_output_watchdog(&self, mut millis: u32) -> Result<(), PiControlError> {
+        unsafe { raw::set_output_watchdog(self.0.as_raw_fd(), &mut millis) }?;
+        Ok(())
     }
 
     /// Blocks until an event occurs in the piControl driver.
     ///
-    /// Returns the event.
+    /// Returns the event. Events newer drivers send, but this crate doesn't
+    /// know yet, are skipped.
+    ///
+    /// # Errors
+    /// Same as [`try_wait_for_event`](Self::try_wait_for_event).
     ///
     /// # Examples
     /// ```no_run
     /// # use revpi::picontrol::raw::{PiControlRaw, raw};
     /// use raw::Event;
     /// let raw = PiControlRaw::new().unwrap();
-    /// let event = raw.wait_for_event();
+    /// let event = raw.wait_for_event().unwrap();
     /// if matches!(Event::Reset, event) {
     ///     println!("piControl was reset");
     /// }
     /// ```
-    pub fn wait_for_event(&self) -> Event {
-        let mut event = 0i32;
-        unsafe { raw::wait_for_event(self.0.as_raw_fd(), &mut event) }.unwrap();
-        // TODO from primitive
-        match event {
-            1 => Event::Reset,
-            _ => panic!("an unspecified event occured"),
-        }
+    pub fn wait_for_event(&self) -> Result<Event, PiControlError> {
+        self.try_wait_for_event()
+    }
+}
+
+// maps the error of an ioctl that accesses values
+fn bridge_error(e: IoctlError) -> PiControlError {
+    match e {
+        IoctlError::Fault => PiControlError::BridgeNotRunning,
+        e => e.into(),
+    }
+}
+
+// maps the error of an ioctl only supported by some models
+fn model_error(e: IoctlError) -> PiControlError {
+    match e {
+        IoctlError::NotPermitted => PiControlError::NotSupportedOnThisModel,
+        e => bridge_error(e),
     }
 }
@@ -0,0 +1,307 @@
+//! Decoded device information
+
+use super::{raw::SDeviceInfo, PiControlRaw};
+use crate::picontrol::PiControlError;
+use std::{fmt, ops::Range};
+
+// set by the driver for configured modules that aren't connected
+const NOT_CONNECTED: u16 = 0x8000;
+
+/// Type of a module as reported by the driver in
+/// [`SDeviceInfo::i16uModuleType`]
+///
+/// These are the same numbers as the product types of the config, see
+/// `rsc::ProductType`, but available without the `rsc` feature.
+///
+/// # Examples
+/// ```
+/// # use revpi::picontrol::raw::ModuleType;
+/// assert_eq!(ModuleType::from_u16(96), ModuleType::Dio);
+/// assert_eq!(ModuleType::from_u16(0x8000 | 96).to_string(), "RevPi DIO");
+/// assert_eq!(u16::from(ModuleType::Dio), 96);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModuleType {
+    GatewayCanOpen,
+    GatewayCcLink,
+    GatewayDeviceNet,
+    GatewayEtherCat,
+    GatewayEtherNetIp,
+    GatewayPowerlink,
+    GatewayProfibus,
+    GatewayProfinet,
+    GatewaySercos3,
+    GatewaySerial,
+    GatewayModbusRtu,
+    GatewayModbusTcp,
+    GatewayDmx,
+    Core,
+    Dio,
+    Di,
+    Do,
+    Aio,
+    Compact,
+    Connect,
+    ConCan,
+    ConMbus,
+    ConBt,
+    Mio,
+    Flat,
+    Ro,
+    /// Any other module type
+    Unknown(u16),
+}
+
+impl ModuleType {
+    /// Decodes a module type, ignoring the bit the driver sets for modules
+    /// that are configured but not connected
+    pub fn from_u16(v: u16) -> Self {
+        use ModuleType::*;
+        match v & !NOT_CONNECTED {
+            71 => GatewayCanOpen,
+            72 => GatewayCcLink,
+            73 => GatewayDeviceNet,
+            74 => GatewayEtherCat,
+            75 => GatewayEtherNetIp,
+            76 => GatewayPowerlink,
+            77 => GatewayProfibus,
+            78 => GatewayProfinet,
+            81 => GatewaySercos3,
+            82 => GatewaySerial,
+            92 => GatewayModbusRtu,
+            93 => GatewayModbusTcp,
+            100 => GatewayDmx,
+            95 => Core,
+            96 => Dio,
+            97 => Di,
+            98 => Do,
+            103 => Aio,
+            104 => Compact,
+            105 => Connect,
+            109 => ConCan,
+            110 => ConMbus,
+            111 => ConBt,
+            118 => Mio,
+            135 => Flat,
+            137 => Ro,
+            v => Unknown(v),
+        }
+    }
+}
+
+impl From<ModuleType> for u16 {
+    fn from(t: ModuleType) -> Self {
+        use ModuleType::*;
+        match t {
+            GatewayCanOpen => 71,
+            GatewayCcLink => 72,
+            GatewayDeviceNet => 73,
+            GatewayEtherCat => 74,
+            GatewayEtherNetIp => 75,
+            GatewayPowerlink => 76,
+            GatewayProfibus => 77,
+            GatewayProfinet => 78,
+            GatewaySercos3 => 81,
+            GatewaySerial => 82,
+            GatewayModbusRtu => 92,
+            GatewayModbusTcp => 93,
+            GatewayDmx => 100,
+            Core => 95,
+            Dio => 96,
+            Di => 97,
+            Do => 98,
+            Aio => 103,
+            Compact => 104,
+            Connect => 105,
+            ConCan => 109,
+            ConMbus => 110,
+            ConBt => 111,
+            Mio => 118,
+            Flat => 135,
+            Ro => 137,
+            Unknown(v) => v,
+        }
+    }
+}
+
+impl From<u16> for ModuleType {
+    fn from(v: u16) -> Self {
+        Self::from_u16(v)
+    }
+}
+
+impl fmt::Display for ModuleType {
+    /// Writes the product name, e.g. `RevPi DIO`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ModuleType::*;
+        let name = match self {
+            GatewayCanOpen => "RevPi Gate CANopen",
+            GatewayCcLink => "RevPi Gate CC-Link",
+            GatewayDeviceNet => "RevPi Gate DeviceNet",
+            GatewayEtherCat => "RevPi Gate EtherCAT",
+            GatewayEtherNetIp => "RevPi Gate EtherNet/IP",
+            GatewayPowerlink => "RevPi Gate POWERLINK",
+            GatewayProfibus => "RevPi Gate PROFIBUS",
+            GatewayProfinet => "RevPi Gate PROFINET",
+            GatewaySercos3 => "RevPi Gate SERCOS III",
+            GatewaySerial => "RevPi Gate Serial",
+            GatewayModbusRtu => "RevPi Gate Modbus RTU",
+            GatewayModbusTcp => "RevPi Gate Modbus TCP",
+            GatewayDmx => "RevPi Gate DMX",
+            Core => "RevPi Core",
+            Dio => "RevPi DIO",
+            Di => "RevPi DI",
+            Do => "RevPi DO",
+            Aio => "RevPi AIO",
+            Compact => "RevPi Compact",
+            Connect => "RevPi Connect",
+            ConCan => "RevPi Con CAN",
+            ConMbus => "RevPi Con M-Bus",
+            ConBt => "RevPi Con BT",
+            Mio => "RevPi MIO",
+            Flat => "RevPi Flat",
+            Ro => "RevPi RO",
+            Unknown(v) => return write!(f, "unknown module type {}", v),
+        };
+        f.write_str(name)
+    }
+}
+
+/// Firmware version of a device, ordered by major, minor and SVN revision
+///
+/// # Examples
+/// ```
+/// # use revpi::picontrol::raw::FirmwareVersion;
+/// let version = FirmwareVersion::new(1, 2, 345);
+/// assert_eq!(version.to_string(), "1.2.345");
+/// assert!(version < FirmwareVersion::new(1, 10, 0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    /// SVN revision of the firmware build
+    pub svn: u32,
+}
+
+impl FirmwareVersion {
+    /// Creates a version from its parts
+    pub fn new(major: u16, minor: u16, svn: u32) -> Self {
+        Self { major, minor, svn }
+    }
+}
+
+impl fmt::Display for FirmwareVersion {
+    /// Writes `major.minor.svn`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.svn)
+    }
+}
+
+/// Information about a connected device, decoded from [`SDeviceInfo`]
+///
+/// All ranges are absolute addresses in the processimage and can be passed to
+/// the methods of [`PiControlRaw`] directly.
+///
+/// # Examples
+/// ```no_run
+/// # use revpi::picontrol::raw::{DeviceInfo, PiControlRaw};
+/// let raw = PiControlRaw::new().unwrap();
+/// let dev = DeviceInfo::from(raw.get_device_info(32).unwrap());
+/// println!("{:?}: {:?}", dev.inputs(), dev.read_inputs(&raw).unwrap());
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DeviceInfo(SDeviceInfo);
+
+impl DeviceInfo {
+    /// Returns the underlying [`SDeviceInfo`]
+    pub fn raw(&self) -> &SDeviceInfo {
+        &self.0
+    }
+
+    /// Returns the position of the device as configured in PiCtory
+    pub fn address(&self) -> u8 {
+        self.0.i8uAddress
+    }
+
+    /// Returns the serial number of the device
+    pub fn serial_number(&self) -> u32 {
+        self.0.i32uSerialNumber
+    }
+
+    /// Returns the type of the module
+    pub fn module_type(&self) -> ModuleType {
+        ModuleType::from_u16(self.0.i16uModuleType)
+    }
+
+    /// Returns the version of the firmware running on the device
+    pub fn firmware_version(&self) -> FirmwareVersion {
+        FirmwareVersion::new(
+            self.0.i16uSW_Major,
+            self.0.i16uSW_Minor,
+            self.0.i32uSVN_Revision,
+        )
+    }
+
+    /// Returns whether the module is connected. The driver also reports
+    /// modules that are configured, but weren't found.
+    pub fn is_connected(&self) -> bool {
+        self.0.i16uModuleType & NOT_CONNECTED == 0
+    }
+
+    /// Returns the type of the module, which can be compared with the
+    /// configured one, see [`Device::product`](crate::rsc::Device::product)
+    #[cfg(feature = "rsc")]
+    pub fn product_type(&self) -> crate::rsc::ProductType {
+        self.0.i16uModuleType.into()
+    }
+
+    /// Returns whether the device is active, i.e. configured and connected
+    pub fn is_active(&self) -> bool {
+        self.0.i8uActive != 0
+    }
+
+    /// Returns the addresses of the inputs of the device
+    pub fn inputs(&self) -> Range<u16> {
+        self.0.i16uInputOffset..self.0.i16uInputOffset + self.0.i16uInputLength
+    }
+
+    /// Returns the addresses of the outputs of the device
+    pub fn outputs(&self) -> Range<u16> {
+        self.0.i16uOutputOffset..self.0.i16uOutputOffset + self.0.i16uOutputLength
+    }
+
+    /// Returns the addresses of the config area of the device
+    pub fn config(&self) -> Range<u16> {
+        self.0.i16uConfigOffset..self.0.i16uConfigOffset + self.0.i16uConfigLength
+    }
+
+    fn read(raw: &PiControlRaw, range: Range<u16>) -> Result<Vec<u8>, PiControlError> {
+        let mut bytes = vec![0u8; range.len()];
+        // the range was given by the driver, so it lies inside the processimage
+        unsafe { raw.get_bytes(range.start, &mut bytes) }?;
+        Ok(bytes)
+    }
+
+    /// Reads all inputs of the device at once.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if reading the processimage fails.
+    pub fn read_inputs(&self, raw: &PiControlRaw) -> Result<Vec<u8>, PiControlError> {
+        Self::read(raw, self.inputs())
+    }
+
+    /// Reads all outputs of the device at once.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if reading the processimage fails.
+    pub fn read_outputs(&self, raw: &PiControlRaw) -> Result<Vec<u8>, PiControlError> {
+        Self::read(raw, self.outputs())
+    }
+}
+
+impl From<SDeviceInfo> for DeviceInfo {
+    fn from(info: SDeviceInfo) -> Self {
+        Self(info)
+    }
+}
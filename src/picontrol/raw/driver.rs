@@ -0,0 +1,187 @@
+//! Version and capabilities of the piControl driver
+
+use super::{
+    raw::{self, Event, IoctlError},
+    PiControlRaw,
+};
+use crate::picontrol::PiControlError;
+use std::{fmt, fs, ops::BitOr, os::unix::prelude::AsRawFd, str::FromStr, sync::atomic::Ordering};
+
+// version of the driver, as exported by the kernel for every module
+const DRIVER_VERSION: &str = "/sys/module/piControl/version";
+
+/// Version of the piControl driver, see [`PiControlRaw::driver_version`]
+///
+/// # Examples
+/// ```
+/// # use revpi::picontrol::raw::DriverVersion;
+/// let version: DriverVersion = "2.1.0-rc1".parse().unwrap();
+/// assert_eq!(version, DriverVersion::new(2, 1, 0));
+/// assert!(version >= DriverVersion::new(2, 0, 7));
+/// assert!("unknown".parse::<DriverVersion>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DriverVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl DriverVersion {
+    /// Creates a version from its parts
+    pub fn new(major: u16, minor: u16, patch: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl FromStr for DriverVersion {
+    type Err = PiControlError;
+
+    /// Parses `major.minor.patch`, ignoring suffixes like `-rc1`. Missing
+    /// parts are `0`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PiControlError::InvalidArgument("version");
+        let s = s.trim();
+        let end = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(s.len());
+        let mut parts = s[..end].split('.').map(|part| part.parse::<u16>());
+        let major = parts.next().ok_or_else(invalid)?.map_err(|_| invalid())?;
+        let mut next = || parts.next().unwrap_or(Ok(0)).map_err(|_| invalid());
+        Ok(Self::new(major, next()?, next()?))
+    }
+}
+
+impl fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Requests a driver might not know, returned by
+/// [`PiControlRaw::capabilities`]
+///
+/// # Examples
+/// ```
+/// # use revpi::picontrol::raw::Capabilities;
+/// let caps = Capabilities::all();
+/// assert!(caps.contains(Capabilities::WAIT_FOR_EVENT | Capabilities::RO_COUNTERS));
+/// assert_eq!(format!("{:?}", Capabilities::RO_COUNTERS), "Capabilities(RO_COUNTERS)");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// [`PiControlRaw::wait_for_event`]
+    pub const WAIT_FOR_EVENT: Capabilities = Capabilities(0x01);
+    /// [`PiControlRaw::ro_counters`]
+    pub const RO_COUNTERS: Capabilities = Capabilities(0x02);
+
+    const NAMES: [(Capabilities, &'static str); 2] = [
+        (Capabilities::WAIT_FOR_EVENT, "WAIT_FOR_EVENT"),
+        (Capabilities::RO_COUNTERS, "RO_COUNTERS"),
+    ];
+
+    /// Returns all capabilities
+    pub fn all() -> Self {
+        Capabilities::WAIT_FOR_EVENT | Capabilities::RO_COUNTERS
+    }
+
+    /// Returns whether all capabilities of `other` are set
+    pub fn contains(&self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        Capabilities(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Debug for Capabilities {
+    /// Lists the names of the set capabilities
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = Capabilities::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        write!(f, "Capabilities({})", names.join(" | "))
+    }
+}
+
+impl PiControlRaw {
+    /// Returns the version of the loaded piControl driver, `None` if it
+    /// can't be read or parsed
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use revpi::picontrol::raw::PiControlRaw;
+    /// if let Some(version) = PiControlRaw::driver_version() {
+    ///     println!("piControl {}", version);
+    /// }
+    /// ```
+    pub fn driver_version() -> Option<DriverVersion> {
+        fs::read_to_string(DRIVER_VERSION).ok()?.parse().ok()
+    }
+
+    /// Returns the requests the driver is known to support
+    ///
+    /// There is no way to ask the driver, and trying a request might block or
+    /// change the state of the RevPi. So every request is assumed to be
+    /// supported until the driver rejected it as unknown, from then on its
+    /// capability is cleared and the request fails with
+    /// [`PiControlError::NotSupportedByDriver`] right away. Higher layers can
+    /// check this to disable features, e.g. watching for resets.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities(Capabilities::all().0 & !self.2.load(Ordering::Relaxed))
+    }
+
+    // fails right away if `capability` is known to be missing
+    pub(super) fn require(&self, capability: Capabilities) -> Result<(), PiControlError> {
+        match self.capabilities().contains(capability) {
+            true => Ok(()),
+            false => Err(PiControlError::NotSupportedByDriver),
+        }
+    }
+
+    // maps the error of a request the driver rejected as unknown, clearing
+    // `capability`
+    pub(super) fn unsupported(&self, capability: Capabilities) -> PiControlError {
+        self.2.fetch_or(capability.0, Ordering::Relaxed);
+        PiControlError::NotSupportedByDriver
+    }
+
+    /// Blocks until an event occurs in the piControl driver, like
+    /// [`wait_for_event`](Self::wait_for_event)
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::NotSupportedByDriver`] if the driver doesn't
+    /// know events, see [`capabilities`](Self::capabilities), and a
+    /// [`PiControlError::IoError`] if waiting failed otherwise.
+    pub fn try_wait_for_event(&self) -> Result<Event, PiControlError> {
+        self.require(Capabilities::WAIT_FOR_EVENT)?;
+        loop {
+            let mut event = 0i32;
+            // the request takes no input, so these only mean it is unknown
+            unsafe { raw::wait_for_event(self.0.as_raw_fd(), &mut event) }.map_err(
+                |e| match e {
+                    IoctlError::InvalidArgument | IoctlError::NotTty => {
+                        self.unsupported(Capabilities::WAIT_FOR_EVENT)
+                    }
+                    e => e.into(),
+                },
+            )?;
+            if event == Event::Reset as i32 {
+                return Ok(Event::Reset);
+            }
+        }
+    }
+}
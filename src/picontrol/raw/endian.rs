@@ -0,0 +1,93 @@
+//! Typed access to multi-byte values with an explicit byte order
+
+use super::PiControlRaw;
+use crate::picontrol::PiControlError;
+#[cfg(feature = "toml")]
+use serde::Deserialize;
+use std::mem::size_of;
+
+/// Byte order of a multi-byte value, e.g. for
+/// [`PiControlRaw::get_i64`]
+///
+/// # Examples
+/// ```no_run
+/// # use revpi::picontrol::raw::{Endianness, PiControlRaw};
+/// let raw = PiControlRaw::new().unwrap();
+/// // a big endian float, sent by a PLC through a gateway
+/// let temperature = unsafe { raw.get_f32(75, Endianness::Big) }.unwrap();
+/// unsafe { raw.set_i64(83, -42, Endianness::Big) }.unwrap();
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+#[cfg_attr(feature = "toml", derive(Deserialize))]
+#[cfg_attr(feature = "toml", serde(rename_all = "lowercase"))]
+pub enum Endianness {
+    /// Least significant byte first, like the rest of the processimage
+    #[default]
+    Little,
+    /// Most significant byte first, common for PROFINET payloads
+    Big,
+}
+
+macro_rules! impl_typed {
+    ($($t:ident: $get:ident, $set:ident;)*) => {
+        impl PiControlRaw {
+            $(
+                #[doc = concat!("Reads a `", stringify!($t), "` from the processimage, stored")]
+                /// with the byte order `endianness`.
+                ///
+                /// # Errors
+                /// Same as [`get_bytes`](Self::get_bytes).
+                ///
+                /// # Safety
+                /// You have to ensure that `address` is valid and points to the right value,
+                /// otherwise you might get something unexpected.
+                pub unsafe fn $get(
+                    &self,
+                    address: u16,
+                    endianness: Endianness,
+                ) -> Result<$t, PiControlError> {
+                    let mut bytes = [0u8; size_of::<$t>()];
+                    self.get_bytes(address, &mut bytes)?;
+                    Ok(match endianness {
+                        Endianness::Little => $t::from_le_bytes(bytes),
+                        Endianness::Big => $t::from_be_bytes(bytes),
+                    })
+                }
+
+                #[doc = concat!("Writes a `", stringify!($t), "` to the processimage, stored")]
+                /// with the byte order `endianness`.
+                ///
+                /// # Errors
+                /// Same as [`set_bytes`](Self::set_bytes).
+                ///
+                /// # Safety
+                /// You have to ensure that `address` is valid and points to the right value,
+                /// otherwise you might write in the wrong place.
+                pub unsafe fn $set(
+                    &self,
+                    address: u16,
+                    value: $t,
+                    endianness: Endianness,
+                ) -> Result<(), PiControlError> {
+                    let bytes = match endianness {
+                        Endianness::Little => value.to_le_bytes(),
+                        Endianness::Big => value.to_be_bytes(),
+                    };
+                    self.set_bytes(address, &bytes)
+                }
+            )*
+        }
+    };
+}
+
+// get_word and get_dword stay as they are, always little endian
+impl_typed! {
+    u16: get_u16, set_u16;
+    i16: get_i16, set_i16;
+    u32: get_u32, set_u32;
+    i32: get_i32, set_i32;
+    u64: get_u64, set_u64;
+    i64: get_i64, set_i64;
+    f32: get_f32, set_f32;
+    f64: get_f64, set_f64;
+}
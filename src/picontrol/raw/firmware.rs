@@ -0,0 +1,186 @@
+//! Checked firmware updates of modules
+
+use super::{IoState, ModuleType, PiControlRaw};
+use crate::{
+    picontrol::{PiControlError, BRIDGE_TIMEOUT},
+    util::ensure,
+};
+use std::{
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+// virtual devices are placed from this address on, they have no firmware
+const FIRST_VIRTUAL_ADDRESS: u8 = 64;
+
+/// Step of a firmware update, reported to
+/// [`FirmwareUpdater::on_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateStep {
+    /// Checking the model and the connected modules
+    Checking,
+    /// Stopping I/O communication
+    StoppingIo,
+    /// Transferring the firmware, the module must not lose power now
+    Updating,
+    /// Waiting for the piBridge and the module to come up again
+    Waiting,
+    /// Restarting I/O communication
+    RestartingIo,
+    /// The update finished successfully
+    Done,
+}
+
+/// Firmware update of a module, returned by
+/// [`PiControlRaw::firmware_updater`]
+///
+/// Unlike [`PiControlRaw::update_device_firmware`], [`run`](Self::run) makes
+/// sure the update can't hit the wrong module:
+/// 1. the RevPi has to be a RevPi Core or RevPi Connect and exactly one module
+///    has to be connected, the one to update
+/// 2. I/O communication is stopped
+/// 3. the firmware is updated
+/// 4. the piBridge and the module are polled until they are up again
+/// 5. I/O communication is restarted, unless it was stopped before
+///
+/// I/O communication is restarted even if the update failed.
+///
+/// # Examples
+/// ```no_run
+/// # use revpi::picontrol::raw::PiControlRaw;
+/// let raw = PiControlRaw::new().unwrap();
+/// raw.firmware_updater(32)
+///     .on_progress(|step| println!("{:?}", step))
+///     .run()
+///     .unwrap();
+/// ```
+pub struct FirmwareUpdater<'a> {
+    raw: &'a PiControlRaw,
+    module: u8,
+    timeout: Duration,
+    on_progress: Option<Box<dyn FnMut(UpdateStep) + 'a>>,
+}
+
+impl fmt::Debug for FirmwareUpdater<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FirmwareUpdater")
+            .field("raw", &self.raw)
+            .field("module", &self.module)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> FirmwareUpdater<'a> {
+    /// Sets how long to wait for the piBridge and the module after the
+    /// update, [`BRIDGE_TIMEOUT`] by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets a callback that is called whenever the update enters a new step
+    pub fn on_progress<F: FnMut(UpdateStep) + 'a>(mut self, f: F) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    fn progress(&mut self, step: UpdateStep) {
+        if let Some(f) = self.on_progress.as_mut() {
+            f(step);
+        }
+    }
+
+    // the module to update, after checking model and connected modules
+    fn check(&self) -> Result<u8, PiControlError> {
+        let devices = self.raw.get_device_info_list()?;
+        ensure!(
+            devices.iter().any(|dev| dev.address() == 0
+                && matches!(dev.module_type(), ModuleType::Core | ModuleType::Connect)),
+            PiControlError::NotSupportedOnThisModel
+        );
+        let modules: Vec<_> = devices
+            .iter()
+            .filter(|dev| {
+                dev.is_connected() && dev.address() != 0 && dev.address() < FIRST_VIRTUAL_ADDRESS
+            })
+            .collect();
+        ensure!(
+            modules.len() == 1,
+            PiControlError::ModuleCount(modules.len())
+        );
+        ensure!(
+            self.module == 0 || modules[0].address() == self.module,
+            PiControlError::DeviceNotFound(self.module)
+        );
+        Ok(modules[0].address())
+    }
+
+    fn wait(&self, module: u8) -> Result<(), PiControlError> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            // the device list is only valid while the piBridge is running
+            if self.raw.bridge_running()?
+                && self
+                    .raw
+                    .get_device_info_list()?
+                    .iter()
+                    .any(|dev| dev.address() == module && dev.is_connected())
+            {
+                return Ok(());
+            }
+            ensure!(Instant::now() < deadline, PiControlError::Timeout);
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Updates the firmware, see [`FirmwareUpdater`]
+    ///
+    /// The module still might get bricked if it loses power during the
+    /// update.
+    ///
+    /// # Errors
+    /// Returns [`PiControlError::NotSupportedOnThisModel`] if the RevPi is not
+    /// a RevPi Core or RevPi Connect, [`PiControlError::ModuleCount`] if not
+    /// exactly one module is connected, [`PiControlError::DeviceNotFound`] if
+    /// it isn't the module given to
+    /// [`PiControlRaw::firmware_updater`] and [`PiControlError::Timeout`] if
+    /// the module didn't come up in time after the update.
+    pub fn run(mut self) -> Result<(), PiControlError> {
+        self.progress(UpdateStep::Checking);
+        let module = self.check()?;
+        let was_stopped = self.raw.io_state() == Some(IoState::Stopped);
+        self.progress(UpdateStep::StoppingIo);
+        self.raw.stop_io()?;
+        self.progress(UpdateStep::Updating);
+        // exactly one module is connected, as the driver requires
+        let mut result = unsafe { self.raw.update_device_firmware(module as u32) };
+        if result.is_ok() {
+            self.progress(UpdateStep::Waiting);
+            result = self.wait(module);
+        }
+        if !was_stopped {
+            self.progress(UpdateStep::RestartingIo);
+            // the error of the update is more important
+            result = result.and(self.raw.start_io().map(|_| ()));
+        }
+        if result.is_ok() {
+            self.progress(UpdateStep::Done);
+        }
+        result
+    }
+}
+
+impl PiControlRaw {
+    /// Prepares a checked firmware update of the module at `module`, see
+    /// [`FirmwareUpdater`]. If `module` is `0`, the connected module is
+    /// updated, whatever its address.
+    pub fn firmware_updater(&self, module: u8) -> FirmwareUpdater<'_> {
+        FirmwareUpdater {
+            raw: self,
+            module,
+            timeout: BRIDGE_TIMEOUT,
+            on_progress: None,
+        }
+    }
+}
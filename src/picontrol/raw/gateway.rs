@@ -0,0 +1,100 @@
+//! Download of configs to master gateways
+
+use super::{
+    raw::{self, SConfigData, CONFIG_DATA_LEN},
+    PiControlRaw,
+};
+use crate::picontrol::PiControlError;
+use std::os::unix::prelude::AsRawFd;
+
+/// Side of the RevPi a gateway is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Download of a config to master gateways, returned by
+/// [`PiControlRaw::gateway_config`]
+///
+/// I/O communication is stopped while the session exists. The data is sent
+/// with [`send`](Self::send) and communication is restarted with
+/// [`start`](Self::start), or when the session is dropped.
+///
+/// # Examples
+/// ```no_run
+/// # use revpi::picontrol::raw::{PiControlRaw, Side};
+/// let raw = PiControlRaw::new().unwrap();
+/// let config = std::fs::read("gateway.cfg").unwrap();
+/// let mut session = raw.gateway_config().unwrap();
+/// session.send(Side::Left, &config).unwrap();
+/// session.start().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct GatewayConfigSession<'a> {
+    raw: &'a PiControlRaw,
+    started: bool,
+}
+
+impl<'a> GatewayConfigSession<'a> {
+    pub(super) fn new(raw: &'a PiControlRaw) -> Result<Self, PiControlError> {
+        // restarted by start or drop
+        unsafe { raw::config_stop(raw.0.as_raw_fd()) }.map_err(PiControlError::from)?;
+        Ok(Self {
+            raw,
+            started: false,
+        })
+    }
+
+    /// Sends `data` to the gateway on `side`, split into as many ioctls as
+    /// needed.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver rejected the data.
+    pub fn send(&mut self, side: Side, data: &[u8]) -> Result<(), PiControlError> {
+        for chunk in data.chunks(CONFIG_DATA_LEN) {
+            let mut config = SConfigData {
+                bLeft: (side == Side::Left) as u8,
+                // at most CONFIG_DATA_LEN
+                i16uLen: chunk.len() as u16,
+                ..Default::default()
+            };
+            config.acData[..chunk.len()].copy_from_slice(chunk);
+            // I/O communication was stopped in new
+            unsafe { raw::config_send(self.raw.0.as_raw_fd(), &mut config) }
+                .map_err(PiControlError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Restarts I/O communication, so the gateways run with the new config.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver couldn't restart.
+    pub fn start(mut self) -> Result<(), PiControlError> {
+        self.started = true;
+        unsafe { raw::config_start(self.raw.0.as_raw_fd()) }.map_err(PiControlError::from)?;
+        Ok(())
+    }
+}
+
+impl Drop for GatewayConfigSession<'_> {
+    fn drop(&mut self) {
+        if !self.started {
+            // nothing to report the error to, but I/O must not stay stopped
+            let _ = unsafe { raw::config_start(self.raw.0.as_raw_fd()) };
+        }
+    }
+}
+
+impl PiControlRaw {
+    /// Stops I/O communication to download a config to master gateways, see
+    /// [`GatewayConfigSession`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the driver couldn't stop I/O
+    /// communication.
+    pub fn gateway_config(&self) -> Result<GatewayConfigSession<'_>, PiControlError> {
+        GatewayConfigSession::new(self)
+    }
+}
@@ -1,7 +1,8 @@
 //! Raw bindings and struct definitions for piControl
 
 use libc;
-use std::os::unix::prelude::RawFd;
+use std::{io, os::unix::prelude::RawFd};
+use thiserror::Error;
 
 // TODO possibly do without libc?
 
@@ -12,14 +13,94 @@ pub const REV_PI_DEV_CNT_MAX: usize = 64;
 pub const REV_PI_ERROR_MSG_LEN: usize = 256;
 /// Length of the processimage
 pub const KB_PI_LEN: usize = 4096;
+/// Location of the piControl device
+pub const PICONTROL_DEVICE: &str = "/dev/piControl0";
 /// Location of the running config
 pub const PICONFIG_FILE: &str = "/etc/revpi/config.rsc";
 /// Location of the running config on wheezy
 pub const PICONFIG_FILE_WHEEZY: &str = "/opt/KUNBUS/config.rsc";
 
+/// Error returned by the ioctls, decoded from `errno`
+///
+/// What an error means depends on the ioctl, see the documentation of the
+/// functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+pub enum IoctlError {
+    /// `EFAULT`, mostly returned if the bridge wasn't running
+    #[error("bad address or piBridge not running")]
+    Fault,
+    /// `EINVAL`
+    #[error("invalid argument")]
+    InvalidArgument,
+    /// `ENOENT`
+    #[error("no such entry")]
+    NoEntry,
+    /// `ENOMEM`
+    #[error("out of memory")]
+    OutOfMemory,
+    /// `EBADF`
+    #[error("bad file descriptor")]
+    BadFileDescriptor,
+    /// `ENOTTY`, the file descriptor doesn't refer to piControl
+    #[error("inappropriate ioctl for device")]
+    NotTty,
+    /// `ENXIO`
+    #[error("no such device")]
+    NoDevice,
+    /// `EPERM`
+    #[error("operation not permitted")]
+    NotPermitted,
+    /// `ETIMEDOUT`
+    #[error("timed out")]
+    TimedOut,
+    /// Any other `errno`
+    #[error("ioctl failed with errno {0}")]
+    Other(i32),
+}
+
+impl IoctlError {
+    /// Decodes `errno`
+    pub fn from_errno(errno: i32) -> Self {
+        match errno {
+            libc::EFAULT => IoctlError::Fault,
+            libc::EINVAL => IoctlError::InvalidArgument,
+            libc::ENOENT => IoctlError::NoEntry,
+            libc::ENOMEM => IoctlError::OutOfMemory,
+            libc::EBADF => IoctlError::BadFileDescriptor,
+            libc::ENOTTY => IoctlError::NotTty,
+            libc::ENXIO => IoctlError::NoDevice,
+            libc::EPERM => IoctlError::NotPermitted,
+            libc::ETIMEDOUT => IoctlError::TimedOut,
+            errno => IoctlError::Other(errno),
+        }
+    }
+
+    /// Returns the `errno` this error was decoded from
+    pub fn errno(&self) -> i32 {
+        match *self {
+            IoctlError::Fault => libc::EFAULT,
+            IoctlError::InvalidArgument => libc::EINVAL,
+            IoctlError::NoEntry => libc::ENOENT,
+            IoctlError::OutOfMemory => libc::ENOMEM,
+            IoctlError::BadFileDescriptor => libc::EBADF,
+            IoctlError::NotTty => libc::ENOTTY,
+            IoctlError::NoDevice => libc::ENXIO,
+            IoctlError::NotPermitted => libc::EPERM,
+            IoctlError::TimedOut => libc::ETIMEDOUT,
+            IoctlError::Other(errno) => errno,
+        }
+    }
+}
+
+impl From<IoctlError> for io::Error {
+    fn from(e: IoctlError) -> Self {
+        io::Error::from_raw_os_error(e.errno())
+    }
+}
+
 /// Rust binding for the `SDeviceInfo` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L124)
 #[allow(non_snake_case)]
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(C)]
 pub struct SDeviceInfo {
     pub i8uAddress: u8,
@@ -42,34 +123,57 @@ pub struct SDeviceInfo {
     pub i8uReserve: [u8; 30],
 }
 
-// #[derive(Debug)]
-// #[repr(u8)]
-// pub enum EntryInfoType {
-//     Input = 1,
-//     Output,
-//     Memory,
-//     Config,
-// }
-
-// #[allow(non_snake_case)]
-// #[derive(Debug)]
-// #[repr(C)]
-// pub struct SEntryInfo {
-//     i8uAddress: u8,
-//     i8uType: EntryInfoType,
-//     i16uIndex: u16,
-//     i16uBitLength: u16,
-//     i8uBitPos: u8,
-//     i16uOffset: u16,
-//     i32uDefault: u32,
-//     strVarName: [u8; 32],
-// }
+/// Type of an entry, decoded from [`SEntryInfo::i8uType`]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum EntryInfoType {
+    Input = 1,
+    Output,
+    Memory,
+    Config,
+}
+
+/// Rust binding for the `SEntryInfo` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h)
+///
+/// The driver keeps one entry per configured variable, but doesn't offer an
+/// ioctl to read them, so this is only a binding of the layout.
+#[allow(non_snake_case)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[repr(C)]
+pub struct SEntryInfo {
+    pub i8uAddress: u8,
+    /// [`EntryInfoType`], plus `0x80` if the entry is exported
+    pub i8uType: u8,
+    pub i16uIndex: u16,
+    pub i16uBitLength: u16,
+    pub i8uBitPos: u8,
+    pub i16uOffset: u16,
+    pub i32uDefault: u32,
+    pub strVarName: [u8; 32],
+}
+
+impl SEntryInfo {
+    /// Returns the type of the entry, or `None` if it is undefined
+    pub fn entry_type(&self) -> Option<EntryInfoType> {
+        match self.i8uType & 0x7f {
+            1 => Some(EntryInfoType::Input),
+            2 => Some(EntryInfoType::Output),
+            3 => Some(EntryInfoType::Memory),
+            4 => Some(EntryInfoType::Config),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the entry is exported
+    pub fn exported(&self) -> bool {
+        self.i8uType & 0x80 != 0
+    }
+}
 
 // TODO Bindings for module types
 
 /// Rust binding for the `SPIValue` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L163)
 #[allow(non_snake_case)]
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(C)]
 pub struct SPIValue {
     pub i16uAddress: u16,
@@ -77,9 +181,24 @@ pub struct SPIValue {
     pub i8uValue: u8,
 }
 
+/// Rust binding for the `SAIOCalibrate` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h)
+///
+/// `channels` is a bitmap of the channels to calibrate, `x_val` and `y_val`
+/// are the measured and the expected value of the calibration point.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[repr(C)]
+pub struct SAIOCalibrate {
+    pub address: u8,
+    pub mode: u8,
+    pub channels: u8,
+    pub reserved: u8,
+    pub x_val: u16,
+    pub y_val: u16,
+}
+
 /// Rust binding for the `SPIVariable` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L170)
 #[allow(non_snake_case)]
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(C)]
 pub struct SPIVariable {
     pub strVarName: [u8; 32],
@@ -90,7 +209,7 @@ pub struct SPIVariable {
 
 /// Rust binding for the `SDIOResetCounter` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L178)
 #[allow(non_snake_case)]
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(C)]
 pub struct SDIOResetCounter {
     pub i8uAddress: u8,
@@ -99,7 +218,8 @@ pub struct SDIOResetCounter {
 
 /// Rust bindings for the Events defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L116)
 ///
-/// Currently only Reset is supported
+/// Currently only Reset is supported, other events are skipped by
+/// [`PiControlRaw::wait_for_event`](super::PiControlRaw::wait_for_event)
 #[derive(Debug, PartialEq, Eq)]
 #[repr(i32)]
 pub enum Event {
@@ -107,14 +227,39 @@ pub enum Event {
     Reset = 1,
 }
 
-// #[allow(non_snake_case)]
-// #[derive(Debug, Default)]
-// #[repr(C)]
-// pub struct SConfigData {
-//     bLeft: u8,
-//     i16uLen: u16,
-//     acData: [u8; 256]
-// }
+/// Number of relays of an RO, see [`RevPiROCounters`]
+pub const REVPI_RO_NUM_RELAYS: usize = 4;
+
+/// Rust binding for the `revpi_ro_ioctl_counters` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h)
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[repr(C, packed)]
+pub struct RevPiROCounters {
+    pub addr: u8,
+    pub counter: [u32; REVPI_RO_NUM_RELAYS],
+}
+
+/// Length of the data in a [`SConfigData`]
+pub const CONFIG_DATA_LEN: usize = 256;
+
+/// Rust binding for the `SConfigData` struct defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h)
+#[allow(non_snake_case)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(C)]
+pub struct SConfigData {
+    pub bLeft: u8,
+    pub i16uLen: u16,
+    pub acData: [u8; CONFIG_DATA_LEN],
+}
+
+impl Default for SConfigData {
+    fn default() -> Self {
+        Self {
+            bLeft: 0,
+            i16uLen: 0,
+            acData: [0; CONFIG_DATA_LEN],
+        }
+    }
+}
 
 /// Rust bindings for the ioctls defined in [`piControl.h`](https://github.com/RevolutionPi/piControl/blob/master/piControl.h#L94)
 #[derive(Debug, PartialEq, Eq)]
@@ -143,24 +288,27 @@ pub enum KBRequests {
     // stop/start IO communication, can be used for I/O simulation
     StopIO,
     // for download of configuration to Master Gateway: stop IO communication completely
-    //ConfigStop,
+    ConfigStop,
     // for download of configuration to Master Gateway: download config data
-    //ConfigSend,
+    ConfigSend,
     // for download of configuration to Master Gateway: restart IO communication
-    //ConfigStart,
+    ConfigStart,
     // activate a watchdog for this handle. If write is not called for a given period all outputs are set to 0
-    SetOutputWatchdog,
+    SetOutputWatchdog = 0x4b1a,
     // set the f_pos, the unsigned int * is used to interpret the pos value
-    //SetPos,
-    //AIOCalibrate,
+    SetPos,
+    // calibrate the analog inputs or outputs of an AIO module
+    AIOCalibrate = 0x4b1c,
+    // get the switching cycles of the relays of an RO, only in newer drivers
+    ROGetCounter,
     // wait for an event. This call is normally blocking
     WaitForEvent = 0x4b32,
 }
 
-unsafe fn ioctl<T>(fd: RawFd, request: KBRequests, argp: T) -> Result<u32, i32> {
+unsafe fn ioctl<T>(fd: RawFd, request: KBRequests, argp: T) -> Result<u32, IoctlError> {
     let res = libc::ioctl(fd, request as libc::c_ulong, argp);
     if res <= -1 {
-        Err(*libc::__errno_location())
+        Err(IoctlError::from_errno(*libc::__errno_location()))
     } else {
         Ok(res as u32)
     }
@@ -169,14 +317,18 @@ unsafe fn ioctl<T>(fd: RawFd, request: KBRequests, argp: T) -> Result<u32, i32>
 /// Resets the the RevPi I/O module comms and config
 ///
 /// # Errors
-/// If the bridge takes too long to come up, `libc::ETIMEDOUT` is returened.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If the bridge takes too long to come up, [`IoctlError::TimedOut`] is returened.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// Resetting the driver reloads the config, so every address looked up
+/// before might be invalid afterwards.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn reset(fd: RawFd) -> Result<u32, i32> {
+pub unsafe fn reset(fd: RawFd) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::Reset, 0u64)
 }
 
@@ -186,15 +338,19 @@ pub unsafe fn reset(fd: RawFd) -> Result<u32, i32> {
 ///
 /// # Errors
 /// If successful, the number of devices written will be returned.\
-/// If the kernel module ran out of memory, `libc::ENOMEM` is returned.
-/// If `devs` wasn't accessible libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If the kernel module ran out of memory, [`IoctlError::OutOfMemory`] is returned.
+/// If `devs` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `devs` must be valid for writes of [`REV_PI_DEV_CNT_MAX`] [`SDeviceInfo`]
+/// entries.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn get_device_info_list(fd: RawFd, devs: *mut SDeviceInfo) -> Result<u32, i32> {
+pub unsafe fn get_device_info_list(fd: RawFd, devs: *mut SDeviceInfo) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::GetDeviceInfoList, devs)
 }
 
@@ -205,15 +361,18 @@ pub unsafe fn get_device_info_list(fd: RawFd, devs: *mut SDeviceInfo) -> Result<
 /// desired device address.
 ///
 /// # Errors
-/// If the device wasn't found, libc::ENXIO` is returned.\
-/// If `dev` wasn't accessible `libc::EFAULT` is returned.\
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.\
+/// If the device wasn't found, [`IoctlError::NoDevice`] is returned.\
+/// If `dev` wasn't accessible [`IoctlError::Fault`] is returned.\
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.\
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// libc::ENOTTY` is returened.\
+/// [`IoctlError::NotTty`] is returened.\
+///
+/// # Safety
+/// `dev` must be a valid pointer to a [`SDeviceInfo`].
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn get_device_info(fd: RawFd, dev: *mut SDeviceInfo) -> Result<u32, i32> {
+pub unsafe fn get_device_info(fd: RawFd, dev: *mut SDeviceInfo) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::GetDeviceInfo, dev)
 }
 
@@ -225,14 +384,17 @@ pub unsafe fn get_device_info(fd: RawFd, dev: *mut SDeviceInfo) -> Result<u32, i
 ///
 /// # Errors
 /// If the address was larger than [`KB_PI_LEN`], the bridge wasn't running or
-/// `val` wasn't accessible libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// `val` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `val` must be a valid pointer to a [`SPIValue`].
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn get_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, i32> {
+pub unsafe fn get_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::GetValue, val)
 }
 
@@ -242,15 +404,18 @@ pub unsafe fn get_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, i32> {
 ///
 /// # Errors
 /// If the address was larger than [`KB_PI_LEN`], the bridge wasn't running or
-/// `val` wasn't accessible `libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// `val` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `val` must be a valid pointer to a [`SPIValue`].
 ///
 /// # Further Information
 /// For more information see [`get_value`], `man ioctl`, `man picontrol_ioctl`
 /// or the kernel module
-pub unsafe fn set_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, i32> {
+pub unsafe fn set_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::SetValue, val)
 }
 
@@ -261,15 +426,18 @@ pub unsafe fn set_value(fd: RawFd, val: *mut SPIValue) -> Result<u32, i32> {
 ///
 /// # Errors
 /// If the variable wasn't found, the bridge wasn't running or var wasn't accessible
-/// `libc::EFAULT` is returned.
-/// If there were no variable entries, `libc::ENOENT` is returned.
-/// If fd is not a valid file descriptor, libc::EBADF` is returened.
+/// [`IoctlError::Fault`] is returned.
+/// If there were no variable entries, [`IoctlError::NoEntry`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `var` must be a valid pointer to a [`SPIVariable`].
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn find_variable(fd: RawFd, var: *mut SPIVariable) -> Result<u32, i32> {
+pub unsafe fn find_variable(fd: RawFd, var: *mut SPIVariable) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::FindVariable, var)
 }
 
@@ -279,14 +447,17 @@ pub unsafe fn find_variable(fd: RawFd, var: *mut SPIVariable) -> Result<u32, i32
 /// long. Currently only one process should call this ioctl.
 ///
 /// # Errors
-/// If `image` wasn't accessible libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If `image` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `image` must be valid for reads of [`KB_PI_LEN`] bytes.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn set_exported_outputs(fd: RawFd, image: *const u8) -> Result<u32, i32> {
+pub unsafe fn set_exported_outputs(fd: RawFd, image: *const u8) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::SetExportedOutputs, image)
 }
 
@@ -296,16 +467,19 @@ pub unsafe fn set_exported_outputs(fd: RawFd, image: *const u8) -> Result<u32, i
 /// exactly one module is connected.
 ///
 /// # Errors
-/// If the RevPi isnt a Core or Connect, `libc::EPERM` is returned.
+/// If the RevPi isnt a Core or Connect, [`IoctlError::NotPermitted`] is returned.
 /// If the bridge isn't running or too many or too little modules are connected,
-/// `libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// The module might get bricked if the update is interrupted.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn update_device_firmware(fd: RawFd, module: u32) -> Result<u32, i32> {
+pub unsafe fn update_device_firmware(fd: RawFd, module: u32) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::UpdateDeviceFirmware, module)
 }
 
@@ -316,18 +490,21 @@ pub unsafe fn update_device_firmware(fd: RawFd, module: u32) -> Result<u32, i32>
 /// bit in `i16uBitfield` must be set. `i16uBitfield` must not be `0`.
 ///
 /// # Errors
-/// If the RevPi isnt a Core or Connect, `libc::EPERM` is returned.
-/// If the module wasn't found or if the bitfield was `0`, `libc::EINVAL`
+/// If the RevPi isnt a Core or Connect, [`IoctlError::NotPermitted`] is returned.
+/// If the module wasn't found or if the bitfield was `0`, [`IoctlError::InvalidArgument`]
 /// is returned.
-/// If the bridge wasn't running or ctr wasn't accessible `libc::EFAULT`
+/// If the bridge wasn't running or ctr wasn't accessible [`IoctlError::Fault`]
 /// is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `ctr` must be a valid pointer to a [`SDIOResetCounter`].
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn dio_reset_counter(fd: RawFd, ctr: *mut SDIOResetCounter) -> Result<u32, i32> {
+pub unsafe fn dio_reset_counter(fd: RawFd, ctr: *mut SDIOResetCounter) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::DIOResetCounter, ctr)
 }
 
@@ -337,14 +514,17 @@ pub unsafe fn dio_reset_counter(fd: RawFd, ctr: *mut SDIOResetCounter) -> Result
 /// bytes. The message will be written into it.
 ///
 /// # Errors
-/// If `msg` wasn't accessible `libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If `msg` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `msg` must be valid for writes of [`REV_PI_ERROR_MSG_LEN`] bytes.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn get_last_message(fd: RawFd, msg: *mut i8) -> Result<u32, i32> {
+pub unsafe fn get_last_message(fd: RawFd, msg: *mut i8) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::GetLastMessage, msg)
 }
 
@@ -354,34 +534,160 @@ pub unsafe fn get_last_message(fd: RawFd, msg: *mut i8) -> Result<u32, i32> {
 ///
 /// # Errors
 /// If the call is successfull, the new mode will be returned.\
-/// If the bridge wasn't running or stop wasn't accessible `libc::EFAULT`
+/// If the bridge wasn't running or stop wasn't accessible [`IoctlError::Fault`]
 /// is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `stop` must be a valid pointer to an `i32`.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn stop_io(fd: RawFd, stop: *mut i32) -> Result<u32, i32> {
+pub unsafe fn stop_io(fd: RawFd, stop: *mut i32) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::StopIO, stop)
 }
 
+/// Stops I/O communication completely, to download a config to a master
+/// gateway
+///
+/// # Errors
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// No I/O happens until [`config_start`] is called.
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn config_stop(fd: RawFd) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::ConfigStop, 0u64)
+}
+
+/// Sends config data to a master gateway
+///
+/// `data` must point to a [`SConfigData`] struct with `bLeft` set to `1` for
+/// the gateway on the left and `0` for the one on the right and `i16uLen`
+/// set to the number of bytes in `acData`. Only valid after [`config_stop`].
+///
+/// # Errors
+/// If the gateway rejected the data or `data` wasn't accessible an error
+/// code is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `data` must be a valid pointer to a [`SConfigData`].
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn config_send(fd: RawFd, data: *mut SConfigData) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::ConfigSend, data)
+}
+
+/// Restarts I/O communication after [`config_stop`]
+///
+/// # Errors
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// The gateways start with the config that was sent.
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn config_start(fd: RawFd) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::ConfigStart, 0u64)
+}
+
 /// Activate an application watchdog
 ///
 /// `millis` must point to the watchdog period in milliseconds.
 ///
 /// # Errors
-/// If `millis` wasn't accessible `libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If `millis` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `millis` must be a valid pointer to an `u32`.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn set_output_watchdog(fd: RawFd, millis: *mut u32) -> Result<u32, i32> {
+pub unsafe fn set_output_watchdog(fd: RawFd, millis: *mut u32) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::SetOutputWatchdog, millis)
 }
 
+/// Sets the position in the processimage the next `read` or `write` on `fd`
+/// starts at
+///
+/// `pos` must point to the new position.
+///
+/// # Errors
+/// If the position was outside of the processimage, an error code is returned.
+/// If `pos` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `pos` must be a valid pointer to an `u32`.
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn set_pos(fd: RawFd, pos: *mut u32) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::SetPos, pos)
+}
+
+/// Calibrates the channels of an AIO module
+///
+/// `cal` must point to a [`SAIOCalibrate`] struct with its members
+/// initialized properly.
+///
+/// # Errors
+/// If the module wasn't found or isn't an AIO, [`IoctlError::InvalidArgument`] is returned.
+/// If the bridge wasn't running or `cal` wasn't accessible [`IoctlError::Fault`]
+/// is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `cal` must be a valid pointer to a [`SAIOCalibrate`].
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn aio_calibrate(fd: RawFd, cal: *mut SAIOCalibrate) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::AIOCalibrate, cal)
+}
+
+/// Reads the switching cycles the relays of an RO counted
+///
+/// `ctr` must point to a [`RevPiROCounters`] struct with `addr` set to the
+/// address of the RO.
+///
+/// # Errors
+/// If there is no RO at the address, [`IoctlError::InvalidArgument`] is returned.
+/// If the bridge wasn't running or `ctr` wasn't accessible [`IoctlError::Fault`]
+/// is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
+/// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
+/// or the driver is too old to know the ioctl, [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `ctr` must be a valid pointer to a [`RevPiROCounters`].
+///
+/// # Further Information
+/// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
+pub unsafe fn ro_get_counter(fd: RawFd, ctr: *mut RevPiROCounters) -> Result<u32, IoctlError> {
+    ioctl(fd, KBRequests::ROGetCounter, ctr)
+}
+
 /// Wait for an event from piControl
 ///
 /// Writes the event that happened into `event`. Currently only a reset
@@ -389,13 +695,16 @@ pub unsafe fn set_output_watchdog(fd: RawFd, millis: *mut u32) -> Result<u32, i3
 /// This is a blocking call.
 ///
 /// # Errors
-/// If `event` wasn't accessible `libc::EFAULT` is returned.
-/// If fd is not a valid file descriptor, `libc::EBADF` is returened.
+/// If `event` wasn't accessible [`IoctlError::Fault`] is returned.
+/// If fd is not a valid file descriptor, [`IoctlError::BadFileDescriptor`] is returened.
 /// If fd is not a character special device or doesn't refer to `"/dev/piControl0"`,
-/// `libc::ENOTTY` is returened.
+/// [`IoctlError::NotTty`] is returened.
+///
+/// # Safety
+/// `event` must be a valid pointer to an `i32`.
 ///
 /// # Further Information
 /// For more information see `man ioctl`, `man picontrol_ioctl` or the kernel module
-pub unsafe fn wait_for_event(fd: RawFd, event: *mut i32) -> Result<u32, i32> {
+pub unsafe fn wait_for_event(fd: RawFd, event: *mut i32) -> Result<u32, IoctlError> {
     ioctl(fd, KBRequests::WaitForEvent, event)
 }
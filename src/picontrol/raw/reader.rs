@@ -0,0 +1,94 @@
+//! Streaming access to the processimage
+
+use super::{
+    raw::{self, KB_PI_LEN},
+    PiControlRaw,
+};
+use crate::picontrol::PiControlError;
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    os::unix::prelude::AsRawFd,
+    path::Path,
+};
+
+/// Reads the processimage through [`Read`] and [`Seek`]
+///
+/// The position is set with the `KB_SET_POS` ioctl before every read, so the
+/// reader can be used with any code that expects standard I/O. Reads stop at
+/// the end of the processimage.
+///
+/// # Examples
+/// ```no_run
+/// # use revpi::picontrol::raw::ProcessImageReader;
+/// use std::io::{Read, Seek, SeekFrom};
+///
+/// let mut reader = ProcessImageReader::new().unwrap();
+/// let mut inputs = [0; 11];
+/// reader.seek(SeekFrom::Start(0)).unwrap();
+/// reader.read_exact(&mut inputs).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct ProcessImageReader {
+    raw: PiControlRaw,
+    pos: u64,
+}
+
+impl ProcessImageReader {
+    /// Opens `"/dev/piControl0"` for reading
+    ///
+    /// # Errors
+    /// Same as [`PiControlRaw::new`].
+    pub fn new() -> Result<Self, PiControlError> {
+        Ok(PiControlRaw::new()?.into())
+    }
+
+    /// Opens the device at `path` for reading
+    ///
+    /// # Errors
+    /// Same as [`PiControlRaw::open`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PiControlError> {
+        Ok(PiControlRaw::open(path)?.into())
+    }
+}
+
+impl From<PiControlRaw> for ProcessImageReader {
+    fn from(raw: PiControlRaw) -> Self {
+        Self { raw, pos: 0 }
+    }
+}
+
+impl Read for ProcessImageReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = (KB_PI_LEN as u64).saturating_sub(self.pos) as usize;
+        if left == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        // checked above
+        let mut pos = self.pos as u32;
+        unsafe { raw::set_pos(self.raw.0.as_raw_fd(), &mut pos) }.map_err(io::Error::from)?;
+        let len = buf.len().min(left);
+        let n = (&self.raw.0).read(&mut buf[..len])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for ProcessImageReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => (KB_PI_LEN as u64).checked_add_signed(offset),
+        };
+        match pos {
+            Some(pos) if pos <= KB_PI_LEN as u64 => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "position outside of the processimage",
+            )),
+        }
+    }
+}
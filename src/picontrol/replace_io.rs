@@ -0,0 +1,371 @@
+//! Replaced IOs of RevPiModIO
+
+use super::{raw::Endianness, PiControlError};
+#[cfg(feature = "rsc")]
+use super::{NameTable, Var};
+use crate::util::ensure;
+use std::{fmt, fs, io, path::Path};
+
+fn invalid(line: usize, msg: &str) -> PiControlError {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid replace_io file, line {line}: {msg}"),
+    )
+    .into()
+}
+
+/// Type of a replaced IO, the `frm` of RevPiModIO, which uses the format
+/// characters of Python's `struct` module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IoFormat {
+    /// `?`, a single bit
+    Bool,
+    /// `b`
+    I8,
+    /// `B`
+    U8,
+    /// `h`
+    I16,
+    /// `H`
+    U16,
+    /// `i` or `l`
+    I32,
+    /// `I` or `L`
+    U32,
+    /// `q`
+    I64,
+    /// `Q`
+    U64,
+    /// `f`
+    F32,
+    /// `d`
+    F64,
+    /// `Ns`, a byte array of `N` bytes
+    Bytes(u16),
+}
+
+impl IoFormat {
+    /// Returns the length in bits
+    pub fn bits(&self) -> u16 {
+        use IoFormat::*;
+        match *self {
+            Bool => 1,
+            I8 | U8 => 8,
+            I16 | U16 => 16,
+            I32 | U32 | F32 => 32,
+            I64 | U64 | F64 => 64,
+            Bytes(n) => n.saturating_mul(8),
+        }
+    }
+
+    /// Returns the unsigned format of a variable of `length` bits, as
+    /// RevPiModIO uses it for the IOs of PiCtory, `None` if there is none
+    pub fn unsigned(length: u16) -> Option<Self> {
+        match length {
+            1 => Some(IoFormat::Bool),
+            8 => Some(IoFormat::U8),
+            16 => Some(IoFormat::U16),
+            32 => Some(IoFormat::U32),
+            64 => Some(IoFormat::U64),
+            _ => None,
+        }
+    }
+
+    fn parse(frm: &str) -> Option<Self> {
+        use IoFormat::*;
+        Some(match frm {
+            "?" => Bool,
+            "b" => I8,
+            "B" => U8,
+            "h" => I16,
+            "H" => U16,
+            "i" | "l" => I32,
+            "I" | "L" => U32,
+            "q" => I64,
+            "Q" => U64,
+            "f" => F32,
+            "d" => F64,
+            // python's struct reads a lone `s` as one byte
+            "s" => Bytes(1),
+            s => Bytes(s.strip_suffix('s')?.parse().ok().filter(|&n| n > 0)?),
+        })
+    }
+}
+
+impl fmt::Display for IoFormat {
+    /// Formats as the `frm` of RevPiModIO
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use IoFormat::*;
+        let c = match *self {
+            Bool => "?",
+            I8 => "b",
+            U8 => "B",
+            I16 => "h",
+            U16 => "H",
+            I32 => "i",
+            U32 => "I",
+            I64 => "q",
+            U64 => "Q",
+            F32 => "f",
+            F64 => "d",
+            Bytes(n) => return write!(f, "{n}s"),
+        };
+        f.write_str(c)
+    }
+}
+
+/// An IO that replaces (a part of) another one under a new name and type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplaceIo {
+    /// Name of the new IO
+    pub name: String,
+    /// Name of the IO it replaces
+    pub replace: String,
+    pub format: IoFormat,
+    /// Bit inside the replaced IO, only used by [`IoFormat::Bool`]. It may be
+    /// larger than 7 to address the following bytes.
+    pub bit: u16,
+    pub byteorder: Endianness,
+    /// Default value as written in the file
+    pub default: Option<String>,
+    /// Description, the `bmk` of RevPiModIO
+    pub description: Option<String>,
+    /// Whether RevPiModIO exports the IO, e.g. to the processimage server
+    pub export: bool,
+}
+
+impl ReplaceIo {
+    /// Creates the IO `name` of `format` at the start of `replace`
+    pub fn new(name: &str, replace: &str, format: IoFormat) -> Self {
+        Self {
+            name: name.to_string(),
+            replace: replace.to_string(),
+            format,
+            bit: 0,
+            byteorder: Endianness::Little,
+            default: None,
+            description: None,
+            export: false,
+        }
+    }
+
+    /// Sets the bit of an [`IoFormat::Bool`]
+    pub fn bit(mut self, bit: u16) -> Self {
+        self.bit = bit;
+        self
+    }
+
+    /// Sets the byte order
+    pub fn byteorder(mut self, byteorder: Endianness) -> Self {
+        self.byteorder = byteorder;
+        self
+    }
+
+    // the place of the new IO inside `var`, the replaced one
+    #[cfg(feature = "rsc")]
+    fn place(&self, var: Var) -> Option<Var> {
+        if self.format == IoFormat::Bool {
+            return match var.length {
+                1 if self.bit == 0 => Some(var),
+                1 => None,
+                length if self.bit < length => Some(Var {
+                    address: var.address.checked_add(self.bit / 8)?,
+                    bit: (self.bit % 8) as u8,
+                    length: 1,
+                }),
+                _ => None,
+            };
+        }
+        (var.length != 1 && self.format.bits() <= var.length).then_some(Var {
+            address: var.address,
+            bit: 0,
+            length: self.format.bits(),
+        })
+    }
+}
+
+/// The `replace_io` file of RevPiModIO, an INI file with a section for every
+/// replaced IO
+///
+/// Applied to a [`NameTable`], the new IOs can be used by name from Rust, so
+/// Rust and Python applications share the same names. The type of an IO is
+/// up to the application, e.g. with
+/// [`PiControl::get_value_as`](super::PiControl::get_value_as).
+///
+/// # Example
+/// ```
+/// # use revpi::picontrol::{IoFormat, ReplaceIo, ReplaceIoConfig};
+/// let config = ReplaceIoConfig::parse(
+///     "[Temperature]\nreplace = AIn_1\nfrm = h\n\n\
+///      [Door_Open]\nreplace = RevPiStatus\nfrm = ?\nbit = 3\n",
+/// )
+/// .unwrap();
+/// assert_eq!(config.ios[0], ReplaceIo::new("Temperature", "AIn_1", IoFormat::I16));
+/// assert_eq!(config.ios[1].bit, 3);
+/// assert_eq!(ReplaceIoConfig::parse(&config.to_string()).unwrap(), config);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReplaceIoConfig {
+    /// The IOs in the order of the file
+    pub ios: Vec<ReplaceIo>,
+}
+
+impl ReplaceIoConfig {
+    /// Parses a `replace_io` file
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file is malformed, e.g. a
+    /// section lacks `replace` or `frm`, or contains unknown keys.
+    pub fn parse(s: &str) -> Result<Self, PiControlError> {
+        let mut ios: Vec<(usize, ReplaceIo, bool)> = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| invalid(line_no, "unclosed section"))?
+                    .trim();
+                ensure!(
+                    ios.iter().all(|(_, io, _)| io.name != name),
+                    invalid(line_no, "duplicate section")
+                );
+                // the format is checked at the end of the section
+                let io = ReplaceIo::new(name, "", IoFormat::Bool);
+                ios.push((line_no, io, false));
+                continue;
+            }
+            let (key, value) = line
+                .split_once(['=', ':'])
+                .ok_or_else(|| invalid(line_no, "expected key = value"))?;
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+            let (_, io, has_format) = ios
+                .last_mut()
+                .ok_or_else(|| invalid(line_no, "key outside of a section"))?;
+            match key.as_str() {
+                "replace" => io.replace = value.to_string(),
+                "frm" => {
+                    io.format =
+                        IoFormat::parse(value).ok_or_else(|| invalid(line_no, "unknown frm"))?;
+                    *has_format = true;
+                }
+                "bit" => io.bit = value.parse().map_err(|_| invalid(line_no, "invalid bit"))?,
+                "byteorder" => {
+                    io.byteorder = match value {
+                        "little" => Endianness::Little,
+                        "big" => Endianness::Big,
+                        _ => return Err(invalid(line_no, "invalid byteorder")),
+                    }
+                }
+                "defaultvalue" => io.default = Some(value.to_string()),
+                "bmk" => io.description = Some(value.to_string()),
+                "export" => {
+                    io.export = match value.to_lowercase().as_str() {
+                        "1" | "true" | "yes" | "on" => true,
+                        "0" | "false" | "no" | "off" => false,
+                        _ => return Err(invalid(line_no, "invalid export")),
+                    }
+                }
+                _ => return Err(invalid(line_no, "unknown key")),
+            }
+        }
+        let mut config = Self::default();
+        for (line_no, io, has_format) in ios {
+            ensure!(!io.replace.is_empty(), invalid(line_no, "missing replace"));
+            ensure!(has_format, invalid(line_no, "missing frm"));
+            config.ios.push(io);
+        }
+        Ok(config)
+    }
+
+    /// Reads and parses the file at `path`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file can't be read or is
+    /// malformed.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PiControlError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Writes the config to the file at `path`, so RevPiModIO can load it
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file can't be written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PiControlError> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+}
+
+impl fmt::Display for ReplaceIoConfig {
+    /// Formats as `replace_io` file
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, io) in self.ios.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", io.name)?;
+            writeln!(f, "replace = {}", io.replace)?;
+            writeln!(f, "frm = {}", io.format)?;
+            if io.format == IoFormat::Bool {
+                writeln!(f, "bit = {}", io.bit)?;
+            }
+            if io.byteorder == Endianness::Big {
+                writeln!(f, "byteorder = big")?;
+            }
+            if let Some(default) = &io.default {
+                writeln!(f, "defaultvalue = {default}")?;
+            }
+            if let Some(description) = &io.description {
+                writeln!(f, "bmk = {description}")?;
+            }
+            if io.export {
+                writeln!(f, "export = 1")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rsc")]
+impl NameTable {
+    /// Adds the IOs of `config` to the table, at the place of the IOs they
+    /// replace. The replaced IOs stay in the table, unlike in RevPiModIO.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if a replaced IO isn't
+    /// in the table or the new IO doesn't fit into it. The table is left
+    /// unchanged then.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::{NameTable, PiControl, ReplaceIoConfig};
+    /// # use revpi::rsc::RSC;
+    /// # use std::fs::File;
+    /// let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+    /// let mut table = NameTable::from_rsc(&rsc).unwrap();
+    /// table
+    ///     .apply_replace_io(&ReplaceIoConfig::from_file("/etc/myapp/replace_ios.conf").unwrap())
+    ///     .unwrap();
+    /// let pi = PiControl::with_name_table(table).unwrap();
+    /// let temperature: i16 = pi.get_value_as("Temperature").unwrap();
+    /// ```
+    pub fn apply_replace_io(&mut self, config: &ReplaceIoConfig) -> Result<(), PiControlError> {
+        let mut vars = Vec::new();
+        for io in config.ios.iter() {
+            let var = self
+                .var(&io.replace)
+                .ok_or(PiControlError::InvalidArgument("replace"))?;
+            let var = io
+                .place(var)
+                .ok_or(PiControlError::InvalidArgument("frm"))?;
+            vars.push((io.name.clone(), var));
+        }
+        for (name, var) in vars {
+            self.insert(name, var);
+        }
+        Ok(())
+    }
+}
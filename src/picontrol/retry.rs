@@ -0,0 +1,132 @@
+//! Retrying of transient driver errors
+
+use super::{PiControl, PiControlError};
+use std::{io, thread, time::Duration};
+
+/// How [`PiControl`] retries calls that failed transiently, set with
+/// [`PiControlBuilder::retry`](super::PiControlBuilder::retry)
+///
+/// While the driver resets or a module is plugged in, every access fails for
+/// a short time with [`PiControlError::BridgeNotRunning`] or a timeout. Such
+/// calls are repeated after a delay, which doubles after every attempt up to
+/// the maximum. Other errors are returned immediately.\
+/// The default doesn't retry at all.
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{
+///     backend::{FaultInjector, MockBackend},
+///     PiControl, RetryPolicy, Value,
+/// };
+/// use std::{sync::Arc, time::Duration};
+///
+/// let faults = Arc::new(FaultInjector::new(
+///     MockBackend::new().variable("RevPiLED", 6, 0, 8),
+/// ));
+/// let pi = PiControl::builder()
+///     .backend(faults.clone())
+///     .retry(RetryPolicy::new(3, Duration::from_millis(1)))
+///     .build()
+///     .unwrap();
+/// // every get_value is a lookup and a read, let the first read time out
+/// faults.fail_nth(2, libc::ETIMEDOUT);
+/// assert_eq!(pi.get_value("RevPiLED").unwrap(), Value::Byte(0));
+/// assert_eq!(faults.calls(), 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl RetryPolicy {
+    /// Retries up to `max_retries` times, waiting `initial_backoff` before the
+    /// first retry. The delay is limited to one second, see
+    /// [`max_backoff`](Self::max_backoff).
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    /// Doesn't retry, every error is returned immediately
+    pub fn none() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+
+    /// Limits the delay between two attempts to `max_backoff`
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns the delays before each retry
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::picontrol::RetryPolicy;
+    /// # use std::time::Duration;
+    /// let policy = RetryPolicy::new(4, Duration::from_millis(100))
+    ///     .max_backoff(Duration::from_millis(300));
+    /// let delays: Vec<_> = policy.delays().map(|d| d.as_millis()).collect();
+    /// assert_eq!(delays, [100, 200, 300, 300]);
+    /// ```
+    pub fn delays(&self) -> impl Iterator<Item = Duration> {
+        let max = self.max_backoff;
+        std::iter::successors(Some(self.initial_backoff.min(max)), move |d| {
+            Some(d.saturating_mul(2).min(max))
+        })
+        .take(self.max_retries as usize)
+    }
+
+    /// Returns whether `e` is worth retrying, i.e. the piBridge wasn't
+    /// running or the driver timed out
+    pub fn is_transient(e: &PiControlError) -> bool {
+        match e {
+            PiControlError::BridgeNotRunning | PiControlError::Timeout => true,
+            PiControlError::IoError(e) => e.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+
+    // calls `f` until it succeeds, fails permanently or the retries run out
+    pub(crate) fn run<T, F>(&self, mut f: F) -> Result<T, PiControlError>
+    where
+        F: FnMut() -> Result<T, PiControlError>,
+    {
+        let mut delays = self.delays();
+        loop {
+            match f() {
+                Err(e) if Self::is_transient(&e) => match delays.next() {
+                    Some(delay) => thread::sleep(delay),
+                    None => return Err(e),
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+impl PiControl {
+    /// Returns the [`RetryPolicy`] of this PiControl
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.shared.retry
+    }
+
+    // runs `f` with the retry policy
+    pub(crate) fn retry<T, F>(&self, f: F) -> Result<T, PiControlError>
+    where
+        F: FnMut() -> Result<T, PiControlError>,
+    {
+        self.shared.retry.run(f)
+    }
+}
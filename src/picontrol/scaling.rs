@@ -0,0 +1,237 @@
+//! Linear scaling of variables into engineering units
+
+use super::{PiControl, PiControlError, Value};
+use crate::util::ensure;
+
+/// Maps the raw range of a variable linearly onto a range in engineering
+/// units, e.g. `0..=10000` of an analog input onto `0.0..=10.0` V
+///
+/// If `raw_min` is negative, the variable is read as a signed integer. An
+/// offset is added after scaling, e.g. to calibrate a sensor.
+///
+/// # Example
+/// ```
+/// # use revpi::picontrol::Scaling;
+/// let scaling = Scaling::new(4000, 20000, 0.0, 100.0)
+///     .unwrap()
+///     .with_offset(-0.5)
+///     .with_unit("%");
+/// assert_eq!(scaling.apply(12000), 49.5);
+/// assert_eq!(scaling.undo(49.5), 12000.0);
+/// assert_eq!(scaling.unit(), Some("%"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaling {
+    raw_min: i64,
+    raw_max: i64,
+    min: f64,
+    max: f64,
+    offset: f64,
+    unit: Option<String>,
+}
+
+impl Scaling {
+    /// Creates the scaling of `raw_min..=raw_max` onto `min..=max`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the raw range is
+    /// empty, since the scaling couldn't be undone.
+    pub fn new(raw_min: i64, raw_max: i64, min: f64, max: f64) -> Result<Self, PiControlError> {
+        ensure!(
+            raw_min < raw_max,
+            PiControlError::InvalidArgument("raw range")
+        );
+        Ok(Self {
+            raw_min,
+            raw_max,
+            min,
+            max,
+            offset: 0.0,
+            unit: None,
+        })
+    }
+
+    /// Adds `offset` after scaling
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the unit of the engineering values, e.g. `"V"`
+    pub fn with_unit(mut self, unit: &str) -> Self {
+        self.unit = Some(unit.to_string());
+        self
+    }
+
+    /// Returns the raw range
+    pub fn raw_range(&self) -> (i64, i64) {
+        (self.raw_min, self.raw_max)
+    }
+
+    /// Returns the range in engineering units, without the offset
+    pub fn range(&self) -> (f64, f64) {
+        (self.min, self.max)
+    }
+
+    /// Returns the offset
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Returns the unit, if any
+    pub fn unit(&self) -> Option<&str> {
+        self.unit.as_deref()
+    }
+
+    /// Converts the raw value `raw` into engineering units
+    pub fn apply(&self, raw: i64) -> f64 {
+        let ratio = (raw - self.raw_min) as f64 / (self.raw_max - self.raw_min) as f64;
+        self.min + ratio * (self.max - self.min) + self.offset
+    }
+
+    /// Converts `value` in engineering units back into a raw value, which
+    /// may have a fraction
+    pub fn undo(&self, value: f64) -> f64 {
+        let ratio = (value - self.offset - self.min) / (self.max - self.min);
+        self.raw_min as f64 + ratio * (self.raw_max - self.raw_min) as f64
+    }
+
+    fn signed(&self) -> bool {
+        self.raw_min < 0
+    }
+
+    // the integer in `value`, interpreted as signed if the raw range is signed
+    fn raw(&self, value: &Value) -> Option<i64> {
+        let bits = value.bitcnt() as u32;
+        let v = match value {
+            Value::Bytes(_) | Value::String(_) => return None,
+            v => v.as_u32() as i64,
+        };
+        match self.signed() && bits > 1 && v >> (bits - 1) == 1 {
+            true => Some(v - (1 << bits)),
+            false => Some(v),
+        }
+    }
+
+    // a value of `length` bits holding `raw`, `None` if it doesn't fit
+    fn value(&self, raw: i64, length: u16) -> Option<Value> {
+        let (min, max) = match (length, self.signed()) {
+            (1, _) => (0, 1),
+            (8 | 16 | 32, true) => (-(1 << (length - 1)), (1 << (length - 1)) - 1),
+            (8 | 16 | 32, false) => (0, (1 << length) - 1),
+            _ => return None,
+        };
+        if raw < min || raw > max {
+            return None;
+        }
+        let bits = raw as u32;
+        Some(match length {
+            1 => Value::Bit(bits == 1),
+            8 => Value::Byte(bits as u8),
+            16 => Value::Word(bits as u16),
+            _ => Value::DWord(bits),
+        })
+    }
+}
+
+impl PiControl {
+    /// Returns the scaling of the variable `name`, as set with
+    /// [`PiControlBuilder::scaling`](super::PiControlBuilder::scaling)
+    pub fn scaling(&self, name: &str) -> Option<&Scaling> {
+        self.shared.scalings.get(name)
+    }
+
+    fn require_scaling(&self, name: &str) -> Result<&Scaling, PiControlError> {
+        self.scaling(name)
+            .ok_or(PiControlError::InvalidArgument("scaling"))
+    }
+
+    /// Reads the variable `name` and converts it into engineering units with
+    /// its [`Scaling`]
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the variable has no
+    /// scaling or is a byte array and the errors of
+    /// [`get_value`](Self::get_value).
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::picontrol::{backend::MockBackend, PiControl, Scaling};
+    /// # use std::sync::Arc;
+    /// let mock = Arc::new(
+    ///     MockBackend::new()
+    ///         .variable("AIn_1", 0, 0, 16)
+    ///         .variable("AOut_1", 2, 0, 16),
+    /// );
+    /// let pi = PiControl::builder()
+    ///     .backend(mock.clone())
+    ///     .scaling("AIn_1", Scaling::new(-10000, 10000, -10.0, 10.0).unwrap())
+    ///     .scaling("AOut_1", Scaling::new(0, 10000, 0.0, 100.0).unwrap())
+    ///     .build()
+    ///     .unwrap();
+    /// mock.write(0, &(-2500i16).to_le_bytes()).unwrap();
+    /// assert_eq!(pi.get_scaled("AIn_1").unwrap(), -2.5);
+    /// pi.set_scaled("AOut_1", 42.0).unwrap();
+    /// assert_eq!(mock.read(2, 2).unwrap(), 4200u16.to_le_bytes());
+    /// assert!(pi.set_scaled("AOut_1", -1.0).is_err());
+    /// ```
+    pub fn get_scaled(&self, name: &str) -> Result<f64, PiControlError> {
+        let scaling = self.require_scaling(name)?;
+        let raw = scaling
+            .raw(&self.get_value(name)?)
+            .ok_or(PiControlError::InvalidArgument("length"))?;
+        Ok(scaling.apply(raw))
+    }
+
+    /// Converts `value` from engineering units with the [`Scaling`] of the
+    /// variable `name` and writes it, rounded to the nearest raw value. See
+    /// [`get_scaled`](Self::get_scaled) for an example.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the variable has no
+    /// scaling or if the raw value is outside of the raw range or doesn't fit
+    /// into the variable, and the errors of [`set_value`](Self::set_value).
+    pub fn set_scaled(&self, name: &str, value: f64) -> Result<(), PiControlError> {
+        let scaling = self.require_scaling(name)?;
+        let raw = scaling.undo(value).round();
+        ensure!(
+            raw >= scaling.raw_min as f64 && raw <= scaling.raw_max as f64,
+            PiControlError::InvalidArgument("value")
+        );
+        let var = self.find_variable(name)?;
+        let value = scaling
+            .value(raw as i64, var.length)
+            .ok_or(PiControlError::InvalidArgument("value"))?;
+        self.set_var(var, value)
+    }
+
+    // the value of `name` as number, scaled if it has a scaling and raw
+    // unsigned otherwise
+    pub(crate) fn get_number(&self, name: &str) -> Result<f64, PiControlError> {
+        if self.scaling(name).is_some() {
+            return self.get_scaled(name);
+        }
+        match self.get_value(name)? {
+            Value::Bytes(_) | Value::String(_) => Err(PiControlError::InvalidArgument("length")),
+            value => Ok(value.as_u32() as f64),
+        }
+    }
+
+    // writes `value` to `name`, scaled if it has a scaling and otherwise
+    // rounded to the nearest raw unsigned value
+    pub(crate) fn set_number(&self, name: &str, value: f64) -> Result<(), PiControlError> {
+        if self.scaling(name).is_some() {
+            return self.set_scaled(name, value);
+        }
+        let var = self.find_variable(name)?;
+        let raw = value.round();
+        let value = match var.length {
+            1 | 8 | 16 | 32 if raw >= 0.0 && raw < 2f64.powi(var.length as i32) => {
+                Value::decode(&(raw as u32).to_le_bytes(), 0, var.length as usize)
+            }
+            _ => None,
+        }
+        .ok_or(PiControlError::InvalidArgument("value"))?;
+        self.set_var(var, value)
+    }
+}
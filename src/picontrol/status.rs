@@ -0,0 +1,149 @@
+//! Decoding of the `RevPiStatus` input
+
+use super::{PiControl, PiControlError, Value};
+use std::{fmt, ops::BitOr};
+
+/// Name of the variable holding the status of the base module
+pub const STATUS_VARIABLE: &str = "RevPiStatus";
+
+/// Bits of the `RevPiStatus` input, returned by [`PiControl::status`]
+///
+/// # Example
+/// ```
+/// # use revpi::picontrol::Status;
+/// let status = Status::from_bits(0x15);
+/// assert!(status.contains(Status::RUNNING | Status::MISSING_MODULE));
+/// assert!(status.left_gateway());
+/// assert!(!status.has_unconfigured_module());
+/// assert_eq!(format!("{:?}", status), "Status(RUNNING | MISSING_MODULE | LEFT_GATEWAY)");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Status(u8);
+
+impl Status {
+    /// piControl is running
+    pub const RUNNING: Status = Status(0x01);
+    /// A module is connected that isn't configured in PiCtory
+    pub const UNCONFIGURED_MODULE: Status = Status(0x02);
+    /// A module configured in PiCtory isn't connected
+    pub const MISSING_MODULE: Status = Status(0x04);
+    /// The modules need more space than the processimage has
+    pub const IMAGE_OVERFLOW: Status = Status(0x08);
+    /// A gateway is connected on the left side
+    pub const LEFT_GATEWAY: Status = Status(0x10);
+    /// A gateway is connected on the right side
+    pub const RIGHT_GATEWAY: Status = Status(0x20);
+    /// State of the digital input on X2, only on the RevPi Connect
+    pub const X2_DI: Status = Status(0x40);
+
+    const NAMES: [(Status, &'static str); 7] = [
+        (Status::RUNNING, "RUNNING"),
+        (Status::UNCONFIGURED_MODULE, "UNCONFIGURED_MODULE"),
+        (Status::MISSING_MODULE, "MISSING_MODULE"),
+        (Status::IMAGE_OVERFLOW, "IMAGE_OVERFLOW"),
+        (Status::LEFT_GATEWAY, "LEFT_GATEWAY"),
+        (Status::RIGHT_GATEWAY, "RIGHT_GATEWAY"),
+        (Status::X2_DI, "X2_DI"),
+    ];
+
+    /// Decodes the value of `RevPiStatus`, unknown bits are kept
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the value of `RevPiStatus`
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns whether all bits of `other` are set
+    pub fn contains(&self, other: Status) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// See [`Status::RUNNING`]
+    pub fn is_running(&self) -> bool {
+        self.contains(Status::RUNNING)
+    }
+
+    /// See [`Status::UNCONFIGURED_MODULE`]
+    pub fn has_unconfigured_module(&self) -> bool {
+        self.contains(Status::UNCONFIGURED_MODULE)
+    }
+
+    /// See [`Status::MISSING_MODULE`]
+    pub fn has_missing_module(&self) -> bool {
+        self.contains(Status::MISSING_MODULE)
+    }
+
+    /// Returns whether the configuration matches the connected modules, i.e.
+    /// none is missing and none is unconfigured
+    pub fn config_matches(&self) -> bool {
+        self.0 & (Status::UNCONFIGURED_MODULE.0 | Status::MISSING_MODULE.0) == 0
+    }
+
+    /// See [`Status::IMAGE_OVERFLOW`]
+    pub fn image_overflow(&self) -> bool {
+        self.contains(Status::IMAGE_OVERFLOW)
+    }
+
+    /// See [`Status::LEFT_GATEWAY`]
+    pub fn left_gateway(&self) -> bool {
+        self.contains(Status::LEFT_GATEWAY)
+    }
+
+    /// See [`Status::RIGHT_GATEWAY`]
+    pub fn right_gateway(&self) -> bool {
+        self.contains(Status::RIGHT_GATEWAY)
+    }
+}
+
+impl BitOr for Status {
+    type Output = Status;
+
+    fn bitor(self, rhs: Status) -> Status {
+        Status(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Debug for Status {
+    /// Lists the names of the set bits, unknown bits as a number
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = Status::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        let known = Status::NAMES
+            .iter()
+            .fold(0, |bits, (flag, _)| bits | flag.0);
+        if self.0 & !known != 0 {
+            names.push(format!("{:#04x}", self.0 & !known));
+        }
+        write!(f, "Status({})", names.join(" | "))
+    }
+}
+
+impl PiControl {
+    /// Reads and decodes `RevPiStatus`
+    ///
+    /// # Errors
+    /// Same as [`PiControl::get_value`], and a
+    /// [`PiControlError::InvalidArgument`] if the variable isn't a byte.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use revpi::picontrol::PiControl;
+    /// let pi = PiControl::new().unwrap();
+    /// let status = pi.status().unwrap();
+    /// if !status.config_matches() {
+    ///     eprintln!("the modules don't match the config: {:?}", status);
+    /// }
+    /// ```
+    pub fn status(&self) -> Result<Status, PiControlError> {
+        match self.get_value(STATUS_VARIABLE)? {
+            Value::Byte(b) => Ok(Status(b)),
+            _ => Err(PiControlError::InvalidArgument("RevPiStatus")),
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! PWM and pulses on digital outputs
+//!
+//! A [`PwmScheduler`] moves a [`PiControl`] into a thread of its own, which
+//! switches digital outputs on and off according to their [`Signal`]:
+//! ```no_run
+//! use revpi::picontrol::PiControl;
+//! use revpi::pwm::{PwmScheduler, Signal};
+//! use std::time::Duration;
+//!
+//! let pwm = PwmScheduler::spawn(PiControl::new().unwrap());
+//! pwm.set("O_1", Signal::Pwm { frequency: 2.0, duty: 25 }).unwrap();
+//! pwm.set("O_2", Signal::Pulse(Duration::from_millis(500))).unwrap();
+//! // switches all outputs off and returns the PiControl
+//! let pi = pwm.join();
+//! ```
+//!
+//! Outputs are switched by software, so the timing is only as good as the
+//! thread gets scheduled and the piBridge transfers the outputs, every 5 to
+//! 10ms. That's why the frequency is limited to [`MAX_FREQUENCY`]. The
+//! outputs of a DIO or DO can generate PWM in hardware instead, with the
+//! frequency configured in PiCtory, see [`Output::Dio`].
+
+use crate::{
+    modules::dio::{Channel, Dio},
+    picontrol::{PiControl, PiControlError, Value},
+    util::ensure,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Highest frequency of software PWM in Hz
+pub const MAX_FREQUENCY: f64 = 20.0;
+/// Lowest frequency of software PWM in Hz
+pub const MIN_FREQUENCY: f64 = 0.01;
+
+/// A digital output
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Output {
+    /// A bit variable, switched by software
+    Variable(String),
+    /// An output of a DIO or DO that is configured for PWM in PiCtory. Its
+    /// PWM is generated by the module, pulses and levels are written as duty
+    /// cycle of 0% or 100%.
+    Dio(Dio, Channel),
+}
+
+impl From<&str> for Output {
+    /// Returns an [`Output::Variable`]
+    fn from(name: &str) -> Self {
+        Output::Variable(name.to_string())
+    }
+}
+
+/// What an output does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Off,
+    On,
+    /// Switched on for `duty` percent of every period. The frequency is in
+    /// Hz and ignored by [`Output::Dio`].
+    Pwm {
+        frequency: f64,
+        duty: u8,
+    },
+    /// Switched on once for the duration, then off
+    Pulse(Duration),
+}
+
+impl Signal {
+    fn validate(&self) -> Result<(), PiControlError> {
+        match *self {
+            Signal::Pwm { frequency, duty } => {
+                ensure!(
+                    (MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency),
+                    PiControlError::InvalidArgument("frequency")
+                );
+                ensure!(duty <= 100, PiControlError::InvalidArgument("duty"));
+            }
+            Signal::Pulse(duration) => {
+                ensure!(
+                    !duration.is_zero(),
+                    PiControlError::InvalidArgument("duration")
+                )
+            }
+            Signal::Off | Signal::On => {}
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    output: Output,
+    signal: Signal,
+    done: Sender<Result<(), PiControlError>>,
+}
+
+// an output switched by the thread
+struct Scheduled {
+    output: Output,
+    signal: Signal,
+    on: bool,
+    // start of the current period or the pulse
+    start: Instant,
+    // next time the output is switched, None if never
+    next: Option<Instant>,
+}
+
+impl Scheduled {
+    fn new(output: Output, signal: Signal, now: Instant) -> Self {
+        let mut scheduled = Self {
+            output,
+            signal,
+            on: false,
+            start: now,
+            next: None,
+        };
+        scheduled.on = match signal {
+            Signal::Off => false,
+            Signal::On | Signal::Pulse(_) => true,
+            Signal::Pwm { duty, .. } => duty > 0,
+        };
+        scheduled.next = match (&scheduled.output, signal) {
+            (_, Signal::Pulse(duration)) => Some(now + duration),
+            // the module generates the PWM
+            (Output::Dio(..), _) => None,
+            (_, Signal::Pwm { duty, .. }) if duty > 0 && duty < 100 => {
+                Some(now + scheduled.on_time())
+            }
+            _ => None,
+        };
+        scheduled
+    }
+
+    fn period(&self) -> Duration {
+        match self.signal {
+            Signal::Pwm { frequency, .. } => Duration::from_secs_f64(1.0 / frequency),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn on_time(&self) -> Duration {
+        match self.signal {
+            Signal::Pwm { duty, .. } => self.period() * duty as u32 / 100,
+            _ => Duration::ZERO,
+        }
+    }
+
+    // switches the output if it is due, returns whether it was
+    fn advance(&mut self, now: Instant) -> bool {
+        match self.next {
+            Some(next) if next <= now => {}
+            _ => return false,
+        }
+        self.on = !self.on;
+        self.next = match self.signal {
+            Signal::Pwm { .. } if self.on => {
+                self.start += self.period();
+                // start over if the thread fell behind by a whole period
+                if self.start + self.period() <= now {
+                    self.start = now;
+                }
+                Some(self.start + self.on_time())
+            }
+            Signal::Pwm { .. } => Some(self.start + self.period()),
+            _ => None,
+        };
+        true
+    }
+}
+
+fn write(pi: &PiControl, output: &Output, signal: Signal, on: bool) -> Result<(), PiControlError> {
+    match (output, signal) {
+        (Output::Variable(name), _) => pi.set_value(name, Value::Bit(on)),
+        // the module generates the PWM
+        (Output::Dio(dio, channel), Signal::Pwm { duty, .. }) => dio.set_pwm(pi, *channel, duty),
+        (Output::Dio(dio, channel), _) => dio.set_pwm(pi, *channel, if on { 100 } else { 0 }),
+    }
+}
+
+fn run(pi: &PiControl, requests: Receiver<Request>, errors: &AtomicU64) {
+    let mut outputs: Vec<Scheduled> = Vec::new();
+    loop {
+        let next = outputs.iter().filter_map(|s| s.next).min();
+        let request = match next {
+            Some(next) => requests.recv_timeout(next.saturating_duration_since(Instant::now())),
+            None => requests.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let now = Instant::now();
+        match request {
+            Ok(request) => {
+                outputs.retain(|s| s.output != request.output);
+                let scheduled = Scheduled::new(request.output, request.signal, now);
+                let result = write(pi, &scheduled.output, scheduled.signal, scheduled.on);
+                // the requester might not wait for the result
+                let _ = request.done.send(result);
+                outputs.push(scheduled);
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        for scheduled in outputs.iter_mut() {
+            if scheduled.advance(now)
+                && write(pi, &scheduled.output, scheduled.signal, scheduled.on).is_err()
+            {
+                errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    for scheduled in outputs {
+        if write(pi, &scheduled.output, Signal::Off, false).is_err() {
+            errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Owns a [`PiControl`] and switches outputs in its own thread, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use revpi::pwm::{PwmScheduler, Signal};
+/// use std::{sync::Arc, thread, time::Duration};
+///
+/// let mock = Arc::new(MockBackend::new().variable("O_1", 0, 0, 1));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let pwm = PwmScheduler::spawn(pi);
+/// pwm.set("O_1", Signal::Pulse(Duration::from_millis(50))).unwrap();
+/// assert_eq!(mock.read(0, 1).unwrap(), [1]);
+/// thread::sleep(Duration::from_millis(200));
+/// assert_eq!(mock.read(0, 1).unwrap(), [0]);
+/// assert!(pwm.set("O_1", Signal::Pwm { frequency: 1000.0, duty: 50 }).is_err());
+/// pwm.join();
+/// ```
+#[derive(Debug)]
+pub struct PwmScheduler {
+    requests: Sender<Request>,
+    errors: Arc<AtomicU64>,
+    handle: JoinHandle<PiControl>,
+}
+
+impl PwmScheduler {
+    /// Moves `pi` into a new thread switching the outputs
+    pub fn spawn(pi: PiControl) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let errors = Arc::new(AtomicU64::new(0));
+        let handle = thread::spawn({
+            let errors = errors.clone();
+            move || {
+                run(&pi, receiver, &errors);
+                pi
+            }
+        });
+        Self {
+            requests,
+            errors,
+            handle,
+        }
+    }
+
+    /// Replaces the signal of `output` with `signal` and waits until the
+    /// output was first written
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the frequency isn't
+    /// between [`MIN_FREQUENCY`] and [`MAX_FREQUENCY`], the duty cycle is
+    /// larger than 100 or the pulse is empty, [`PiControlError::Disconnected`]
+    /// if the thread stopped, and the error of writing the output.
+    pub fn set<O: Into<Output>>(&self, output: O, signal: Signal) -> Result<(), PiControlError> {
+        signal.validate()?;
+        let (done, result) = mpsc::channel();
+        self.requests
+            .send(Request {
+                output: output.into(),
+                signal,
+                done,
+            })
+            .map_err(|_| PiControlError::Disconnected)?;
+        result.recv().map_err(|_| PiControlError::Disconnected)?
+    }
+
+    /// Returns how often switching an output failed in the thread
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Switches all outputs off, stops the thread and returns the
+    /// [`PiControl`]
+    ///
+    /// # Panics
+    /// Panics if the thread panicked.
+    pub fn join(self) -> PiControl {
+        drop(self.requests);
+        self.handle.join().unwrap()
+    }
+}
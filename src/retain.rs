@@ -0,0 +1,348 @@
+//! Variables that survive power cycles
+//!
+//! [`Retain`] saves the values of some variables, e.g. setpoints, to a file
+//! and writes them back into the processimage on the next start, like the
+//! retain export of RevPiModIO. Saving from the control loop only writes the
+//! file if a value changed and at most once per interval:
+//! ```no_run
+//! use revpi::cycle::Cycle;
+//! use revpi::picontrol::PiControl;
+//! use revpi::retain::Retain;
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let mut retain = Retain::new("/var/lib/myapp/retain")
+//!     .variable("Setpoint")
+//!     .variable("Mode")
+//!     .interval(Duration::from_secs(10));
+//! retain.restore(&pi).unwrap();
+//! let mut cycle = Cycle::new(Duration::from_millis(10));
+//! loop {
+//!     cycle.wait().unwrap();
+//!     retain.poll(&pi).unwrap();
+//! }
+//! ```
+//!
+//! The file is replaced atomically, so a power loss while saving leaves the
+//! previous values.
+//!
+//! # Format
+//! The file starts with the magic `RPRT`, the version `1` and the number of
+//! variables as little endian word. Every variable follows as the length of
+//! its name as byte, the name, the length of its value as little endian word
+//! and the bytes of the value as in the processimage, a bit as `0` or `1`.
+//! It ends with the CRC-32 (IEEE) of everything before as little endian u32.
+
+use crate::{
+    picontrol::{PiControl, PiControlError, Value},
+    util::ensure,
+};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+const MAGIC: &[u8; 4] = b"RPRT";
+const VERSION: u8 = 1;
+
+fn invalid() -> PiControlError {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid retain file").into()
+}
+
+// CRC-32 with the polynomial of IEEE 802.3, as used by zip and PNG
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn encode(values: &[(&str, Vec<u8>)]) -> Result<Vec<u8>, PiControlError> {
+    let mut buf = MAGIC.to_vec();
+    buf.push(VERSION);
+    let count =
+        u16::try_from(values.len()).map_err(|_| PiControlError::InvalidArgument("count"))?;
+    buf.extend_from_slice(&count.to_le_bytes());
+    for (name, value) in values {
+        let name_len =
+            u8::try_from(name.len()).map_err(|_| PiControlError::InvalidArgument("name"))?;
+        buf.push(name_len);
+        buf.extend_from_slice(name.as_bytes());
+        let len =
+            u16::try_from(value.len()).map_err(|_| PiControlError::InvalidArgument("value"))?;
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+    let crc = crc32(&buf);
+    buf.extend_from_slice(&crc.to_le_bytes());
+    Ok(buf)
+}
+
+fn decode(buf: &[u8]) -> Result<Vec<(String, Vec<u8>)>, PiControlError> {
+    ensure!(buf.len() >= 11, invalid());
+    let (data, crc) = buf.split_at(buf.len() - 4);
+    ensure!(crc32(data).to_le_bytes() == crc, invalid());
+    ensure!(&data[0..4] == MAGIC && data[4] == VERSION, invalid());
+    let count = u16::from_le_bytes([data[5], data[6]]);
+    let mut rest = &data[7..];
+    // splits off the next `len` bytes
+    let mut take = |len: usize| {
+        ensure!(rest.len() >= len, invalid());
+        let (head, tail) = rest.split_at(len);
+        rest = tail;
+        Ok(head)
+    };
+    let mut values = Vec::new();
+    for _ in 0..count {
+        let name_len = take(1)?[0] as usize;
+        let name = String::from_utf8(take(name_len)?.to_vec()).map_err(|_| invalid())?;
+        let len = take(2)?;
+        let len = u16::from_le_bytes([len[0], len[1]]) as usize;
+        values.push((name, take(len)?.to_vec()));
+    }
+    ensure!(rest.is_empty(), invalid());
+    Ok(values)
+}
+
+// replaces the file at `path` with `bytes`, so it either has the old or the
+// new content even if power is lost
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // the rename itself has to reach the disk too
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Saves and restores the values of variables, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use revpi::retain::Retain;
+/// use std::{sync::Arc, time::Duration};
+///
+/// let path = std::env::temp_dir().join("revpi-retain-doctest");
+/// let mock = Arc::new(MockBackend::new().variable("Setpoint", 0, 0, 16));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut retain = Retain::new(&path).variable("Setpoint").interval(Duration::ZERO);
+/// mock.write(0, &1234u16.to_le_bytes()).unwrap();
+/// assert!(retain.poll(&pi).unwrap());
+/// assert!(!retain.poll(&pi).unwrap());
+///
+/// // after a power cycle
+/// mock.write(0, &[0, 0]).unwrap();
+/// assert_eq!(retain.restore(&pi).unwrap(), 1);
+/// assert_eq!(mock.read(0, 2).unwrap(), 1234u16.to_le_bytes());
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Retain {
+    path: PathBuf,
+    variables: Vec<String>,
+    interval: Duration,
+    // time and content of the last save
+    saved: Option<(Instant, Vec<u8>)>,
+}
+
+impl Retain {
+    /// Creates a retain file at `path` without any variables, saved at most
+    /// once a second by [`Retain::poll`]
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            variables: Vec::new(),
+            interval: Duration::from_secs(1),
+            saved: None,
+        }
+    }
+
+    /// Adds the variable `name`
+    pub fn variable(mut self, name: &str) -> Self {
+        self.variables.push(name.to_string());
+        self
+    }
+
+    /// Sets the minimum time between two saves by [`Retain::poll`]. Every
+    /// save is a write to the disk, which wears out SD cards and eMMCs.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Writes the saved values back into the processimage and returns how
+    /// many were written. Values of variables that aren't configured anymore,
+    /// that the processimage doesn't have anymore or whose length changed are
+    /// skipped, a missing file restores nothing.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the file can't be read or is
+    /// corrupt, e.g. its CRC doesn't match, the errors of looking up a
+    /// variable other than not finding it and the errors of
+    /// [`PiControl::set_value`].
+    pub fn restore(&self, pi: &PiControl) -> Result<usize, PiControlError> {
+        let buf = match fs::read(&self.path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut restored = 0;
+        for (name, bytes) in decode(&buf)? {
+            if !self.variables.contains(&name) {
+                continue;
+            }
+            let length = match pi.find_variable(&name) {
+                Ok(var) => var.length as usize,
+                Err(PiControlError::InvalidArgument("name")) => continue,
+                Err(e) => return Err(e),
+            };
+            if length.div_ceil(8) != bytes.len() {
+                continue;
+            }
+            if let Some(value) = Value::decode(&bytes, 0, length) {
+                pi.set_value(&name, value)?;
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+
+    /// Saves the current values of all variables to the file.
+    ///
+    /// # Errors
+    /// Returns the errors of [`PiControl::get_value`] and a
+    /// [`PiControlError::IoError`] if the file can't be written.
+    pub fn save(&mut self, pi: &PiControl) -> Result<(), PiControlError> {
+        let buf = self.read(pi)?;
+        write_atomic(&self.path, &buf)?;
+        self.saved = Some((Instant::now(), buf));
+        Ok(())
+    }
+
+    /// Saves the values if one changed since the last save and the interval
+    /// is over. Returns whether the file was written.
+    ///
+    /// # Errors
+    /// Same as [`Retain::save`].
+    pub fn poll(&mut self, pi: &PiControl) -> Result<bool, PiControlError> {
+        if let Some((at, _)) = &self.saved {
+            if at.elapsed() < self.interval {
+                return Ok(false);
+            }
+        }
+        let buf = self.read(pi)?;
+        if matches!(&self.saved, Some((_, saved)) if *saved == buf) {
+            return Ok(false);
+        }
+        write_atomic(&self.path, &buf)?;
+        self.saved = Some((Instant::now(), buf));
+        Ok(true)
+    }
+
+    // the content of the file for the current values
+    fn read(&self, pi: &PiControl) -> Result<Vec<u8>, PiControlError> {
+        let mut values = Vec::new();
+        for name in self.variables.iter() {
+            values.push((name.as_str(), pi.get_value(name)?.to_le_bytes()));
+        }
+        encode(&values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picontrol::backend::{FaultInjector, MockBackend};
+    use std::sync::Arc;
+
+    fn values() -> Vec<(&'static str, Vec<u8>)> {
+        vec![("Setpoint", vec![0xd2, 0x04]), ("Mode", vec![1])]
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let buf = encode(&values()).unwrap();
+        assert_eq!(&buf[..7], b"RPRT\x01\x02\x00");
+        let decoded = decode(&buf).unwrap();
+        let expected: Vec<_> = values()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        assert_eq!(decoded, expected);
+        assert_eq!(decode(&encode(&[]).unwrap()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn truncated() {
+        let buf = encode(&values()).unwrap();
+        for len in 0..buf.len() {
+            assert!(decode(&buf[..len]).is_err(), "{} bytes", len);
+        }
+        // a count larger than the values with a matching CRC
+        let mut data = buf[..buf.len() - 4].to_vec();
+        data[5] = 3;
+        let crc = crc32(&data);
+        data.extend_from_slice(&crc.to_le_bytes());
+        assert!(decode(&data).is_err());
+    }
+
+    #[test]
+    fn crc_mismatch() {
+        let mut buf = encode(&values()).unwrap();
+        buf[10] ^= 1;
+        assert!(matches!(decode(&buf), Err(PiControlError::IoError(_))));
+    }
+
+    #[test]
+    fn restore_skips_missing_variables() {
+        let path = std::env::temp_dir().join(format!("revpi-retain-{}", std::process::id()));
+        write_atomic(&path, &encode(&values()).unwrap()).unwrap();
+        // Setpoint is gone from the processimage
+        let mock = Arc::new(MockBackend::new().variable("Mode", 0, 0, 8));
+        let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+        let retain = Retain::new(&path).variable("Setpoint").variable("Mode");
+        let restored = retain.restore(&pi);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored.unwrap(), 1);
+        assert_eq!(mock.read(0, 1).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn restore_returns_lookup_errors() {
+        let path = std::env::temp_dir().join(format!("revpi-retain-err-{}", std::process::id()));
+        write_atomic(&path, &encode(&values()).unwrap()).unwrap();
+        let mock = Arc::new(MockBackend::new().variable("Mode", 0, 0, 8));
+        let faults = Arc::new(FaultInjector::new(mock.clone()));
+        let pi = PiControl::builder()
+            .backend(faults.clone())
+            .build()
+            .unwrap();
+        let retain = Retain::new(&path).variable("Mode");
+        // the lookup of Mode fails
+        faults.fail_nth(1, libc::EIO);
+        let restored = retain.restore(&pi);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(restored, Err(PiControlError::IoError(_))));
+        assert_eq!(mock.read(0, 1).unwrap(), vec![0]);
+    }
+}
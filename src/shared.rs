@@ -0,0 +1,228 @@
+//! Areas of the processimage shared between applications
+//!
+//! piControl never touches the bytes of a virtual device, so applications can
+//! use them to exchange data, like PiCtory's virtual modules. A
+//! [`SharedArea`] is such a device: [`SharedArea::register`] adds it to the
+//! config, after which its variables can be accessed by name like any other,
+//! and [`SharedArea::update`] changes it while other processes are locked
+//! out:
+//! ```no_run
+//! use revpi::picontrol::PiControl;
+//! use revpi::rsc::{InOutMemBuilder, RSC};
+//! use revpi::shared::SharedArea;
+//! use std::fs::File;
+//!
+//! let path = "/etc/revpi/config.rsc";
+//! let mut rsc: RSC = serde_json::from_reader(File::open(path).unwrap()).unwrap();
+//! SharedArea::register(&mut rsc, "Exchange", 64, vec![
+//!     InOutMemBuilder::new("Setpoint", 16),
+//!     InOutMemBuilder::new("Count", 32),
+//! ])
+//! .unwrap();
+//! let pi = PiControl::new().unwrap();
+//! pi.apply_new_config(&rsc, path).unwrap();
+//!
+//! // in every application
+//! let area = SharedArea::from_rsc(&rsc, "Exchange").unwrap();
+//! area.update(&pi, |bytes| bytes[2] += 1).unwrap();
+//! let setpoint = pi.get_value("Setpoint").unwrap();
+//! ```
+//!
+//! A single [`SharedArea::read`] or [`SharedArea::write`] is one access to
+//! the driver and therefore never sees a half written area. The lock is only
+//! needed if a value is read and written back. It is an advisory lock on a
+//! file named after the area, so it only protects against applications using
+//! [`SharedArea`] as well.
+
+use crate::picontrol::{PiControl, PiControlError};
+use crate::util::ensure;
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    ops::Range,
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+/// Product type of virtual devices in the config
+pub const VIRTUAL_PRODUCT_TYPE: u16 = 102;
+
+/// A virtual device shared between applications, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use revpi::shared::SharedArea;
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let area = SharedArea::new("Exchange", 100..104).lock_dir(std::env::temp_dir());
+/// area.write(&pi, 0, &[1, 2]).unwrap();
+/// area.update(&pi, |bytes| bytes[3] = bytes[0] + bytes[1]).unwrap();
+/// assert_eq!(mock.read(100, 4).unwrap(), vec![1, 2, 0, 3]);
+/// assert!(area.write(&pi, 3, &[1, 2]).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SharedArea {
+    name: String,
+    range: Range<u16>,
+    lock_dir: PathBuf,
+}
+
+impl SharedArea {
+    /// Creates the area `name` at the given addresses of the processimage.
+    /// The lock file is placed in `/run/lock` by default.
+    pub fn new<S: Into<String>>(name: S, range: Range<u16>) -> Self {
+        Self {
+            name: name.into(),
+            range,
+            lock_dir: PathBuf::from("/run/lock"),
+        }
+    }
+
+    /// Sets the directory of the lock file, which has to be the same for all
+    /// applications sharing the area
+    pub fn lock_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.lock_dir = dir.into();
+        self
+    }
+
+    /// Adds a virtual device `name` at `position` with `vars` as inputs to
+    /// `rsc`, see [`RSC::add_device`](crate::rsc::RSC::add_device).
+    /// The config has to be applied before the area can be used, see
+    /// [`PiControl::apply_new_config`].
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::RscError`] if the position or a variable
+    /// name is already taken or the device doesn't fit into the processimage.
+    #[cfg(feature = "rsc")]
+    pub fn register<I>(
+        rsc: &mut crate::rsc::RSC,
+        name: &str,
+        position: u64,
+        vars: I,
+    ) -> Result<Self, PiControlError>
+    where
+        I: IntoIterator<Item = crate::rsc::InOutMemBuilder>,
+    {
+        use crate::rsc::{DeviceBuilder, DeviceKind, ProductType};
+        let builder = vars.into_iter().fold(
+            DeviceBuilder::new(
+                DeviceKind::Virtual,
+                ProductType::from(VIRTUAL_PRODUCT_TYPE),
+                name,
+            )
+            .position(position),
+            DeviceBuilder::input,
+        );
+        rsc.add_device(builder)?;
+        Self::from_rsc(rsc, name)
+    }
+
+    /// Returns the area of the virtual device `name` in `rsc`, which spans
+    /// all of its variables
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if there is no virtual
+    /// device `name` or it lies outside of the processimage.
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(rsc: &crate::rsc::RSC, name: &str) -> Result<Self, PiControlError> {
+        let device = rsc
+            .devices
+            .iter()
+            .find(|d| d.name == name && d.dev_type == crate::rsc::DeviceKind::Virtual)
+            .ok_or(PiControlError::InvalidArgument("name"))?;
+        let end = device
+            .variables()
+            .filter_map(|var| device.end_of(var))
+            .max()
+            .unwrap_or(device.offset);
+        let invalid = |_| PiControlError::InvalidArgument("device");
+        let start = u16::try_from(device.offset).map_err(invalid)?;
+        let end = u16::try_from(end).map_err(invalid)?;
+        Ok(Self::new(name, start..end))
+    }
+
+    /// Returns the name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the addresses of the area
+    pub fn range(&self) -> Range<u16> {
+        self.range.clone()
+    }
+
+    /// Returns the path of the lock file
+    pub fn lock_path(&self) -> PathBuf {
+        self.lock_dir.join(format!("revpi-{}.lock", self.name))
+    }
+
+    // absolute address of `len` bytes at `offset`
+    fn address(&self, offset: u16, len: usize) -> Result<u16, PiControlError> {
+        ensure!(
+            offset as usize + len <= self.range.len(),
+            PiControlError::InvalidArgument("offset")
+        );
+        Ok(self.range.start + offset)
+    }
+
+    /// Reads `bytes.len()` bytes at `offset` inside the area
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the area and the error of the backend if reading fails.
+    pub fn read(
+        &self,
+        pi: &PiControl,
+        offset: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), PiControlError> {
+        let address = self.address(offset, bytes.len())?;
+        unsafe { pi.inner.get_bytes(address, bytes) }
+    }
+
+    /// Writes `bytes` at `offset` inside the area
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if the bytes don't lie
+    /// inside the area and the error of the backend if writing fails.
+    pub fn write(&self, pi: &PiControl, offset: u16, bytes: &[u8]) -> Result<(), PiControlError> {
+        let address = self.address(offset, bytes.len())?;
+        unsafe { pi.inner.set_bytes(address, bytes) }
+    }
+
+    /// Waits for the lock, reads the whole area, lets `f` change it and
+    /// writes it back
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the lock file can't be
+    /// created or locked and the error of the backend if reading or writing
+    /// fails.
+    pub fn update<T, F>(&self, pi: &PiControl, f: F) -> Result<T, PiControlError>
+    where
+        F: FnOnce(&mut [u8]) -> T,
+    {
+        let _lock = lock(&self.lock_path())?;
+        let mut bytes = vec![0u8; self.range.len()];
+        self.read(pi, 0, &mut bytes)?;
+        let res = f(&mut bytes);
+        self.write(pi, 0, &bytes)?;
+        Ok(res)
+    }
+}
+
+// the lock is released when the file is closed
+fn lock(path: &Path) -> Result<File, PiControlError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(file)
+}
@@ -0,0 +1,295 @@
+//! Generic access to variables for protocol layers
+//!
+//! A [`TagProvider`] lists named values with their data type, reads and
+//! writes them and reports their changes, which is all an OPC UA, MQTT or
+//! REST layer needs to publish the RevPi. [`PiTags`] is the provider on top of
+//! [`PiControl`] with the variables of a config as tags:
+//! ```no_run
+//! use revpi::picontrol::PiControl;
+//! use revpi::rsc::RSC;
+//! use revpi::tags::{PiTags, TagProvider};
+//! use std::fs::File;
+//!
+//! let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+//! let mut tags = PiTags::from_rsc(PiControl::new().unwrap(), &rsc);
+//! for tag in tags.tags() {
+//!     println!("{} {} {}", tag.name, tag.data_type.as_str(), tags.read(&tag.name).unwrap());
+//! }
+//! tags.subscribe("I_1").unwrap();
+//! loop {
+//!     for change in tags.changes().unwrap() {
+//!         println!("{}: {} -> {}", change.name, change.old, change.new);
+//!     }
+//! }
+//! ```
+//!
+//! With the `rest` feature, [`rest`] serves any provider over HTTP as a
+//! reference for such a layer.
+
+#[cfg(feature = "rest")]
+pub mod rest;
+
+use crate::{
+    monitor::{Change, Monitor},
+    picontrol::{PiControl, PiControlError, Value},
+    util::ensure,
+};
+use std::{mem, time::Duration};
+
+/// Data type of a tag, given by the bit length of the variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// A single bit
+    Bool,
+    /// Unsigned 8 bit integer
+    Byte,
+    /// Unsigned 16 bit integer
+    Word,
+    /// Unsigned 32 bit integer
+    DWord,
+}
+
+impl DataType {
+    /// Returns the type of a variable with `bit_length` bits, `None` if there
+    /// is no such type
+    pub fn from_bit_length(bit_length: u16) -> Option<Self> {
+        match bit_length {
+            1 => Some(DataType::Bool),
+            8 => Some(DataType::Byte),
+            16 => Some(DataType::Word),
+            32 => Some(DataType::DWord),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bits
+    pub fn bit_length(&self) -> u16 {
+        match self {
+            DataType::Bool => 1,
+            DataType::Byte => 8,
+            DataType::Word => 16,
+            DataType::DWord => 32,
+        }
+    }
+
+    /// Returns the name used in text protocols, `"bool"`, `"byte"`, `"word"`
+    /// or `"dword"`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataType::Bool => "bool",
+            DataType::Byte => "byte",
+            DataType::Word => "word",
+            DataType::DWord => "dword",
+        }
+    }
+
+    /// Parses `s` as a value of this type, formatted like
+    /// [`Value`]'s `Display` does or as `true` or `false` for bools.
+    /// Returns `None` if `s` isn't a number or out of range.
+    ///
+    /// # Example
+    /// ```
+    /// # use revpi::{picontrol::Value, tags::DataType};
+    /// assert_eq!(DataType::Bool.parse("true"), Some(Value::Bit(true)));
+    /// assert_eq!(DataType::Word.parse("1000"), Some(Value::Word(1000)));
+    /// assert_eq!(DataType::Byte.parse("1000"), None);
+    /// ```
+    pub fn parse(&self, s: &str) -> Option<Value> {
+        let s = s.trim();
+        match (self, s) {
+            (DataType::Bool, "true") => return Some(Value::Bit(true)),
+            (DataType::Bool, "false") => return Some(Value::Bit(false)),
+            _ => {}
+        }
+        let n: u32 = s.parse().ok()?;
+        match self {
+            DataType::Bool => (n <= 1).then_some(Value::Bit(n == 1)),
+            DataType::Byte => u8::try_from(n).ok().map(Value::Byte),
+            DataType::Word => u16::try_from(n).ok().map(Value::Word),
+            DataType::DWord => Some(Value::DWord(n)),
+        }
+    }
+}
+
+/// A named value offered by a [`TagProvider`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tag {
+    /// Name, unique within the provider
+    pub name: String,
+    /// Data type of the values
+    pub data_type: DataType,
+    /// Whether [`TagProvider::write`] accepts the tag
+    pub writable: bool,
+    /// Description, may be empty
+    pub comment: String,
+}
+
+/// Source of tags for protocol layers, see the
+/// [module documentation](self)
+pub trait TagProvider {
+    /// Returns all tags
+    fn tags(&self) -> &[Tag];
+
+    /// Returns the tag `name`
+    fn tag(&self, name: &str) -> Option<&Tag> {
+        self.tags().iter().find(|tag| tag.name == name)
+    }
+
+    /// Returns the current value of the tag `name`
+    ///
+    /// # Errors
+    /// Returns an error if there is no such tag or reading fails.
+    fn read(&self, name: &str) -> Result<Value, PiControlError>;
+
+    /// Sets the tag `name` to `value`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::ReadOnly`] if the tag isn't writable and
+    /// an error if there is no such tag, the value doesn't fit or writing
+    /// fails.
+    fn write(&mut self, name: &str, value: Value) -> Result<(), PiControlError>;
+
+    /// Reports the changes of the tag `name` from now on, see
+    /// [`TagProvider::changes`]. Subscribing twice has no effect.
+    ///
+    /// # Errors
+    /// Returns an error if there is no such tag.
+    fn subscribe(&mut self, name: &str) -> Result<(), PiControlError>;
+
+    /// Returns the subscribed tags that changed since the last call
+    ///
+    /// # Errors
+    /// Returns an error if reading fails.
+    fn changes(&mut self) -> Result<Vec<Change>, PiControlError>;
+}
+
+/// [`TagProvider`] for the variables of the processimage
+///
+/// Changes are found by polling with a [`Monitor`], so a change that is
+/// reverted before the next call of [`TagProvider::changes`] goes unnoticed.
+///
+/// # Example
+/// ```
+/// use revpi::picontrol::{backend::MockBackend, PiControl, Value};
+/// use revpi::tags::{DataType, PiTags, Tag, TagProvider};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new().variable("I_1", 0, 0, 1).variable("O_1", 1, 0, 16));
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let tag = |name: &str, data_type, writable| Tag {
+///     name: name.to_string(),
+///     data_type,
+///     writable,
+///     comment: String::new(),
+/// };
+/// let mut tags = PiTags::new(pi)
+///     .tag(tag("I_1", DataType::Bool, false))
+///     .tag(tag("O_1", DataType::Word, true));
+/// tags.write("O_1", Value::Word(7)).unwrap();
+/// assert_eq!(mock.read(1, 2).unwrap(), vec![7, 0]);
+/// assert!(tags.write("I_1", Value::Bit(true)).is_err());
+///
+/// tags.subscribe("I_1").unwrap();
+/// assert!(tags.changes().unwrap().is_empty());
+/// mock.write(0, &[1]).unwrap();
+/// assert_eq!(tags.changes().unwrap()[0].new, Value::Bit(true));
+/// ```
+#[derive(Debug)]
+pub struct PiTags {
+    pi: PiControl,
+    tags: Vec<Tag>,
+    subscribed: Vec<String>,
+    monitor: Monitor,
+}
+
+impl PiTags {
+    /// Creates a provider without any tags
+    pub fn new(pi: PiControl) -> Self {
+        Self {
+            pi,
+            tags: Vec::new(),
+            subscribed: Vec::new(),
+            monitor: Monitor::new(Duration::ZERO),
+        }
+    }
+
+    /// Adds `tag`, which has to name a variable of the processimage
+    pub fn tag(mut self, tag: Tag) -> Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Creates a provider with every exported variable configured in `rsc`
+    /// as tag. Outputs and the variables of virtual devices are writable.
+    #[cfg(feature = "rsc")]
+    pub fn from_rsc(pi: PiControl, rsc: &crate::rsc::RSC) -> Self {
+        let mut tags = Self::new(pi);
+        for device in rsc.devices.iter() {
+            let virtual_device = device.dev_type == crate::rsc::DeviceKind::Virtual;
+            for (vars, output) in [
+                (&device.inp, false),
+                (&device.out, true),
+                (&device.mem, false),
+            ] {
+                for var in vars
+                    .values()
+                    .filter(|var| var.exported && !var.name.is_empty())
+                {
+                    let data_type = match DataType::from_bit_length(var.bit_length as u16) {
+                        Some(data_type) => data_type,
+                        None => continue,
+                    };
+                    tags = tags.tag(Tag {
+                        name: var.name.clone(),
+                        data_type,
+                        writable: output || virtual_device,
+                        comment: var.comment.clone(),
+                    });
+                }
+            }
+        }
+        tags
+    }
+
+    /// Returns the underlying [`PiControl`]
+    pub fn pi(&self) -> &PiControl {
+        &self.pi
+    }
+
+    fn known(&self, name: &str) -> Result<&Tag, PiControlError> {
+        self.tag(name).ok_or(PiControlError::InvalidArgument("tag"))
+    }
+}
+
+impl TagProvider for PiTags {
+    fn tags(&self) -> &[Tag] {
+        &self.tags
+    }
+
+    fn read(&self, name: &str) -> Result<Value, PiControlError> {
+        self.known(name)?;
+        self.pi.get_value(name)
+    }
+
+    fn write(&mut self, name: &str, value: Value) -> Result<(), PiControlError> {
+        ensure!(
+            self.known(name)?.writable,
+            PiControlError::ReadOnly(name.to_string())
+        );
+        self.pi.set_value(name, value)
+    }
+
+    fn subscribe(&mut self, name: &str) -> Result<(), PiControlError> {
+        self.known(name)?;
+        if !self.subscribed.iter().any(|s| s == name) {
+            self.subscribed.push(name.to_string());
+            let monitor = mem::replace(&mut self.monitor, Monitor::new(Duration::ZERO));
+            self.monitor = monitor.watch(name);
+        }
+        Ok(())
+    }
+
+    fn changes(&mut self) -> Result<Vec<Change>, PiControlError> {
+        self.monitor.poll(&self.pi)
+    }
+}
@@ -0,0 +1,122 @@
+//! Plain text REST interface for a [`TagProvider`]
+//!
+//! A reference for integrating a protocol layer, kept minimal on purpose:
+//!
+//! | Request               | Response                                          |
+//! |-----------------------|---------------------------------------------------|
+//! | `GET /tags`           | one line per tag: name, data type, `rw` or `ro`   |
+//! | `GET /tags/NAME`      | the value                                         |
+//! | `PUT /tags/NAME`      | writes the value in the body, see [`DataType::parse`](super::DataType::parse) |
+//! | `GET /changes`        | one line per change since the last request: name, old and new value |
+//!
+//! All tags are subscribed when serving starts. Errors are answered with
+//! `400` for malformed values, `403` for read only tags, `404` for unknown
+//! tags and `500` for everything else.
+//! ```no_run
+//! use revpi::picontrol::PiControl;
+//! use revpi::rsc::RSC;
+//! use revpi::tags::{rest, PiTags};
+//! use std::fs::File;
+//!
+//! let rsc: RSC = serde_json::from_reader(File::open("/etc/revpi/config.rsc").unwrap()).unwrap();
+//! let mut tags = PiTags::from_rsc(PiControl::new().unwrap(), &rsc);
+//! rest::serve(&mut tags, "0.0.0.0:8080").unwrap();
+//! ```
+
+use super::TagProvider;
+use crate::{http, picontrol::PiControlError};
+use std::{
+    fmt::Write,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+const CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+// status line and body answering `request`
+fn answer<T: TagProvider>(provider: &mut T, request: &http::Request) -> (&'static str, String) {
+    let ok = |body: String| ("200 OK", body);
+    let name = request.path.strip_prefix("/tags/");
+    let res = match (request.method.as_str(), request.path.as_str(), name) {
+        ("GET", "/tags", _) => {
+            let mut body = String::new();
+            for tag in provider.tags() {
+                let access = if tag.writable { "rw" } else { "ro" };
+                let _ = writeln!(body, "{} {} {}", tag.name, tag.data_type.as_str(), access);
+            }
+            Ok(ok(body))
+        }
+        ("GET", "/changes", _) => provider.changes().map(|changes| {
+            let mut body = String::new();
+            for change in changes {
+                let _ = writeln!(body, "{} {} {}", change.name, change.old, change.new);
+            }
+            ok(body)
+        }),
+        ("GET", _, Some(name)) => match provider.tag(name) {
+            Some(_) => provider.read(name).map(|value| ok(format!("{}\n", value))),
+            None => return ("404 Not Found", "unknown tag\n".to_string()),
+        },
+        ("PUT", _, Some(name)) => {
+            let data_type = match provider.tag(name) {
+                Some(tag) => tag.data_type,
+                None => return ("404 Not Found", "unknown tag\n".to_string()),
+            };
+            let value = std::str::from_utf8(&request.body)
+                .ok()
+                .and_then(|s| data_type.parse(s));
+            match value {
+                Some(value) => provider
+                    .write(name, value)
+                    .map(|_| ("204 No Content", String::new())),
+                None => return ("400 Bad Request", "invalid value\n".to_string()),
+            }
+        }
+        ("GET", _, _) => return ("404 Not Found", "not found\n".to_string()),
+        _ => return ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    res.unwrap_or_else(|e| match e {
+        PiControlError::ReadOnly(_) => ("403 Forbidden", format!("{}\n", e)),
+        e => ("500 Internal Server Error", format!("{}\n", e)),
+    })
+}
+
+/// Answers a single request on `stream`, see the
+/// [module documentation](self)
+///
+/// # Errors
+/// Returns a [`PiControlError::IoError`] if the request can't be read or the
+/// response can't be written. Errors of the provider are sent to the client
+/// instead.
+pub fn handle<T: TagProvider>(
+    provider: &mut T,
+    mut stream: TcpStream,
+) -> Result<(), PiControlError> {
+    let request = http::read_request(&mut stream)?;
+    let (status, body) = answer(provider, &request);
+    http::respond(&mut stream, status, CONTENT_TYPE, &body)
+}
+
+/// Subscribes all tags of `provider`, listens on `addr` and answers requests
+/// one after another, forever
+///
+/// # Errors
+/// Returns a [`PiControlError::IoError`] if `addr` can't be bound and the
+/// error of the provider if subscribing fails.
+pub fn serve<T, A>(provider: &mut T, addr: A) -> Result<(), PiControlError>
+where
+    T: TagProvider,
+    A: ToSocketAddrs,
+{
+    let names: Vec<_> = provider.tags().iter().map(|tag| tag.name.clone()).collect();
+    for name in names {
+        provider.subscribe(&name)?;
+    }
+    // the first call only records the current values
+    provider.changes()?;
+    let listener = TcpListener::bind(addr)?;
+    for stream in listener.incoming().flatten() {
+        // a broken connection only concerns its client
+        let _ = handle(provider, stream);
+    }
+    Ok(())
+}
@@ -0,0 +1,354 @@
+//! Recording and replaying the processimage
+//!
+//! A [`Recorder`] writes the changes of a region of the processimage with
+//! timestamps to a compact binary trace, a [`TraceReader`] reads it back and
+//! replays it into another [`PiControl`], e.g. one with a
+//! [`MockBackend`](crate::picontrol::backend::MockBackend) or a
+//! [`FileBackend`](crate::picontrol::backend::FileBackend), so a problem
+//! seen in the field can be debugged offline:
+//! ```no_run
+//! use revpi::clock::SystemClock;
+//! use revpi::cycle::Cycle;
+//! use revpi::picontrol::PiControl;
+//! use revpi::trace::Recorder;
+//! use std::{fs::File, io::BufWriter, time::Duration};
+//!
+//! let pi = PiControl::new().unwrap();
+//! let file = BufWriter::new(File::create("field.trace").unwrap());
+//! let mut recorder = Recorder::new(file, 0..4096, SystemClock::new()).unwrap();
+//! let mut cycle = Cycle::new(Duration::from_millis(10));
+//! loop {
+//!     cycle.wait().unwrap();
+//!     recorder.record(&pi).unwrap();
+//! }
+//! ```
+//!
+//! # Format
+//! The file starts with the magic `RPTR`, the version `1`, the region as two
+//! little endian words and the wall-clock time of the start in microseconds
+//! since the Unix epoch as little endian u64. It is followed by one record per
+//! change: the microseconds since the previous record, the number of changed
+//! runs of bytes and for every run the number of unchanged bytes before it,
+//! its length and its bytes, all numbers as LEB128. The first record
+//! contains the whole region.
+
+use crate::{
+    clock::Clock,
+    picontrol::{PiControl, PiControlError},
+    util::ensure,
+};
+use std::{
+    io::{self, Read, Write},
+    ops::Range,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8; 4] = b"RPTR";
+const VERSION: u8 = 1;
+
+fn invalid() -> PiControlError {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid trace").into()
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+// `None` at the end of the file
+fn read_varint<R: Read>(reader: &mut R) -> Result<Option<u64>, PiControlError> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        if reader.read(&mut byte)? == 0 {
+            return match shift {
+                0 => Ok(None),
+                _ => Err(invalid()),
+            };
+        }
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(n));
+        }
+    }
+    Err(invalid())
+}
+
+/// Writes the changes of a region of the processimage to a trace, see the
+/// [module documentation](self)
+///
+/// # Example
+/// ```
+/// use revpi::clock::SystemClock;
+/// use revpi::picontrol::{backend::MockBackend, PiControl};
+/// use revpi::trace::{Recorder, TraceReader};
+/// use std::sync::Arc;
+///
+/// let mock = Arc::new(MockBackend::new());
+/// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+/// let mut recorder = Recorder::new(Vec::new(), 10..14, SystemClock::new()).unwrap();
+/// recorder.record(&pi).unwrap();
+/// mock.write(12, &[7]).unwrap();
+/// recorder.record(&pi).unwrap();
+/// // unchanged, nothing is written
+/// assert!(!recorder.record(&pi).unwrap());
+///
+/// let trace = recorder.finish().unwrap();
+/// let reader = TraceReader::new(trace.as_slice()).unwrap();
+/// assert_eq!(reader.region(), 10..14);
+/// let frames: Vec<_> = reader.map(|f| f.unwrap().bytes).collect();
+/// assert_eq!(frames, vec![vec![0, 0, 0, 0], vec![0, 0, 7, 0]]);
+///
+/// let replayed = Arc::new(MockBackend::new());
+/// let sim = PiControl::builder().backend(replayed.clone()).build().unwrap();
+/// let reader = TraceReader::new(trace.as_slice()).unwrap();
+/// assert_eq!(reader.replay(&sim, 10.0).unwrap(), 2);
+/// assert_eq!(replayed.read(10, 4).unwrap(), vec![0, 0, 7, 0]);
+/// ```
+#[derive(Debug)]
+pub struct Recorder<W: Write, C: Clock> {
+    writer: W,
+    clock: C,
+    region: Range<u16>,
+    start: Duration,
+    last_time: Duration,
+    // the region as last recorded, None before the first record
+    last: Option<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write, C: Clock> Recorder<W, C> {
+    /// Creates a recorder of `region`, which writes the header to `writer`
+    /// and stamps the records with `clock`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `region` is empty and
+    /// a [`PiControlError::IoError`] if writing fails.
+    pub fn new(mut writer: W, region: Range<u16>, mut clock: C) -> Result<Self, PiControlError> {
+        ensure!(
+            !region.is_empty(),
+            PiControlError::InvalidArgument("region")
+        );
+        let now = clock.now();
+        let wall = now.wall.duration_since(UNIX_EPOCH).unwrap_or_default();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&region.start.to_le_bytes())?;
+        writer.write_all(&region.end.to_le_bytes())?;
+        writer.write_all(&(wall.as_micros() as u64).to_le_bytes())?;
+        Ok(Self {
+            writer,
+            clock,
+            buffer: vec![0; region.len()],
+            region,
+            start: now.monotonic,
+            last_time: Duration::ZERO,
+            last: None,
+        })
+    }
+
+    /// Returns the recorded region
+    pub fn region(&self) -> Range<u16> {
+        self.region.clone()
+    }
+
+    /// Reads the region and writes a record if it changed. Returns whether a
+    /// record was written.
+    ///
+    /// # Errors
+    /// Returns the error of the backend if reading fails and a
+    /// [`PiControlError::IoError`] if writing fails.
+    pub fn record(&mut self, pi: &PiControl) -> Result<bool, PiControlError> {
+        let mut bytes = std::mem::take(&mut self.buffer);
+        let res = unsafe { pi.inner.get_bytes(self.region.start, &mut bytes) }
+            .and_then(|_| self.record_bytes(&bytes));
+        self.buffer = bytes;
+        res
+    }
+
+    /// Writes a record with `bytes` as content of the region if it changed,
+    /// e.g. for bytes read elsewhere. Returns whether a record was written.
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `bytes` doesn't have
+    /// the length of the region and a [`PiControlError::IoError`] if writing
+    /// fails.
+    pub fn record_bytes(&mut self, bytes: &[u8]) -> Result<bool, PiControlError> {
+        ensure!(
+            bytes.len() == self.region.len(),
+            PiControlError::InvalidArgument("bytes")
+        );
+        if self.last.as_deref() == Some(bytes) {
+            return Ok(false);
+        }
+        // runs of changed bytes, the whole region in the first record
+        let changed = |i: usize| self.last.as_ref().is_none_or(|last| bytes[i] != last[i]);
+        let mut runs = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            if !changed(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < bytes.len() && changed(i) {
+                i += 1;
+            }
+            runs.push(start..i);
+        }
+        let time = self.clock.now().monotonic.saturating_sub(self.start);
+        // rounded like the reader does, so the error doesn't add up
+        let delta = time.saturating_sub(self.last_time).as_micros() as u64;
+        write_varint(&mut self.writer, delta)?;
+        write_varint(&mut self.writer, runs.len() as u64)?;
+        let mut end = 0;
+        for run in runs {
+            write_varint(&mut self.writer, (run.start - end) as u64)?;
+            write_varint(&mut self.writer, run.len() as u64)?;
+            self.writer.write_all(&bytes[run.clone()])?;
+            end = run.end;
+        }
+        self.last_time += Duration::from_micros(delta);
+        self.last = Some(bytes.to_vec());
+        Ok(true)
+    }
+
+    /// Flushes and returns the writer
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if flushing fails.
+    pub fn finish(mut self) -> Result<W, PiControlError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Content of the region at a point of a trace
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    /// Time since the start of the recording
+    pub time: Duration,
+    /// Content of the whole region
+    pub bytes: Vec<u8>,
+}
+
+/// Reads a trace frame by frame, see the [module documentation](self)
+#[derive(Debug)]
+pub struct TraceReader<R: Read> {
+    reader: R,
+    region: Range<u16>,
+    start: SystemTime,
+    time: Duration,
+    bytes: Vec<u8>,
+    failed: bool,
+}
+
+impl<R: Read> TraceReader<R> {
+    /// Reads the header of the trace in `reader`
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if reading fails or `reader`
+    /// doesn't contain a trace.
+    pub fn new(mut reader: R) -> Result<Self, PiControlError> {
+        let mut header = [0u8; 17];
+        reader.read_exact(&mut header)?;
+        ensure!(&header[0..4] == MAGIC && header[4] == VERSION, invalid());
+        let start = u16::from_le_bytes([header[5], header[6]]);
+        let end = u16::from_le_bytes([header[7], header[8]]);
+        ensure!(start < end, invalid());
+        let mut wall = [0u8; 8];
+        wall.copy_from_slice(&header[9..17]);
+        Ok(Self {
+            reader,
+            region: start..end,
+            start: UNIX_EPOCH + Duration::from_micros(u64::from_le_bytes(wall)),
+            time: Duration::ZERO,
+            bytes: vec![0; (end - start) as usize],
+            failed: false,
+        })
+    }
+
+    /// Returns the recorded region
+    pub fn region(&self) -> Range<u16> {
+        self.region.clone()
+    }
+
+    /// Returns the wall-clock time of the start of the recording
+    pub fn start(&self) -> SystemTime {
+        self.start
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, PiControlError> {
+        let delta = match read_varint(&mut self.reader)? {
+            Some(delta) => delta,
+            None => return Ok(None),
+        };
+        let runs = read_varint(&mut self.reader)?.ok_or_else(invalid)?;
+        let mut end = 0usize;
+        for _ in 0..runs {
+            let skip = read_varint(&mut self.reader)?.ok_or_else(invalid)? as usize;
+            let len = read_varint(&mut self.reader)?.ok_or_else(invalid)? as usize;
+            let start = end.checked_add(skip).ok_or_else(invalid)?;
+            end = start.checked_add(len).ok_or_else(invalid)?;
+            ensure!(end <= self.bytes.len(), invalid());
+            self.reader.read_exact(&mut self.bytes[start..end])?;
+        }
+        self.time += Duration::from_micros(delta);
+        Ok(Some(Frame {
+            time: self.time,
+            bytes: self.bytes.clone(),
+        }))
+    }
+
+    /// Writes every frame into the region of `pi` at its time, scaled by
+    /// `speed`, e.g. `2.0` for twice as fast. Returns the number of frames.
+    ///
+    /// Only the recorded bytes are written, so the backend should be one that
+    /// nothing else writes to, e.g. a
+    /// [`MockBackend`](crate::picontrol::backend::MockBackend).
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::InvalidArgument`] if `speed` isn't
+    /// positive, the error of reading the trace and the error of the backend
+    /// if writing fails.
+    pub fn replay(self, pi: &PiControl, speed: f64) -> Result<u64, PiControlError> {
+        ensure!(
+            speed > 0.0 && speed.is_finite(),
+            PiControlError::InvalidArgument("speed")
+        );
+        let start = std::time::Instant::now();
+        let address = self.region.start;
+        let mut frames = 0;
+        for frame in self {
+            let frame = frame?;
+            let due = frame.time.div_f64(speed);
+            if let Some(remaining) = due.checked_sub(start.elapsed()) {
+                thread::sleep(remaining);
+            }
+            unsafe { pi.inner.set_bytes(address, &frame.bytes) }?;
+            frames += 1;
+        }
+        Ok(frames)
+    }
+}
+
+impl<R: Read> Iterator for TraceReader<R> {
+    type Item = Result<Frame, PiControlError>;
+
+    /// Returns the next frame, the iteration ends after an error
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let res = self.next_frame().transpose();
+        self.failed = matches!(res, Some(Err(_)));
+        res
+    }
+}
@@ -1,10 +1,24 @@
 // Basically does same as anyhow::ensure
 macro_rules! ensure {
     ($cond:expr, $err:expr) => {
-        if $cond {
+        if !$cond {
             return Err($err);
         }
     };
 }
 
 pub(crate) use ensure;
+
+#[cfg(test)]
+mod tests {
+    fn check(value: u8) -> Result<u8, &'static str> {
+        ensure!(value < 8, "too large");
+        Ok(value)
+    }
+
+    #[test]
+    fn ensure_returns_only_if_false() {
+        assert_eq!(check(7), Ok(7));
+        assert_eq!(check(8), Err("too large"));
+    }
+}
@@ -0,0 +1,441 @@
+//! Live values for browsers over WebSocket
+//!
+//! [`WsServer`] polls a [`Monitor`] and sends every change to all connected
+//! WebSocket clients as a JSON text message, so browser HMIs can show live
+//! process data without polling themselves:
+//! ```no_run
+//! use revpi::{monitor::Monitor, picontrol::PiControl, ws::WsServer};
+//! use std::time::Duration;
+//!
+//! let pi = PiControl::new().unwrap();
+//! let monitor = Monitor::new(Duration::from_millis(20)).watch("I_1").watch("O_1");
+//! WsServer::new(monitor).serve(&pi, "0.0.0.0:8081").unwrap();
+//! ```
+//! In the browser:
+//! ```js
+//! const ws = new WebSocket("ws://revpi:8081/");
+//! ws.onmessage = (e) => {
+//!     const { name, value } = JSON.parse(e.data);
+//!     document.getElementById(name).textContent = value;
+//! };
+//! ```
+//! A client first gets the current value of every watched variable as
+//! `{"name": "I_1", "value": true}`, then every change as
+//! `{"name": "I_1", "old": true, "value": false}`. Bits are sent as bools,
+//! everything else as unsigned numbers. Messages from the client are ignored,
+//! except for pings and closing the connection.
+
+use crate::{
+    cycle::{Cycle, OverrunPolicy},
+    http,
+    monitor::{Change, Monitor},
+    picontrol::{PiControl, PiControlError},
+};
+use serde_json::json;
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+// appended to the key of the client, see RFC 6455
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const TEXT: u8 = 0x1;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xa;
+// clients only send pings and closes, whose payload is at most 125 bytes
+const MAX_FRAME: usize = 4096;
+// a frame of MAX_FRAME bytes with the longest header
+const MAX_BUFFER: usize = MAX_FRAME + 14;
+// status code of a close frame for messages that are too big
+const TOO_BIG: u16 = 1009;
+
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    // received bytes that don't make up a whole frame yet
+    buffer: Vec<u8>,
+}
+
+/// Streams changes of variables to WebSocket clients, see the
+/// [module documentation](self)
+#[derive(Debug)]
+pub struct WsServer {
+    monitor: Monitor,
+    clients: Vec<Client>,
+}
+
+impl WsServer {
+    /// Creates a server without clients, streaming the changes `monitor`
+    /// reports
+    pub fn new(monitor: Monitor) -> Self {
+        Self {
+            monitor,
+            clients: Vec::new(),
+        }
+    }
+
+    /// Returns the number of connected clients
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Performs the WebSocket handshake on `stream`, sends the current values
+    /// and adds the client
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if the request isn't a WebSocket
+    /// handshake or the connection fails, and the error of `pi` if a value
+    /// can't be read.
+    ///
+    /// # Example
+    /// ```
+    /// use revpi::{monitor::Monitor, picontrol::{backend::MockBackend, PiControl}, ws::WsServer};
+    /// use std::io::{Read, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// let mock = Arc::new(MockBackend::new().variable("I_1", 0, 0, 1));
+    /// let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+    /// let mut server = WsServer::new(Monitor::new(Duration::from_millis(10)).watch("I_1"));
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    /// client
+    ///     .write_all(
+    ///         b"GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+    ///           Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+    ///     )
+    ///     .unwrap();
+    /// server.accept(&pi, listener.accept().unwrap().0).unwrap();
+    /// let mut response = [0u8; 129];
+    /// client.read_exact(&mut response).unwrap();
+    /// let response = String::from_utf8_lossy(&response);
+    /// assert!(response.starts_with("HTTP/1.1 101"));
+    /// assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    /// let mut frame = [0u8; 30];
+    /// client.read_exact(&mut frame).unwrap();
+    /// assert_eq!(&frame[2..], br#"{"name":"I_1","value":false}"#);
+    /// ```
+    pub fn accept(&mut self, pi: &PiControl, mut stream: TcpStream) -> Result<(), PiControlError> {
+        let request = http::read_request(&mut stream)?;
+        let key = match (request.method.as_str(), request.header("upgrade")) {
+            ("GET", Some(upgrade)) if upgrade.eq_ignore_ascii_case("websocket") => {
+                request.header("sec-websocket-key")
+            }
+            _ => None,
+        };
+        let key = match key {
+            Some(key) => key,
+            None => {
+                let _ = http::respond(&mut stream, "400 Bad Request", "text/plain", "");
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "no websocket").into());
+            }
+        };
+        let accept = base64(&sha1(format!("{}{}", key, GUID).as_bytes()));
+        write!(
+            stream,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept
+        )?;
+        for name in self.monitor.names() {
+            let message = json!({ "name": name, "value": http::json(pi.get_value(name)?) });
+            send(&mut stream, TEXT, message.to_string().as_bytes())?;
+        }
+        self.clients.push(Client {
+            stream,
+            buffer: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Polls the monitor, sends the changes to all clients and answers their
+    /// pings. Clients that closed the connection or can't be written to are
+    /// dropped.
+    ///
+    /// # Errors
+    /// Same as [`Monitor::poll`].
+    pub fn poll(&mut self, pi: &PiControl) -> Result<Vec<Change>, PiControlError> {
+        let changes = self.monitor.poll(pi)?;
+        let messages: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                json!({
+                    "name": change.name,
+                    "old": http::json(change.old.clone()),
+                    "value": http::json(change.new.clone()),
+                })
+                .to_string()
+            })
+            .collect();
+        self.clients.retain_mut(|client| {
+            let ok = client.receive().and_then(|open| {
+                for message in messages.iter().filter(|_| open) {
+                    send(&mut client.stream, TEXT, message.as_bytes())?;
+                }
+                Ok(open)
+            });
+            matches!(ok, Ok(true))
+        });
+        Ok(changes)
+    }
+
+    /// Listens on `addr`, accepts clients and sends changes every interval of
+    /// the monitor, forever
+    ///
+    /// # Errors
+    /// Returns a [`PiControlError::IoError`] if `addr` can't be bound and the
+    /// error of [`WsServer::poll`].
+    pub fn serve<A: ToSocketAddrs>(
+        &mut self,
+        pi: &PiControl,
+        addr: A,
+    ) -> Result<(), PiControlError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let mut cycle = Cycle::new(self.monitor.interval()).overrun_policy(OverrunPolicy::Skip);
+        loop {
+            cycle.wait()?;
+            while let Ok((stream, _)) = listener.accept() {
+                // a failed handshake only concerns its client
+                let _ = stream
+                    .set_nonblocking(false)
+                    .map_err(PiControlError::from)
+                    .and_then(|_| self.accept(pi, stream));
+            }
+            self.poll(pi)?;
+        }
+    }
+}
+
+impl Client {
+    // handles the frames received so far, returns whether the connection is
+    // still open
+    fn receive(&mut self) -> Result<bool, PiControlError> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk = [0u8; 1024];
+        let read = loop {
+            // the rest stays in the socket until the buffer was parsed
+            if self.buffer.len() >= MAX_BUFFER {
+                break Ok(true);
+            }
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Ok(false),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(true),
+                Err(e) => break Err(e),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        if !read? {
+            return Ok(false);
+        }
+        loop {
+            let (opcode, payload, len) = match parse(&self.buffer) {
+                Parsed::Frame(opcode, payload, len) => (opcode, payload, len),
+                Parsed::Incomplete => return Ok(true),
+                Parsed::TooBig => {
+                    let _ = send(&mut self.stream, CLOSE, &TOO_BIG.to_be_bytes());
+                    return Ok(false);
+                }
+            };
+            self.buffer.drain(..len);
+            match opcode {
+                CLOSE => {
+                    let _ = send(&mut self.stream, CLOSE, &payload);
+                    return Ok(false);
+                }
+                PING => send(&mut self.stream, PONG, &payload)?,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+    // opcode, unmasked payload and length of the frame
+    Frame(u8, Vec<u8>, usize),
+    Incomplete,
+    // the payload is longer than MAX_FRAME
+    TooBig,
+}
+
+// parses the first frame in `buf`
+fn parse(buf: &[u8]) -> Parsed {
+    parse_frame(buf).unwrap_or(Parsed::Incomplete)
+}
+
+// None if `buf` doesn't hold the whole frame yet
+fn parse_frame(buf: &[u8]) -> Option<Parsed> {
+    let opcode = buf.first()? & 0x0f;
+    let masked = buf.get(1)? & 0x80 != 0;
+    let (len, mut pos) = match buf[1] & 0x7f {
+        126 => (
+            u16::from_be_bytes(buf.get(2..4)?.try_into().ok()?) as u64,
+            4,
+        ),
+        127 => (u64::from_be_bytes(buf.get(2..10)?.try_into().ok()?), 10),
+        len => (len as u64, 2),
+    };
+    let len = match usize::try_from(len) {
+        Ok(len) if len <= MAX_FRAME => len,
+        _ => return Some(Parsed::TooBig),
+    };
+    let mask = match masked {
+        true => {
+            pos += 4;
+            buf.get(pos - 4..pos)?.to_vec()
+        }
+        false => vec![0; 4],
+    };
+    let payload = buf.get(pos..pos + len)?;
+    let payload = payload
+        .iter()
+        .enumerate()
+        .map(|(i, b)| b ^ mask[i % 4])
+        .collect();
+    Some(Parsed::Frame(opcode, payload, pos + len))
+}
+
+// sends a single unmasked frame
+fn send(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> Result<(), PiControlError> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    Ok(())
+}
+
+// SHA-1, only used for the handshake, see RFC 3174
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut msg = data.to_vec();
+    msg.push(0x80);
+    while msg.len() % 64 != 56 {
+        msg.push(0);
+    }
+    msg.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in msg.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (chunk, h) in out.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&h.to_be_bytes());
+    }
+    out
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(CHARS[(n >> (18 - 6 * i)) as usize & 0x3f] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masked_ping() {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![0x80 | PING, 0x80 | 3];
+        frame.extend_from_slice(&mask);
+        frame.extend(b"abc".iter().zip(mask).map(|(b, m)| b ^ m));
+        assert_eq!(parse(&frame), Parsed::Frame(PING, b"abc".to_vec(), 9));
+        assert_eq!(parse(&frame[..8]), Parsed::Incomplete);
+        assert_eq!(parse(&[]), Parsed::Incomplete);
+    }
+
+    #[test]
+    fn extended_lengths() {
+        let mut frame = vec![0x80 | TEXT, 126, 0x01, 0x00];
+        frame.extend_from_slice(&[7; 256]);
+        assert_eq!(parse(&frame), Parsed::Frame(TEXT, vec![7; 256], 260));
+        let frame = [0x80 | TEXT, 126, 0x10, 0x01];
+        assert_eq!(parse(&frame), Parsed::TooBig);
+        // announced before a single byte of the payload arrived
+        let mut frame = vec![0x80 | TEXT, 127];
+        frame.extend_from_slice(&(1u64 << 63).to_be_bytes());
+        assert_eq!(parse(&frame), Parsed::TooBig);
+    }
+
+    #[test]
+    fn closes_on_big_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut client = Client {
+            stream,
+            buffer: Vec::new(),
+        };
+        let mut frame = vec![0x80 | TEXT, 127];
+        frame.extend_from_slice(&u64::MAX.to_be_bytes());
+        peer.write_all(&frame).unwrap();
+        peer.write_all(&[0; 64]).unwrap();
+        // the bytes may need a moment to arrive
+        let mut open = true;
+        for _ in 0..100 {
+            open = client.receive().unwrap();
+            if !open {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!open);
+        let mut close = [0u8; 4];
+        peer.read_exact(&mut close).unwrap();
+        assert_eq!(close, [0x80 | CLOSE, 2, 0x03, 0xf1]);
+    }
+}
@@ -0,0 +1,282 @@
+//! Runs the code generated by the macros against a [`MockBackend`]
+#![cfg(feature = "macro")]
+// the generated methods and fields are named like the variables in PiCtory
+// and most of them aren't used by every test
+#![allow(dead_code, non_snake_case)]
+
+use revpi::picontrol::{backend::MockBackend, Backend};
+use std::sync::Arc;
+
+// expands `revpi_from_json_str!` with a core at offset 0 and a DIO at offset
+// 4. Bytes 9 and 10 hold the outputs of the DIO, 12 its memory.
+macro_rules! fixture {
+    ($name:ident $($options:tt)*) => {
+        revpi::revpi_from_json_str!($name r#"{
+            "App": {"name": "PiCtory", "version": "2.0.6", "saveTS": "20220523193431", "language": "en", "layout": {}},
+            "Summary": {"inpTotal": 9, "outTotal": 6},
+            "Devices": [
+                {
+                    "GUID": "80941337-4242-beed-aaaa-d9df13376969", "id": "device_RevPiCore_20220123_4_5_006",
+                    "type": "BASE", "productType": "95", "position": "0", "name": "RevPi Core", "bmk": "RevPi Core",
+                    "inpVariant": 0, "outVariant": 0, "comment": "", "offset": 0,
+                    "inp": {"0": ["RevPiStatus", "0", "8", "0", true, "0000", "", ""]},
+                    "out": {
+                        "0": ["RevPiLED", "0", "8", "1", true, "0001", "", ""],
+                        "1": ["RS485ErrorLimit1", "10", "16", "2", false, "0002", "", ""]
+                    },
+                    "mem": {}, "extend": {}
+                },
+                {
+                    "GUID": "80941337-4242-beed-aaaa-d9df13376970", "id": "device_dio_20160818_1_0_001",
+                    "type": "LEFT_RIGHT", "productType": "96", "position": "32", "name": "DIO 1", "bmk": "DIO 1",
+                    "inpVariant": 0, "outVariant": 0, "comment": "", "offset": 4,
+                    "inp": {
+                        "0": ["I_1", "0", "1", "0", true, "0000", "", "0"],
+                        "1": ["I_2", "0", "1", "0", true, "0001", "", "1"],
+                        "2": ["Counter", "0", "32", "1", true, "0002", "", ""]
+                    },
+                    "out": {
+                        "0": ["O_1", "0", "1", "5", true, "0003", "", "0"],
+                        "1": ["O_2", "0", "1", "5", true, "0004", "", "1"],
+                        "2": ["PwmDuty", "0", "16", "6", true, "0005", "", ""]
+                    },
+                    "mem": {"0": ["OutputPWMFrequency", "1", "8", "8", true, "0006", "", ""]},
+                    "extend": {}
+                }
+            ]
+        }"# $($options)*);
+    };
+}
+
+// the variables of the fixture, the names are only needed by ProcessImage
+fn mock() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .variable("RevPiStatus", 0, 0, 8)
+            .variable("RevPiLED", 1, 0, 8)
+            .variable("RS485ErrorLimit1", 2, 0, 16)
+            .variable("I_1", 4, 0, 1)
+            .variable("I_2", 4, 1, 1)
+            .variable("Counter", 5, 0, 32)
+            .variable("O_1", 9, 0, 1)
+            .variable("O_2", 9, 1, 1)
+            .variable("PwmDuty", 10, 0, 16)
+            .variable("OutputPWMFrequency", 12, 0, 8),
+    )
+}
+
+mod flat {
+    use super::*;
+
+    fixture!(RevPi);
+
+    #[test]
+    fn getters_and_setters() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        mock.write(0, &[3]).unwrap();
+        mock.write(4, &[0b10, 0x78, 0x56, 0x34, 0x12]).unwrap();
+        assert_eq!(revpi.get_RevPiStatus().unwrap(), 3);
+        assert!(!revpi.get_I_1().unwrap());
+        assert!(revpi.get_I_2().unwrap());
+        assert_eq!(revpi.get_Counter().unwrap(), 0x12345678);
+
+        revpi.set_RevPiLED(5).unwrap();
+        revpi.set_RS485ErrorLimit1(0x1234).unwrap();
+        revpi.set_O_2(true).unwrap();
+        revpi.set_OutputPWMFrequency(7).unwrap();
+        assert_eq!(mock.read(1, 3).unwrap(), vec![5, 0x34, 0x12]);
+        assert_eq!(mock.read(9, 1).unwrap(), vec![0b10]);
+        assert_eq!(mock.read(12, 1).unwrap(), vec![7]);
+        assert_eq!(revpi.get_RS485ErrorLimit1().unwrap(), 0x1234);
+        assert!(revpi.get_O_2().unwrap());
+    }
+
+    #[test]
+    fn read_inputs() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        mock.write(0, &[3, 0, 0, 0, 0b01, 1, 0, 0, 0]).unwrap();
+        let inputs = revpi.read_inputs().unwrap();
+        assert_eq!(
+            inputs,
+            RevPiInputs {
+                RevPiStatus: 3,
+                I_1: true,
+                I_2: false,
+                Counter: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn write_outputs() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        // bits of the byte with O_1 and O_2 that aren't outputs are kept
+        mock.write(9, &[0x80]).unwrap();
+        let outputs = RevPiOutputs {
+            RevPiLED: 1,
+            RS485ErrorLimit1: 0x0302,
+            O_1: true,
+            O_2: false,
+            PwmDuty: 0x0504,
+        };
+        revpi.write_outputs(&outputs).unwrap();
+        assert_eq!(mock.read(1, 3).unwrap(), vec![1, 2, 3]);
+        assert_eq!(mock.read(9, 3).unwrap(), vec![0x81, 4, 5]);
+        // memory isn't an output
+        assert_eq!(mock.read(12, 1).unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(names::REV_PI_LED, "RevPiLED");
+        assert_eq!(names::RS485_ERROR_LIMIT1, "RS485ErrorLimit1");
+        assert_eq!(names::OUTPUT_PWM_FREQUENCY, "OutputPWMFrequency");
+    }
+}
+
+mod grouped {
+    use super::*;
+
+    fixture!(RevPi, grouped);
+
+    #[test]
+    fn devices() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        mock.write(4, &[0b10]).unwrap();
+        assert!(revpi.dio_1.get_I_2().unwrap());
+        revpi.rev_pi_core.set_RevPiLED(9).unwrap();
+        assert_eq!(mock.read(1, 1).unwrap(), vec![9]);
+
+        let inputs = revpi.dio_1.read_inputs().unwrap();
+        assert!(!inputs.I_1 && inputs.I_2);
+        let outputs = RevPiDevice32Outputs {
+            O_1: false,
+            O_2: true,
+            PwmDuty: 6,
+        };
+        revpi.dio_1.write_outputs(&outputs).unwrap();
+        assert_eq!(mock.read(9, 2).unwrap(), vec![0b10, 6]);
+        // the core isn't touched by the DIO
+        assert_eq!(mock.read(1, 1).unwrap(), vec![9]);
+    }
+}
+
+mod renamed {
+    use super::*;
+
+    fixture!(
+        RevPi,
+        rename = "snake_case",
+        prefix = "io_",
+        suffix = "_value"
+    );
+
+    #[test]
+    fn methods() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        revpi.set_io_rev_pi_led_value(4).unwrap();
+        revpi.set_io_rs485_error_limit1_value(0x0201).unwrap();
+        assert_eq!(mock.read(1, 3).unwrap(), vec![4, 1, 2]);
+        mock.write(4, &[0b01]).unwrap();
+        assert!(revpi.get_io_i_1_value().unwrap());
+        assert!(revpi.read_inputs().unwrap().io_i_1_value);
+        // the constants keep their names
+        assert_eq!(names::REV_PI_LED, "RevPiLED");
+    }
+}
+
+mod skip_unexported {
+    use super::*;
+
+    fixture!(RevPi, skip_unexported);
+
+    #[test]
+    fn unexported_output() {
+        let mock = mock();
+        let revpi = RevPi::with_backend(mock.clone());
+        mock.write(2, &[0xff, 0xff]).unwrap();
+        // RS485ErrorLimit1 has no field, so it isn't overwritten
+        let outputs = RevPiOutputs {
+            RevPiLED: 1,
+            O_1: false,
+            O_2: false,
+            PwmDuty: 0,
+        };
+        revpi.write_outputs(&outputs).unwrap();
+        assert_eq!(mock.read(1, 3).unwrap(), vec![1, 0xff, 0xff]);
+        // but it keeps its name
+        assert_eq!(names::RS485_ERROR_LIMIT1, "RS485ErrorLimit1");
+    }
+}
+
+mod offsets {
+    use super::*;
+
+    revpi::revpi_offsets!(Offsets, "tests/offsets.rsc");
+
+    #[test]
+    fn constants() {
+        assert_eq!(Offsets::REV_PI_LED_ADDR, 1);
+        assert_eq!(Offsets::RS485_ERROR_LIMIT1_ADDR, 2);
+        assert_eq!(Offsets::RS485_ERROR_LIMIT1_LEN, 16);
+        assert_eq!(Offsets::O_2_ADDR, 9);
+        assert_eq!(Offsets::O_2_BIT, 1);
+        assert_eq!(Offsets::O_2_LEN, 1);
+
+        let mock = mock();
+        mock.write(Offsets::RS485_ERROR_LIMIT1_ADDR, &[0x10, 0x27])
+            .unwrap();
+        let word = unsafe { mock.get_word(Offsets::RS485_ERROR_LIMIT1_ADDR) };
+        assert_eq!(word.unwrap(), 10000);
+    }
+}
+
+mod process_image {
+    use super::*;
+    use revpi::picontrol::{PiControl, ProcessImage};
+
+    #[derive(ProcessImage, Debug, PartialEq)]
+    struct Machine {
+        #[pi(name = "RevPiLED")]
+        led: u8,
+        #[pi(name = "I_2", read_only)]
+        door_open: bool,
+        #[pi(name = "PwmDuty")]
+        duty: u16,
+        #[pi(skip)]
+        cycles: u64,
+    }
+
+    #[test]
+    fn read_and_write() {
+        let mock = mock();
+        let pi = PiControl::builder().backend(mock.clone()).build().unwrap();
+        mock.write(1, &[2]).unwrap();
+        mock.write(4, &[0b10]).unwrap();
+        mock.write(10, &[0x34, 0x12]).unwrap();
+        let mut machine = Machine::read_from(&pi).unwrap();
+        assert_eq!(
+            machine,
+            Machine {
+                led: 2,
+                door_open: true,
+                duty: 0x1234,
+                cycles: 0,
+            }
+        );
+
+        machine.led = 8;
+        machine.door_open = false;
+        machine.duty = 0x0102;
+        machine.write_to(&pi).unwrap();
+        assert_eq!(mock.read(1, 1).unwrap(), vec![8]);
+        assert_eq!(mock.read(10, 2).unwrap(), vec![2, 1]);
+        // read only fields aren't written
+        assert_eq!(mock.read(4, 1).unwrap(), vec![0b10]);
+    }
+}